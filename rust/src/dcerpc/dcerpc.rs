@@ -18,6 +18,7 @@
 use crate::applayer::{self, *};
 use crate::core::{self, *};
 use crate::dcerpc::parser;
+use crate::dcerpc::stub::{dcerpc_stub_decode_request, DCERPCHandleNames, DCERPCStubCall};
 use crate::direction::{Direction, DIR_BOTH};
 use crate::flow::Flow;
 use crate::frames::*;
@@ -57,6 +58,15 @@ pub const PFC_MAYBE: u8 = 0x40;
 // is omitted.
 pub const PFC_OBJECT_UUID: u8 = 0x80;
 
+// Authentication level of the stubs encrypted by the security provider
+pub const DCERPC_AUTHN_LEVEL_PKT_PRIVACY: u8 = 6;
+// Length of the sec_trailer in front of the auth value
+pub const DCERPC_SEC_TRAILER_LEN: usize = 8;
+// Length of the response fields kept in front of the response stub data
+pub const DCERPC_RESPONSE_FIELDS_LEN: usize = 8;
+// Maximum size of the stub data reassembled from the fragments of a request
+const DCERPC_STUB_FRAGS_MAX: usize = 1 << 20;
+
 // Flag bits in first flag field in connectionless PDU header.
 pub const PFCL1_RESERVED_01: u8 = 0x01; // Reserved for use by implementations
 pub const PFCL1_LASTFRAG: u8 = 0x02; // If set, the PDU is the last fragment
@@ -198,6 +208,8 @@ pub struct DCERPCTransaction {
     pub resp_cmd: u8,
     pub activityuuid: Vec<u8>,
     pub seqnum: u32,
+    /// decoded request, for the interfaces we know about
+    pub stub_call: Option<DCERPCStubCall>,
     pub tx_data: AppLayerTxData,
 }
 
@@ -329,6 +341,11 @@ pub struct DCERPCState {
     ts_ssn_gap: bool,
     tc_ssn_gap: bool,
     pub flow: Option<*const Flow>,
+    /// names of the handles opened by the decoded requests
+    handles: DCERPCHandleNames,
+    /// call ID and stub data of the fragments of the request being
+    /// reassembled for decoding, without their authentication trailers
+    stub_frags_ts: Option<(u32, Vec<u8>)>,
     state_data: AppLayerStateData,
 }
 
@@ -431,6 +448,15 @@ impl DCERPCState {
         None
     }
 
+    fn get_hdr_auth_length(&self) -> Option<u16> {
+        debug_validate_bug_on!(self.header.is_none());
+        if let Some(ref hdr) = self.header {
+            return Some(hdr.auth_length);
+        }
+        // Shouldn't happen
+        None
+    }
+
     pub fn get_hdr_type(&self) -> Option<u8> {
         debug_validate_bug_on!(self.header.is_none());
         if let Some(ref hdr) = self.header {
@@ -723,6 +749,7 @@ impl DCERPCState {
             SCLogDebug!("No transaction found matching the call ID: {:?}", call_id);
             return 0;
         }
+        let tx_id = tx.id;
 
         // Update the stub params based on the packet type
         match hdrtype {
@@ -769,9 +796,95 @@ impl DCERPCState {
         // Update the remaining fragment length
         self.padleft -= retval;
 
+        // Requests are decoded once their last fragment is complete, only
+        // responses that are not fragmented are decoded
+        match hdrtype {
+            Some(DCERPC_TYPE_REQUEST) if self.padleft == 0 => {
+                self.decode_stub_request(tx_id, hdrpfcflags);
+            }
+            Some(DCERPC_TYPE_RESPONSE)
+                if hdrpfcflags & (PFC_FIRST_FRAG | PFC_LAST_FRAG)
+                    == (PFC_FIRST_FRAG | PFC_LAST_FRAG) =>
+            {
+                self.decode_stub_response(tx_id);
+            }
+            _ => {}
+        }
+
         retval
     }
 
+    /// Decode the stub data of a complete request fragment, if it is for
+    /// one of the interfaces we know about.
+    ///
+    /// Each fragment of a request is a transaction of its own, so the
+    /// stubs of the fragments are reassembled here and decoded into the
+    /// transaction of the last one.
+    fn decode_stub_request(&mut self, tx_id: u64, pfcflags: u8) {
+        let bindack = match self.bindack {
+            Some(ref x) => x,
+            None => {
+                return;
+            }
+        };
+        let auth_length = self.get_hdr_auth_length().unwrap_or(0);
+        let handles = &self.handles;
+        let frags = &mut self.stub_frags_ts;
+        if let Some(tx) = self.transactions.iter_mut().find(|tx| tx.id == tx_id) {
+            // the stub decoders only handle little endian data
+            if tx.stub_call.is_some() || tx.endianness == 0 {
+                return;
+            }
+            let fragtype = pfcflags & (PFC_FIRST_FRAG | PFC_LAST_FRAG);
+            let stub = stub_without_auth(&tx.stub_data_buffer_ts, auth_length);
+            let reassembled;
+            let stub = if fragtype == (PFC_FIRST_FRAG | PFC_LAST_FRAG) {
+                // Encrypted requests are still logged with their operation
+                stub.unwrap_or(&[])
+            } else {
+                if fragtype == PFC_FIRST_FRAG {
+                    *frags = Some((tx.call_id, Vec::new()));
+                }
+                match (frags.as_mut(), stub) {
+                    (Some((call_id, buf)), Some(stub))
+                        if *call_id == tx.call_id
+                            && buf.len() + stub.len() <= DCERPC_STUB_FRAGS_MAX =>
+                    {
+                        buf.extend_from_slice(stub);
+                    }
+                    _ => {
+                        *frags = None;
+                    }
+                }
+                if fragtype != PFC_LAST_FRAG {
+                    return;
+                }
+                reassembled = frags.take().map(|(_, buf)| buf).unwrap_or_default();
+                &reassembled
+            };
+            if let Some(uuid) = bindack.accepted_uuid_list.iter().find(|u| u.ctxid == tx.ctxid) {
+                tx.stub_call = dcerpc_stub_decode_request(&uuid.uuid, tx.opnum, stub, handles);
+            }
+        }
+    }
+
+    /// Name the handle returned by a complete response.
+    fn decode_stub_response(&mut self, tx_id: u64) {
+        let auth_length = self.get_hdr_auth_length().unwrap_or(0);
+        let handles = &mut self.handles;
+        if let Some(tx) = self.transactions.iter().find(|tx| tx.id == tx_id) {
+            if !tx.stub_data_buffer_reset_tc {
+                return;
+            }
+            if let Some(ref call) = tx.stub_call {
+                let stub = tx.stub_data_buffer_tc.get(DCERPC_RESPONSE_FIELDS_LEN..).unwrap_or(&[]);
+                if let Some(stub) = stub_without_auth(stub, auth_length) {
+                    handles.update(call, stub);
+                }
+            }
+        }
+    }
+
     /// Handles stub data for both request and response.
     ///
     /// Arguments:
@@ -999,6 +1112,21 @@ impl DCERPCState {
     }
 }
 
+/// Strip the authentication trailer from the stub data of a PDU. Returns
+/// None if the stub data is encrypted.
+fn stub_without_auth(stub: &[u8], auth_length: u16) -> Option<&[u8]> {
+    if auth_length == 0 {
+        return Some(stub);
+    }
+    let trailer = stub.len().checked_sub(auth_length as usize + DCERPC_SEC_TRAILER_LEN)?;
+    let auth_level = stub[trailer + 1];
+    if auth_level == DCERPC_AUTHN_LEVEL_PKT_PRIVACY {
+        return None;
+    }
+    let auth_pad_length = stub[trailer + 2] as usize;
+    Some(&stub[..trailer.saturating_sub(auth_pad_length)])
+}
+
 fn evaluate_stub_params(
     input: &[u8], input_len: usize, hdrflags: u8, lenleft: u16,
     stub_data_buffer: &mut Vec<u8>,stub_data_buffer_reset: &mut bool,
@@ -1273,7 +1401,7 @@ pub unsafe extern "C" fn SCRegisterDcerpcParser() {
 mod tests {
     use crate::applayer::{AppLayerResult, StreamSlice};
     use crate::core::*;
-    use crate::dcerpc::dcerpc::DCERPCState;
+    use crate::dcerpc::dcerpc::{DCERPCState, PFC_FIRST_FRAG, PFC_LAST_FRAG};
     use crate::direction::Direction;
     use std::cmp;
 
@@ -2174,4 +2302,169 @@ mod tests {
             assert_eq!(expected_uuid2, back.accepted_uuid_list[0].uuid);
         }
     }
+
+    #[test]
+    pub fn test_decode_stub_svcctl() {
+        let bind: &[u8] = &[
+            0x05, 0x00, 0x0b, 0x03, 0x10, 0x00, 0x00, 0x00, 0x48, 0x00, 0x00, 0x00, 0x01, 0x00,
+            0x00, 0x00, 0xb8, 0x10, 0xb8, 0x10, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x01, 0x00, 0x81, 0xbb, 0x7a, 0x36, 0x44, 0x98, 0xf1, 0x35, 0xad, 0x32,
+            0x98, 0xf0, 0x38, 0x00, 0x10, 0x03, 0x02, 0x00, 0x00, 0x00, 0x04, 0x5d, 0x88, 0x8a,
+            0xeb, 0x1c, 0xc9, 0x11, 0x9f, 0xe8, 0x08, 0x00, 0x2b, 0x10, 0x48, 0x60, 0x02, 0x00,
+            0x00, 0x00,
+        ];
+        let bindack: &[u8] = &[
+            0x05, 0x00, 0x0c, 0x03, 0x10, 0x00, 0x00, 0x00, 0x38, 0x00, 0x00, 0x00, 0x01, 0x00,
+            0x00, 0x00, 0xb8, 0x10, 0xb8, 0x10, 0x34, 0x12, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x5d, 0x88, 0x8a, 0xeb, 0x1c,
+            0xc9, 0x11, 0x9f, 0xe8, 0x08, 0x00, 0x2b, 0x10, 0x48, 0x60, 0x02, 0x00, 0x00, 0x00,
+        ];
+        let create_service: &[u8] = &[
+            0x05, 0x00, 0x00, 0x03, 0x10, 0x00, 0x00, 0x00, 0xdc, 0x00, 0x00, 0x00, 0x02, 0x00,
+            0x00, 0x00, 0xc4, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+            0x0f, 0x10, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00,
+            0x50, 0x00, 0x53, 0x00, 0x45, 0x00, 0x58, 0x00, 0x45, 0x00, 0x53, 0x00, 0x56, 0x00,
+            0x43, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x09, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x50, 0x00, 0x53, 0x00, 0x45, 0x00,
+            0x58, 0x00, 0x45, 0x00, 0x53, 0x00, 0x56, 0x00, 0x43, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0xff, 0x01, 0x0f, 0x00, 0x10, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x1a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1a, 0x00, 0x00, 0x00,
+            0x25, 0x00, 0x53, 0x00, 0x79, 0x00, 0x73, 0x00, 0x74, 0x00, 0x65, 0x00, 0x6d, 0x00,
+            0x52, 0x00, 0x6f, 0x00, 0x6f, 0x00, 0x74, 0x00, 0x25, 0x00, 0x5c, 0x00, 0x50, 0x00,
+            0x53, 0x00, 0x45, 0x00, 0x58, 0x00, 0x45, 0x00, 0x53, 0x00, 0x56, 0x00, 0x43, 0x00,
+            0x2e, 0x00, 0x65, 0x00, 0x78, 0x00, 0x65, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let create_service_resp: &[u8] = &[
+            0x05, 0x00, 0x02, 0x03, 0x10, 0x00, 0x00, 0x00, 0x34, 0x00, 0x00, 0x00, 0x02, 0x00,
+            0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a,
+            0x2b, 0x2c, 0x2d, 0x2e, 0x2f, 0x30, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let start_service: &[u8] = &[
+            0x05, 0x00, 0x00, 0x03, 0x10, 0x00, 0x00, 0x00, 0x34, 0x00, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x13, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x2b, 0x2c, 0x2d, 0x2e,
+            0x2f, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut dcerpc_state = DCERPCState::new();
+        for (pdu, dir) in [
+            (bind, Direction::ToServer),
+            (bindack, Direction::ToClient),
+            (create_service, Direction::ToServer),
+            (create_service_resp, Direction::ToClient),
+            (start_service, Direction::ToServer),
+        ] {
+            let flags = if dir == Direction::ToServer { STREAM_TOSERVER } else { STREAM_TOCLIENT };
+            assert_eq!(
+                AppLayerResult::ok(),
+                dcerpc_state.handle_input_data(StreamSlice::from_slice(pdu, flags, 0), dir)
+            );
+        }
+        let call = dcerpc_state.transactions[1].stub_call.as_ref().unwrap();
+        assert_eq!("RCreateServiceW", call.operation);
+        assert_eq!(b"PSEXESVC", &call.service_name[..]);
+        assert_eq!(b"%SystemRoot%\\PSEXESVC.exe", &call.binary_path[..]);
+        // the service of the handle returned by the first request
+        let call = dcerpc_state.transactions[2].stub_call.as_ref().unwrap();
+        assert_eq!("RStartServiceW", call.operation);
+        assert_eq!(b"PSEXESVC", &call.service_name[..]);
+
+        // the same request split in two fragments, with call ID 4
+        let mut first_frag = create_service[..124].to_vec();
+        first_frag[3] = PFC_FIRST_FRAG;
+        first_frag[8] = 124;
+        first_frag[12] = 4;
+        let mut last_frag = first_frag[..24].to_vec();
+        last_frag[3] = PFC_LAST_FRAG;
+        last_frag[8] = 120;
+        last_frag.extend_from_slice(&create_service[124..]);
+        for pdu in [&first_frag, &last_frag] {
+            assert_eq!(
+                AppLayerResult::ok(),
+                dcerpc_state.handle_input_data(
+                    StreamSlice::from_slice(pdu, STREAM_TOSERVER, 0),
+                    Direction::ToServer
+                )
+            );
+        }
+        assert!(dcerpc_state.transactions[3].stub_call.is_none());
+        let call = dcerpc_state.transactions[4].stub_call.as_ref().unwrap();
+        assert_eq!("RCreateServiceW", call.operation);
+        assert_eq!(b"PSEXESVC", &call.service_name[..]);
+        assert_eq!(b"%SystemRoot%\\PSEXESVC.exe", &call.binary_path[..]);
+    }
+
+    #[test]
+    pub fn test_decode_stub_samr() {
+        let bind: &[u8] = &[
+            0x05, 0x00, 0x0b, 0x03, 0x10, 0x00, 0x00, 0x00, 0x48, 0x00, 0x00, 0x00, 0x01, 0x00,
+            0x00, 0x00, 0xb8, 0x10, 0xb8, 0x10, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x01, 0x00, 0x78, 0x57, 0x34, 0x12, 0x34, 0x12, 0xcd, 0xab, 0xef, 0x00,
+            0x01, 0x23, 0x45, 0x67, 0x89, 0xac, 0x01, 0x00, 0x00, 0x00, 0x04, 0x5d, 0x88, 0x8a,
+            0xeb, 0x1c, 0xc9, 0x11, 0x9f, 0xe8, 0x08, 0x00, 0x2b, 0x10, 0x48, 0x60, 0x02, 0x00,
+            0x00, 0x00,
+        ];
+        let bindack: &[u8] = &[
+            0x05, 0x00, 0x0c, 0x03, 0x10, 0x00, 0x00, 0x00, 0x38, 0x00, 0x00, 0x00, 0x01, 0x00,
+            0x00, 0x00, 0xb8, 0x10, 0xb8, 0x10, 0x34, 0x12, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x5d, 0x88, 0x8a, 0xeb, 0x1c,
+            0xc9, 0x11, 0x9f, 0xe8, 0x08, 0x00, 0x2b, 0x10, 0x48, 0x60, 0x02, 0x00, 0x00, 0x00,
+        ];
+        let open_user: &[u8] = &[
+            0x05, 0x00, 0x00, 0x03, 0x10, 0x00, 0x00, 0x00, 0x34, 0x00, 0x00, 0x00, 0x02, 0x00,
+            0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x22, 0x00, 0x01, 0x02, 0x03, 0x04,
+            0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12,
+            0x13, 0x14, 0x1b, 0x01, 0x02, 0x00, 0xf4, 0x01, 0x00, 0x00,
+        ];
+        let open_user_resp: &[u8] = &[
+            0x05, 0x00, 0x02, 0x03, 0x10, 0x00, 0x00, 0x00, 0x30, 0x00, 0x00, 0x00, 0x02, 0x00,
+            0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x41, 0x42, 0x43, 0x44,
+            0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x4b, 0x4c, 0x4d, 0x4e, 0x4f, 0x50, 0x51, 0x52,
+            0x53, 0x54, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let get_groups: &[u8] = &[
+            0x05, 0x00, 0x00, 0x03, 0x10, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x27, 0x00, 0x41, 0x42, 0x43, 0x44,
+            0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x4b, 0x4c, 0x4d, 0x4e, 0x4f, 0x50, 0x51, 0x52,
+            0x53, 0x54,
+        ];
+        let lookup_names_sealed: &[u8] = &[
+            0x05, 0x00, 0x00, 0x03, 0x10, 0x00, 0x00, 0x00, 0x58, 0x00, 0x10, 0x00, 0x04, 0x00,
+            0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x00, 0x00, 0x11, 0x00, 0x5a, 0x5a, 0x5a, 0x5a,
+            0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a,
+            0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a,
+            0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x0a, 0x06, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77, 0x77,
+            0x77, 0x77, 0x77, 0x77,
+        ];
+        let mut dcerpc_state = DCERPCState::new();
+        for (pdu, dir) in [
+            (bind, Direction::ToServer),
+            (bindack, Direction::ToClient),
+            (open_user, Direction::ToServer),
+            (open_user_resp, Direction::ToClient),
+            (get_groups, Direction::ToServer),
+            (lookup_names_sealed, Direction::ToServer),
+        ] {
+            let flags = if dir == Direction::ToServer { STREAM_TOSERVER } else { STREAM_TOCLIENT };
+            assert_eq!(
+                AppLayerResult::ok(),
+                dcerpc_state.handle_input_data(StreamSlice::from_slice(pdu, flags, 0), dir)
+            );
+        }
+        let call = dcerpc_state.transactions[1].stub_call.as_ref().unwrap();
+        assert_eq!("SamrOpenUser", call.operation);
+        assert_eq!(vec![500], call.account_rids);
+        // the RID of the user handle opened by the first request
+        let call = dcerpc_state.transactions[2].stub_call.as_ref().unwrap();
+        assert_eq!("SamrGetGroupsForUser", call.operation);
+        assert_eq!(vec![500], call.account_rids);
+        // encrypted stub, only the operation is known
+        let call = dcerpc_state.transactions[3].stub_call.as_ref().unwrap();
+        assert_eq!("SamrLookupNamesInDomain", call.operation);
+        assert!(call.account_names.is_empty());
+    }
 }
@@ -19,9 +19,24 @@ use super::dcerpc::{
     DCERPCState, DCERPCTransaction, DCERPC_TYPE_REQUEST, DCERPC_TYPE_RESPONSE,
     DCERPC_UUID_ENTRY_FLAG_FF,
 };
-use crate::detect::uint::{detect_match_uint, detect_parse_uint, DetectUintData};
+use super::stub::DCERPCStubCall;
+use crate::core::STREAM_TOSERVER;
+use crate::detect::uint::{
+    detect_match_uint, detect_parse_uint, DetectUintData, SCDetectU32Free, SCDetectU32Parse,
+};
+use crate::detect::{helper_keyword_register_sticky_buffer, SigTableElmtStickyBuffer};
+use crate::flow::flow_get_alproto;
+use crate::smb::smb::{SMBTransaction, SMBTransactionTypeData};
 use std::ffi::CStr;
-use std::os::raw::{c_char, c_void};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use suricata_sys::sys::{
+    AppProto, AppProtoEnum, DetectEngineCtx, DetectEngineThreadCtx, Flow,
+    SCDetectBufferSetActiveList, SCDetectHelperBufferMpmRegister, SCDetectHelperBufferRegister,
+    SCDetectHelperKeywordRegister, SCDetectHelperMultiBufferMpmRegister,
+    SCDetectSignatureSetAppProto, SCSigMatchAppendSMToList, SCSigTableAppLiteElmt, SigMatchCtx,
+    Signature,
+};
 use uuid::Uuid;
 
 pub const DETECT_DCE_OPNUM_RANGE_UNINITIALIZED: u32 = 100000;
@@ -290,6 +305,721 @@ pub unsafe extern "C" fn SCDcerpcOpnumFree(ptr: *mut c_void) {
     }
 }
 
+const ALPROTO_DCERPC: AppProto = AppProtoEnum::ALPROTO_DCERPC as AppProto;
+const ALPROTO_SMB: AppProto = AppProtoEnum::ALPROTO_SMB as AppProto;
+
+static mut G_DCERPC_SERVICE_NAME_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_DISPLAY_NAME_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_BINARY_PATH_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_START_NAME_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_TASK_NAME_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_TASK_XML_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_TASK_COMMAND_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_REGISTRY_KEY_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_REGISTRY_VALUE_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_REGISTRY_DATA_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_REGISTRY_FILE_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_WMI_NAMESPACE_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_WMI_OBJECT_PATH_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_WMI_METHOD_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_WMI_QUERY_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_REPLICATED_OBJECT_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_REQUESTED_ATTRIBUTE_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_ACCOUNT_NAME_BUFFER_ID: c_int = 0;
+static mut G_DCERPC_ACCOUNT_RID_KW_ID: u16 = 0;
+static mut G_DCERPC_ACCOUNT_RID_BUFFER_ID: c_int = 0;
+
+/// Sets up a rule for one of the buffers of the decoded requests. Like the
+/// other dce keywords, these match on both DCERPC and SMB flows.
+unsafe fn dcerpc_stub_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, buffer_id: c_int,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DCERPC) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, buffer_id) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+fn smb_stub_call(tx: &SMBTransaction) -> Option<&DCERPCStubCall> {
+    if let Some(SMBTransactionTypeData::DCERPC(ref x)) = tx.type_data {
+        return x.stub_call.as_deref();
+    }
+    return None;
+}
+
+unsafe fn stub_get(
+    call: Option<&DCERPCStubCall>, buffer: *mut *const u8, buffer_len: *mut u32,
+    field: fn(&DCERPCStubCall) -> &[u8],
+) -> bool {
+    if let Some(call) = call {
+        let v = field(call);
+        if !v.is_empty() {
+            *buffer = v.as_ptr();
+            *buffer_len = v.len() as u32;
+            return true;
+        }
+    }
+    *buffer = ptr::null();
+    *buffer_len = 0;
+    return false;
+}
+
+unsafe fn stub_multi_get(
+    call: Option<&DCERPCStubCall>, local_id: u32, buffer: *mut *const u8, buffer_len: *mut u32,
+    field: fn(&DCERPCStubCall) -> &[Vec<u8>],
+) -> bool {
+    if let Some(call) = call {
+        if let Some(v) = field(call).get(local_id as usize) {
+            *buffer = v.as_ptr();
+            *buffer_len = v.len() as u32;
+            return true;
+        }
+    }
+    *buffer = ptr::null();
+    *buffer_len = 0;
+    return false;
+}
+
+unsafe fn smb_stub_get(
+    tx: *const c_void, buffer: *mut *const u8, buffer_len: *mut u32,
+    field: fn(&DCERPCStubCall) -> &[u8],
+) -> bool {
+    let tx = cast_pointer!(tx, SMBTransaction);
+    stub_get(smb_stub_call(tx), buffer, buffer_len, field)
+}
+
+unsafe fn dcerpc_tx_stub_get(
+    tx: *const c_void, buffer: *mut *const u8, buffer_len: *mut u32,
+    field: fn(&DCERPCStubCall) -> &[u8],
+) -> bool {
+    let tx = cast_pointer!(tx, DCERPCTransaction);
+    stub_get(tx.stub_call.as_ref(), buffer, buffer_len, field)
+}
+
+unsafe extern "C" fn dcerpc_service_name_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_SERVICE_NAME_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_service_name_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.service_name)
+}
+
+unsafe extern "C" fn dcerpc_service_name_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.service_name)
+}
+
+unsafe extern "C" fn dcerpc_display_name_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_DISPLAY_NAME_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_display_name_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.display_name)
+}
+
+unsafe extern "C" fn dcerpc_display_name_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.display_name)
+}
+
+unsafe extern "C" fn dcerpc_binary_path_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_BINARY_PATH_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_binary_path_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.binary_path)
+}
+
+unsafe extern "C" fn dcerpc_binary_path_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.binary_path)
+}
+
+unsafe extern "C" fn dcerpc_start_name_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_START_NAME_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_start_name_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.start_name)
+}
+
+unsafe extern "C" fn dcerpc_start_name_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.start_name)
+}
+
+unsafe extern "C" fn dcerpc_task_name_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_TASK_NAME_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_task_name_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.task_name)
+}
+
+unsafe extern "C" fn dcerpc_task_name_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.task_name)
+}
+
+unsafe extern "C" fn dcerpc_task_xml_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_TASK_XML_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_task_xml_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.task_xml)
+}
+
+unsafe extern "C" fn dcerpc_task_xml_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.task_xml)
+}
+
+unsafe extern "C" fn dcerpc_task_command_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_TASK_COMMAND_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_task_command_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.task_command)
+}
+
+unsafe extern "C" fn dcerpc_task_command_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.task_command)
+}
+
+unsafe extern "C" fn dcerpc_registry_key_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_REGISTRY_KEY_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_registry_key_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.registry_key)
+}
+
+unsafe extern "C" fn dcerpc_registry_key_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.registry_key)
+}
+
+unsafe extern "C" fn dcerpc_registry_value_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_REGISTRY_VALUE_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_registry_value_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.registry_value)
+}
+
+unsafe extern "C" fn dcerpc_registry_value_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.registry_value)
+}
+
+unsafe extern "C" fn dcerpc_registry_data_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_REGISTRY_DATA_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_registry_data_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.registry_data)
+}
+
+unsafe extern "C" fn dcerpc_registry_data_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.registry_data)
+}
+
+unsafe extern "C" fn dcerpc_registry_file_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_REGISTRY_FILE_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_registry_file_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.registry_file)
+}
+
+unsafe extern "C" fn dcerpc_registry_file_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.registry_file)
+}
+
+unsafe extern "C" fn dcerpc_wmi_namespace_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_WMI_NAMESPACE_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_wmi_namespace_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.wmi_namespace)
+}
+
+unsafe extern "C" fn dcerpc_wmi_namespace_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.wmi_namespace)
+}
+
+unsafe extern "C" fn dcerpc_wmi_object_path_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_WMI_OBJECT_PATH_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_wmi_object_path_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.wmi_object_path)
+}
+
+unsafe extern "C" fn dcerpc_wmi_object_path_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.wmi_object_path)
+}
+
+unsafe extern "C" fn dcerpc_wmi_method_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_WMI_METHOD_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_wmi_method_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.wmi_method)
+}
+
+unsafe extern "C" fn dcerpc_wmi_method_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.wmi_method)
+}
+
+unsafe extern "C" fn dcerpc_wmi_query_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_WMI_QUERY_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_wmi_query_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.wmi_query)
+}
+
+unsafe extern "C" fn dcerpc_wmi_query_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.wmi_query)
+}
+
+unsafe extern "C" fn dcerpc_replicated_object_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_REPLICATED_OBJECT_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_replicated_object_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    smb_stub_get(tx, buffer, buffer_len, |c| &c.replicated_object)
+}
+
+unsafe extern "C" fn dcerpc_replicated_object_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    dcerpc_tx_stub_get(tx, buffer, buffer_len, |c| &c.replicated_object)
+}
+
+unsafe extern "C" fn dcerpc_requested_attribute_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_REQUESTED_ATTRIBUTE_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_requested_attribute_get(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, _flags: u8, local_id: u32,
+    buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, SMBTransaction);
+    stub_multi_get(smb_stub_call(tx), local_id, buffer, buffer_len, |c| {
+        &c.requested_attributes
+    })
+}
+
+unsafe extern "C" fn dcerpc_requested_attribute_get(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, _flags: u8, local_id: u32,
+    buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, DCERPCTransaction);
+    stub_multi_get(tx.stub_call.as_ref(), local_id, buffer, buffer_len, |c| {
+        &c.requested_attributes
+    })
+}
+
+unsafe extern "C" fn dcerpc_account_name_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    dcerpc_stub_setup(de, s, G_DCERPC_ACCOUNT_NAME_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_account_name_get(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, _flags: u8, local_id: u32,
+    buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, SMBTransaction);
+    stub_multi_get(smb_stub_call(tx), local_id, buffer, buffer_len, |c| {
+        &c.account_names
+    })
+}
+
+unsafe extern "C" fn dcerpc_account_name_get(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, _flags: u8, local_id: u32,
+    buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, DCERPCTransaction);
+    stub_multi_get(tx.stub_call.as_ref(), local_id, buffer, buffer_len, |c| {
+        &c.account_names
+    })
+}
+
+/// Matches if any of the RIDs of the decoded request matches.
+unsafe extern "C" fn dcerpc_account_rid_match(
+    _de: *mut DetectEngineThreadCtx, f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let f = cast_pointer!(f, Flow);
+    let ctx = cast_pointer!(ctx, DetectUintData<u32>);
+    let call = if flow_get_alproto(f) == ALPROTO_DCERPC {
+        let tx = cast_pointer!(tx, DCERPCTransaction);
+        tx.stub_call.as_ref()
+    } else {
+        let tx = cast_pointer!(tx, SMBTransaction);
+        smb_stub_call(tx)
+    };
+    if let Some(call) = call {
+        if call
+            .account_rids
+            .iter()
+            .any(|rid| detect_match_uint(ctx, *rid))
+        {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dcerpc_account_rid_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DCERPC) != 0 {
+        return -1;
+    }
+    let ctx = SCDetectU32Parse(raw) as *mut c_void;
+    if ctx.is_null() {
+        return -1;
+    }
+    if SCSigMatchAppendSMToList(
+        de,
+        s,
+        G_DCERPC_ACCOUNT_RID_KW_ID,
+        ctx as *mut SigMatchCtx,
+        G_DCERPC_ACCOUNT_RID_BUFFER_ID,
+    )
+    .is_null()
+    {
+        dcerpc_account_rid_free(ptr::null_mut(), ctx);
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dcerpc_account_rid_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    let ctx = cast_pointer!(ctx, DetectUintData<u32>);
+    SCDetectU32Free(ctx);
+}
+
+type StubSetupFn =
+    unsafe extern "C" fn(*mut DetectEngineCtx, *mut Signature, *const c_char) -> c_int;
+type StubGetFn = unsafe extern "C" fn(*const c_void, u8, *mut *const u8, *mut u32) -> bool;
+type StubMultiGetFn = unsafe extern "C" fn(
+    *mut DetectEngineThreadCtx,
+    *const c_void,
+    u8,
+    u32,
+    *mut *const u8,
+    *mut u32,
+) -> bool;
+
+unsafe fn dcerpc_stub_keyword_register(name: &str, desc: &str, setup: StubSetupFn) {
+    let kw = SigTableElmtStickyBuffer {
+        name: name.to_string(),
+        desc: format!("sticky buffer to match on the {}", desc),
+        url: format!(
+            "/rules/dcerpc-keywords.html#{}",
+            name.replace(['.', '_'], "-")
+        ),
+        setup,
+    };
+    let _kw_id = helper_keyword_register_sticky_buffer(&kw);
+}
+
+/// Registers a sticky buffer on a field of the decoded requests, for SMB
+/// and DCERPC. The buffer name is the keyword name.
+unsafe fn dcerpc_stub_register(
+    name: &str, desc: &str, setup: StubSetupFn, smb_get: StubGetFn, dcerpc_get: StubGetFn,
+) -> c_int {
+    dcerpc_stub_keyword_register(name, desc, setup);
+    let name = std::ffi::CString::new(name).unwrap();
+    let desc = std::ffi::CString::new(desc).unwrap();
+    SCDetectHelperBufferMpmRegister(
+        name.as_ptr(),
+        desc.as_ptr(),
+        ALPROTO_SMB,
+        STREAM_TOSERVER,
+        Some(smb_get),
+    );
+    let buffer_id = SCDetectHelperBufferMpmRegister(
+        name.as_ptr(),
+        desc.as_ptr(),
+        ALPROTO_DCERPC,
+        STREAM_TOSERVER,
+        Some(dcerpc_get),
+    );
+    return buffer_id;
+}
+
+/// Registers a multi buffer sticky buffer on a list of the decoded
+/// requests, for SMB and DCERPC.
+unsafe fn dcerpc_stub_multi_register(
+    name: &str, desc: &str, setup: StubSetupFn, smb_get: StubMultiGetFn, dcerpc_get: StubMultiGetFn,
+) -> c_int {
+    dcerpc_stub_keyword_register(name, desc, setup);
+    let name = std::ffi::CString::new(name).unwrap();
+    let desc = std::ffi::CString::new(desc).unwrap();
+    SCDetectHelperMultiBufferMpmRegister(
+        name.as_ptr(),
+        desc.as_ptr(),
+        ALPROTO_SMB,
+        STREAM_TOSERVER,
+        Some(smb_get),
+    );
+    let buffer_id = SCDetectHelperMultiBufferMpmRegister(
+        name.as_ptr(),
+        desc.as_ptr(),
+        ALPROTO_DCERPC,
+        STREAM_TOSERVER,
+        Some(dcerpc_get),
+    );
+    return buffer_id;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCDetectDcerpcRegister() {
+    G_DCERPC_SERVICE_NAME_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.service_name",
+        "service name of SVCCTL requests",
+        dcerpc_service_name_setup,
+        smb_service_name_get,
+        dcerpc_service_name_get,
+    );
+    G_DCERPC_DISPLAY_NAME_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.display_name",
+        "service display name of SVCCTL requests",
+        dcerpc_display_name_setup,
+        smb_display_name_get,
+        dcerpc_display_name_get,
+    );
+    G_DCERPC_BINARY_PATH_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.binary_path",
+        "service binary path of SVCCTL requests",
+        dcerpc_binary_path_setup,
+        smb_binary_path_get,
+        dcerpc_binary_path_get,
+    );
+    G_DCERPC_START_NAME_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.start_name",
+        "service account of SVCCTL requests",
+        dcerpc_start_name_setup,
+        smb_start_name_get,
+        dcerpc_start_name_get,
+    );
+    G_DCERPC_TASK_NAME_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.task_name",
+        "task name of scheduled task requests",
+        dcerpc_task_name_setup,
+        smb_task_name_get,
+        dcerpc_task_name_get,
+    );
+    G_DCERPC_TASK_XML_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.task_xml",
+        "task XML of scheduled task registrations",
+        dcerpc_task_xml_setup,
+        smb_task_xml_get,
+        dcerpc_task_xml_get,
+    );
+    G_DCERPC_TASK_COMMAND_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.task_command",
+        "command of ATSVC jobs",
+        dcerpc_task_command_setup,
+        smb_task_command_get,
+        dcerpc_task_command_get,
+    );
+    G_DCERPC_REGISTRY_KEY_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.registry_key",
+        "registry key of WINREG requests",
+        dcerpc_registry_key_setup,
+        smb_registry_key_get,
+        dcerpc_registry_key_get,
+    );
+    G_DCERPC_REGISTRY_VALUE_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.registry_value",
+        "registry value name of WINREG requests",
+        dcerpc_registry_value_setup,
+        smb_registry_value_get,
+        dcerpc_registry_value_get,
+    );
+    G_DCERPC_REGISTRY_DATA_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.registry_data",
+        "registry value data of WINREG requests",
+        dcerpc_registry_data_setup,
+        smb_registry_data_get,
+        dcerpc_registry_data_get,
+    );
+    G_DCERPC_REGISTRY_FILE_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.registry_file",
+        "registry hive file of WINREG save requests",
+        dcerpc_registry_file_setup,
+        smb_registry_file_get,
+        dcerpc_registry_file_get,
+    );
+    G_DCERPC_WMI_NAMESPACE_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.wmi_namespace",
+        "namespace of WMI logins",
+        dcerpc_wmi_namespace_setup,
+        smb_wmi_namespace_get,
+        dcerpc_wmi_namespace_get,
+    );
+    G_DCERPC_WMI_OBJECT_PATH_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.wmi_object_path",
+        "object path of WMI requests",
+        dcerpc_wmi_object_path_setup,
+        smb_wmi_object_path_get,
+        dcerpc_wmi_object_path_get,
+    );
+    G_DCERPC_WMI_METHOD_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.wmi_method",
+        "method of WMI requests",
+        dcerpc_wmi_method_setup,
+        smb_wmi_method_get,
+        dcerpc_wmi_method_get,
+    );
+    G_DCERPC_WMI_QUERY_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.wmi_query",
+        "query of WMI requests",
+        dcerpc_wmi_query_setup,
+        smb_wmi_query_get,
+        dcerpc_wmi_query_get,
+    );
+    G_DCERPC_REPLICATED_OBJECT_BUFFER_ID = dcerpc_stub_register(
+        "dcerpc.replicated_object",
+        "object to replicate of DRSUAPI requests",
+        dcerpc_replicated_object_setup,
+        smb_replicated_object_get,
+        dcerpc_replicated_object_get,
+    );
+    G_DCERPC_REQUESTED_ATTRIBUTE_BUFFER_ID = dcerpc_stub_multi_register(
+        "dcerpc.requested_attribute",
+        "attributes to replicate of DRSUAPI requests",
+        dcerpc_requested_attribute_setup,
+        smb_requested_attribute_get,
+        dcerpc_requested_attribute_get,
+    );
+    G_DCERPC_ACCOUNT_NAME_BUFFER_ID = dcerpc_stub_multi_register(
+        "dcerpc.account_name",
+        "account names of DRSUAPI and SAMR requests",
+        dcerpc_account_name_setup,
+        smb_account_name_get,
+        dcerpc_account_name_get,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dcerpc.account_rid\0".as_ptr() as *const c_char,
+        desc: b"match on the account RIDs of SAMR requests\0".as_ptr() as *const c_char,
+        url: b"/rules/dcerpc-keywords.html#dcerpc-account-rid\0".as_ptr() as *const c_char,
+        AppLayerTxMatch: Some(dcerpc_account_rid_match),
+        Setup: Some(dcerpc_account_rid_setup),
+        Free: Some(dcerpc_account_rid_free),
+        flags: 0,
+    };
+    G_DCERPC_ACCOUNT_RID_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    SCDetectHelperBufferRegister(
+        b"dcerpc.account_rid\0".as_ptr() as *const c_char,
+        ALPROTO_SMB,
+        STREAM_TOSERVER,
+    );
+    G_DCERPC_ACCOUNT_RID_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dcerpc.account_rid\0".as_ptr() as *const c_char,
+        ALPROTO_DCERPC,
+        STREAM_TOSERVER,
+    );
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
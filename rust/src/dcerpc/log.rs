@@ -18,6 +18,7 @@ use uuid::Uuid;
 
 use crate::dcerpc::dcerpc::*;
 use crate::dcerpc::dcerpc_udp::*;
+use crate::dcerpc::stub::DCERPCStubCall;
 use crate::jsonbuilder::{JsonBuilder, JsonError};
 
 fn log_bind_interfaces(jsb: &mut JsonBuilder, state: &DCERPCState) -> Result<(), JsonError> {
@@ -51,6 +52,9 @@ fn log_dcerpc_header_tcp(
                 jsb.set_uint("stub_data_size", tx.stub_data_buffer_ts.len() as u64)?;
                 jsb.close()?;
                 log_bind_interfaces(jsb, state)?;
+                if let Some(ref call) = tx.stub_call {
+                    log_dcerpc_stub_call(jsb, call)?;
+                }
             }
             DCERPC_TYPE_BIND => log_bind_interfaces(jsb, state)?,
             _ => {}
@@ -125,6 +129,81 @@ fn log_dcerpc_header_udp(
     return Ok(());
 }
 
+fn service_start_type_string(t: u32) -> String {
+    match t {
+        0 => "boot".to_string(),
+        1 => "system".to_string(),
+        2 => "auto".to_string(),
+        3 => "demand".to_string(),
+        4 => "disabled".to_string(),
+        _ => t.to_string(),
+    }
+}
+
+fn log_stub_field(jsb: &mut JsonBuilder, key: &str, val: &[u8]) -> Result<(), JsonError> {
+    if !val.is_empty() {
+        jsb.set_string_from_bytes(key, val)?;
+    }
+    return Ok(());
+}
+
+fn log_stub_list(jsb: &mut JsonBuilder, key: &str, val: &[Vec<u8>]) -> Result<(), JsonError> {
+    if !val.is_empty() {
+        jsb.open_array(key)?;
+        for v in val {
+            jsb.append_string_from_bytes(v)?;
+        }
+        jsb.close()?;
+    }
+    return Ok(());
+}
+
+/// Log the decoded request of a known interface, in an object named after
+/// the interface.
+pub fn log_dcerpc_stub_call(jsb: &mut JsonBuilder, call: &DCERPCStubCall) -> Result<(), JsonError> {
+    jsb.open_object(call.interface)?;
+    jsb.set_string("operation", call.operation)?;
+    log_stub_field(jsb, "service_name", &call.service_name)?;
+    log_stub_field(jsb, "display_name", &call.display_name)?;
+    log_stub_field(jsb, "binary_path", &call.binary_path)?;
+    log_stub_field(jsb, "start_name", &call.start_name)?;
+    if let Some(t) = call.service_type {
+        jsb.set_uint("service_type", t)?;
+    }
+    if let Some(t) = call.start_type {
+        jsb.set_string("start_type", &service_start_type_string(t))?;
+    }
+    log_stub_field(jsb, "task_name", &call.task_name)?;
+    log_stub_field(jsb, "task_xml", &call.task_xml)?;
+    log_stub_field(jsb, "task_command", &call.task_command)?;
+    log_stub_field(jsb, "registry_key", &call.registry_key)?;
+    log_stub_field(jsb, "registry_value", &call.registry_value)?;
+    log_stub_field(jsb, "registry_data", &call.registry_data)?;
+    log_stub_field(jsb, "registry_file", &call.registry_file)?;
+    log_stub_field(jsb, "wmi_namespace", &call.wmi_namespace)?;
+    log_stub_field(jsb, "wmi_object_path", &call.wmi_object_path)?;
+    log_stub_field(jsb, "wmi_method", &call.wmi_method)?;
+    log_stub_field(jsb, "wmi_query", &call.wmi_query)?;
+    log_stub_field(jsb, "replicated_object", &call.replicated_object)?;
+    log_stub_field(jsb, "replicated_object_guid", &call.replicated_object_guid)?;
+    if let Some(op) = call.extended_operation {
+        jsb.set_uint("extended_operation", op)?;
+    }
+    log_stub_list(jsb, "requested_attributes", &call.requested_attributes)?;
+    log_stub_field(jsb, "domain_name", &call.domain_name)?;
+    log_stub_field(jsb, "domain_sid", &call.domain_sid)?;
+    log_stub_list(jsb, "account_names", &call.account_names)?;
+    if !call.account_rids.is_empty() {
+        jsb.open_array("account_rids")?;
+        for rid in &call.account_rids {
+            jsb.append_uint(*rid as u64)?;
+        }
+        jsb.close()?;
+    }
+    jsb.close()?;
+    return Ok(());
+}
+
 #[no_mangle]
 pub extern "C" fn SCDcerpcLogJsonRecordTcp(
     state: &DCERPCState, tx: &DCERPCTransaction, jsb: &mut JsonBuilder,
@@ -22,3 +22,5 @@ pub mod dcerpc_udp;
 pub mod parser;
 pub mod detect;
 pub mod log;
+pub mod ndr;
+pub mod stub;
@@ -0,0 +1,213 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Little endian NDR20 reader for DCERPC stub data.
+//!
+//! NDR primitives are aligned on their size relative to the start of the
+//! stub, so the reader keeps track of its offset instead of using nom
+//! parsers.
+
+/// Size of a context handle.
+pub const NDR_CONTEXT_HANDLE_LEN: usize = 20;
+
+/// Max number of characters read from a single string.
+const NDR_MAX_STRING_LEN: usize = 65536;
+
+pub struct NdrReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> NdrReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    /// Align on `n` bytes, for the structures that are aligned on their
+    /// largest member.
+    pub fn align(&mut self, n: usize) -> Option<()> {
+        let pad = (n - self.offset % n) % n;
+        if self.offset + pad > self.data.len() {
+            return None;
+        }
+        self.offset += pad;
+        Some(())
+    }
+
+    pub fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.offset.checked_add(n)?;
+        let b = self.data.get(self.offset..end)?;
+        self.offset = end;
+        Some(b)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        self.align(2)?;
+        let b = self.bytes(2)?;
+        Some(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        self.align(4)?;
+        let b = self.bytes(4)?;
+        Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        self.align(8)?;
+        let b = self.bytes(8)?;
+        Some(u64::from_le_bytes([
+            b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7],
+        ]))
+    }
+
+    /// Read a referent id, returns whether the pointer is not null.
+    pub fn pointer(&mut self) -> Option<bool> {
+        Some(self.u32()? != 0)
+    }
+
+    pub fn context_handle(&mut self) -> Option<&'a [u8]> {
+        self.align(4)?;
+        self.bytes(NDR_CONTEXT_HANDLE_LEN)
+    }
+
+    /// Conformant array of bytes.
+    pub fn byte_array(&mut self) -> Option<&'a [u8]> {
+        let max_count = self.u32()? as usize;
+        self.bytes(max_count)
+    }
+
+    /// Conformant varying string of UTF-16 characters, returned as UTF-8
+    /// without the terminating null characters.
+    pub fn wstring(&mut self) -> Option<Vec<u8>> {
+        let _max_count = self.u32()?;
+        let _offset = self.u32()?;
+        let actual_count = self.u32()? as usize;
+        self.utf16(actual_count)
+    }
+
+    /// Conformant varying string of 8 bit characters.
+    pub fn string(&mut self) -> Option<Vec<u8>> {
+        let _max_count = self.u32()?;
+        let _offset = self.u32()?;
+        let actual_count = self.u32()? as usize;
+        if actual_count > NDR_MAX_STRING_LEN {
+            return None;
+        }
+        let mut s = self.bytes(actual_count)?.to_vec();
+        while s.last() == Some(&0) {
+            s.pop();
+        }
+        Some(s)
+    }
+
+    /// Unique pointer to a conformant varying UTF-16 string. A null
+    /// pointer gives an empty string.
+    pub fn unique_wstring(&mut self) -> Option<Vec<u8>> {
+        if self.pointer()? {
+            self.wstring()
+        } else {
+            Some(Vec::new())
+        }
+    }
+
+    pub fn unique_string(&mut self) -> Option<Vec<u8>> {
+        if self.pointer()? {
+            self.string()
+        } else {
+            Some(Vec::new())
+        }
+    }
+
+    /// RPC_UNICODE_STRING, the counted string with its buffer pointer
+    /// deferred after the structure.
+    pub fn unicode_string(&mut self) -> Option<Vec<u8>> {
+        let _length = self.u16()?;
+        let _max_length = self.u16()?;
+        self.unique_wstring()
+    }
+
+    /// BSTR, pointer to a FLAGGED_WORD_BLOB.
+    pub fn bstr(&mut self) -> Option<Vec<u8>> {
+        if !self.pointer()? {
+            return Some(Vec::new());
+        }
+        let _max_count = self.u32()?;
+        let _flags = self.u32()?;
+        let size = self.u32()? as usize;
+        self.utf16(size)
+    }
+
+    /// ORPCTHIS header in front of the parameters of DCOM calls. Calls
+    /// with extensions are not supported.
+    pub fn orpcthis(&mut self) -> Option<()> {
+        let _version = self.u32()?;
+        let _flags = self.u32()?;
+        let _reserved = self.u32()?;
+        let _cid = self.bytes(16)?;
+        if self.pointer()? {
+            return None;
+        }
+        Some(())
+    }
+
+    fn utf16(&mut self, count: usize) -> Option<Vec<u8>> {
+        if count > NDR_MAX_STRING_LEN {
+            return None;
+        }
+        let b = self.bytes(count * 2)?;
+        Some(ndr_utf16_to_utf8(b))
+    }
+}
+
+/// Convert UTF-16 data to UTF-8, without the terminating null characters.
+pub fn ndr_utf16_to_utf8(b: &[u8]) -> Vec<u8> {
+    let mut s: Vec<u16> = b
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    while s.last() == Some(&0) {
+        s.pop();
+    }
+    String::from_utf16_lossy(&s).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ndr_reader() {
+        let data = [
+            0x01, 0x00, 0x00, 0x00, // referent id
+            0x03, 0x00, 0x00, 0x00, // max count
+            0x00, 0x00, 0x00, 0x00, // offset
+            0x03, 0x00, 0x00, 0x00, // actual count
+            0x61, 0x00, 0x62, 0x00, 0x00, 0x00, // "ab\0"
+            0x00, 0x00, // padding
+            0x2a, 0x00, 0x00, 0x00,
+        ];
+        let mut r = NdrReader::new(&data);
+        assert_eq!(r.unique_wstring(), Some(b"ab".to_vec()));
+        assert_eq!(r.u32(), Some(42));
+        assert_eq!(r.u8(), None);
+    }
+}
@@ -0,0 +1,1082 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Decoding of the request stub data of the interfaces used to run code
+//! on remote hosts: service control manager (SVCCTL), scheduled tasks
+//! (ATSVC and ITaskSchedulerService), remote registry (WINREG) and WMI,
+//! and of the interfaces used to dump credentials and enumerate accounts:
+//! directory replication (DRSUAPI) and the security account manager
+//! (SAMR).
+
+use crate::dcerpc::ndr::*;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+
+// Interface UUIDs, in the byte order of their string form.
+pub const DCERPC_UUID_SVCCTL: [u8; 16] = [
+    0x36, 0x7a, 0xbb, 0x81, 0x98, 0x44, 0x35, 0xf1, 0xad, 0x32, 0x98, 0xf0, 0x38, 0x00, 0x10, 0x03,
+];
+pub const DCERPC_UUID_ATSVC: [u8; 16] = [
+    0x1f, 0xf7, 0x06, 0x82, 0x0a, 0x51, 0x30, 0xe8, 0x07, 0x6d, 0x74, 0x0b, 0xe8, 0xce, 0xe9, 0x8b,
+];
+pub const DCERPC_UUID_TSCH: [u8; 16] = [
+    0x86, 0xd3, 0x59, 0x49, 0x83, 0xc9, 0x40, 0x44, 0xb4, 0x24, 0xdb, 0x36, 0x32, 0x31, 0xfd, 0x0c,
+];
+pub const DCERPC_UUID_WINREG: [u8; 16] = [
+    0x33, 0x8c, 0xd0, 0x01, 0x22, 0x44, 0x31, 0xf1, 0xaa, 0xaa, 0x90, 0x00, 0x38, 0x00, 0x10, 0x03,
+];
+pub const DCERPC_UUID_IWBEMLEVEL1LOGIN: [u8; 16] = [
+    0xf3, 0x09, 0xad, 0x18, 0xd8, 0x6a, 0x11, 0xd0, 0xa0, 0x75, 0x00, 0xc0, 0x4f, 0xb6, 0x88, 0x20,
+];
+pub const DCERPC_UUID_IWBEMSERVICES: [u8; 16] = [
+    0x95, 0x56, 0xdc, 0x99, 0x82, 0x8c, 0x11, 0xcf, 0xa3, 0x7e, 0x00, 0xaa, 0x00, 0x32, 0x40, 0xc7,
+];
+pub const DCERPC_UUID_DRSUAPI: [u8; 16] = [
+    0xe3, 0x51, 0x42, 0x35, 0x4b, 0x06, 0x11, 0xd1, 0xab, 0x04, 0x00, 0xc0, 0x4f, 0xc2, 0xdc, 0xd2,
+];
+pub const DCERPC_UUID_SAMR: [u8; 16] = [
+    0x12, 0x34, 0x57, 0x78, 0x12, 0x34, 0xab, 0xcd, 0xef, 0x00, 0x01, 0x23, 0x45, 0x67, 0x89, 0xac,
+];
+
+// SVCCTL opnums
+const SVCCTL_DELETE_SERVICE: u16 = 2;
+const SVCCTL_CHANGE_SERVICE_CONFIG_W: u16 = 11;
+const SVCCTL_CREATE_SERVICE_W: u16 = 12;
+const SVCCTL_OPEN_SERVICE_W: u16 = 16;
+const SVCCTL_START_SERVICE_W: u16 = 19;
+const SVCCTL_CREATE_SERVICE_A: u16 = 24;
+const SVCCTL_OPEN_SERVICE_A: u16 = 28;
+const SVCCTL_START_SERVICE_A: u16 = 31;
+
+// ATSVC opnums
+const ATSVC_JOB_ADD: u16 = 0;
+
+// ITaskSchedulerService opnums
+const TSCH_REGISTER_TASK: u16 = 1;
+const TSCH_RUN: u16 = 12;
+const TSCH_DELETE: u16 = 13;
+
+// WINREG opnums
+const WINREG_OPEN_CLASSES_ROOT: u16 = 0;
+const WINREG_OPEN_CURRENT_USER: u16 = 1;
+const WINREG_OPEN_LOCAL_MACHINE: u16 = 2;
+const WINREG_OPEN_USERS: u16 = 4;
+const WINREG_CREATE_KEY: u16 = 6;
+const WINREG_DELETE_KEY: u16 = 7;
+const WINREG_DELETE_VALUE: u16 = 8;
+const WINREG_OPEN_KEY: u16 = 15;
+const WINREG_QUERY_VALUE: u16 = 17;
+const WINREG_SAVE_KEY: u16 = 20;
+const WINREG_SET_VALUE: u16 = 22;
+const WINREG_OPEN_CURRENT_CONFIG: u16 = 27;
+const WINREG_SAVE_KEY_EX: u16 = 35;
+
+const REG_SZ: u32 = 1;
+const REG_EXPAND_SZ: u32 = 2;
+
+// IWbemLevel1Login opnums
+const WBEM_NTLM_LOGIN: u16 = 6;
+
+// IWbemServices opnums
+const WBEM_GET_OBJECT: u16 = 6;
+const WBEM_EXEC_QUERY: u16 = 20;
+const WBEM_EXEC_QUERY_ASYNC: u16 = 21;
+const WBEM_EXEC_METHOD: u16 = 24;
+const WBEM_EXEC_METHOD_ASYNC: u16 = 25;
+
+// DRSUAPI opnums
+const DRS_GET_NC_CHANGES: u16 = 3;
+const DRS_CRACK_NAMES: u16 = 12;
+
+// SAMR opnums
+const SAMR_LOOKUP_DOMAIN_IN_SAM_SERVER: u16 = 5;
+const SAMR_ENUMERATE_DOMAINS_IN_SAM_SERVER: u16 = 6;
+const SAMR_OPEN_DOMAIN: u16 = 7;
+const SAMR_ENUMERATE_GROUPS_IN_DOMAIN: u16 = 11;
+const SAMR_ENUMERATE_USERS_IN_DOMAIN: u16 = 13;
+const SAMR_ENUMERATE_ALIASES_IN_DOMAIN: u16 = 15;
+const SAMR_LOOKUP_NAMES_IN_DOMAIN: u16 = 17;
+const SAMR_LOOKUP_IDS_IN_DOMAIN: u16 = 18;
+const SAMR_OPEN_GROUP: u16 = 19;
+const SAMR_GET_MEMBERS_IN_GROUP: u16 = 25;
+const SAMR_OPEN_ALIAS: u16 = 27;
+const SAMR_GET_MEMBERS_IN_ALIAS: u16 = 33;
+const SAMR_OPEN_USER: u16 = 34;
+const SAMR_QUERY_INFORMATION_USER: u16 = 36;
+const SAMR_GET_GROUPS_FOR_USER: u16 = 39;
+const SAMR_QUERY_DISPLAY_INFORMATION: u16 = 40;
+const SAMR_QUERY_INFORMATION_USER2: u16 = 47;
+const SAMR_QUERY_DISPLAY_INFORMATION2: u16 = 48;
+const SAMR_QUERY_DISPLAY_INFORMATION3: u16 = 51;
+
+/// Max number of names, RIDs or attributes read from a single request.
+const DCERPC_STUB_MAX_ITEMS: usize = 1000;
+
+/// OID prefixes of the default prefix table of MS-DRSR, used to resolve
+/// the attributes of requests without their own prefix table.
+const DRS_DEFAULT_PREFIXES: &[(u32, &[u8])] = &[
+    (0, &[0x55, 0x04]),                                      // 2.5.4
+    (1, &[0x55, 0x06]),                                      // 2.5.6
+    (2, &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x14, 0x01, 0x02]),  // 1.2.840.113556.1.2
+    (3, &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x14, 0x01, 0x03]),  // 1.2.840.113556.1.3
+    (8, &[0x55, 0x05]),                                      // 2.5.5
+    (9, &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x14, 0x01, 0x04]),  // 1.2.840.113556.1.4
+    (10, &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x14, 0x01, 0x05]), // 1.2.840.113556.1.5
+];
+
+/// Max number of handles named per connection.
+const DCERPC_HANDLE_NAMES_SIZE: usize = 256;
+
+/// Decoded request of a known interface. Fields not used by the operation
+/// are left empty.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DCERPCStubCall {
+    /// Short name of the interface: svcctl, atsvc, tsch, winreg or wmi.
+    pub interface: &'static str,
+    pub operation: &'static str,
+
+    pub service_name: Vec<u8>,
+    pub display_name: Vec<u8>,
+    pub binary_path: Vec<u8>,
+    pub start_name: Vec<u8>,
+    pub service_type: Option<u32>,
+    pub start_type: Option<u32>,
+
+    pub task_name: Vec<u8>,
+    pub task_xml: Vec<u8>,
+    pub task_command: Vec<u8>,
+
+    pub registry_key: Vec<u8>,
+    pub registry_value: Vec<u8>,
+    pub registry_data: Vec<u8>,
+    pub registry_file: Vec<u8>,
+
+    pub wmi_namespace: Vec<u8>,
+    pub wmi_object_path: Vec<u8>,
+    pub wmi_method: Vec<u8>,
+    pub wmi_query: Vec<u8>,
+
+    /// Distinguished name of the naming context or object to replicate.
+    pub replicated_object: Vec<u8>,
+    pub replicated_object_guid: Vec<u8>,
+    pub extended_operation: Option<u32>,
+    /// LDAP names of the attributes to replicate, or their OID when the
+    /// attribute is not known.
+    pub requested_attributes: Vec<Vec<u8>>,
+
+    pub domain_name: Vec<u8>,
+    pub domain_sid: Vec<u8>,
+    /// Names to translate or look up.
+    pub account_names: Vec<Vec<u8>>,
+    pub account_rids: Vec<u32>,
+
+    /// Whether the response gives a handle to the service, registry key or
+    /// account named by the request.
+    opens_handle: bool,
+}
+
+/// Names of the service and registry key handles and RIDs of the account
+/// handles opened on a connection, so that the calls using them can be
+/// logged with the name.
+#[derive(Debug)]
+pub struct DCERPCHandleNames {
+    cache: LruCache<Vec<u8>, Vec<u8>>,
+    rids: LruCache<Vec<u8>, u32>,
+}
+
+impl Default for DCERPCHandleNames {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DCERPCHandleNames {
+    pub fn new() -> Self {
+        Self {
+            cache: LruCache::new(NonZeroUsize::new(DCERPC_HANDLE_NAMES_SIZE).unwrap()),
+            rids: LruCache::new(NonZeroUsize::new(DCERPC_HANDLE_NAMES_SIZE).unwrap()),
+        }
+    }
+
+    fn name(&self, handle: &[u8]) -> Vec<u8> {
+        self.cache.peek(handle).cloned().unwrap_or_default()
+    }
+
+    fn rid(&self, handle: &[u8]) -> Vec<u32> {
+        self.rids.peek(handle).into_iter().copied().collect()
+    }
+
+    /// Name the handle returned in the response to a call opening a
+    /// service or a registry key.
+    pub fn update(&mut self, call: &DCERPCStubCall, response: &[u8]) {
+        if !call.opens_handle {
+            return;
+        }
+        if let Some(handle) = parse_open_response(call.operation, response) {
+            if call.interface == "samr" {
+                if let Some(rid) = call.account_rids.first() {
+                    self.rids.put(handle.to_vec(), *rid);
+                }
+                return;
+            }
+            let name = if call.interface == "svcctl" {
+                &call.service_name
+            } else {
+                &call.registry_key
+            };
+            self.cache.put(handle.to_vec(), name.to_vec());
+        }
+    }
+}
+
+fn parse_open_response<'a>(operation: &str, response: &'a [u8]) -> Option<&'a [u8]> {
+    let mut r = NdrReader::new(response);
+    if operation.starts_with("RCreateService") && r.pointer()? {
+        let _tag_id = r.u32()?;
+    }
+    let handle = r.context_handle()?;
+    if operation == "BaseRegCreateKey" && r.pointer()? {
+        let _disposition = r.u32()?;
+    }
+    let status = r.u32()?;
+    if status != 0 {
+        return None;
+    }
+    Some(handle)
+}
+
+/// Decode the stub data of a request to one of the known interfaces.
+pub fn dcerpc_stub_decode_request(
+    uuid: &[u8], opnum: u16, stub: &[u8], handles: &DCERPCHandleNames,
+) -> Option<DCERPCStubCall> {
+    let mut call = DCERPCStubCall::default();
+    let mut r = NdrReader::new(stub);
+    // The operation is set before the parameters are read, so that a
+    // truncated request still gives the parameters read so far.
+    if uuid == DCERPC_UUID_SVCCTL {
+        call.interface = "svcctl";
+        let _ = decode_svcctl(&mut call, opnum, &mut r, handles);
+    } else if uuid == DCERPC_UUID_ATSVC {
+        call.interface = "atsvc";
+        let _ = decode_atsvc(&mut call, opnum, &mut r);
+    } else if uuid == DCERPC_UUID_TSCH {
+        call.interface = "tsch";
+        let _ = decode_tsch(&mut call, opnum, &mut r);
+    } else if uuid == DCERPC_UUID_WINREG {
+        call.interface = "winreg";
+        let _ = decode_winreg(&mut call, opnum, &mut r, handles);
+    } else if uuid == DCERPC_UUID_IWBEMLEVEL1LOGIN || uuid == DCERPC_UUID_IWBEMSERVICES {
+        call.interface = "wmi";
+        let _ = decode_wmi(&mut call, uuid, opnum, &mut r);
+    } else if uuid == DCERPC_UUID_DRSUAPI {
+        call.interface = "drsuapi";
+        let _ = decode_drsuapi(&mut call, opnum, &mut r);
+    } else if uuid == DCERPC_UUID_SAMR {
+        call.interface = "samr";
+        let _ = decode_samr(&mut call, opnum, &mut r, handles);
+    }
+    if call.operation.is_empty() {
+        return None;
+    }
+    Some(call)
+}
+
+fn decode_svcctl(
+    call: &mut DCERPCStubCall, opnum: u16, r: &mut NdrReader, handles: &DCERPCHandleNames,
+) -> Option<()> {
+    match opnum {
+        SVCCTL_CREATE_SERVICE_W | SVCCTL_CREATE_SERVICE_A => {
+            let wide = opnum == SVCCTL_CREATE_SERVICE_W;
+            call.operation = if wide {
+                "RCreateServiceW"
+            } else {
+                "RCreateServiceA"
+            };
+            call.opens_handle = true;
+            let _scm = r.context_handle()?;
+            call.service_name = if wide { r.wstring()? } else { r.string()? };
+            call.display_name = if wide {
+                r.unique_wstring()?
+            } else {
+                r.unique_string()?
+            };
+            let _desired_access = r.u32()?;
+            call.service_type = Some(r.u32()?);
+            call.start_type = Some(r.u32()?);
+            let _error_control = r.u32()?;
+            call.binary_path = if wide { r.wstring()? } else { r.string()? };
+            let _load_order_group = if wide {
+                r.unique_wstring()?
+            } else {
+                r.unique_string()?
+            };
+            if r.pointer()? {
+                let _tag_id = r.u32()?;
+            }
+            if r.pointer()? {
+                let _dependencies = r.byte_array()?;
+            }
+            let _depend_size = r.u32()?;
+            call.start_name = if wide {
+                r.unique_wstring()?
+            } else {
+                r.unique_string()?
+            };
+        }
+        SVCCTL_OPEN_SERVICE_W | SVCCTL_OPEN_SERVICE_A => {
+            let wide = opnum == SVCCTL_OPEN_SERVICE_W;
+            call.operation = if wide {
+                "ROpenServiceW"
+            } else {
+                "ROpenServiceA"
+            };
+            call.opens_handle = true;
+            let _scm = r.context_handle()?;
+            call.service_name = if wide { r.wstring()? } else { r.string()? };
+        }
+        SVCCTL_START_SERVICE_W | SVCCTL_START_SERVICE_A => {
+            call.operation = if opnum == SVCCTL_START_SERVICE_W {
+                "RStartServiceW"
+            } else {
+                "RStartServiceA"
+            };
+            call.service_name = handles.name(r.context_handle()?);
+        }
+        SVCCTL_DELETE_SERVICE => {
+            call.operation = "RDeleteService";
+            call.service_name = handles.name(r.context_handle()?);
+        }
+        SVCCTL_CHANGE_SERVICE_CONFIG_W => {
+            call.operation = "RChangeServiceConfigW";
+            call.service_name = handles.name(r.context_handle()?);
+            call.service_type = Some(r.u32()?);
+            call.start_type = Some(r.u32()?);
+            let _error_control = r.u32()?;
+            call.binary_path = r.unique_wstring()?;
+        }
+        _ => {}
+    }
+    Some(())
+}
+
+fn decode_atsvc(call: &mut DCERPCStubCall, opnum: u16, r: &mut NdrReader) -> Option<()> {
+    if opnum == ATSVC_JOB_ADD {
+        call.operation = "NetrJobAdd";
+        let _server_name = r.unique_wstring()?;
+        // AT_INFO, with the command pointer deferred after the structure
+        let _job_time = r.u32()?;
+        let _days_of_month = r.u32()?;
+        let _days_of_week = r.u8()?;
+        let _flags = r.u8()?;
+        if r.pointer()? {
+            call.task_command = r.wstring()?;
+        }
+    }
+    Some(())
+}
+
+fn decode_tsch(call: &mut DCERPCStubCall, opnum: u16, r: &mut NdrReader) -> Option<()> {
+    match opnum {
+        TSCH_REGISTER_TASK => {
+            call.operation = "SchRpcRegisterTask";
+            call.task_name = r.unique_wstring()?;
+            call.task_xml = r.wstring()?;
+        }
+        TSCH_RUN => {
+            call.operation = "SchRpcRun";
+            call.task_name = r.wstring()?;
+        }
+        TSCH_DELETE => {
+            call.operation = "SchRpcDelete";
+            call.task_name = r.wstring()?;
+        }
+        _ => {}
+    }
+    Some(())
+}
+
+fn registry_subkey(parent: Vec<u8>, subkey: Vec<u8>) -> Vec<u8> {
+    if parent.is_empty() {
+        return subkey;
+    }
+    if subkey.is_empty() {
+        return parent;
+    }
+    let mut key = parent;
+    key.push(b'\\');
+    key.extend_from_slice(&subkey);
+    return key;
+}
+
+fn decode_winreg(
+    call: &mut DCERPCStubCall, opnum: u16, r: &mut NdrReader, handles: &DCERPCHandleNames,
+) -> Option<()> {
+    match opnum {
+        WINREG_OPEN_CLASSES_ROOT
+        | WINREG_OPEN_CURRENT_USER
+        | WINREG_OPEN_LOCAL_MACHINE
+        | WINREG_OPEN_USERS
+        | WINREG_OPEN_CURRENT_CONFIG => {
+            let (operation, key) = match opnum {
+                WINREG_OPEN_CLASSES_ROOT => ("OpenClassesRoot", "HKCR"),
+                WINREG_OPEN_CURRENT_USER => ("OpenCurrentUser", "HKCU"),
+                WINREG_OPEN_LOCAL_MACHINE => ("OpenLocalMachine", "HKLM"),
+                WINREG_OPEN_USERS => ("OpenUsers", "HKU"),
+                _ => ("OpenCurrentConfig", "HKCC"),
+            };
+            call.operation = operation;
+            call.opens_handle = true;
+            call.registry_key = key.as_bytes().to_vec();
+        }
+        WINREG_CREATE_KEY | WINREG_OPEN_KEY | WINREG_DELETE_KEY => {
+            call.operation = match opnum {
+                WINREG_CREATE_KEY => "BaseRegCreateKey",
+                WINREG_OPEN_KEY => "BaseRegOpenKey",
+                _ => "BaseRegDeleteKey",
+            };
+            call.opens_handle = opnum != WINREG_DELETE_KEY;
+            let parent = handles.name(r.context_handle()?);
+            call.registry_key = registry_subkey(parent, r.unicode_string()?);
+        }
+        WINREG_DELETE_VALUE | WINREG_QUERY_VALUE | WINREG_SET_VALUE => {
+            call.operation = match opnum {
+                WINREG_DELETE_VALUE => "BaseRegDeleteValue",
+                WINREG_QUERY_VALUE => "BaseRegQueryValue",
+                _ => "BaseRegSetValue",
+            };
+            call.registry_key = handles.name(r.context_handle()?);
+            call.registry_value = r.unicode_string()?;
+            if opnum == WINREG_SET_VALUE {
+                let value_type = r.u32()?;
+                let data = r.byte_array()?;
+                if value_type == REG_SZ || value_type == REG_EXPAND_SZ {
+                    call.registry_data = ndr_utf16_to_utf8(data);
+                }
+            }
+        }
+        WINREG_SAVE_KEY | WINREG_SAVE_KEY_EX => {
+            call.operation = if opnum == WINREG_SAVE_KEY {
+                "BaseRegSaveKey"
+            } else {
+                "BaseRegSaveKeyEx"
+            };
+            call.registry_key = handles.name(r.context_handle()?);
+            call.registry_file = r.unicode_string()?;
+        }
+        _ => {}
+    }
+    Some(())
+}
+
+fn decode_wmi(call: &mut DCERPCStubCall, uuid: &[u8], opnum: u16, r: &mut NdrReader) -> Option<()> {
+    if uuid == DCERPC_UUID_IWBEMLEVEL1LOGIN {
+        if opnum == WBEM_NTLM_LOGIN {
+            call.operation = "NTLMLogin";
+            r.orpcthis()?;
+            call.wmi_namespace = r.unique_wstring()?;
+        }
+        return Some(());
+    }
+    match opnum {
+        WBEM_GET_OBJECT => {
+            call.operation = "GetObject";
+            r.orpcthis()?;
+            call.wmi_object_path = r.bstr()?;
+        }
+        WBEM_EXEC_QUERY | WBEM_EXEC_QUERY_ASYNC => {
+            call.operation = if opnum == WBEM_EXEC_QUERY {
+                "ExecQuery"
+            } else {
+                "ExecQueryAsync"
+            };
+            r.orpcthis()?;
+            let _language = r.bstr()?;
+            call.wmi_query = r.bstr()?;
+        }
+        WBEM_EXEC_METHOD | WBEM_EXEC_METHOD_ASYNC => {
+            call.operation = if opnum == WBEM_EXEC_METHOD {
+                "ExecMethod"
+            } else {
+                "ExecMethodAsync"
+            };
+            r.orpcthis()?;
+            call.wmi_object_path = r.bstr()?;
+            call.wmi_method = r.bstr()?;
+        }
+        _ => {}
+    }
+    Some(())
+}
+
+/// Format a GUID in the little endian layout of the wire.
+fn guid_string(g: &[u8]) -> Vec<u8> {
+    format!(
+        "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        g[3], g[2], g[1], g[0], g[5], g[4], g[7], g[6], g[8], g[9], g[10], g[11], g[12], g[13],
+        g[14], g[15]
+    )
+    .into_bytes()
+}
+
+/// Decode a BER encoded OID to its dotted form.
+fn oid_string(ber: &[u8]) -> Option<String> {
+    let mut arcs: Vec<u64> = Vec::new();
+    let mut v: u64 = 0;
+    for b in ber {
+        v = v.checked_mul(128)? | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = std::cmp::min(v / 40, 2);
+                arcs.push(first);
+                arcs.push(v - first * 40);
+            } else {
+                arcs.push(v);
+            }
+            v = 0;
+        }
+    }
+    if arcs.is_empty() {
+        return None;
+    }
+    let arcs: Vec<String> = arcs.iter().map(|a| a.to_string()).collect();
+    Some(arcs.join("."))
+}
+
+fn drs_attribute_ldap_name(oid: &str) -> Option<&'static str> {
+    let name = match oid {
+        "2.5.4.0" => "objectClass",
+        "2.5.4.3" => "cn",
+        "1.2.840.113556.1.4.1" => "name",
+        "1.2.840.113556.1.4.2" => "objectGUID",
+        "1.2.840.113556.1.4.8" => "userAccountControl",
+        "1.2.840.113556.1.4.27" => "currentValue",
+        "1.2.840.113556.1.4.55" => "dBCSPwd",
+        "1.2.840.113556.1.4.90" => "unicodePwd",
+        "1.2.840.113556.1.4.94" => "ntPwdHistory",
+        "1.2.840.113556.1.4.96" => "pwdLastSet",
+        "1.2.840.113556.1.4.98" => "primaryGroupID",
+        "1.2.840.113556.1.4.100" => "priorValue",
+        "1.2.840.113556.1.4.125" => "supplementalCredentials",
+        "1.2.840.113556.1.4.129" => "trustAuthIncoming",
+        "1.2.840.113556.1.4.135" => "trustAuthOutgoing",
+        "1.2.840.113556.1.4.146" => "objectSid",
+        "1.2.840.113556.1.4.159" => "accountExpires",
+        "1.2.840.113556.1.4.160" => "lmPwdHistory",
+        "1.2.840.113556.1.4.221" => "sAMAccountName",
+        "1.2.840.113556.1.4.302" => "sAMAccountType",
+        "1.2.840.113556.1.4.609" => "sIDHistory",
+        "1.2.840.113556.1.4.656" => "userPrincipalName",
+        "1.2.840.113556.1.4.1696" => "lastLogonTimestamp",
+        _ => {
+            return None;
+        }
+    };
+    Some(name)
+}
+
+/// Name of an ATTRTYP, resolved through the prefix table of the request
+/// or the default one.
+fn drs_attribute_name(attr: u32, prefixes: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let ndx = attr >> 16;
+    let prefix = prefixes
+        .iter()
+        .find(|(n, _)| *n == ndx)
+        .map(|(_, p)| p.as_slice())
+        .or_else(|| {
+            DRS_DEFAULT_PREFIXES
+                .iter()
+                .find(|(n, _)| *n == ndx)
+                .map(|(_, p)| *p)
+        });
+    let oid = prefix.and_then(|prefix| {
+        let mut ber = prefix.to_vec();
+        let mut low = attr & 0xffff;
+        if low < 128 {
+            ber.push(low as u8);
+        } else {
+            if low >= 32768 {
+                low -= 32768;
+            }
+            ber.push((((low / 128) % 128) + 128) as u8);
+            ber.push((low % 128) as u8);
+        }
+        oid_string(&ber)
+    });
+    match oid {
+        Some(oid) => match drs_attribute_ldap_name(&oid) {
+            Some(name) => name.as_bytes().to_vec(),
+            None => oid.into_bytes(),
+        },
+        None => format!("0x{:08x}", attr).into_bytes(),
+    }
+}
+
+/// DSNAME, returns the GUID and the distinguished name.
+fn drs_dsname<'a>(r: &mut NdrReader<'a>) -> Option<(&'a [u8], Vec<u8>)> {
+    let max_count = r.u32()? as usize;
+    let _struct_len = r.u32()?;
+    let _sid_len = r.u32()?;
+    let guid = r.bytes(16)?;
+    let _sid = r.bytes(28)?;
+    let _name_len = r.u32()?;
+    let name = r.bytes(max_count.checked_mul(2)?)?;
+    Some((guid, ndr_utf16_to_utf8(name)))
+}
+
+/// PARTIAL_ATTR_VECTOR_V1_EXT
+fn drs_partial_attr_vector(r: &mut NdrReader, attrs: &mut Vec<u32>) -> Option<()> {
+    let max_count = r.u32()? as usize;
+    let _version = r.u32()?;
+    let _reserved = r.u32()?;
+    let _count = r.u32()?;
+    for _ in 0..max_count {
+        let attr = r.u32()?;
+        if attrs.len() < DCERPC_STUB_MAX_ITEMS {
+            attrs.push(attr);
+        }
+    }
+    Some(())
+}
+
+/// IDL_DRSGetNCChanges request, in version 5, 8 or 10.
+fn decode_drs_get_nc_changes(call: &mut DCERPCStubCall, r: &mut NdrReader) -> Option<()> {
+    let _drs = r.context_handle()?;
+    let version = r.u32()?;
+    let _tag = r.u32()?;
+    if version != 5 && version != 8 && version != 10 {
+        return Some(());
+    }
+    // the arms of the union have 8 byte members
+    r.align(8)?;
+    let _dsa_obj_dest = r.bytes(16)?;
+    let _invoc_id_src = r.bytes(16)?;
+    let nc = r.pointer()?;
+    let _usn_high_obj_update = r.u64()?;
+    let _usn_reserved = r.u64()?;
+    let _usn_high_prop_update = r.u64()?;
+    let up_to_date_vec = r.pointer()?;
+    let _flags = r.u32()?;
+    let _max_objects = r.u32()?;
+    let _max_bytes = r.u32()?;
+    call.extended_operation = Some(r.u32()?);
+    let _fsmo_info = r.u64()?;
+    let mut partial_attr_set = false;
+    let mut partial_attr_set_ex = false;
+    let mut prefix_entries = false;
+    if version >= 8 {
+        partial_attr_set = r.pointer()?;
+        partial_attr_set_ex = r.pointer()?;
+        let _prefix_count = r.u32()?;
+        prefix_entries = r.pointer()?;
+    }
+    if version == 10 {
+        let _more_flags = r.u32()?;
+    }
+
+    if nc {
+        let (guid, name) = drs_dsname(r)?;
+        call.replicated_object = name;
+        if guid.iter().any(|&b| b != 0) {
+            call.replicated_object_guid = guid_string(guid);
+        }
+    }
+    if up_to_date_vec {
+        // UPTODATE_VECTOR_V1_EXT, skipped
+        let cursors = r.u32()? as usize;
+        r.align(8)?;
+        r.bytes(cursors.checked_mul(24)?.checked_add(16)?)?;
+    }
+    let mut attrs = Vec::new();
+    if partial_attr_set {
+        drs_partial_attr_vector(r, &mut attrs)?;
+    }
+    if partial_attr_set_ex {
+        drs_partial_attr_vector(r, &mut attrs)?;
+    }
+    // SCHEMA_PREFIX_TABLE entries, then the deferred OID prefixes
+    let mut prefixes = Vec::new();
+    if prefix_entries {
+        let count = r.u32()? as usize;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let ndx = r.u32()?;
+            let _length = r.u32()?;
+            let elements = r.pointer()?;
+            if entries.len() < DCERPC_STUB_MAX_ITEMS {
+                entries.push((ndx, elements));
+            }
+        }
+        for (ndx, elements) in entries {
+            if elements {
+                prefixes.push((ndx, r.byte_array()?.to_vec()));
+            }
+        }
+    }
+    call.requested_attributes = attrs
+        .iter()
+        .map(|&a| drs_attribute_name(a, &prefixes))
+        .collect();
+    Some(())
+}
+
+fn decode_drsuapi(call: &mut DCERPCStubCall, opnum: u16, r: &mut NdrReader) -> Option<()> {
+    match opnum {
+        DRS_GET_NC_CHANGES => {
+            call.operation = "DRSGetNCChanges";
+            decode_drs_get_nc_changes(call, r)?;
+        }
+        DRS_CRACK_NAMES => {
+            call.operation = "DRSCrackNames";
+            let _drs = r.context_handle()?;
+            let version = r.u32()?;
+            let _tag = r.u32()?;
+            if version != 1 {
+                return Some(());
+            }
+            let _code_page = r.u32()?;
+            let _locale_id = r.u32()?;
+            let _flags = r.u32()?;
+            let _format_offered = r.u32()?;
+            let _format_desired = r.u32()?;
+            let _count = r.u32()?;
+            if r.pointer()? {
+                let count = r.u32()? as usize;
+                let mut names = Vec::new();
+                for _ in 0..count {
+                    let name = r.pointer()?;
+                    if names.len() < DCERPC_STUB_MAX_ITEMS {
+                        names.push(name);
+                    }
+                }
+                for name in names {
+                    if name {
+                        call.account_names.push(r.wstring()?);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    Some(())
+}
+
+/// RPC_SID, in its string form.
+fn samr_sid(r: &mut NdrReader) -> Option<Vec<u8>> {
+    let _max_count = r.u32()?;
+    let revision = r.u8()?;
+    let count = r.u8()?;
+    let auth = r.bytes(6)?;
+    let auth = auth.iter().fold(0u64, |a, &b| (a << 8) | b as u64);
+    let mut sid = format!("S-{}-{}", revision, auth);
+    for _ in 0..count {
+        sid.push_str(&format!("-{}", r.u32()?));
+    }
+    Some(sid.into_bytes())
+}
+
+fn decode_samr(
+    call: &mut DCERPCStubCall, opnum: u16, r: &mut NdrReader, handles: &DCERPCHandleNames,
+) -> Option<()> {
+    match opnum {
+        SAMR_ENUMERATE_DOMAINS_IN_SAM_SERVER
+        | SAMR_ENUMERATE_GROUPS_IN_DOMAIN
+        | SAMR_ENUMERATE_USERS_IN_DOMAIN
+        | SAMR_ENUMERATE_ALIASES_IN_DOMAIN
+        | SAMR_QUERY_DISPLAY_INFORMATION
+        | SAMR_QUERY_DISPLAY_INFORMATION2
+        | SAMR_QUERY_DISPLAY_INFORMATION3 => {
+            call.operation = match opnum {
+                SAMR_ENUMERATE_DOMAINS_IN_SAM_SERVER => "SamrEnumerateDomainsInSamServer",
+                SAMR_ENUMERATE_GROUPS_IN_DOMAIN => "SamrEnumerateGroupsInDomain",
+                SAMR_ENUMERATE_USERS_IN_DOMAIN => "SamrEnumerateUsersInDomain",
+                SAMR_ENUMERATE_ALIASES_IN_DOMAIN => "SamrEnumerateAliasesInDomain",
+                SAMR_QUERY_DISPLAY_INFORMATION => "SamrQueryDisplayInformation",
+                SAMR_QUERY_DISPLAY_INFORMATION2 => "SamrQueryDisplayInformation2",
+                _ => "SamrQueryDisplayInformation3",
+            };
+        }
+        SAMR_LOOKUP_DOMAIN_IN_SAM_SERVER => {
+            call.operation = "SamrLookupDomainInSamServer";
+            let _server = r.context_handle()?;
+            call.domain_name = r.unicode_string()?;
+        }
+        SAMR_OPEN_DOMAIN => {
+            call.operation = "SamrOpenDomain";
+            let _server = r.context_handle()?;
+            let _desired_access = r.u32()?;
+            call.domain_sid = samr_sid(r)?;
+        }
+        SAMR_LOOKUP_NAMES_IN_DOMAIN => {
+            call.operation = "SamrLookupNamesInDomain";
+            let _domain = r.context_handle()?;
+            let _count = r.u32()?;
+            let _max_count = r.u32()?;
+            let _offset = r.u32()?;
+            let count = r.u32()? as usize;
+            // RPC_UNICODE_STRING array, with the buffers deferred after it
+            let mut names = Vec::new();
+            for _ in 0..count {
+                let _length = r.u16()?;
+                let _max_length = r.u16()?;
+                let name = r.pointer()?;
+                if names.len() < DCERPC_STUB_MAX_ITEMS {
+                    names.push(name);
+                }
+            }
+            for name in names {
+                if name {
+                    call.account_names.push(r.wstring()?);
+                }
+            }
+        }
+        SAMR_LOOKUP_IDS_IN_DOMAIN => {
+            call.operation = "SamrLookupIdsInDomain";
+            let _domain = r.context_handle()?;
+            let _count = r.u32()?;
+            let _max_count = r.u32()?;
+            let _offset = r.u32()?;
+            let count = r.u32()? as usize;
+            for _ in 0..count {
+                let rid = r.u32()?;
+                if call.account_rids.len() < DCERPC_STUB_MAX_ITEMS {
+                    call.account_rids.push(rid);
+                }
+            }
+        }
+        SAMR_OPEN_GROUP | SAMR_OPEN_ALIAS | SAMR_OPEN_USER => {
+            call.operation = match opnum {
+                SAMR_OPEN_GROUP => "SamrOpenGroup",
+                SAMR_OPEN_ALIAS => "SamrOpenAlias",
+                _ => "SamrOpenUser",
+            };
+            call.opens_handle = true;
+            let _domain = r.context_handle()?;
+            let _desired_access = r.u32()?;
+            call.account_rids.push(r.u32()?);
+        }
+        SAMR_GET_MEMBERS_IN_GROUP
+        | SAMR_GET_MEMBERS_IN_ALIAS
+        | SAMR_QUERY_INFORMATION_USER
+        | SAMR_GET_GROUPS_FOR_USER
+        | SAMR_QUERY_INFORMATION_USER2 => {
+            call.operation = match opnum {
+                SAMR_GET_MEMBERS_IN_GROUP => "SamrGetMembersInGroup",
+                SAMR_GET_MEMBERS_IN_ALIAS => "SamrGetMembersInAlias",
+                SAMR_QUERY_INFORMATION_USER => "SamrQueryInformationUser",
+                SAMR_GET_GROUPS_FOR_USER => "SamrGetGroupsForUser",
+                _ => "SamrQueryInformationUser2",
+            };
+            call.account_rids = handles.rid(r.context_handle()?);
+        }
+        _ => {}
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dcerpc_stub_svcctl() {
+        let mut handles = DCERPCHandleNames::new();
+        let create = hex::decode("000000000102030405060708090a0b0c0d0e0f100900000000000000090000005000530045005800450053005600430000000000000002000900000000000000090000005000530045005800450053005600430000000000ff010f001000000003000000000000001a000000000000001a0000002500530079007300740065006d0052006f006f00740025005c00500053004500580045005300560043002e00650078006500000000000000000000000000000000000000000000000000000000000000").unwrap();
+        let call = dcerpc_stub_decode_request(
+            &DCERPC_UUID_SVCCTL,
+            SVCCTL_CREATE_SERVICE_W,
+            &create,
+            &handles,
+        )
+        .unwrap();
+        assert_eq!(call.operation, "RCreateServiceW");
+        assert_eq!(call.service_name, b"PSEXESVC");
+        assert_eq!(call.display_name, b"PSEXESVC");
+        assert_eq!(call.binary_path, b"%SystemRoot%\\PSEXESVC.exe");
+        assert_eq!(call.service_type, Some(0x10));
+        assert_eq!(call.start_type, Some(3));
+
+        let response =
+            hex::decode("00000000000000002122232425262728292a2b2c2d2e2f3000000000").unwrap();
+        handles.update(&call, &response);
+        let start =
+            hex::decode("000000002122232425262728292a2b2c2d2e2f300000000000000000").unwrap();
+        let call = dcerpc_stub_decode_request(
+            &DCERPC_UUID_SVCCTL,
+            SVCCTL_START_SERVICE_W,
+            &start,
+            &handles,
+        )
+        .unwrap();
+        assert_eq!(call.operation, "RStartServiceW");
+        assert_eq!(call.service_name, b"PSEXESVC");
+
+        // unknown opnum
+        assert!(dcerpc_stub_decode_request(&DCERPC_UUID_SVCCTL, 0, &start, &handles).is_none());
+    }
+
+    #[test]
+    fn test_dcerpc_stub_winreg() {
+        let mut handles = DCERPCHandleNames::new();
+        let call = dcerpc_stub_decode_request(
+            &DCERPC_UUID_WINREG,
+            WINREG_OPEN_LOCAL_MACHINE,
+            &[],
+            &handles,
+        )
+        .unwrap();
+        let response = hex::decode("000000000102030405060708090a0b0c0d0e0f1000000000").unwrap();
+        handles.update(&call, &response);
+
+        let open = hex::decode("000000000102030405060708090a0b0c0d0e0f105c005c00040002002e000000000000002e00000053004f004600540057004100520045005c004d006900630072006f0073006f00660074005c00570069006e0064006f00770073005c00430075007200720065006e007400560065007200730069006f006e005c00520075006e0000000000000006000200").unwrap();
+        let call =
+            dcerpc_stub_decode_request(&DCERPC_UUID_WINREG, WINREG_OPEN_KEY, &open, &handles)
+                .unwrap();
+        assert_eq!(
+            call.registry_key,
+            b"HKLM\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run"
+        );
+        let response = hex::decode("000000004142434445464748494a4b4c4d4e4f5000000000").unwrap();
+        handles.update(&call, &response);
+
+        let set = hex::decode("000000004142434445464748494a4b4c4d4e4f50100010000400020008000000000000000800000075007000640061007400650072000000010000001800000043003a005c006500760069006c002e00650078006500000018000000").unwrap();
+        let call =
+            dcerpc_stub_decode_request(&DCERPC_UUID_WINREG, WINREG_SET_VALUE, &set, &handles)
+                .unwrap();
+        assert_eq!(call.operation, "BaseRegSetValue");
+        assert_eq!(
+            call.registry_key,
+            b"HKLM\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run"
+        );
+        assert_eq!(call.registry_value, b"updater");
+        assert_eq!(call.registry_data, b"C:\\evil.exe");
+    }
+
+    #[test]
+    fn test_dcerpc_stub_tsch_wmi() {
+        let handles = DCERPCHandleNames::new();
+        let register = hex::decode("000002000600000000000000060000005c006500760069006c0000003500000000000000350000003c005400610073006b003e003c0045007800650063003e003c0043006f006d006d0061006e0064003e0063006d0064002e006500780065003c002f0043006f006d006d0061006e0064003e003c002f0045007800650063003e003c002f005400610073006b003e00000000000600000000000000030000000000000000000000").unwrap();
+        let call =
+            dcerpc_stub_decode_request(&DCERPC_UUID_TSCH, TSCH_REGISTER_TASK, &register, &handles)
+                .unwrap();
+        assert_eq!(call.task_name, b"\\evil");
+        assert_eq!(
+            call.task_xml,
+            b"<Task><Exec><Command>cmd.exe</Command></Exec></Task>"
+        );
+
+        let exec = hex::decode("050007000000000000000000000102030405060708090a0b0c0d0e0f00000000557365720d0000001a0000000d000000570069006e00330032005f00500072006f006300650073007300000055736572060000000c000000060000004300720065006100740065000000000000000000").unwrap();
+        let call = dcerpc_stub_decode_request(
+            &DCERPC_UUID_IWBEMSERVICES,
+            WBEM_EXEC_METHOD,
+            &exec,
+            &handles,
+        )
+        .unwrap();
+        assert_eq!(call.interface, "wmi");
+        assert_eq!(call.wmi_object_path, b"Win32_Process");
+        assert_eq!(call.wmi_method, b"Create");
+
+        // truncated request keeps what could be decoded
+        let call = dcerpc_stub_decode_request(
+            &DCERPC_UUID_TSCH,
+            TSCH_REGISTER_TASK,
+            &register[..40],
+            &handles,
+        )
+        .unwrap();
+        assert_eq!(call.task_name, b"\\evil");
+        assert!(call.task_xml.is_empty());
+    }
+
+    #[test]
+    fn test_dcerpc_stub_drsuapi() {
+        let handles = DCERPCHandleNames::new();
+        let changes = hex::decode("0102030405060708090a0b0c0d0e0f10111213140800000008000000000000001111111111111111111111111111111122222222222222222222222222222222000002000000000000000000000000000000000000000000000000000000000000000000300000000100000000000000060000000000000000000000000000000400020000000000010000000800020024000000000000000000000078563412cdab3412a1b2c3d4e5f60718000000000000000000000000000000000000000000000000000000002300000043004e003d006b00720062007400670074002c0043004e003d00550073006500720073002c00440043003d0063006f00720070002c00440043003d006c006f00630061006c000000040000000100000000000000040000005a0015007d00150003000000238115000100000015000000080000000c000200080000002a864886f7140104").unwrap();
+        let call = dcerpc_stub_decode_request(
+            &DCERPC_UUID_DRSUAPI,
+            DRS_GET_NC_CHANGES,
+            &changes,
+            &handles,
+        )
+        .unwrap();
+        assert_eq!(call.interface, "drsuapi");
+        assert_eq!(call.operation, "DRSGetNCChanges");
+        assert_eq!(
+            call.replicated_object,
+            b"CN=krbtgt,CN=Users,DC=corp,DC=local"
+        );
+        assert_eq!(
+            call.replicated_object_guid,
+            b"12345678-abcd-1234-a1b2-c3d4e5f60718"
+        );
+        assert_eq!(call.extended_operation, Some(6));
+        // prefix table of the request, default prefix table and unknown
+        // attribute
+        assert_eq!(
+            call.requested_attributes,
+            vec![
+                b"unicodePwd".to_vec(),
+                b"supplementalCredentials".to_vec(),
+                b"cn".to_vec(),
+                b"1.2.840.113556.1.4.291".to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dcerpc_stub_samr() {
+        let mut handles = DCERPCHandleNames::new();
+        let lookup = hex::decode("0102030405060708090a0b0c0d0e0f101112131402000000e803000000000000020000001a001a00000002000c000c00040002000d000000000000000d000000410064006d0069006e006900730074007200610074006f00720000000600000000000000060000006b0072006200740067007400").unwrap();
+        let call = dcerpc_stub_decode_request(
+            &DCERPC_UUID_SAMR,
+            SAMR_LOOKUP_NAMES_IN_DOMAIN,
+            &lookup,
+            &handles,
+        )
+        .unwrap();
+        assert_eq!(
+            call.account_names,
+            vec![b"Administrator".to_vec(), b"krbtgt".to_vec()]
+        );
+
+        let open_domain = hex::decode("0102030405060708090a0b0c0d0e0f1011121314050300000400000001040000000000051500000057040000ae080000050d0000").unwrap();
+        let call =
+            dcerpc_stub_decode_request(&DCERPC_UUID_SAMR, SAMR_OPEN_DOMAIN, &open_domain, &handles)
+                .unwrap();
+        assert_eq!(call.domain_sid, b"S-1-5-21-1111-2222-3333");
+
+        let open_user =
+            hex::decode("0102030405060708090a0b0c0d0e0f10111213141b010200f4010000").unwrap();
+        let call =
+            dcerpc_stub_decode_request(&DCERPC_UUID_SAMR, SAMR_OPEN_USER, &open_user, &handles)
+                .unwrap();
+        assert_eq!(call.account_rids, vec![500]);
+        let response = hex::decode("000000004142434445464748494a4b4c4d4e4f5000000000").unwrap();
+        handles.update(&call, &response);
+
+        let groups = hex::decode("000000004142434445464748494a4b4c4d4e4f50").unwrap();
+        let call = dcerpc_stub_decode_request(
+            &DCERPC_UUID_SAMR,
+            SAMR_GET_GROUPS_FOR_USER,
+            &groups,
+            &handles,
+        )
+        .unwrap();
+        assert_eq!(call.operation, "SamrGetGroupsForUser");
+        assert_eq!(call.account_rids, vec![500]);
+    }
+}
@@ -138,7 +138,7 @@ fn parse_entropy<'a>(
     Ok((input, entropy))
 }
 
-fn calculate_entropy(data: &[u8]) -> f64 {
+pub(crate) fn calculate_entropy(data: &[u8]) -> f64 {
     if data.is_empty() {
         return 0.0;
     }
@@ -18,8 +18,9 @@
 use nom7::branch::alt;
 use nom7::bytes::complete::{is_a, tag, tag_no_case, take_while};
 use nom7::character::complete::{char, digit1, hex_digit1};
-use nom7::combinator::{all_consuming, map_opt, opt, value, verify};
+use nom7::combinator::{all_consuming, map_opt, opt, recognize, value, verify};
 use nom7::error::{make_error, Error, ErrorKind};
+use nom7::sequence::pair;
 use nom7::Err;
 use nom7::IResult;
 
@@ -129,8 +130,14 @@ pub fn detect_parse_uint_value<T: DetectIntType>(i: &str) -> IResult<&str, T> {
 }
 
 pub fn detect_parse_uint_with_unit<T: DetectIntType>(i: &str) -> IResult<&str, T> {
-    let (i, arg1) = map_opt(digit1, |s: &str| s.parse::<T>().ok())(i)?;
+    // the sign is only accepted by the signed types
+    let (i, arg1) = map_opt(recognize(pair(opt(char('-')), digit1)), |s: &str| {
+        s.parse::<T>().ok()
+    })(i)?;
     let (i, unit) = opt(detect_parse_uint_unit)(i)?;
+    if arg1 < T::zero() && unit.is_some() {
+        return Err(Err::Error(make_error(i, ErrorKind::Verify)));
+    }
     if arg1 >= T::one() {
         if let Some(u) = unit {
             if T::max_value().to_u64().unwrap() / u < arg1.to_u64().unwrap() {
@@ -167,8 +174,8 @@ pub fn detect_parse_uint_start_interval<T: DetectIntType>(
     let (i, _) = opt(is_a(" "))(i)?;
     let (i, _) = alt((tag("-"), tag("<>")))(i)?;
     let (i, _) = opt(is_a(" "))(i)?;
-    let (i, arg2) = verify(detect_parse_uint_value, |x| {
-        x > &arg1 && *x - arg1 > T::one()
+    let (i, arg2) = verify(detect_parse_uint_value, |x: &T| {
+        x > &arg1 && x.checked_sub(&arg1).map_or(true, |d| d > T::one())
     })(i)?;
     let mode = if neg.is_some() {
         DetectUintMode::DetectUintModeNegRg
@@ -487,6 +494,26 @@ pub unsafe extern "C" fn SCDetectU8Free(ctx: &mut DetectUintData<u8>) {
     std::mem::drop(Box::from_raw(ctx));
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn SCDetectI8Parse(
+    ustr: *const std::os::raw::c_char,
+) -> *mut DetectUintData<i8> {
+    let ft_name: &CStr = CStr::from_ptr(ustr); //unsafe
+    if let Ok(s) = ft_name.to_str() {
+        if let Ok((_, ctx)) = detect_parse_uint::<i8>(s) {
+            let boxed = Box::new(ctx);
+            return Box::into_raw(boxed) as *mut _;
+        }
+    }
+    return std::ptr::null_mut();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCDetectI8Free(ctx: &mut DetectUintData<i8>) {
+    // Just unbox...
+    std::mem::drop(Box::from_raw(ctx));
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn SCDetectU16Parse(
     ustr: *const std::os::raw::c_char,
@@ -609,4 +636,20 @@ mod tests {
         assert!(detect_parse_uint::<u8>("").is_err());
         assert!(detect_parse_uint::<u8>("<444").is_err());
     }
+
+    #[test]
+    fn test_parse_uint_signed() {
+        let (_, val) = detect_parse_uint::<i8>("<-20").unwrap();
+        assert_eq!(val.mode, DetectUintMode::DetectUintModeLt);
+        assert_eq!(val.arg1, -20);
+        assert!(detect_match_uint(&val, -23));
+        assert!(!detect_match_uint(&val, -6));
+        let (_, val) = detect_parse_uint::<i8>("-100-100").unwrap();
+        assert_eq!(val.mode, DetectUintMode::DetectUintModeRange);
+        assert_eq!(val.arg1, -100);
+        assert_eq!(val.arg2, 100);
+        assert!(detect_parse_uint::<i8>("-2kb").is_err());
+        assert!(detect_parse_uint::<u8>("-1").is_err());
+        assert!(detect_parse_uint::<u8>("<-1").is_err());
+    }
 }
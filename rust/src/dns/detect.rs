@@ -15,13 +15,23 @@
  * 02110-1301, USA.
  */
 
-use super::dns::{DNSRcode, DNSRecordType, DNSTransaction, ALPROTO_DNS};
+use super::dns::{
+    DNSEdnsClientSubnet, DNSEdnsOption, DNSExtendedError, DNSMessage, DNSRData, DNSRDataOPT,
+    DNSRDataSVCB, DNSRcode, DNSRecordType, DNSTransaction, ALPROTO_DNS,
+};
+use super::features::{dns_answer_payload_size, dns_name_features, DNSNameFeatures};
 use crate::core::{STREAM_TOCLIENT, STREAM_TOSERVER};
+use crate::detect::float::{
+    detect_match_float, DetectFloatData, SCDetectF64Free, SCDetectF64Parse,
+};
 use crate::detect::uint::{
-    detect_match_uint, detect_parse_uint_enum, DetectUintData, SCDetectU16Free, SCDetectU8Free,
+    detect_match_uint, detect_parse_uint_enum, DetectUintData, SCDetectU16Free, SCDetectU16Parse,
+    SCDetectU32Free, SCDetectU32Parse, SCDetectU64Free, SCDetectU64Parse, SCDetectU8Free,
     SCDetectU8Parse,
 };
-use crate::detect::{helper_keyword_register_sticky_buffer, SigTableElmtStickyBuffer};
+use crate::detect::{
+    helper_keyword_register_sticky_buffer, SigTableElmtStickyBuffer, SIGMATCH_NOOPT,
+};
 use crate::direction::Direction;
 use std::ffi::CStr;
 use std::os::raw::{c_int, c_void};
@@ -124,6 +134,328 @@ unsafe extern "C" fn dns_rrtype_match(
     return 0;
 }
 
+/// Iterate over the SVCB and HTTPS answers of the message in the
+/// direction of flags.
+fn dns_tx_svcb_answers(tx: &DNSTransaction, flags: u8) -> impl Iterator<Item = &DNSRDataSVCB> {
+    let message = if flags & Direction::ToClient as u8 != 0 {
+        tx.response.as_ref()
+    } else {
+        tx.request.as_ref()
+    };
+    message
+        .into_iter()
+        .flat_map(|message| message.answers.iter())
+        .filter_map(|answer| match &answer.data {
+            DNSRData::SVCB(svcb) => Some(svcb),
+            _ => None,
+        })
+}
+
+/// Get the alpn at index i, counting over all SVCB and HTTPS answers.
+fn dns_svcb_alpn(tx: &DNSTransaction, flags: u8, i: u32) -> Option<&[u8]> {
+    dns_tx_svcb_answers(tx, flags)
+        .flat_map(|svcb| svcb.alpn.iter())
+        .nth(i as usize)
+        .map(|alpn| alpn.as_slice())
+}
+
+fn dns_svcb_has_ech(tx: &DNSTransaction, flags: u8) -> bool {
+    dns_tx_svcb_answers(tx, flags).any(|svcb| svcb.ech.is_some())
+}
+
+/// Match if an SVCB or HTTPS answer has an ECH configuration.
+unsafe extern "C" fn dns_svcb_ech_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, _ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    if dns_svcb_has_ech(tx, flags) {
+        return 1;
+    }
+    return 0;
+}
+
+/// Get the message in the direction of flags.
+fn dns_tx_message(tx: &DNSTransaction, flags: u8) -> Option<&DNSMessage> {
+    if flags & Direction::ToClient as u8 != 0 {
+        tx.response.as_ref()
+    } else {
+        tx.request.as_ref()
+    }
+}
+
+/// Iterate over the EDNS options of the message in the direction of
+/// flags.
+fn dns_tx_edns_options(tx: &DNSTransaction, flags: u8) -> impl Iterator<Item = &DNSRDataOPT> {
+    dns_tx_message(tx, flags)
+        .into_iter()
+        .flat_map(|message| message.additionals.iter())
+        .filter_map(|additional| match &additional.data {
+            DNSRData::OPT(opts) => Some(opts.iter()),
+            _ => None,
+        })
+        .flatten()
+}
+
+/// Get the extended DNS error extra text at index i.
+fn dns_ede_text(tx: &DNSTransaction, flags: u8, i: u32) -> Option<&[u8]> {
+    dns_tx_edns_options(tx, flags)
+        .filter_map(|opt| match &opt.value {
+            DNSEdnsOption::ExtendedError { extra_text, .. } if !extra_text.is_empty() => {
+                Some(extra_text.as_slice())
+            }
+            _ => None,
+        })
+        .nth(i as usize)
+}
+
+/// Get the name server identifier at index i.
+fn dns_nsid(tx: &DNSTransaction, flags: u8, i: u32) -> Option<&[u8]> {
+    dns_tx_edns_options(tx, flags)
+        .filter(|opt| opt.value == DNSEdnsOption::Nsid && !opt.data.is_empty())
+        .map(|opt| opt.data.as_slice())
+        .nth(i as usize)
+}
+
+/// Iterate over the client subnet options of the message in the
+/// direction of flags.
+fn dns_tx_ecs(tx: &DNSTransaction, flags: u8) -> impl Iterator<Item = &DNSEdnsClientSubnet> {
+    dns_tx_edns_options(tx, flags).filter_map(|opt| match &opt.value {
+        DNSEdnsOption::ClientSubnet(ecs) => Some(ecs),
+        _ => None,
+    })
+}
+
+/// Get the client subnet address at index i, as truncated to the source
+/// prefix on the wire.
+fn dns_ecs_address(tx: &DNSTransaction, flags: u8, i: u32) -> Option<&[u8]> {
+    dns_tx_ecs(tx, flags)
+        .map(|ecs| ecs.address.as_slice())
+        .nth(i as usize)
+}
+
+/// Get the client cookie, or the server cookie if `server` is set, at
+/// index i.
+fn dns_cookie(tx: &DNSTransaction, flags: u8, i: u32, server: bool) -> Option<&[u8]> {
+    dns_tx_edns_options(tx, flags)
+        .filter_map(|opt| match &opt.value {
+            DNSEdnsOption::Cookie {
+                client,
+                server: server_cookie,
+            } => {
+                let cookie = if server { server_cookie } else { client };
+                Some(cookie.as_slice()).filter(|cookie| !cookie.is_empty())
+            }
+            _ => None,
+        })
+        .nth(i as usize)
+}
+
+/// Perform the extended DNS error match, on any of the errors.
+unsafe extern "C" fn dns_ede_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u16>);
+    for opt in dns_tx_edns_options(tx, flags) {
+        if let DNSEdnsOption::ExtendedError { info_code, .. } = opt.value {
+            if detect_match_uint(ctx, info_code) {
+                return 1;
+            }
+        }
+    }
+    return 0;
+}
+
+/// Perform the client subnet source prefix match.
+unsafe extern "C" fn dns_ecs_source_prefix_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u8>);
+    for ecs in dns_tx_ecs(tx, flags) {
+        if detect_match_uint(ctx, ecs.source_prefix) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+/// Perform the client subnet scope prefix match.
+unsafe extern "C" fn dns_ecs_scope_prefix_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u8>);
+    for ecs in dns_tx_ecs(tx, flags) {
+        if detect_match_uint(ctx, ecs.scope_prefix) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+/// Perform the client subnet address family match.
+unsafe extern "C" fn dns_ecs_family_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u16>);
+    for ecs in dns_tx_ecs(tx, flags) {
+        if detect_match_uint(ctx, ecs.family) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+/// Perform the padding length match, on any of the padding options.
+unsafe extern "C" fn dns_padding_length_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u16>);
+    for opt in dns_tx_edns_options(tx, flags) {
+        if opt.value == DNSEdnsOption::Padding && detect_match_uint(ctx, opt.data.len() as u16) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+/// Iterate over the features of the query names of the message in the
+/// direction of flags.
+fn dns_tx_query_features(
+    tx: &DNSTransaction, flags: u8,
+) -> impl Iterator<Item = DNSNameFeatures> + '_ {
+    dns_tx_message(tx, flags)
+        .into_iter()
+        .flat_map(|message| message.queries.iter())
+        .map(|query| dns_name_features(&query.name.value))
+}
+
+/// Match a float feature of any of the query names.
+unsafe fn dns_query_float_match(
+    tx: *mut c_void, flags: u8, ctx: *const SigMatchCtx, feature: fn(&DNSNameFeatures) -> f64,
+) -> c_int {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    let ctx = cast_pointer!(ctx, DetectFloatData<f64>);
+    for features in dns_tx_query_features(tx, flags) {
+        if detect_match_float::<f64>(ctx, feature(&features)) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+/// Match an integer feature of any of the query names.
+unsafe fn dns_query_uint_match(
+    tx: *mut c_void, flags: u8, ctx: *const SigMatchCtx, feature: fn(&DNSNameFeatures) -> u8,
+) -> c_int {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u8>);
+    for features in dns_tx_query_features(tx, flags) {
+        if detect_match_uint(ctx, feature(&features)) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dns_query_entropy_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    dns_query_float_match(tx, flags, ctx, |f| f.entropy)
+}
+
+unsafe extern "C" fn dns_query_digit_ratio_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    dns_query_float_match(tx, flags, ctx, |f| f.digit_ratio)
+}
+
+unsafe extern "C" fn dns_query_upper_ratio_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    dns_query_float_match(tx, flags, ctx, |f| f.upper_ratio)
+}
+
+unsafe extern "C" fn dns_query_other_ratio_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    dns_query_float_match(tx, flags, ctx, |f| f.other_ratio)
+}
+
+unsafe extern "C" fn dns_query_longest_label_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    dns_query_uint_match(tx, flags, ctx, |f| f.longest_label)
+}
+
+unsafe extern "C" fn dns_query_label_count_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    dns_query_uint_match(tx, flags, ctx, |f| f.label_count)
+}
+
+/// Match the number of unique subdomains seen under the parent domain
+/// of the query.
+unsafe extern "C" fn dns_parent_subdomains_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u32>);
+    if let Some(parent) = &tx.parent {
+        if detect_match_uint(ctx, parent.subdomains) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+/// Match the number of bytes of names queried under the parent domain
+/// of the query.
+unsafe extern "C" fn dns_parent_bytes_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u64>);
+    if let Some(parent) = &tx.parent {
+        if detect_match_uint(ctx, parent.bytes) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+/// Match the size of the TXT and NULL data of the answers.
+unsafe extern "C" fn dns_payload_size_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u32>);
+    if let Some(response) = &tx.response {
+        if detect_match_uint(ctx, dns_answer_payload_size(response)) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
 static mut G_DNS_ANSWER_NAME_BUFFER_ID: c_int = 0;
 static mut G_DNS_QUERY_NAME_BUFFER_ID: c_int = 0;
 static mut G_DNS_QUERY_BUFFER_ID: c_int = 0;
@@ -133,6 +465,42 @@ static mut G_DNS_RCODE_KW_ID: u16 = 0;
 static mut G_DNS_RCODE_BUFFER_ID: c_int = 0;
 static mut G_DNS_RRTYPE_KW_ID: u16 = 0;
 static mut G_DNS_RRTYPE_BUFFER_ID: c_int = 0;
+static mut G_DNS_SVCB_ALPN_BUFFER_ID: c_int = 0;
+static mut G_DNS_SVCB_ECH_KW_ID: u16 = 0;
+static mut G_DNS_SVCB_ECH_BUFFER_ID: c_int = 0;
+static mut G_DNS_EDE_KW_ID: u16 = 0;
+static mut G_DNS_EDE_BUFFER_ID: c_int = 0;
+static mut G_DNS_EDE_TEXT_BUFFER_ID: c_int = 0;
+static mut G_DNS_ECS_SOURCE_PREFIX_KW_ID: u16 = 0;
+static mut G_DNS_ECS_SOURCE_PREFIX_BUFFER_ID: c_int = 0;
+static mut G_DNS_NSID_BUFFER_ID: c_int = 0;
+static mut G_DNS_ECS_SCOPE_PREFIX_KW_ID: u16 = 0;
+static mut G_DNS_ECS_SCOPE_PREFIX_BUFFER_ID: c_int = 0;
+static mut G_DNS_ECS_FAMILY_KW_ID: u16 = 0;
+static mut G_DNS_ECS_FAMILY_BUFFER_ID: c_int = 0;
+static mut G_DNS_ECS_ADDRESS_BUFFER_ID: c_int = 0;
+static mut G_DNS_COOKIE_CLIENT_BUFFER_ID: c_int = 0;
+static mut G_DNS_COOKIE_SERVER_BUFFER_ID: c_int = 0;
+static mut G_DNS_PADDING_LENGTH_KW_ID: u16 = 0;
+static mut G_DNS_PADDING_LENGTH_BUFFER_ID: c_int = 0;
+static mut G_DNS_QUERY_ENTROPY_KW_ID: u16 = 0;
+static mut G_DNS_QUERY_ENTROPY_BUFFER_ID: c_int = 0;
+static mut G_DNS_QUERY_DIGIT_RATIO_KW_ID: u16 = 0;
+static mut G_DNS_QUERY_DIGIT_RATIO_BUFFER_ID: c_int = 0;
+static mut G_DNS_QUERY_UPPER_RATIO_KW_ID: u16 = 0;
+static mut G_DNS_QUERY_UPPER_RATIO_BUFFER_ID: c_int = 0;
+static mut G_DNS_QUERY_OTHER_RATIO_KW_ID: u16 = 0;
+static mut G_DNS_QUERY_OTHER_RATIO_BUFFER_ID: c_int = 0;
+static mut G_DNS_QUERY_LONGEST_LABEL_KW_ID: u16 = 0;
+static mut G_DNS_QUERY_LONGEST_LABEL_BUFFER_ID: c_int = 0;
+static mut G_DNS_QUERY_LABEL_COUNT_KW_ID: u16 = 0;
+static mut G_DNS_QUERY_LABEL_COUNT_BUFFER_ID: c_int = 0;
+static mut G_DNS_PARENT_SUBDOMAINS_KW_ID: u16 = 0;
+static mut G_DNS_PARENT_SUBDOMAINS_BUFFER_ID: c_int = 0;
+static mut G_DNS_PARENT_BYTES_KW_ID: u16 = 0;
+static mut G_DNS_PARENT_BYTES_BUFFER_ID: c_int = 0;
+static mut G_DNS_PAYLOAD_SIZE_KW_ID: u16 = 0;
+static mut G_DNS_PAYLOAD_SIZE_BUFFER_ID: c_int = 0;
 
 unsafe extern "C" fn dns_opcode_setup(
     de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
@@ -251,99 +619,560 @@ unsafe extern "C" fn dns_rrtype_free(_de: *mut DetectEngineCtx, ctx: *mut c_void
     SCDetectU16Free(ctx);
 }
 
-unsafe extern "C" fn dns_detect_answer_name_setup(
-    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+unsafe extern "C" fn dns_detect_answer_name_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_DNS_ANSWER_NAME_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+/// Get the DNS response answer name and index i.
+pub(crate) unsafe extern "C" fn dns_tx_get_answer_name(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, flags: u8, i: u32, buf: *mut *const u8,
+    len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    let answers = if flags & Direction::ToClient as u8 != 0 {
+        tx.response.as_ref().map(|response| &response.answers)
+    } else {
+        tx.request.as_ref().map(|request| &request.answers)
+    };
+    let index = i as usize;
+
+    if let Some(answers) = answers {
+        if let Some(answer) = answers.get(index) {
+            if !answer.name.value.is_empty() {
+                *buf = answer.name.value.as_ptr();
+                *len = answer.name.value.len() as u32;
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+unsafe extern "C" fn dns_detect_query_name_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_DNS_QUERY_NAME_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+/// Get the DNS response answer name and index i.
+pub(crate) unsafe extern "C" fn dns_tx_get_query_name(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, flags: u8, i: u32, buf: *mut *const u8,
+    len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    let queries = if flags & Direction::ToClient as u8 != 0 {
+        tx.response.as_ref().map(|response| &response.queries)
+    } else {
+        tx.request.as_ref().map(|request| &request.queries)
+    };
+    let index = i as usize;
+
+    if let Some(queries) = queries {
+        if let Some(query) = queries.get(index) {
+            if !query.name.value.is_empty() {
+                *buf = query.name.value.as_ptr();
+                *len = query.name.value.len() as u32;
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+unsafe extern "C" fn dns_tx_get_query(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, _flags: u8, i: u32, buf: *mut *const u8,
+    len: *mut u32,
+) -> bool {
+    return dns_tx_get_query_name(_de, tx, Direction::ToServer as u8, i, buf, len);
+}
+
+unsafe extern "C" fn dns_detect_query_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_DNS_QUERY_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dns_detect_svcb_alpn_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_DNS_SVCB_ALPN_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dns_tx_get_svcb_alpn(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, flags: u8, i: u32, buf: *mut *const u8,
+    len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    if let Some(alpn) = dns_svcb_alpn(tx, flags, i) {
+        *buf = alpn.as_ptr();
+        *len = alpn.len() as u32;
+        return true;
+    }
+    false
+}
+
+unsafe extern "C" fn dns_svcb_ech_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const libc::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
+        return -1;
+    }
+    if SCSigMatchAppendSMToList(
+        de,
+        s,
+        G_DNS_SVCB_ECH_KW_ID,
+        std::ptr::null_mut(),
+        G_DNS_SVCB_ECH_BUFFER_ID,
+    )
+    .is_null()
+    {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dns_ede_parse(ustr: *const std::os::raw::c_char) -> *mut DetectUintData<u16> {
+    let ft_name: &CStr = CStr::from_ptr(ustr); //unsafe
+    if let Ok(s) = ft_name.to_str() {
+        if let Some(ctx) = detect_parse_uint_enum::<u16, DNSExtendedError>(s) {
+            let boxed = Box::new(ctx);
+            return Box::into_raw(boxed) as *mut _;
+        }
+    }
+    return std::ptr::null_mut();
+}
+
+unsafe extern "C" fn dns_ede_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
+        return -1;
+    }
+    let ctx = dns_ede_parse(raw) as *mut c_void;
+    if ctx.is_null() {
+        return -1;
+    }
+    if SCSigMatchAppendSMToList(
+        de,
+        s,
+        G_DNS_EDE_KW_ID,
+        ctx as *mut SigMatchCtx,
+        G_DNS_EDE_BUFFER_ID,
+    )
+    .is_null()
+    {
+        dns_ede_free(std::ptr::null_mut(), ctx);
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dns_ede_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    // Just unbox...
+    let ctx = cast_pointer!(ctx, DetectUintData<u16>);
+    SCDetectU16Free(ctx);
+}
+
+unsafe extern "C" fn dns_detect_ede_text_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_DNS_EDE_TEXT_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dns_tx_get_ede_text(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, flags: u8, i: u32, buf: *mut *const u8,
+    len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    if let Some(text) = dns_ede_text(tx, flags, i) {
+        *buf = text.as_ptr();
+        *len = text.len() as u32;
+        return true;
+    }
+    false
+}
+
+unsafe extern "C" fn dns_ecs_source_prefix_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
+        return -1;
+    }
+    let ctx = SCDetectU8Parse(raw) as *mut c_void;
+    if ctx.is_null() {
+        return -1;
+    }
+    if SCSigMatchAppendSMToList(
+        de,
+        s,
+        G_DNS_ECS_SOURCE_PREFIX_KW_ID,
+        ctx as *mut SigMatchCtx,
+        G_DNS_ECS_SOURCE_PREFIX_BUFFER_ID,
+    )
+    .is_null()
+    {
+        dns_ecs_source_prefix_free(std::ptr::null_mut(), ctx);
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dns_ecs_source_prefix_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    // Just unbox...
+    let ctx = cast_pointer!(ctx, DetectUintData<u8>);
+    SCDetectU8Free(ctx);
+}
+
+unsafe extern "C" fn dns_detect_nsid_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_DNS_NSID_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dns_tx_get_nsid(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, flags: u8, i: u32, buf: *mut *const u8,
+    len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    if let Some(nsid) = dns_nsid(tx, flags, i) {
+        *buf = nsid.as_ptr();
+        *len = nsid.len() as u32;
+        return true;
+    }
+    false
+}
+
+unsafe extern "C" fn dns_ecs_scope_prefix_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    let ctx = SCDetectU8Parse(raw) as *mut c_void;
+    dns_feature_setup(
+        de,
+        s,
+        ctx,
+        G_DNS_ECS_SCOPE_PREFIX_KW_ID,
+        G_DNS_ECS_SCOPE_PREFIX_BUFFER_ID,
+        dns_u8_free,
+    )
+}
+
+unsafe extern "C" fn dns_ecs_family_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    let ctx = SCDetectU16Parse(raw) as *mut c_void;
+    dns_feature_setup(
+        de,
+        s,
+        ctx,
+        G_DNS_ECS_FAMILY_KW_ID,
+        G_DNS_ECS_FAMILY_BUFFER_ID,
+        dns_u16_free,
+    )
+}
+
+unsafe extern "C" fn dns_detect_ecs_address_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_DNS_ECS_ADDRESS_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dns_tx_get_ecs_address(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, flags: u8, i: u32, buf: *mut *const u8,
+    len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    if let Some(address) = dns_ecs_address(tx, flags, i) {
+        *buf = address.as_ptr();
+        *len = address.len() as u32;
+        return true;
+    }
+    false
+}
+
+unsafe extern "C" fn dns_detect_cookie_client_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_DNS_COOKIE_CLIENT_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dns_tx_get_cookie_client(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, flags: u8, i: u32, buf: *mut *const u8,
+    len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    if let Some(cookie) = dns_cookie(tx, flags, i, false) {
+        *buf = cookie.as_ptr();
+        *len = cookie.len() as u32;
+        return true;
+    }
+    false
+}
+
+unsafe extern "C" fn dns_detect_cookie_server_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_DNS_COOKIE_SERVER_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dns_tx_get_cookie_server(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, flags: u8, i: u32, buf: *mut *const u8,
+    len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, DNSTransaction);
+    if let Some(cookie) = dns_cookie(tx, flags, i, true) {
+        *buf = cookie.as_ptr();
+        *len = cookie.len() as u32;
+        return true;
+    }
+    false
+}
+
+unsafe extern "C" fn dns_padding_length_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    let ctx = SCDetectU16Parse(raw) as *mut c_void;
+    dns_feature_setup(
+        de,
+        s,
+        ctx,
+        G_DNS_PADDING_LENGTH_KW_ID,
+        G_DNS_PADDING_LENGTH_BUFFER_ID,
+        dns_u16_free,
+    )
+}
+
+/// Append the parsed context of a keyword to the signature, freeing it
+/// on failure.
+unsafe fn dns_feature_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, ctx: *mut c_void, kw_id: u16, buffer_id: c_int,
+    free: unsafe extern "C" fn(*mut DetectEngineCtx, *mut c_void),
+) -> c_int {
+    if ctx.is_null() {
+        return -1;
+    }
+    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
+        free(std::ptr::null_mut(), ctx);
+        return -1;
+    }
+    if SCSigMatchAppendSMToList(de, s, kw_id, ctx as *mut SigMatchCtx, buffer_id).is_null() {
+        free(std::ptr::null_mut(), ctx);
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn dns_f64_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    // Just unbox...
+    let ctx = cast_pointer!(ctx, DetectFloatData<f64>);
+    SCDetectF64Free(ctx);
+}
+
+unsafe extern "C" fn dns_u8_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    // Just unbox...
+    let ctx = cast_pointer!(ctx, DetectUintData<u8>);
+    SCDetectU8Free(ctx);
+}
+
+unsafe extern "C" fn dns_u16_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    // Just unbox...
+    let ctx = cast_pointer!(ctx, DetectUintData<u16>);
+    SCDetectU16Free(ctx);
+}
+
+unsafe extern "C" fn dns_u32_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    // Just unbox...
+    let ctx = cast_pointer!(ctx, DetectUintData<u32>);
+    SCDetectU32Free(ctx);
+}
+
+unsafe extern "C" fn dns_u64_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    // Just unbox...
+    let ctx = cast_pointer!(ctx, DetectUintData<u64>);
+    SCDetectU64Free(ctx);
+}
+
+unsafe extern "C" fn dns_query_entropy_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    let ctx = SCDetectF64Parse(raw) as *mut c_void;
+    dns_feature_setup(
+        de,
+        s,
+        ctx,
+        G_DNS_QUERY_ENTROPY_KW_ID,
+        G_DNS_QUERY_ENTROPY_BUFFER_ID,
+        dns_f64_free,
+    )
+}
+
+unsafe extern "C" fn dns_query_digit_ratio_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    let ctx = SCDetectF64Parse(raw) as *mut c_void;
+    dns_feature_setup(
+        de,
+        s,
+        ctx,
+        G_DNS_QUERY_DIGIT_RATIO_KW_ID,
+        G_DNS_QUERY_DIGIT_RATIO_BUFFER_ID,
+        dns_f64_free,
+    )
+}
+
+unsafe extern "C" fn dns_query_upper_ratio_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    let ctx = SCDetectF64Parse(raw) as *mut c_void;
+    dns_feature_setup(
+        de,
+        s,
+        ctx,
+        G_DNS_QUERY_UPPER_RATIO_KW_ID,
+        G_DNS_QUERY_UPPER_RATIO_BUFFER_ID,
+        dns_f64_free,
+    )
+}
+
+unsafe extern "C" fn dns_query_other_ratio_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
 ) -> c_int {
-    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
-        return -1;
-    }
-    if SCDetectBufferSetActiveList(de, s, G_DNS_ANSWER_NAME_BUFFER_ID) < 0 {
-        return -1;
-    }
-    return 0;
+    let ctx = SCDetectF64Parse(raw) as *mut c_void;
+    dns_feature_setup(
+        de,
+        s,
+        ctx,
+        G_DNS_QUERY_OTHER_RATIO_KW_ID,
+        G_DNS_QUERY_OTHER_RATIO_BUFFER_ID,
+        dns_f64_free,
+    )
 }
 
-/// Get the DNS response answer name and index i.
-pub(crate) unsafe extern "C" fn dns_tx_get_answer_name(
-    _de: *mut DetectEngineThreadCtx, tx: *const c_void, flags: u8, i: u32, buf: *mut *const u8,
-    len: *mut u32,
-) -> bool {
-    let tx = cast_pointer!(tx, DNSTransaction);
-    let answers = if flags & Direction::ToClient as u8 != 0 {
-        tx.response.as_ref().map(|response| &response.answers)
-    } else {
-        tx.request.as_ref().map(|request| &request.answers)
-    };
-    let index = i as usize;
-
-    if let Some(answers) = answers {
-        if let Some(answer) = answers.get(index) {
-            if !answer.name.value.is_empty() {
-                *buf = answer.name.value.as_ptr();
-                *len = answer.name.value.len() as u32;
-                return true;
-            }
-        }
-    }
-
-    false
+unsafe extern "C" fn dns_query_longest_label_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    let ctx = SCDetectU8Parse(raw) as *mut c_void;
+    dns_feature_setup(
+        de,
+        s,
+        ctx,
+        G_DNS_QUERY_LONGEST_LABEL_KW_ID,
+        G_DNS_QUERY_LONGEST_LABEL_BUFFER_ID,
+        dns_u8_free,
+    )
 }
 
-unsafe extern "C" fn dns_detect_query_name_setup(
-    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+unsafe extern "C" fn dns_query_label_count_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
 ) -> c_int {
-    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
-        return -1;
-    }
-    if SCDetectBufferSetActiveList(de, s, G_DNS_QUERY_NAME_BUFFER_ID) < 0 {
-        return -1;
-    }
-    return 0;
+    let ctx = SCDetectU8Parse(raw) as *mut c_void;
+    dns_feature_setup(
+        de,
+        s,
+        ctx,
+        G_DNS_QUERY_LABEL_COUNT_KW_ID,
+        G_DNS_QUERY_LABEL_COUNT_BUFFER_ID,
+        dns_u8_free,
+    )
 }
 
-/// Get the DNS response answer name and index i.
-pub(crate) unsafe extern "C" fn dns_tx_get_query_name(
-    _de: *mut DetectEngineThreadCtx, tx: *const c_void, flags: u8, i: u32, buf: *mut *const u8,
-    len: *mut u32,
-) -> bool {
-    let tx = cast_pointer!(tx, DNSTransaction);
-    let queries = if flags & Direction::ToClient as u8 != 0 {
-        tx.response.as_ref().map(|response| &response.queries)
-    } else {
-        tx.request.as_ref().map(|request| &request.queries)
-    };
-    let index = i as usize;
-
-    if let Some(queries) = queries {
-        if let Some(query) = queries.get(index) {
-            if !query.name.value.is_empty() {
-                *buf = query.name.value.as_ptr();
-                *len = query.name.value.len() as u32;
-                return true;
-            }
-        }
-    }
-
-    false
+unsafe extern "C" fn dns_parent_subdomains_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    let ctx = SCDetectU32Parse(raw) as *mut c_void;
+    dns_feature_setup(
+        de,
+        s,
+        ctx,
+        G_DNS_PARENT_SUBDOMAINS_KW_ID,
+        G_DNS_PARENT_SUBDOMAINS_BUFFER_ID,
+        dns_u32_free,
+    )
 }
 
-unsafe extern "C" fn dns_tx_get_query(
-    _de: *mut DetectEngineThreadCtx, tx: *const c_void, _flags: u8, i: u32, buf: *mut *const u8,
-    len: *mut u32,
-) -> bool {
-    return dns_tx_get_query_name(_de, tx, Direction::ToServer as u8, i, buf, len);
+unsafe extern "C" fn dns_parent_bytes_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    let ctx = SCDetectU64Parse(raw) as *mut c_void;
+    dns_feature_setup(
+        de,
+        s,
+        ctx,
+        G_DNS_PARENT_BYTES_KW_ID,
+        G_DNS_PARENT_BYTES_BUFFER_ID,
+        dns_u64_free,
+    )
 }
 
-unsafe extern "C" fn dns_detect_query_setup(
-    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+unsafe extern "C" fn dns_payload_size_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
 ) -> c_int {
-    if SCDetectSignatureSetAppProto(s, ALPROTO_DNS) != 0 {
-        return -1;
-    }
-    if SCDetectBufferSetActiveList(de, s, G_DNS_QUERY_BUFFER_ID) < 0 {
-        return -1;
-    }
-    return 0;
+    let ctx = SCDetectU32Parse(raw) as *mut c_void;
+    dns_feature_setup(
+        de,
+        s,
+        ctx,
+        G_DNS_PAYLOAD_SIZE_KW_ID,
+        G_DNS_PAYLOAD_SIZE_BUFFER_ID,
+        dns_u32_free,
+    )
 }
 
 #[no_mangle]
@@ -446,12 +1275,339 @@ pub unsafe extern "C" fn SCDetectDNSRegister() {
         Some(dns_tx_get_query), // reuse, will be called only toserver
         1,                      // request complete
     );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("dns.svcb.alpn"),
+        desc: String::from("DNS SVCB and HTTPS answer alpn sticky buffer"),
+        url: String::from("/rules/dns-keywords.html#dns-svcb-alpn"),
+        setup: dns_detect_svcb_alpn_setup,
+    };
+    let _g_dns_svcb_alpn_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_DNS_SVCB_ALPN_BUFFER_ID = SCDetectHelperMultiBufferProgressMpmRegister(
+        b"dns.svcb.alpn\0".as_ptr() as *const libc::c_char,
+        b"dns svcb alpn\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOCLIENT,
+        Some(dns_tx_get_svcb_alpn),
+        1, // response complete
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.svcb.ech\0".as_ptr() as *const libc::c_char,
+        desc: b"Match if a DNS SVCB or HTTPS answer has an ECH configuration.\0".as_ptr()
+            as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-svcb-ech\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_svcb_ech_match),
+        Setup: Some(dns_svcb_ech_setup),
+        Free: None,
+        flags: SIGMATCH_NOOPT,
+    };
+    G_DNS_SVCB_ECH_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_SVCB_ECH_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.svcb.ech\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.ede\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the extended DNS error info code.\0".as_ptr() as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-ede\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_ede_match),
+        Setup: Some(dns_ede_setup),
+        Free: Some(dns_ede_free),
+        flags: 0,
+    };
+    G_DNS_EDE_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_EDE_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.ede\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("dns.ede.text"),
+        desc: String::from("DNS extended error extra text sticky buffer"),
+        url: String::from("/rules/dns-keywords.html#dns-ede-text"),
+        setup: dns_detect_ede_text_setup,
+    };
+    let _g_dns_ede_text_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_DNS_EDE_TEXT_BUFFER_ID = SCDetectHelperMultiBufferProgressMpmRegister(
+        b"dns.ede.text\0".as_ptr() as *const libc::c_char,
+        b"dns extended error extra text\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(dns_tx_get_ede_text),
+        1, // request or response complete
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.ecs.source_prefix\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the EDNS client subnet source prefix length.\0".as_ptr()
+            as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-ecs-source-prefix\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_ecs_source_prefix_match),
+        Setup: Some(dns_ecs_source_prefix_setup),
+        Free: Some(dns_ecs_source_prefix_free),
+        flags: 0,
+    };
+    G_DNS_ECS_SOURCE_PREFIX_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_ECS_SOURCE_PREFIX_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.ecs.source_prefix\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("dns.nsid"),
+        desc: String::from("DNS name server identifier sticky buffer"),
+        url: String::from("/rules/dns-keywords.html#dns-nsid"),
+        setup: dns_detect_nsid_setup,
+    };
+    let _g_dns_nsid_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_DNS_NSID_BUFFER_ID = SCDetectHelperMultiBufferProgressMpmRegister(
+        b"dns.nsid\0".as_ptr() as *const libc::c_char,
+        b"dns name server identifier\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(dns_tx_get_nsid),
+        1, // request or response complete
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.ecs.scope_prefix\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the EDNS client subnet scope prefix length.\0".as_ptr()
+            as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-ecs-scope-prefix\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_ecs_scope_prefix_match),
+        Setup: Some(dns_ecs_scope_prefix_setup),
+        Free: Some(dns_u8_free),
+        flags: 0,
+    };
+    G_DNS_ECS_SCOPE_PREFIX_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_ECS_SCOPE_PREFIX_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.ecs.scope_prefix\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.ecs.family\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the EDNS client subnet address family.\0".as_ptr() as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-ecs-family\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_ecs_family_match),
+        Setup: Some(dns_ecs_family_setup),
+        Free: Some(dns_u16_free),
+        flags: 0,
+    };
+    G_DNS_ECS_FAMILY_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_ECS_FAMILY_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.ecs.family\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("dns.ecs.address"),
+        desc: String::from("DNS EDNS client subnet address sticky buffer"),
+        url: String::from("/rules/dns-keywords.html#dns-ecs-address"),
+        setup: dns_detect_ecs_address_setup,
+    };
+    let _g_dns_ecs_address_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_DNS_ECS_ADDRESS_BUFFER_ID = SCDetectHelperMultiBufferProgressMpmRegister(
+        b"dns.ecs.address\0".as_ptr() as *const libc::c_char,
+        b"dns client subnet address\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(dns_tx_get_ecs_address),
+        1, // request or response complete
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("dns.cookie.client"),
+        desc: String::from("DNS client cookie sticky buffer"),
+        url: String::from("/rules/dns-keywords.html#dns-cookie-client"),
+        setup: dns_detect_cookie_client_setup,
+    };
+    let _g_dns_cookie_client_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_DNS_COOKIE_CLIENT_BUFFER_ID = SCDetectHelperMultiBufferProgressMpmRegister(
+        b"dns.cookie.client\0".as_ptr() as *const libc::c_char,
+        b"dns client cookie\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(dns_tx_get_cookie_client),
+        1, // request or response complete
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("dns.cookie.server"),
+        desc: String::from("DNS server cookie sticky buffer"),
+        url: String::from("/rules/dns-keywords.html#dns-cookie-server"),
+        setup: dns_detect_cookie_server_setup,
+    };
+    let _g_dns_cookie_server_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_DNS_COOKIE_SERVER_BUFFER_ID = SCDetectHelperMultiBufferProgressMpmRegister(
+        b"dns.cookie.server\0".as_ptr() as *const libc::c_char,
+        b"dns server cookie\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(dns_tx_get_cookie_server),
+        1, // request or response complete
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.padding_length\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the length of the EDNS padding option.\0".as_ptr() as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-padding-length\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_padding_length_match),
+        Setup: Some(dns_padding_length_setup),
+        Free: Some(dns_u16_free),
+        flags: 0,
+    };
+    G_DNS_PADDING_LENGTH_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_PADDING_LENGTH_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.padding_length\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.query.entropy\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the Shannon entropy of the query name labels.\0".as_ptr()
+            as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-query-entropy\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_query_entropy_match),
+        Setup: Some(dns_query_entropy_setup),
+        Free: Some(dns_f64_free),
+        flags: 0,
+    };
+    G_DNS_QUERY_ENTROPY_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_QUERY_ENTROPY_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.query.entropy\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.query.digit_ratio\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the ratio of digits in the query name labels.\0".as_ptr()
+            as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-query-digit-ratio\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_query_digit_ratio_match),
+        Setup: Some(dns_query_digit_ratio_setup),
+        Free: Some(dns_f64_free),
+        flags: 0,
+    };
+    G_DNS_QUERY_DIGIT_RATIO_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_QUERY_DIGIT_RATIO_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.query.digit_ratio\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.query.upper_ratio\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the ratio of upper case letters in the query name labels.\0".as_ptr()
+            as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-query-upper-ratio\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_query_upper_ratio_match),
+        Setup: Some(dns_query_upper_ratio_setup),
+        Free: Some(dns_f64_free),
+        flags: 0,
+    };
+    G_DNS_QUERY_UPPER_RATIO_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_QUERY_UPPER_RATIO_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.query.upper_ratio\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.query.other_ratio\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the ratio of unusual characters in the query name labels.\0".as_ptr()
+            as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-query-other-ratio\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_query_other_ratio_match),
+        Setup: Some(dns_query_other_ratio_setup),
+        Free: Some(dns_f64_free),
+        flags: 0,
+    };
+    G_DNS_QUERY_OTHER_RATIO_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_QUERY_OTHER_RATIO_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.query.other_ratio\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.query.longest_label\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the length of the longest query name label.\0".as_ptr()
+            as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-query-longest-label\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_query_longest_label_match),
+        Setup: Some(dns_query_longest_label_setup),
+        Free: Some(dns_u8_free),
+        flags: 0,
+    };
+    G_DNS_QUERY_LONGEST_LABEL_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_QUERY_LONGEST_LABEL_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.query.longest_label\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.query.label_count\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the number of query name labels.\0".as_ptr() as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-query-label-count\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_query_label_count_match),
+        Setup: Some(dns_query_label_count_setup),
+        Free: Some(dns_u8_free),
+        flags: 0,
+    };
+    G_DNS_QUERY_LABEL_COUNT_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_QUERY_LABEL_COUNT_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.query.label_count\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.parent.subdomains\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the number of unique subdomains queried under the parent domain.\0".as_ptr()
+            as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-parent-subdomains\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_parent_subdomains_match),
+        Setup: Some(dns_parent_subdomains_setup),
+        Free: Some(dns_u32_free),
+        flags: 0,
+    };
+    G_DNS_PARENT_SUBDOMAINS_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_PARENT_SUBDOMAINS_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.parent.subdomains\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.parent.bytes\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the bytes of names queried under the parent domain.\0".as_ptr()
+            as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-parent-bytes\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_parent_bytes_match),
+        Setup: Some(dns_parent_bytes_setup),
+        Free: Some(dns_u64_free),
+        flags: 0,
+    };
+    G_DNS_PARENT_BYTES_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_PARENT_BYTES_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.parent.bytes\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOSERVER,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"dns.payload_size\0".as_ptr() as *const libc::c_char,
+        desc: b"Match the size of the TXT and NULL answer data.\0".as_ptr() as *const libc::c_char,
+        url: b"rules/dns-keywords.html#dns-payload-size\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(dns_payload_size_match),
+        Setup: Some(dns_payload_size_setup),
+        Free: Some(dns_u32_free),
+        flags: 0,
+    };
+    G_DNS_PAYLOAD_SIZE_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_DNS_PAYLOAD_SIZE_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"dns.payload_size\0".as_ptr() as *const libc::c_char,
+        ALPROTO_DNS,
+        STREAM_TOCLIENT,
+    );
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::detect::float::detect_parse_float;
     use crate::detect::uint::{detect_parse_uint, DetectUintMode};
+    use crate::dns::dns::{DNSAnswerEntry, DNSHeader, DNSName, DNSNameFlags, DNSQueryEntry};
 
     #[test]
     fn parse_opcode_good() {
@@ -659,6 +1815,200 @@ mod test {
         assert!(detect_parse_uint_enum::<u16, DNSRecordType>("!asdf").is_none());
     }
 
+    #[test]
+    fn test_svcb_alpn_ech() {
+        let svcb = |alpn: Vec<&[u8]>, ech: Option<Vec<u8>>| DNSAnswerEntry {
+            name: DNSName {
+                value: b"example.com".to_vec(),
+                flags: DNSNameFlags::default(),
+            },
+            rrtype: DNSRecordType::HTTPS as u16,
+            rrclass: 1,
+            ttl: 300,
+            data: DNSRData::SVCB(DNSRDataSVCB {
+                priority: 1,
+                target: DNSName {
+                    value: Vec::new(),
+                    flags: DNSNameFlags::default(),
+                },
+                mandatory: Vec::new(),
+                alpn: alpn.iter().map(|a| a.to_vec()).collect(),
+                no_default_alpn: false,
+                port: None,
+                ipv4hint: Vec::new(),
+                ech,
+                ipv6hint: Vec::new(),
+                params: Vec::new(),
+            }),
+        };
+        let mut tx = DNSTransaction::default();
+        let toclient = Direction::ToClient as u8;
+        assert!(dns_svcb_alpn(&tx, toclient, 0).is_none());
+
+        tx.response = Some(DNSMessage {
+            header: DNSHeader {
+                tx_id: 1,
+                flags: 0x8180,
+                questions: 0,
+                answer_rr: 2,
+                authority_rr: 0,
+                additional_rr: 0,
+            },
+            queries: Vec::new(),
+            answers: vec![
+                svcb(vec![b"h3", b"h2"], None),
+                svcb(vec![b"http/1.1"], None),
+            ],
+            authorities: Vec::new(),
+            invalid_authorities: false,
+            additionals: Vec::new(),
+            invalid_additionals: false,
+        });
+        assert_eq!(dns_svcb_alpn(&tx, toclient, 0), Some(&b"h3"[..]));
+        assert_eq!(dns_svcb_alpn(&tx, toclient, 2), Some(&b"http/1.1"[..]));
+        assert!(dns_svcb_alpn(&tx, toclient, 3).is_none());
+        assert!(dns_svcb_alpn(&tx, Direction::ToServer as u8, 0).is_none());
+        assert!(!dns_svcb_has_ech(&tx, toclient));
+
+        if let Some(response) = &mut tx.response {
+            response.answers.push(svcb(Vec::new(), Some(vec![0, 1])));
+        }
+        assert!(dns_svcb_has_ech(&tx, toclient));
+    }
+
+    #[test]
+    fn test_edns_options() {
+        assert_eq!(
+            detect_parse_uint_enum::<u16, DNSExtendedError>("dnssec_bogus").unwrap(),
+            DetectUintData {
+                mode: DetectUintMode::DetectUintModeEqual,
+                arg1: 6,
+                arg2: 0,
+            }
+        );
+
+        let opt = |code: u16, data: &[u8], value: DNSEdnsOption| DNSRDataOPT {
+            code,
+            data: data.to_vec(),
+            value,
+        };
+        let tx = DNSTransaction {
+            response: Some(DNSMessage {
+                header: DNSHeader {
+                    tx_id: 1,
+                    flags: 0x8182,
+                    questions: 0,
+                    answer_rr: 0,
+                    authority_rr: 0,
+                    additional_rr: 1,
+                },
+                queries: Vec::new(),
+                answers: Vec::new(),
+                authorities: Vec::new(),
+                invalid_authorities: false,
+                additionals: vec![DNSAnswerEntry {
+                    name: DNSName {
+                        value: Vec::new(),
+                        flags: DNSNameFlags::default(),
+                    },
+                    rrtype: DNSRecordType::OPT as u16,
+                    rrclass: 1232,
+                    ttl: 0,
+                    data: DNSRData::OPT(vec![
+                        opt(3, b"ns1", DNSEdnsOption::Nsid),
+                        opt(
+                            15,
+                            b"\x00\x06bad",
+                            DNSEdnsOption::ExtendedError {
+                                info_code: 6,
+                                extra_text: b"bad".to_vec(),
+                            },
+                        ),
+                        opt(
+                            8,
+                            b"\x00\x01\x18\x10\xc0\x00\x02",
+                            DNSEdnsOption::ClientSubnet(DNSEdnsClientSubnet {
+                                family: 1,
+                                source_prefix: 24,
+                                scope_prefix: 16,
+                                address: vec![192, 0, 2],
+                            }),
+                        ),
+                        opt(
+                            10,
+                            b"\x01\x02\x03\x04\x05\x06\x07\x08",
+                            DNSEdnsOption::Cookie {
+                                client: vec![1, 2, 3, 4, 5, 6, 7, 8],
+                                server: Vec::new(),
+                            },
+                        ),
+                    ]),
+                }],
+                invalid_additionals: false,
+            }),
+            ..Default::default()
+        };
+        let toclient = Direction::ToClient as u8;
+        assert_eq!(dns_nsid(&tx, toclient, 0), Some(&b"ns1"[..]));
+        assert!(dns_nsid(&tx, toclient, 1).is_none());
+        assert_eq!(dns_ede_text(&tx, toclient, 0), Some(&b"bad"[..]));
+        assert!(dns_ede_text(&tx, Direction::ToServer as u8, 0).is_none());
+        assert_eq!(dns_ecs_address(&tx, toclient, 0), Some(&[192, 0, 2][..]));
+        assert_eq!(dns_tx_ecs(&tx, toclient).next().unwrap().scope_prefix, 16);
+        assert_eq!(
+            dns_cookie(&tx, toclient, 0, false),
+            Some(&[1, 2, 3, 4, 5, 6, 7, 8][..])
+        );
+        // the server cookie is empty
+        assert!(dns_cookie(&tx, toclient, 0, true).is_none());
+    }
+
+    #[test]
+    fn test_query_features() {
+        let query = |name: &[u8]| DNSQueryEntry {
+            name: DNSName {
+                value: name.to_vec(),
+                flags: DNSNameFlags::default(),
+            },
+            rrtype: DNSRecordType::TXT as u16,
+            rrclass: 1,
+        };
+        let tx = DNSTransaction {
+            request: Some(DNSMessage {
+                header: DNSHeader {
+                    tx_id: 1,
+                    flags: 0x0100,
+                    questions: 2,
+                    answer_rr: 0,
+                    authority_rr: 0,
+                    additional_rr: 0,
+                },
+                queries: vec![
+                    query(b"www.suricata.io"),
+                    query(b"0a1b2c3d4e5f60718293a4b5c6d7e8f9.t.example.com"),
+                ],
+                answers: Vec::new(),
+                authorities: Vec::new(),
+                invalid_authorities: false,
+                additionals: Vec::new(),
+                invalid_additionals: false,
+            }),
+            ..Default::default()
+        };
+        let toserver = Direction::ToServer as u8;
+        let features: Vec<DNSNameFeatures> = dns_tx_query_features(&tx, toserver).collect();
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0].label_count, 3);
+        assert_eq!(features[1].longest_label, 32);
+        assert!(dns_tx_query_features(&tx, Direction::ToClient as u8)
+            .next()
+            .is_none());
+
+        let ctx = detect_parse_float::<f64>(">3.5").unwrap().1;
+        assert!(!detect_match_float::<f64>(&ctx, features[0].entropy));
+        assert!(detect_match_float::<f64>(&ctx, features[1].entropy));
+    }
+
     #[test]
     fn test_match_rrtype() {
         assert!(detect_match_uint(
@@ -22,13 +22,16 @@ use std::ffi::CString;
 use std::os::raw::c_void;
 
 use crate::applayer::*;
+use crate::conf::conf_get_bool;
 use crate::core::{self, *};
 use crate::direction::Direction;
 use crate::direction::DIR_BOTH;
+use crate::dns::features::{DNSParentStats, DNSParentTracker};
 use crate::dns::parser;
 use crate::flow::Flow;
 use crate::frames::Frame;
 
+use nom7::multi::length_data;
 use nom7::number::streaming::be_u16;
 use nom7::{Err, IResult};
 use suricata_sys::sys::{AppProto, DetectEngineThreadCtx};
@@ -89,6 +92,7 @@ pub enum DNSRecordType {
     HIP = 55,
     CDS = 59,
     CDNSKEY = 60,
+    SVCB = 64,
     HTTPS = 65,
     SPF = 99, // Obsolete
     TKEY = 249,
@@ -96,6 +100,7 @@ pub enum DNSRecordType {
     MAILA = 254, // Obsolete
     ANY = 255,
     URI = 256,
+    CAA = 257,
 }
 
 /// DNS error codes.
@@ -124,6 +129,36 @@ pub enum DNSRcode {
     BADTRUNC = 22,
 }
 
+/// Extended DNS error codes (RFC 8914).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumStringU16)]
+pub enum DNSExtendedError {
+    Other = 0,
+    UnsupportedDnskeyAlgorithm = 1,
+    UnsupportedDsDigestType = 2,
+    StaleAnswer = 3,
+    ForgedAnswer = 4,
+    DnssecIndeterminate = 5,
+    DnssecBogus = 6,
+    SignatureExpired = 7,
+    SignatureNotYetValid = 8,
+    DnskeyMissing = 9,
+    RrsigsMissing = 10,
+    NoZoneKeyBitSet = 11,
+    NsecMissing = 12,
+    CachedError = 13,
+    NotReady = 14,
+    Blocked = 15,
+    Censored = 16,
+    Filtered = 17,
+    Prohibited = 18,
+    StaleNxdomainAnswer = 19,
+    NotAuthoritative = 20,
+    NotSupported = 21,
+    NoReachableAuthority = 22,
+    NetworkError = 23,
+    InvalidData = 24,
+}
+
 pub(super) static mut ALPROTO_DNS: AppProto = ALPROTO_UNKNOWN;
 
 #[derive(AppLayerFrameType)]
@@ -169,12 +204,53 @@ pub struct DNSQueryEntry {
     pub rrclass: u16,
 }
 
+/// EDNS option codes.
+pub const DNS_EDNS_NSID: u16 = 3;
+pub const DNS_EDNS_CLIENT_SUBNET: u16 = 8;
+pub const DNS_EDNS_COOKIE: u16 = 10;
+pub const DNS_EDNS_PADDING: u16 = 12;
+pub const DNS_EDNS_EXTENDED_ERROR: u16 = 15;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DNSEdnsClientSubnet {
+    /// Address family, 1 for IPv4 and 2 for IPv6
+    pub family: u16,
+    /// Source prefix length
+    pub source_prefix: u8,
+    /// Scope prefix length
+    pub scope_prefix: u8,
+    /// Address, truncated to the source prefix length
+    pub address: Vec<u8>,
+}
+
+/// Decoded EDNS option data.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DNSEdnsOption {
+    /// Name server identifier, the data being the identifier
+    Nsid,
+    ClientSubnet(DNSEdnsClientSubnet),
+    Cookie {
+        client: Vec<u8>,
+        server: Vec<u8>,
+    },
+    /// Padding, the data being the padding
+    Padding,
+    ExtendedError {
+        info_code: u16,
+        extra_text: Vec<u8>,
+    },
+    /// Unknown option, or option that failed to decode
+    Unknown,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct DNSRDataOPT {
     /// Option Code
     pub code: u16,
     /// Option Data
     pub data: Vec<u8>,
+    /// Decoded Option Data
+    pub value: DNSEdnsOption,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -217,6 +293,124 @@ pub struct DNSRDataSRV {
     pub target: DNSName,
 }
 
+/// SVCB parameter keys (RFC 9460).
+pub const DNS_SVCB_KEY_MANDATORY: u16 = 0;
+pub const DNS_SVCB_KEY_ALPN: u16 = 1;
+pub const DNS_SVCB_KEY_NO_DEFAULT_ALPN: u16 = 2;
+pub const DNS_SVCB_KEY_PORT: u16 = 3;
+pub const DNS_SVCB_KEY_IPV4HINT: u16 = 4;
+pub const DNS_SVCB_KEY_ECH: u16 = 5;
+pub const DNS_SVCB_KEY_IPV6HINT: u16 = 6;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DNSSvcParam {
+    /// SvcParamKey
+    pub key: u16,
+    /// SvcParamValue
+    pub value: Vec<u8>,
+}
+
+/// RData of the SVCB and HTTPS records.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DNSRDataSVCB {
+    /// Priority, 0 for the alias form
+    pub priority: u16,
+    /// Target
+    pub target: DNSName,
+    /// Keys that are mandatory for the service
+    pub mandatory: Vec<u16>,
+    /// Supported protocols
+    pub alpn: Vec<Vec<u8>>,
+    /// The default protocol of the scheme is not supported
+    pub no_default_alpn: bool,
+    /// Alternative port
+    pub port: Option<u16>,
+    /// IPv4 address hints
+    pub ipv4hint: Vec<Vec<u8>>,
+    /// Encrypted ClientHello configuration list
+    pub ech: Option<Vec<u8>>,
+    /// IPv6 address hints
+    pub ipv6hint: Vec<Vec<u8>>,
+    /// Unknown or malformed parameters
+    pub params: Vec<DNSSvcParam>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DNSRDataCAA {
+    /// Flags, with the critical bit
+    pub flags: u8,
+    /// Property tag
+    pub tag: Vec<u8>,
+    /// Property value
+    pub value: Vec<u8>,
+}
+
+/// RData of the DNSKEY and CDNSKEY records.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DNSRDataDNSKEY {
+    /// Flags
+    pub flags: u16,
+    /// Protocol, always 3
+    pub protocol: u8,
+    /// Algorithm number
+    pub algorithm: u8,
+    /// Public key
+    pub public_key: Vec<u8>,
+    /// Key tag, as computed from the rdata
+    pub key_tag: u16,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DNSRDataRRSIG {
+    /// Type of the signed records
+    pub type_covered: u16,
+    /// Algorithm number
+    pub algorithm: u8,
+    /// Number of labels of the signed name
+    pub labels: u8,
+    /// TTL of the signed records
+    pub original_ttl: u32,
+    /// End of the validity period (seconds since epoch)
+    pub expiration: u32,
+    /// Start of the validity period (seconds since epoch)
+    pub inception: u32,
+    /// Key tag of the signing key
+    pub key_tag: u16,
+    /// Name of the zone of the signing key
+    pub signer: DNSName,
+    /// Signature
+    pub signature: Vec<u8>,
+}
+
+/// RData of the DS and CDS records.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DNSRDataDS {
+    /// Key tag of the key
+    pub key_tag: u16,
+    /// Algorithm number of the key
+    pub algorithm: u8,
+    /// Digest type
+    pub digest_type: u8,
+    /// Digest of the key
+    pub digest: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DNSRDataNAPTR {
+    /// Order
+    pub order: u16,
+    /// Preference
+    pub preference: u16,
+    /// Flags
+    pub flags: Vec<u8>,
+    /// Services
+    pub services: Vec<u8>,
+    /// Regular expression
+    pub regexp: Vec<u8>,
+    /// Replacement
+    pub replacement: DNSName,
+}
+
 bitflags! {
     #[derive(Default)]
     pub struct DNSNameFlags: u8 {
@@ -251,6 +445,15 @@ pub enum DNSRData {
     SRV(DNSRDataSRV),
     SSHFP(DNSRDataSSHFP),
     OPT(Vec<DNSRDataOPT>),
+    // SVCB and HTTPS records
+    SVCB(DNSRDataSVCB),
+    CAA(DNSRDataCAA),
+    // DNSKEY and CDNSKEY records
+    DNSKEY(DNSRDataDNSKEY),
+    RRSIG(DNSRDataRRSIG),
+    // DS and CDS records
+    DS(DNSRDataDS),
+    NAPTR(DNSRDataNAPTR),
     // RData for remaining types is sometimes ignored
     Unknown(Vec<u8>),
 }
@@ -280,6 +483,11 @@ pub struct DNSTransaction {
     pub id: u64,
     pub request: Option<DNSMessage>,
     pub response: Option<DNSMessage>,
+    /// Statistics of the parent domain of the query, as seen in the
+    /// flow up to this request.
+    pub parent: Option<DNSParentStats>,
+    /// Variant the message was parsed as, giving its transport
+    pub variant: DnsVariant,
     pub tx_data: AppLayerTxData,
 }
 
@@ -355,19 +563,38 @@ impl ConfigTracker {
     }
 }
 
-pub(crate) enum DnsVariant {
+/// Variant of DNS, with the transport of unicast DNS messages.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DnsVariant {
+    #[default]
     Dns,
     MulticastDns,
+    /// DNS over TCP, with the 2 byte length prefix
+    DnsOverTcp,
+    /// DNS over HTTP/2
+    DnsOverHttps,
+    /// DNS over the streams of QUIC (RFC 9250)
+    DnsOverQuic,
 }
 
 impl DnsVariant {
     pub fn is_dns(&self) -> bool {
-        matches!(self, DnsVariant::Dns)
+        !self.is_mdns()
     }
 
     pub fn is_mdns(&self) -> bool {
         matches!(self, DnsVariant::MulticastDns)
     }
+
+    /// Name of the transport, as logged.
+    pub fn transport(&self) -> &'static str {
+        match self {
+            DnsVariant::Dns | DnsVariant::MulticastDns => "udp",
+            DnsVariant::DnsOverTcp => "tcp",
+            DnsVariant::DnsOverHttps => "https",
+            DnsVariant::DnsOverQuic => "quic",
+        }
+    }
 }
 
 //#[derive(Default)]
@@ -384,6 +611,8 @@ pub struct DNSState {
     config: Option<ConfigTracker>,
 
     gap: bool,
+
+    parents: DNSParentTracker,
 }
 
 impl State<DNSTransaction> for DNSState {
@@ -431,6 +660,7 @@ pub(crate) fn dns_parse_request(input: &[u8], variant: &DnsVariant) -> Result<DN
             let opcode = ((request.header.flags >> 11) & 0xf) as u8;
 
             let mut tx = DNSTransaction::new(Direction::ToServer);
+            tx.variant = *variant;
             if request.invalid_additionals {
                 tx.set_event(DNSEvent::InvalidAdditionals);
             }
@@ -480,7 +710,9 @@ pub(crate) fn dns_parse_request(input: &[u8], variant: &DnsVariant) -> Result<DN
     }
 }
 
-pub(crate) fn dns_parse_response(input: &[u8]) -> Result<DNSTransaction, DNSParseError> {
+pub(crate) fn dns_parse_response(
+    input: &[u8], variant: &DnsVariant,
+) -> Result<DNSTransaction, DNSParseError> {
     let (body, header) = if let Some((body, header)) = dns_validate_header(input) {
         (body, header)
     } else {
@@ -495,6 +727,7 @@ pub(crate) fn dns_parse_response(input: &[u8]) -> Result<DNSTransaction, DNSPars
             let flags = response.header.flags;
 
             let mut tx = DNSTransaction::new(Direction::ToClient);
+            tx.variant = *variant;
             if response.invalid_additionals {
                 tx.set_event(DNSEvent::InvalidAdditionals);
             }
@@ -544,6 +777,60 @@ pub(crate) fn dns_parse_response(input: &[u8]) -> Result<DNSTransaction, DNSPars
     }
 }
 
+/// Parse a DNS message prefixed with its 2 byte length, as carried by
+/// TCP and by the streams of DNS over QUIC.
+///
+/// Returns the input after the message and the transaction.
+pub(crate) fn dns_parse_framed<'a>(
+    input: &'a [u8], variant: &DnsVariant, direction: Direction,
+) -> Result<(&'a [u8], DNSTransaction), DNSParseError> {
+    let (rem, msg) = match length_data(be_u16)(input) as IResult<&[u8], &[u8]> {
+        Ok(r) => r,
+        Err(Err::Incomplete(_)) => {
+            return Err(DNSParseError::Incomplete);
+        }
+        Err(_) => {
+            return Err(DNSParseError::OtherError);
+        }
+    };
+    let r = if direction == Direction::ToServer {
+        dns_parse_request(msg, variant)
+    } else {
+        dns_parse_response(msg, variant)
+    };
+    match r {
+        Ok(tx) => Ok((rem, tx)),
+        // the message is complete, so a short one is malformed
+        Err(DNSParseError::Incomplete) => Err(DNSParseError::OtherError),
+        Err(e) => Err(e),
+    }
+}
+
+/// Number of bytes needed for the length prefixed message at the start
+/// of the input.
+fn dns_framed_len(input: &[u8]) -> u32 {
+    match be_u16(input) as IResult<&[u8], u16> {
+        Ok((_, len)) => len as u32 + 2,
+        _ => 2,
+    }
+}
+
+/// Create the PDU frame of the message at the start of the input, with
+/// `rem` the input after the message.
+fn dns_framed_pdu(
+    flow: *const Flow, stream_slice: &StreamSlice, input: &[u8], rem: &[u8],
+) -> Option<Frame> {
+    let msg = &input[2..input.len() - rem.len()];
+    Frame::new(
+        flow,
+        stream_slice,
+        msg,
+        msg.len() as i64,
+        DnsFrameType::Pdu as u8,
+        None,
+    )
+}
+
 impl DNSState {
     fn new() -> Self {
         Self {
@@ -553,6 +840,7 @@ impl DNSState {
             transactions: VecDeque::default(),
             config: None,
             gap: false,
+            parents: DNSParentTracker::default(),
         }
     }
 
@@ -564,6 +852,7 @@ impl DNSState {
             transactions: VecDeque::default(),
             config: None,
             gap: false,
+            parents: DNSParentTracker::default(),
         }
     }
 
@@ -599,13 +888,19 @@ impl DNSState {
         tx.tx_data.set_event(event as u8);
     }
 
-    fn parse_request(
-        &mut self, input: &[u8], is_tcp: bool, frame: Option<Frame>, flow: *const Flow,
+    /// Add the transaction of a parsed request, or set the event of
+    /// the parse error.
+    fn handle_request(
+        &mut self, result: Result<DNSTransaction, DNSParseError>, is_tcp: bool,
+        frame: Option<Frame>, flow: *const Flow,
     ) -> bool {
-        match dns_parse_request(input, &self.variant) {
+        match result {
             Ok(mut tx) => {
                 self.tx_id += 1;
                 tx.id = self.tx_id;
+                if let Some(query) = tx.request.as_ref().and_then(|r| r.queries.first()) {
+                    tx.parent = self.parents.update(&query.name.value);
+                }
                 if let Some(frame) = frame {
                     frame.set_tx(flow, tx.id);
                 }
@@ -632,6 +927,11 @@ impl DNSState {
         }
     }
 
+    fn parse_request(&mut self, input: &[u8], frame: Option<Frame>, flow: *const Flow) -> bool {
+        let result = dns_parse_request(input, &self.variant);
+        self.handle_request(result, false, frame, flow)
+    }
+
     pub(crate) fn parse_request_udp(&mut self, flow: *const Flow, stream_slice: StreamSlice) -> bool {
         let input = stream_slice.as_slice();
         let frame = Frame::new(
@@ -642,7 +942,7 @@ impl DNSState {
             DnsFrameType::Pdu as u8,
             None,
         );
-        self.parse_request(input, false, frame, flow)
+        self.parse_request(input, frame, flow)
     }
 
     fn parse_response_udp(&mut self, flow: *const Flow, stream_slice: StreamSlice) -> bool {
@@ -655,13 +955,16 @@ impl DNSState {
             DnsFrameType::Pdu as u8,
             None,
         );
-        self.parse_response(input, false, frame, flow)
+        self.parse_response(input, frame, flow)
     }
 
-    fn parse_response(
-        &mut self, input: &[u8], is_tcp: bool, frame: Option<Frame>, flow: *const Flow,
+    /// Add the transaction of a parsed response, or set the event of
+    /// the parse error.
+    fn handle_response(
+        &mut self, result: Result<DNSTransaction, DNSParseError>, is_tcp: bool,
+        frame: Option<Frame>, flow: *const Flow,
     ) -> bool {
-        match dns_parse_response(input) {
+        match result {
             Ok(mut tx) => {
                 self.tx_id += 1;
                 tx.id = self.tx_id;
@@ -690,10 +993,13 @@ impl DNSState {
         }
     }
 
-    /// TCP variation of response request parser to handle the length
+    fn parse_response(&mut self, input: &[u8], frame: Option<Frame>, flow: *const Flow) -> bool {
+        let result = dns_parse_response(input, &self.variant);
+        self.handle_response(result, false, frame, flow)
+    }
+
+    /// TCP variation of the request parser to handle the length
     /// prefix.
-    ///
-    /// Returns the number of messages parsed.
     fn parse_request_tcp(
         &mut self, flow: *const Flow, stream_slice: StreamSlice,
     ) -> AppLayerResult {
@@ -710,45 +1016,42 @@ impl DNSState {
         let mut cur_i = input;
         let mut consumed = 0;
         while !cur_i.is_empty() {
-            if cur_i.len() == 1 {
-                return AppLayerResult::incomplete(consumed as u32, 2_u32);
+            if cur_i.starts_with(&[0, 0]) {
+                // Skip empty messages.
+                cur_i = &cur_i[2..];
+                consumed += 2;
+                continue;
             }
-            let size = match be_u16(cur_i) as IResult<&[u8], u16> {
-                Ok((_, len)) => len,
-                _ => 0,
-            } as usize;
-            SCLogDebug!(
-                "[request] Have {} bytes, need {} to parse",
-                cur_i.len(),
-                size + 2
-            );
-            if size > 0 && cur_i.len() >= size + 2 {
-                let msg = &cur_i[2..(size + 2)];
-                sc_app_layer_parser_trigger_raw_stream_inspection(flow, Direction::ToServer as i32);
-                let frame = Frame::new(
-                    flow,
-                    &stream_slice,
-                    msg,
-                    msg.len() as i64,
-                    DnsFrameType::Pdu as u8,
-                    None,
-                );
-                if self.parse_request(msg, true, frame, flow) {
-                    cur_i = &cur_i[(size + 2)..];
-                    consumed += size + 2;
-                } else {
+            match dns_parse_framed(cur_i, &DnsVariant::DnsOverTcp, Direction::ToServer) {
+                Ok((rem, tx)) => {
+                    sc_app_layer_parser_trigger_raw_stream_inspection(
+                        flow,
+                        Direction::ToServer as i32,
+                    );
+                    let frame = dns_framed_pdu(flow, &stream_slice, cur_i, rem);
+                    if !self.handle_request(Ok(tx), true, frame, flow) {
+                        return AppLayerResult::err();
+                    }
+                    consumed += cur_i.len() - rem.len();
+                    cur_i = rem;
+                }
+                Err(DNSParseError::Incomplete) => {
+                    let needed = dns_framed_len(cur_i);
+                    SCLogDebug!(
+                        "[request]Not enough DNS traffic to parse. Returning {}/{}",
+                        consumed as u32,
+                        needed
+                    );
+                    return AppLayerResult::incomplete(consumed as u32, needed);
+                }
+                Err(e) => {
+                    sc_app_layer_parser_trigger_raw_stream_inspection(
+                        flow,
+                        Direction::ToServer as i32,
+                    );
+                    self.handle_request(Err(e), true, None, flow);
                     return AppLayerResult::err();
                 }
-            } else if size == 0 {
-                cur_i = &cur_i[2..];
-                consumed += 2;
-            } else {
-                SCLogDebug!(
-                    "[request]Not enough DNS traffic to parse. Returning {}/{}",
-                    consumed as u32,
-                    (size + 2) as u32
-                );
-                return AppLayerResult::incomplete(consumed as u32, (size + 2) as u32);
             }
         }
         AppLayerResult::ok()
@@ -756,8 +1059,6 @@ impl DNSState {
 
     /// TCP variation of the response parser to handle the length
     /// prefix.
-    ///
-    /// Returns the number of messages parsed.
     fn parse_response_tcp(
         &mut self, flow: *const Flow, stream_slice: StreamSlice,
     ) -> AppLayerResult {
@@ -774,45 +1075,42 @@ impl DNSState {
         let mut cur_i = input;
         let mut consumed = 0;
         while !cur_i.is_empty() {
-            if cur_i.len() == 1 {
-                return AppLayerResult::incomplete(consumed as u32, 2_u32);
+            if cur_i.starts_with(&[0, 0]) {
+                // Skip empty messages.
+                cur_i = &cur_i[2..];
+                consumed += 2;
+                continue;
             }
-            let size = match be_u16(cur_i) as IResult<&[u8], u16> {
-                Ok((_, len)) => len,
-                _ => 0,
-            } as usize;
-            SCLogDebug!(
-                "[response] Have {} bytes, need {} to parse",
-                cur_i.len(),
-                size + 2
-            );
-            if size > 0 && cur_i.len() >= size + 2 {
-                let msg = &cur_i[2..(size + 2)];
-                sc_app_layer_parser_trigger_raw_stream_inspection(flow, Direction::ToClient as i32);
-                let frame = Frame::new(
-                    flow,
-                    &stream_slice,
-                    msg,
-                    msg.len() as i64,
-                    DnsFrameType::Pdu as u8,
-                    None,
-                );
-                if self.parse_response(msg, true, frame, flow) {
-                    cur_i = &cur_i[(size + 2)..];
-                    consumed += size + 2;
-                } else {
+            match dns_parse_framed(cur_i, &DnsVariant::DnsOverTcp, Direction::ToClient) {
+                Ok((rem, tx)) => {
+                    sc_app_layer_parser_trigger_raw_stream_inspection(
+                        flow,
+                        Direction::ToClient as i32,
+                    );
+                    let frame = dns_framed_pdu(flow, &stream_slice, cur_i, rem);
+                    if !self.handle_response(Ok(tx), true, frame, flow) {
+                        return AppLayerResult::err();
+                    }
+                    consumed += cur_i.len() - rem.len();
+                    cur_i = rem;
+                }
+                Err(DNSParseError::Incomplete) => {
+                    let needed = dns_framed_len(cur_i);
+                    SCLogDebug!(
+                        "[response]Not enough DNS traffic to parse. Returning {}/{}",
+                        consumed as u32,
+                        needed
+                    );
+                    return AppLayerResult::incomplete(consumed as u32, needed);
+                }
+                Err(e) => {
+                    sc_app_layer_parser_trigger_raw_stream_inspection(
+                        flow,
+                        Direction::ToClient as i32,
+                    );
+                    self.handle_response(Err(e), true, None, flow);
                     return AppLayerResult::err();
                 }
-            } else if size == 0 {
-                cur_i = &cur_i[2..];
-                consumed += 2;
-            } else {
-                SCLogDebug!(
-                    "[response]Not enough DNS traffic to parse. Returning {}/{}",
-                    consumed as u32,
-                    (cur_i.len() - consumed) as u32
-                );
-                return AppLayerResult::incomplete(consumed as u32, (size + 2) as u32);
             }
         }
         AppLayerResult::ok()
@@ -1129,6 +1427,8 @@ fn get_rdata_name(data: &DNSRData) -> Option<&DNSName> {
             Some(name)
         }
         DNSRData::SOA(soa) => Some(&soa.mname),
+        DNSRData::SVCB(svcb) => Some(&svcb.target),
+        DNSRData::NAPTR(naptr) => Some(&naptr.replacement),
         _ => None,
     }
 }
@@ -1352,6 +1652,20 @@ pub unsafe extern "C" fn SCRegisterDnsTcpParser() {
     if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
         let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
         ALPROTO_DNS = alproto;
+        if conf_get_bool("app-layer.protocols.dns.tcp.detect-any-port") {
+            // port 0 runs the probing parser on any port
+            let any_port = CString::new("0").unwrap();
+            AppLayerProtoDetectPPRegister(
+                IPPROTO_TCP,
+                any_port.as_ptr(),
+                alproto,
+                0,
+                std::mem::size_of::<DNSHeader>() as u16 + 2,
+                Direction::ToServer as u8,
+                c_probe_tcp,
+                c_probe_tcp,
+            );
+        }
         if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
             let _ = AppLayerRegisterParser(&parser, alproto);
         }
@@ -1386,8 +1700,8 @@ mod tests {
         // The DNS payload starts at offset 42.
         let dns_payload = &buf[42..];
 
-        // Make a TCP DNS request payload.
-        let mut request = Vec::new();
+        // Make a TCP DNS request payload, after an empty message.
+        let mut request = vec![0, 0];
         request.push(((dns_payload.len() as u16) >> 8) as u8);
         request.push(((dns_payload.len() as u16) & 0xff) as u8);
         request.extend(dns_payload);
@@ -1400,6 +1714,9 @@ mod tests {
                 StreamSlice::from_slice(&request, STREAM_TOSERVER, 0)
             )
         );
+        assert_eq!(state.transactions.len(), 1);
+        assert_eq!(state.transactions[0].variant, DnsVariant::DnsOverTcp);
+        assert_eq!(state.transactions[0].variant.transport(), "tcp");
     }
 
     #[test]
@@ -1535,6 +1852,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_dns_parse_framed() {
+        // www.suricata-ids.org A query, with its length prefix
+        #[rustfmt::skip]
+        let buf: &[u8] = &[
+            0x00, 0x31, 0x8d, 0x32, 0x01, 0x20, 0x00, 0x01,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0x77,
+            0x77, 0x77, 0x0c, 0x73, 0x75, 0x72, 0x69, 0x63,
+            0x61, 0x74, 0x61, 0x2d, 0x69, 0x64, 0x73, 0x03,
+            0x6f, 0x72, 0x67, 0x00, 0x00, 0x01, 0x00, 0x01,
+            0x00, 0x00, 0x29, 0x10, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0xff,
+        ];
+
+        let (rem, tx) =
+            dns_parse_framed(buf, &DnsVariant::DnsOverQuic, Direction::ToServer).unwrap();
+        assert_eq!(rem, &[0xff]);
+        assert_eq!(tx.variant, DnsVariant::DnsOverQuic);
+        assert_eq!(tx.request.unwrap().queries[0].name.value, b"www.suricata-ids.org");
+
+        assert_eq!(
+            dns_parse_framed(&buf[..20], &DnsVariant::DnsOverQuic, Direction::ToServer)
+                .unwrap_err(),
+            DNSParseError::Incomplete
+        );
+    }
+
     // Port of the C RustDNSUDPParserTest02 unit test.
     #[test]
     fn test_dns_udp_parser_test_01() {
@@ -1557,7 +1901,7 @@ mod tests {
             0x80,
         ];
         let mut state = DNSState::new();
-        assert!(state.parse_response(buf, false, None, std::ptr::null()));
+        assert!(state.parse_response(buf, None, std::ptr::null()));
     }
 
     // Port of the C RustDNSUDPParserTest02 unit test.
@@ -1577,7 +1921,7 @@ mod tests {
             0x10,0x00,0x02,0xC0,0x85,0x00,0x00,0x29,0x05,0x00,0x00,0x00,0x00,0x00,0x00,0x00,
         ];
         let mut state = DNSState::new();
-        assert!(state.parse_response(buf, false, None, std::ptr::null()));
+        assert!(state.parse_response(buf, None, std::ptr::null()));
     }
 
     // Port of the C RustDNSUDPParserTest03 unit test.
@@ -1597,7 +1941,7 @@ mod tests {
             0x29,0x05,0x00,0x00,0x00,0x00,0x00,0x00,0x00
         ];
         let mut state = DNSState::new();
-        assert!(state.parse_response(buf, false, None, std::ptr::null()));
+        assert!(state.parse_response(buf, None, std::ptr::null()));
     }
 
     // Port of the C RustDNSUDPParserTest04 unit test.
@@ -1621,7 +1965,7 @@ mod tests {
             0x6b,0x00,0x01,0x00,0x01,0x00,0x09,0x3a,0x80,0x00,0x04,0x0a,0x1e,0x1c,0x5f
         ];
         let mut state = DNSState::new();
-        assert!(state.parse_response(buf, false, None, std::ptr::null()));
+        assert!(state.parse_response(buf, None, std::ptr::null()));
     }
 
     // Port of the C RustDNSUDPParserTest05 unit test.
@@ -1645,7 +1989,7 @@ mod tests {
             0x6b,0x00,0x01,0x00,0x01,0x00,0x09,0x3a,0x80,0x00,0x04,0x0a,0x1e,0x1c,0x5f
         ];
         let mut state = DNSState::new();
-        assert!(!state.parse_response(buf, false, None, std::ptr::null()));
+        assert!(!state.parse_response(buf, None, std::ptr::null()));
     }
 
     // Port of the C RustDNSTCPParserTestMultiRecord unit test.
@@ -0,0 +1,247 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Features of DNS names and messages to score tunneling and domain
+//! generation algorithms.
+
+use crate::detect::entropy::calculate_entropy;
+use crate::dns::dns::{DNSMessage, DNSRData};
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Maximum number of parent domains tracked per flow.
+const DNS_MAX_PARENTS: usize = 64;
+/// Maximum number of unique subdomains tracked per parent domain.
+const DNS_MAX_SUBDOMAINS: usize = 512;
+
+/// Features of a query name.
+#[derive(Debug, Default, PartialEq)]
+pub struct DNSNameFeatures {
+    /// Shannon entropy of the labels, in bits per character
+    pub entropy: f64,
+    /// Length of the longest label
+    pub longest_label: u8,
+    /// Number of labels
+    pub label_count: u8,
+    /// Ratio of digits in the labels
+    pub digit_ratio: f64,
+    /// Ratio of upper case letters in the labels
+    pub upper_ratio: f64,
+    /// Ratio of characters that are not letters, digits, '-' or '_'
+    pub other_ratio: f64,
+}
+
+/// Compute the features of a name, as found in DNSName::value.
+pub fn dns_name_features(name: &[u8]) -> DNSNameFeatures {
+    let mut features = DNSNameFeatures::default();
+    let mut chars = Vec::with_capacity(name.len());
+    for label in name.split(|c| *c == b'.').filter(|l| !l.is_empty()) {
+        features.label_count = features.label_count.saturating_add(1);
+        features.longest_label = std::cmp::max(features.longest_label, label.len() as u8);
+        chars.extend_from_slice(label);
+    }
+    if chars.is_empty() {
+        return features;
+    }
+
+    let mut digits = 0;
+    let mut upper = 0;
+    let mut other = 0;
+    for c in &chars {
+        if c.is_ascii_digit() {
+            digits += 1;
+        } else if c.is_ascii_uppercase() {
+            upper += 1;
+        } else if !c.is_ascii_lowercase() && *c != b'-' && *c != b'_' {
+            other += 1;
+        }
+    }
+    let len = chars.len() as f64;
+    features.entropy = calculate_entropy(&chars);
+    features.digit_ratio = digits as f64 / len;
+    features.upper_ratio = upper as f64 / len;
+    features.other_ratio = other as f64 / len;
+    features
+}
+
+/// Split a name into its subdomain and parent domain, the parent domain
+/// being the registrable domain from the public suffix list, such as
+/// `example.co.uk` for `www.example.co.uk`. A name that is a public
+/// suffix is its own parent domain. The lookup ignores the case of the
+/// name, as queries randomizing it (DNS 0x20) are common.
+pub fn dns_parent_domain(name: &[u8]) -> (&[u8], &[u8]) {
+    let name = name.strip_suffix(b".").unwrap_or(name);
+    if let Some(domain) = psl::domain(&name.to_ascii_lowercase()) {
+        let len = domain.as_bytes().len();
+        if len < name.len() {
+            // skip the dot between the subdomain and the parent domain
+            return (&name[..name.len() - len - 1], &name[name.len() - len..]);
+        }
+    }
+    return (&[], name);
+}
+
+/// Size of the TXT and NULL data in the answers of a message.
+pub fn dns_answer_payload_size(message: &DNSMessage) -> u32 {
+    let mut size: usize = 0;
+    for answer in &message.answers {
+        match &answer.data {
+            DNSRData::TXT(txt) => {
+                size += txt.iter().map(|t| t.len()).sum::<usize>();
+            }
+            DNSRData::NULL(bytes) => {
+                size += bytes.len();
+            }
+            _ => {}
+        }
+    }
+    size as u32
+}
+
+/// Statistics of a parent domain, as seen so far in a flow.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DNSParentStats {
+    /// Parent domain
+    pub domain: Vec<u8>,
+    /// Number of unique subdomains queried
+    pub subdomains: u32,
+    /// Number of bytes of the names queried
+    pub bytes: u64,
+}
+
+#[derive(Default)]
+struct ParentEntry {
+    subdomains: HashSet<u64>,
+    bytes: u64,
+}
+
+/// Tracks the names queried under each parent domain in a flow.
+///
+/// Memory is bounded: new parent domains are not tracked once
+/// DNS_MAX_PARENTS are, and the subdomain count of a parent saturates
+/// at DNS_MAX_SUBDOMAINS.
+#[derive(Default)]
+pub struct DNSParentTracker {
+    parents: HashMap<Vec<u8>, ParentEntry>,
+}
+
+impl DNSParentTracker {
+    /// Account for a query name, returning the updated statistics of
+    /// its parent domain.
+    pub fn update(&mut self, name: &[u8]) -> Option<DNSParentStats> {
+        let (subdomain, parent) = dns_parent_domain(name);
+        if parent.is_empty() {
+            return None;
+        }
+        let parent = parent.to_ascii_lowercase();
+        if !self.parents.contains_key(&parent) && self.parents.len() >= DNS_MAX_PARENTS {
+            return None;
+        }
+        let entry = self.parents.entry(parent.clone()).or_default();
+        entry.bytes += name.len() as u64;
+        if !subdomain.is_empty() && entry.subdomains.len() < DNS_MAX_SUBDOMAINS {
+            let mut hasher = DefaultHasher::new();
+            subdomain.to_ascii_lowercase().hash(&mut hasher);
+            entry.subdomains.insert(hasher.finish());
+        }
+        Some(DNSParentStats {
+            domain: parent,
+            subdomains: entry.subdomains.len() as u32,
+            bytes: entry.bytes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dns_name_features() {
+        let f = dns_name_features(b"www.suricata.io");
+        assert_eq!(f.label_count, 3);
+        assert_eq!(f.longest_label, 8);
+        assert_eq!(f.digit_ratio, 0.0);
+        assert_eq!(f.other_ratio, 0.0);
+
+        // dnscat2 style name with a hex encoded label
+        let f = dns_name_features(b"0a1b2c3d4e5f60718293a4b5c6d7e8f9.t.example.com");
+        assert_eq!(f.label_count, 4);
+        assert_eq!(f.longest_label, 32);
+        assert!(f.entropy > 3.5);
+        assert!(f.digit_ratio > 0.4);
+
+        let f = dns_name_features(b"AbC\xe9.com");
+        assert_eq!(f.upper_ratio, 2.0 / 7.0);
+        assert_eq!(f.other_ratio, 1.0 / 7.0);
+
+        assert_eq!(dns_name_features(b""), DNSNameFeatures::default());
+    }
+
+    #[test]
+    fn test_dns_parent_domain() {
+        assert_eq!(
+            dns_parent_domain(b"a.b.example.com"),
+            (&b"a.b"[..], &b"example.com"[..])
+        );
+        assert_eq!(
+            dns_parent_domain(b"example.com."),
+            (&b""[..], &b"example.com"[..])
+        );
+        assert_eq!(
+            dns_parent_domain(b"localhost"),
+            (&b""[..], &b"localhost"[..])
+        );
+        assert_eq!(
+            dns_parent_domain(b"t.www.example.co.uk"),
+            (&b"t.www"[..], &b"example.co.uk"[..])
+        );
+        assert_eq!(dns_parent_domain(b"co.uk"), (&b""[..], &b"co.uk"[..]));
+        assert_eq!(
+            dns_parent_domain(b"T.wWw.ExAmPlE.CO.UK"),
+            (&b"T.wWw"[..], &b"ExAmPlE.CO.UK"[..])
+        );
+    }
+
+    #[test]
+    fn test_dns_parent_tracker() {
+        let mut tracker = DNSParentTracker::default();
+        tracker.update(b"aaaa.t.example.com").unwrap();
+        tracker.update(b"AAAA.t.example.com").unwrap();
+        let stats = tracker.update(b"bbbb.t.example.com").unwrap();
+        assert_eq!(stats.domain, b"example.com");
+        assert_eq!(stats.subdomains, 2);
+        assert_eq!(stats.bytes, 54);
+
+        let stats = tracker.update(b"example.com").unwrap();
+        assert_eq!(stats.subdomains, 2);
+        assert_eq!(stats.bytes, 65);
+
+        tracker.update(b"t.www.example.co.uk").unwrap();
+        let stats = tracker.update(b"T.WWW.EXAMPLE.CO.UK").unwrap();
+        assert_eq!(stats.domain, b"example.co.uk");
+        assert_eq!(stats.subdomains, 1);
+
+        for i in 0..DNS_MAX_PARENTS {
+            tracker.update(format!("www.example{}.org", i).as_bytes());
+        }
+        assert!(tracker.update(b"www.suricata.io").is_none());
+        assert!(tracker.update(b"mail.example.com").is_some());
+    }
+}
@@ -21,6 +21,7 @@ use std::string::String;
 
 use crate::detect::EnumString;
 use crate::dns::dns::*;
+use crate::dns::features::{dns_answer_payload_size, dns_name_features};
 use crate::jsonbuilder::{JsonBuilder, JsonError};
 
 pub const LOG_A: u64 = BIT_U64!(2);
@@ -85,6 +86,7 @@ pub const LOG_URI: u64 = BIT_U64!(59);
 pub const LOG_FORMAT_GROUPED: u64 = BIT_U64!(60);
 pub const LOG_FORMAT_DETAILED: u64 = BIT_U64!(61);
 pub const LOG_HTTPS: u64 = BIT_U64!(62);
+pub const LOG_CAA: u64 = BIT_U64!(63);
 
 pub const DNS_LOG_VERSION_1: u8 = 1;
 pub const DNS_LOG_VERSION_2: u8 = 2;
@@ -251,9 +253,12 @@ fn dns_log_rrtype_enabled(rtype: u16, flags: u64) -> bool {
         Some(DNSRecordType::CDNSKEY) => {
             return flags & LOG_CDNSKEY != 0;
         }
-        Some(DNSRecordType::HTTPS) => {
+        Some(DNSRecordType::SVCB) | Some(DNSRecordType::HTTPS) => {
             return flags & LOG_HTTPS != 0;
         }
+        Some(DNSRecordType::CAA) => {
+            return flags & LOG_CAA != 0;
+        }
         Some(DNSRecordType::SPF) => {
             return flags & LOG_SPF != 0;
         }
@@ -329,6 +334,51 @@ pub(crate) fn dns_log_opt(opt: &DNSRDataOPT) -> Result<JsonBuilder, JsonError> {
     js.set_uint("code", opt.code as u64)?;
     js.set_hex("data", &opt.data)?;
 
+    match &opt.value {
+        DNSEdnsOption::Nsid => {
+            if !opt.data.is_empty() {
+                js.set_string_from_bytes("nsid", &opt.data)?;
+            }
+        }
+        DNSEdnsOption::ClientSubnet(ecs) => {
+            js.open_object("client_subnet")?;
+            js.set_uint("family", ecs.family as u64)?;
+            js.set_uint("source_prefix", ecs.source_prefix as u64)?;
+            js.set_uint("scope_prefix", ecs.scope_prefix as u64)?;
+            // The address is truncated to the prefix length on the wire.
+            let mut addr = ecs.address.clone();
+            addr.resize(if ecs.family == 1 { 4 } else { 16 }, 0);
+            js.set_string("address", &dns_print_addr(&addr))?;
+            js.close()?;
+        }
+        DNSEdnsOption::Cookie { client, server } => {
+            js.open_object("cookie")?;
+            js.set_hex("client", client)?;
+            if !server.is_empty() {
+                js.set_hex("server", server)?;
+            }
+            js.close()?;
+        }
+        DNSEdnsOption::Padding => {
+            js.set_uint("padding_length", opt.data.len() as u64)?;
+        }
+        DNSEdnsOption::ExtendedError {
+            info_code,
+            extra_text,
+        } => {
+            js.open_object("extended_error")?;
+            js.set_uint("info_code", *info_code as u64)?;
+            if let Some(error) = DNSExtendedError::from_u(*info_code) {
+                js.set_string("info", error.to_str())?;
+            }
+            if !extra_text.is_empty() {
+                js.set_string_from_bytes("extra_text", extra_text)?;
+            }
+            js.close()?;
+        }
+        DNSEdnsOption::Unknown => {}
+    }
+
     js.close()?;
     Ok(js)
 }
@@ -385,6 +435,165 @@ pub(crate) fn dns_log_srv(srv: &DNSRDataSRV) -> Result<JsonBuilder, JsonError> {
     return Ok(js);
 }
 
+/// Log SVCB and HTTPS section fields.
+pub(crate) fn dns_log_svcb(svcb: &DNSRDataSVCB) -> Result<JsonBuilder, JsonError> {
+    let mut js = JsonBuilder::try_new_object()?;
+
+    js.set_uint("priority", svcb.priority as u64)?;
+    js.set_string_from_bytes("target", &svcb.target.value)?;
+    if svcb.target.flags.contains(DNSNameFlags::TRUNCATED) {
+        js.set_bool("target_truncated", true)?;
+    }
+    if !svcb.mandatory.is_empty() {
+        js.open_array("mandatory")?;
+        for key in &svcb.mandatory {
+            js.append_uint(*key as u64)?;
+        }
+        js.close()?;
+    }
+    if !svcb.alpn.is_empty() {
+        js.open_array("alpn")?;
+        for alpn in &svcb.alpn {
+            js.append_string_from_bytes(alpn)?;
+        }
+        js.close()?;
+    }
+    if svcb.no_default_alpn {
+        js.set_bool("no_default_alpn", true)?;
+    }
+    if let Some(port) = svcb.port {
+        js.set_uint("port", port as u64)?;
+    }
+    if !svcb.ipv4hint.is_empty() {
+        js.open_array("ipv4hint")?;
+        for addr in &svcb.ipv4hint {
+            js.append_string(&dns_print_addr(addr))?;
+        }
+        js.close()?;
+    }
+    if let Some(ech) = &svcb.ech {
+        js.set_base64("ech", ech)?;
+    }
+    if !svcb.ipv6hint.is_empty() {
+        js.open_array("ipv6hint")?;
+        for addr in &svcb.ipv6hint {
+            js.append_string(&dns_print_addr(addr))?;
+        }
+        js.close()?;
+    }
+    if !svcb.params.is_empty() {
+        js.open_array("params")?;
+        for param in &svcb.params {
+            js.start_object()?;
+            js.set_uint("key", param.key as u64)?;
+            js.set_hex("value", &param.value)?;
+            js.close()?;
+        }
+        js.close()?;
+    }
+
+    js.close()?;
+    return Ok(js);
+}
+
+/// Log CAA section fields.
+pub(crate) fn dns_log_caa(caa: &DNSRDataCAA) -> Result<JsonBuilder, JsonError> {
+    let mut js = JsonBuilder::try_new_object()?;
+
+    js.set_uint("flags", caa.flags as u64)?;
+    js.set_string_from_bytes("tag", &caa.tag)?;
+    js.set_string_from_bytes("value", &caa.value)?;
+
+    js.close()?;
+    return Ok(js);
+}
+
+/// Log DNSKEY and CDNSKEY section fields.
+pub(crate) fn dns_log_dnskey(dnskey: &DNSRDataDNSKEY) -> Result<JsonBuilder, JsonError> {
+    let mut js = JsonBuilder::try_new_object()?;
+
+    js.set_uint("flags", dnskey.flags as u64)?;
+    js.set_uint("protocol", dnskey.protocol as u64)?;
+    js.set_uint("algorithm", dnskey.algorithm as u64)?;
+    js.set_uint("key_tag", dnskey.key_tag as u64)?;
+    js.set_base64("public_key", &dnskey.public_key)?;
+
+    js.close()?;
+    return Ok(js);
+}
+
+/// Log RRSIG section fields.
+pub(crate) fn dns_log_rrsig(rrsig: &DNSRDataRRSIG) -> Result<JsonBuilder, JsonError> {
+    let mut js = JsonBuilder::try_new_object()?;
+
+    js.set_string("type_covered", &dns_rrtype_string(rrsig.type_covered))?;
+    js.set_uint("algorithm", rrsig.algorithm as u64)?;
+    js.set_uint("labels", rrsig.labels as u64)?;
+    js.set_uint("original_ttl", rrsig.original_ttl as u64)?;
+    js.set_uint("expiration", rrsig.expiration as u64)?;
+    js.set_uint("inception", rrsig.inception as u64)?;
+    js.set_uint("key_tag", rrsig.key_tag as u64)?;
+    js.set_string_from_bytes("signer", &rrsig.signer.value)?;
+    if rrsig.signer.flags.contains(DNSNameFlags::TRUNCATED) {
+        js.set_bool("signer_truncated", true)?;
+    }
+    js.set_base64("signature", &rrsig.signature)?;
+
+    js.close()?;
+    return Ok(js);
+}
+
+/// Log DS and CDS section fields.
+pub(crate) fn dns_log_ds(ds: &DNSRDataDS) -> Result<JsonBuilder, JsonError> {
+    let mut js = JsonBuilder::try_new_object()?;
+
+    js.set_uint("key_tag", ds.key_tag as u64)?;
+    js.set_uint("algorithm", ds.algorithm as u64)?;
+    js.set_uint("digest_type", ds.digest_type as u64)?;
+    js.set_hex("digest", &ds.digest)?;
+
+    js.close()?;
+    return Ok(js);
+}
+
+/// Log NAPTR section fields.
+pub(crate) fn dns_log_naptr(naptr: &DNSRDataNAPTR) -> Result<JsonBuilder, JsonError> {
+    let mut js = JsonBuilder::try_new_object()?;
+
+    js.set_uint("order", naptr.order as u64)?;
+    js.set_uint("preference", naptr.preference as u64)?;
+    js.set_string_from_bytes("flags", &naptr.flags)?;
+    js.set_string_from_bytes("services", &naptr.services)?;
+    js.set_string_from_bytes("regexp", &naptr.regexp)?;
+    js.set_string_from_bytes("replacement", &naptr.replacement.value)?;
+    if naptr.replacement.flags.contains(DNSNameFlags::TRUNCATED) {
+        js.set_bool("replacement_truncated", true)?;
+    }
+
+    js.close()?;
+    return Ok(js);
+}
+
+/// Log the rdata of the types that are logged as an object, returning
+/// the name of the object along with it.
+fn dns_log_rdata_object(data: &DNSRData) -> Result<Option<(&'static str, JsonBuilder)>, JsonError> {
+    let object = match data {
+        DNSRData::SOA(soa) => ("soa", dns_log_soa(soa)?),
+        DNSRData::SSHFP(sshfp) => ("sshfp", dns_log_sshfp(sshfp)?),
+        DNSRData::SRV(srv) => ("srv", dns_log_srv(srv)?),
+        DNSRData::SVCB(svcb) => ("svcb", dns_log_svcb(svcb)?),
+        DNSRData::CAA(caa) => ("caa", dns_log_caa(caa)?),
+        DNSRData::DNSKEY(dnskey) => ("dnskey", dns_log_dnskey(dnskey)?),
+        DNSRData::RRSIG(rrsig) => ("rrsig", dns_log_rrsig(rrsig)?),
+        DNSRData::DS(ds) => ("ds", dns_log_ds(ds)?),
+        DNSRData::NAPTR(naptr) => ("naptr", dns_log_naptr(naptr)?),
+        _ => {
+            return Ok(None);
+        }
+    };
+    return Ok(Some(object));
+}
+
 /// Log a single DNS answer entry.
 ///
 /// For items that may be array, such as TXT records, i will designate
@@ -419,15 +628,6 @@ fn dns_log_json_answer_detail(answer: &DNSAnswerEntry, i: usize) -> Result<JsonB
         DNSRData::NULL(bytes) => {
             jsa.set_string_from_bytes("rdata", bytes)?;
         }
-        DNSRData::SOA(soa) => {
-            jsa.set_object("soa", &dns_log_soa(soa)?)?;
-        }
-        DNSRData::SSHFP(sshfp) => {
-            jsa.set_object("sshfp", &dns_log_sshfp(sshfp)?)?;
-        }
-        DNSRData::SRV(srv) => {
-            jsa.set_object("srv", &dns_log_srv(srv)?)?;
-        }
         DNSRData::OPT(opt) => {
             jsa.open_array("opt")?;
             for val in opt {
@@ -435,7 +635,11 @@ fn dns_log_json_answer_detail(answer: &DNSAnswerEntry, i: usize) -> Result<JsonB
             }
             jsa.close()?;
         }
-        _ => {}
+        _ => {
+            if let Some((name, object)) = dns_log_rdata_object(&answer.data)? {
+                jsa.set_object(name, &object)?;
+            }
+        }
     }
 
     jsa.close()?;
@@ -533,34 +737,17 @@ fn dns_log_json_answer(
                             a.append_string_from_bytes(bytes)?;
                         }
                     }
-                    DNSRData::SOA(soa) => {
-                        if !answer_types.contains_key(&type_string) {
-                            answer_types
-                                .insert(type_string.to_string(), JsonBuilder::try_new_array()?);
-                        }
-                        if let Some(a) = answer_types.get_mut(&type_string) {
-                            a.append_object(&dns_log_soa(soa)?)?;
-                        }
-                    }
-                    DNSRData::SSHFP(sshfp) => {
-                        if !answer_types.contains_key(&type_string) {
-                            answer_types
-                                .insert(type_string.to_string(), JsonBuilder::try_new_array()?);
-                        }
-                        if let Some(a) = answer_types.get_mut(&type_string) {
-                            a.append_object(&dns_log_sshfp(sshfp)?)?;
-                        }
-                    }
-                    DNSRData::SRV(srv) => {
-                        if !answer_types.contains_key(&type_string) {
-                            answer_types
-                                .insert(type_string.to_string(), JsonBuilder::try_new_array()?);
-                        }
-                        if let Some(a) = answer_types.get_mut(&type_string) {
-                            a.append_object(&dns_log_srv(srv)?)?;
+                    _ => {
+                        if let Some((_, object)) = dns_log_rdata_object(&answer.data)? {
+                            if !answer_types.contains_key(&type_string) {
+                                answer_types
+                                    .insert(type_string.to_string(), JsonBuilder::try_new_array()?);
+                            }
+                            if let Some(a) = answer_types.get_mut(&type_string) {
+                                a.append_object(&object)?;
+                            }
                         }
                     }
-                    _ => {}
                 }
             }
 
@@ -701,34 +888,17 @@ fn dns_log_json_answers(
                             a.append_string_from_bytes(bytes)?;
                         }
                     }
-                    DNSRData::SOA(soa) => {
-                        if !answer_types.contains_key(&type_string) {
-                            answer_types
-                                .insert(type_string.to_string(), JsonBuilder::try_new_array()?);
-                        }
-                        if let Some(a) = answer_types.get_mut(&type_string) {
-                            a.append_object(&dns_log_soa(soa)?)?;
-                        }
-                    }
-                    DNSRData::SSHFP(sshfp) => {
-                        if !answer_types.contains_key(&type_string) {
-                            answer_types
-                                .insert(type_string.to_string(), JsonBuilder::try_new_array()?);
-                        }
-                        if let Some(a) = answer_types.get_mut(&type_string) {
-                            a.append_object(&dns_log_sshfp(sshfp)?)?;
-                        }
-                    }
-                    DNSRData::SRV(srv) => {
-                        if !answer_types.contains_key(&type_string) {
-                            answer_types
-                                .insert(type_string.to_string(), JsonBuilder::try_new_array()?);
-                        }
-                        if let Some(a) = answer_types.get_mut(&type_string) {
-                            a.append_object(&dns_log_srv(srv)?)?;
+                    _ => {
+                        if let Some((_, object)) = dns_log_rdata_object(&answer.data)? {
+                            if !answer_types.contains_key(&type_string) {
+                                answer_types
+                                    .insert(type_string.to_string(), JsonBuilder::try_new_array()?);
+                            }
+                            if let Some(a) = answer_types.get_mut(&type_string) {
+                                a.append_object(&object)?;
+                            }
                         }
                     }
-                    _ => {}
                 }
             }
 
@@ -807,6 +977,20 @@ pub extern "C" fn SCDnsLogJsonQuery(
     }
 }
 
+/// Log the tunneling and DGA features of a query name.
+fn dns_log_name_features(name: &[u8], jb: &mut JsonBuilder) -> Result<(), JsonError> {
+    let features = dns_name_features(name);
+    jb.open_object("features")?;
+    jb.set_float("entropy", features.entropy)?;
+    jb.set_uint("longest_label", features.longest_label as u64)?;
+    jb.set_uint("label_count", features.label_count as u64)?;
+    jb.set_float("digit_ratio", features.digit_ratio)?;
+    jb.set_float("upper_ratio", features.upper_ratio)?;
+    jb.set_float("other_ratio", features.other_ratio)?;
+    jb.close()?;
+    Ok(())
+}
+
 /// Common logger for DNS requests and responses.
 ///
 /// It is expected that the JsonBuilder is an open object that the DNS
@@ -814,9 +998,12 @@ pub extern "C" fn SCDnsLogJsonQuery(
 /// "dns" object.
 ///
 /// This logger implements V3 style DNS logging.
-fn log_json(tx: &DNSTransaction, flags: u64, jb: &mut JsonBuilder) -> Result<(), JsonError> {
+fn log_json(
+    tx: &DNSTransaction, flags: u64, features: bool, jb: &mut JsonBuilder,
+) -> Result<(), JsonError> {
     jb.open_object("dns")?;
     jb.set_int("version", 3)?;
+    jb.set_string("transport", tx.variant.transport())?;
 
     let message = if let Some(request) = &tx.request {
         jb.set_string("type", "request")?;
@@ -870,12 +1057,29 @@ fn log_json(tx: &DNSTransaction, flags: u64, jb: &mut JsonBuilder) -> Result<(),
                 if query.name.flags.contains(DNSNameFlags::TRUNCATED) {
                     jb.set_bool("rrname_truncated", true)?;
                 }
+                if features {
+                    dns_log_name_features(&query.name.value, jb)?;
+                }
                 jb.close()?;
             }
         }
         jb.close()?;
     }
 
+    if features {
+        if let Some(parent) = &tx.parent {
+            jb.open_object("parent")?;
+            jb.set_string_from_bytes("domain", &parent.domain)?;
+            jb.set_uint("subdomains", parent.subdomains as u64)?;
+            jb.set_uint("bytes", parent.bytes)?;
+            jb.close()?;
+        }
+        let payload_size = dns_answer_payload_size(message);
+        if payload_size > 0 {
+            jb.set_uint("payload_size", payload_size as u64)?;
+        }
+    }
+
     if !message.answers.is_empty() {
         dns_log_json_answers(jb, message, flags)?;
     }
@@ -935,8 +1139,18 @@ fn log_json(tx: &DNSTransaction, flags: u64, jb: &mut JsonBuilder) -> Result<(),
 
 /// FFI wrapper around the common V3 style DNS logger.
 #[no_mangle]
-pub extern "C" fn SCDnsLogJson(tx: &DNSTransaction, flags: u64, jb: &mut JsonBuilder) -> bool {
-    log_json(tx, flags, jb).is_ok()
+pub extern "C" fn SCDnsLogJson(
+    tx: &DNSTransaction, flags: u64, features: bool, jb: &mut JsonBuilder,
+) -> bool {
+    log_json(tx, flags, features, jb).is_ok()
+}
+
+/// Log a DNS transaction carried by another protocol with all its
+/// record types, in detailed format.
+pub(crate) fn dns_log_json_inner(
+    tx: &DNSTransaction, jb: &mut JsonBuilder,
+) -> Result<(), JsonError> {
+    log_json(tx, !LOG_FORMAT_GROUPED, false, jb)
 }
 
 /// Check if a DNS transaction should be logged based on the
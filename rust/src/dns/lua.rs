@@ -199,6 +199,36 @@ pub extern "C" fn SCDnsLuaGetAnswerTable(clua: &mut CLuaState, tx: &mut DNSTrans
                     lua.pushstring(&String::from_utf8_lossy(&srv.target.value));
                     lua.settable(-3);
                 }
+                DNSRData::SVCB(ref svcb) => {
+                    lua.pushstring("addr");
+                    lua.pushstring(&String::from_utf8_lossy(&svcb.target.value));
+                    lua.settable(-3);
+                }
+                DNSRData::NAPTR(ref naptr) => {
+                    lua.pushstring("addr");
+                    lua.pushstring(&String::from_utf8_lossy(&naptr.replacement.value));
+                    lua.settable(-3);
+                }
+                DNSRData::RRSIG(ref rrsig) => {
+                    lua.pushstring("addr");
+                    lua.pushstring(&String::from_utf8_lossy(&rrsig.signer.value));
+                    lua.settable(-3);
+                }
+                DNSRData::CAA(ref caa) => {
+                    lua.pushstring("addr");
+                    lua.pushstring(&String::from_utf8_lossy(&caa.value));
+                    lua.settable(-3);
+                }
+                DNSRData::DNSKEY(ref dnskey) => {
+                    lua.pushstring("addr");
+                    lua.pushstring(&String::from_utf8_lossy(&dnskey.public_key));
+                    lua.settable(-3);
+                }
+                DNSRData::DS(ref ds) => {
+                    lua.pushstring("addr");
+                    lua.pushstring(&String::from_utf8_lossy(&ds.digest));
+                    lua.settable(-3);
+                }
                 DNSRData::OPT(ref opt) => {
                     if !opt.is_empty() {
                         lua.pushstring("addr");
@@ -19,6 +19,7 @@
 
 pub mod detect;
 pub mod dns;
+pub mod features;
 pub mod log;
 pub mod lua;
 pub mod parser;
@@ -372,6 +372,57 @@ fn dns_parse_rdata_sshfp(input: &[u8]) -> IResult<&[u8], DNSRData> {
     ))
 }
 
+fn dns_parse_edns_option(code: u16, data: &[u8]) -> DNSEdnsOption {
+    match code {
+        DNS_EDNS_NSID => DNSEdnsOption::Nsid,
+        DNS_EDNS_CLIENT_SUBNET => {
+            if data.len() < 4 {
+                return DNSEdnsOption::Unknown;
+            }
+            let family = u16::from_be_bytes([data[0], data[1]]);
+            let address = &data[4..];
+            let max_len = match family {
+                1 => 4,
+                2 => 16,
+                _ => {
+                    return DNSEdnsOption::Unknown;
+                }
+            };
+            if address.len() > max_len {
+                return DNSEdnsOption::Unknown;
+            }
+            DNSEdnsOption::ClientSubnet(DNSEdnsClientSubnet {
+                family,
+                source_prefix: data[2],
+                scope_prefix: data[3],
+                address: address.to_vec(),
+            })
+        }
+        DNS_EDNS_COOKIE => {
+            // An 8 byte client cookie, optionally followed by a
+            // server cookie of 8 to 32 bytes.
+            if data.len() != 8 && !(16..=40).contains(&data.len()) {
+                return DNSEdnsOption::Unknown;
+            }
+            DNSEdnsOption::Cookie {
+                client: data[..8].to_vec(),
+                server: data[8..].to_vec(),
+            }
+        }
+        DNS_EDNS_PADDING => DNSEdnsOption::Padding,
+        DNS_EDNS_EXTENDED_ERROR => {
+            if data.len() < 2 {
+                return DNSEdnsOption::Unknown;
+            }
+            DNSEdnsOption::ExtendedError {
+                info_code: u16::from_be_bytes([data[0], data[1]]),
+                extra_text: data[2..].to_vec(),
+            }
+        }
+        _ => DNSEdnsOption::Unknown,
+    }
+}
+
 fn dns_parse_rdata_opt(input: &[u8]) -> IResult<&[u8], DNSRData> {
     let mut dns_rdata_opt_vec = Vec::new();
     let mut i = input;
@@ -383,11 +434,224 @@ fn dns_parse_rdata_opt(input: &[u8]) -> IResult<&[u8], DNSRData> {
         dns_rdata_opt_vec.push(DNSRDataOPT {
             code,
             data: data.to_vec(),
+            value: dns_parse_edns_option(code, data),
         });
     }
     Ok((i, DNSRData::OPT(dns_rdata_opt_vec)))
 }
 
+fn dns_parse_svcb_value(svcb: &mut DNSRDataSVCB, key: u16, value: &[u8]) -> bool {
+    match key {
+        DNS_SVCB_KEY_MANDATORY => {
+            if value.is_empty() || value.len() % 2 != 0 {
+                return false;
+            }
+            svcb.mandatory = value
+                .chunks(2)
+                .map(|k| u16::from_be_bytes([k[0], k[1]]))
+                .collect();
+        }
+        DNS_SVCB_KEY_ALPN => {
+            let mut alpn = Vec::new();
+            let mut i = value;
+            while !i.is_empty() {
+                let len = i[0] as usize;
+                if len == 0 || i.len() < len + 1 {
+                    return false;
+                }
+                alpn.push(i[1..len + 1].to_vec());
+                i = &i[len + 1..];
+            }
+            if alpn.is_empty() {
+                return false;
+            }
+            svcb.alpn = alpn;
+        }
+        DNS_SVCB_KEY_NO_DEFAULT_ALPN => {
+            if !value.is_empty() {
+                return false;
+            }
+            svcb.no_default_alpn = true;
+        }
+        DNS_SVCB_KEY_PORT => {
+            if value.len() != 2 {
+                return false;
+            }
+            svcb.port = Some(u16::from_be_bytes([value[0], value[1]]));
+        }
+        DNS_SVCB_KEY_IPV4HINT => {
+            if value.is_empty() || value.len() % 4 != 0 {
+                return false;
+            }
+            svcb.ipv4hint = value.chunks(4).map(|a| a.to_vec()).collect();
+        }
+        DNS_SVCB_KEY_ECH => {
+            if value.is_empty() {
+                return false;
+            }
+            svcb.ech = Some(value.to_vec());
+        }
+        DNS_SVCB_KEY_IPV6HINT => {
+            if value.is_empty() || value.len() % 16 != 0 {
+                return false;
+            }
+            svcb.ipv6hint = value.chunks(16).map(|a| a.to_vec()).collect();
+        }
+        _ => return false,
+    }
+    return true;
+}
+
+fn dns_parse_rdata_svcb<'a>(
+    input: &'a [u8], message: &'a [u8], flags: &mut DNSNameFlags,
+) -> IResult<&'a [u8], DNSRData> {
+    let i = input;
+    let (i, priority) = be_u16(i)?;
+    let (mut i, target) = dns_parse_name(i, message, flags)?;
+    let mut svcb = DNSRDataSVCB {
+        priority,
+        target,
+        mandatory: Vec::new(),
+        alpn: Vec::new(),
+        no_default_alpn: false,
+        port: None,
+        ipv4hint: Vec::new(),
+        ech: None,
+        ipv6hint: Vec::new(),
+        params: Vec::new(),
+    };
+    while !i.is_empty() {
+        let (j, key) = be_u16(i)?;
+        let (j, value) = length_data(be_u16)(j)?;
+        i = j;
+        // Keep values that do not decode as they are, so they can
+        // still be logged.
+        if !dns_parse_svcb_value(&mut svcb, key, value) {
+            svcb.params.push(DNSSvcParam {
+                key,
+                value: value.to_vec(),
+            });
+        }
+    }
+    Ok((i, DNSRData::SVCB(svcb)))
+}
+
+fn dns_parse_rdata_caa(input: &[u8]) -> IResult<&[u8], DNSRData> {
+    let i = input;
+    let (i, flags) = be_u8(i)?;
+    let (i, tag) = length_data(be_u8)(i)?;
+    let (i, value) = rest(i)?;
+    Ok((
+        i,
+        DNSRData::CAA(DNSRDataCAA {
+            flags,
+            tag: tag.to_vec(),
+            value: value.to_vec(),
+        }),
+    ))
+}
+
+/// Compute the key tag of a DNSKEY rdata, as per RFC 4034 Appendix B.
+fn dns_dnskey_tag(rdata: &[u8]) -> u16 {
+    let mut ac: u32 = 0;
+    for (i, b) in rdata.iter().enumerate() {
+        if i & 1 == 1 {
+            ac += *b as u32;
+        } else {
+            ac += (*b as u32) << 8;
+        }
+    }
+    ac += (ac >> 16) & 0xffff;
+    return (ac & 0xffff) as u16;
+}
+
+fn dns_parse_rdata_dnskey(input: &[u8]) -> IResult<&[u8], DNSRData> {
+    let i = input;
+    let (i, flags) = be_u16(i)?;
+    let (i, protocol) = be_u8(i)?;
+    let (i, algorithm) = be_u8(i)?;
+    let (i, public_key) = rest(i)?;
+    Ok((
+        i,
+        DNSRData::DNSKEY(DNSRDataDNSKEY {
+            flags,
+            protocol,
+            algorithm,
+            public_key: public_key.to_vec(),
+            key_tag: dns_dnskey_tag(input),
+        }),
+    ))
+}
+
+fn dns_parse_rdata_rrsig<'a>(
+    input: &'a [u8], message: &'a [u8], flags: &mut DNSNameFlags,
+) -> IResult<&'a [u8], DNSRData> {
+    let i = input;
+    let (i, type_covered) = be_u16(i)?;
+    let (i, algorithm) = be_u8(i)?;
+    let (i, labels) = be_u8(i)?;
+    let (i, original_ttl) = be_u32(i)?;
+    let (i, expiration) = be_u32(i)?;
+    let (i, inception) = be_u32(i)?;
+    let (i, key_tag) = be_u16(i)?;
+    let (i, signer) = dns_parse_name(i, message, flags)?;
+    let (i, signature) = rest(i)?;
+    Ok((
+        i,
+        DNSRData::RRSIG(DNSRDataRRSIG {
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            signer,
+            signature: signature.to_vec(),
+        }),
+    ))
+}
+
+fn dns_parse_rdata_ds(input: &[u8]) -> IResult<&[u8], DNSRData> {
+    let i = input;
+    let (i, key_tag) = be_u16(i)?;
+    let (i, algorithm) = be_u8(i)?;
+    let (i, digest_type) = be_u8(i)?;
+    let (i, digest) = rest(i)?;
+    Ok((
+        i,
+        DNSRData::DS(DNSRDataDS {
+            key_tag,
+            algorithm,
+            digest_type,
+            digest: digest.to_vec(),
+        }),
+    ))
+}
+
+fn dns_parse_rdata_naptr<'a>(
+    input: &'a [u8], message: &'a [u8], flags: &mut DNSNameFlags,
+) -> IResult<&'a [u8], DNSRData> {
+    let i = input;
+    let (i, order) = be_u16(i)?;
+    let (i, preference) = be_u16(i)?;
+    let (i, naptr_flags) = length_data(be_u8)(i)?;
+    let (i, services) = length_data(be_u8)(i)?;
+    let (i, regexp) = length_data(be_u8)(i)?;
+    let (i, replacement) = dns_parse_name(i, message, flags)?;
+    Ok((
+        i,
+        DNSRData::NAPTR(DNSRDataNAPTR {
+            order,
+            preference,
+            flags: naptr_flags.to_vec(),
+            services: services.to_vec(),
+            regexp: regexp.to_vec(),
+            replacement,
+        }),
+    ))
+}
+
 fn dns_parse_rdata_unknown(input: &[u8]) -> IResult<&[u8], DNSRData> {
     rest(input).map(|(input, data)| (input, DNSRData::Unknown(data.to_vec())))
 }
@@ -408,6 +672,26 @@ fn dns_parse_rdata<'a>(
         Some(DNSRecordType::SSHFP) => dns_parse_rdata_sshfp(input),
         Some(DNSRecordType::SRV) => dns_parse_rdata_srv(input, message, flags),
         Some(DNSRecordType::OPT) => dns_parse_rdata_opt(input),
+        // Malformed rdata of the following types is kept as unknown
+        // data instead of failing the whole message.
+        Some(DNSRecordType::SVCB) | Some(DNSRecordType::HTTPS) => {
+            dns_parse_rdata_svcb(input, message, flags).or_else(|_| dns_parse_rdata_unknown(input))
+        }
+        Some(DNSRecordType::CAA) => {
+            dns_parse_rdata_caa(input).or_else(|_| dns_parse_rdata_unknown(input))
+        }
+        Some(DNSRecordType::DNSKEY) | Some(DNSRecordType::CDNSKEY) => {
+            dns_parse_rdata_dnskey(input).or_else(|_| dns_parse_rdata_unknown(input))
+        }
+        Some(DNSRecordType::RRSIG) => {
+            dns_parse_rdata_rrsig(input, message, flags).or_else(|_| dns_parse_rdata_unknown(input))
+        }
+        Some(DNSRecordType::DS) | Some(DNSRecordType::CDS) => {
+            dns_parse_rdata_ds(input).or_else(|_| dns_parse_rdata_unknown(input))
+        }
+        Some(DNSRecordType::NAPTR) => {
+            dns_parse_rdata_naptr(input, message, flags).or_else(|_| dns_parse_rdata_unknown(input))
+        }
         _ => dns_parse_rdata_unknown(input),
     }
 }
@@ -488,6 +772,7 @@ mod tests {
 
     use crate::dns::dns::{DNSAnswerEntry, DNSHeader};
     use crate::dns::parser::*;
+    use base64::{engine::general_purpose::STANDARD, Engine};
 
     /// Parse a simple name with no pointers.
     #[test]
@@ -755,11 +1040,16 @@ mod tests {
                 data: DNSRData::OPT(vec![
                     DNSRDataOPT {
                         code: 0x000a,
-                        data: vec![0x7f, 0x86, 0xcf, 0x8b, 0x81, 0xf6, 0xf9, 0x55]
+                        data: vec![0x7f, 0x86, 0xcf, 0x8b, 0x81, 0xf6, 0xf9, 0x55],
+                        value: DNSEdnsOption::Cookie {
+                            client: vec![0x7f, 0x86, 0xcf, 0x8b, 0x81, 0xf6, 0xf9, 0x55],
+                            server: vec![],
+                        }
                     },
                     DNSRDataOPT {
                         code: 0x0003,
-                        data: vec![]
+                        data: vec![],
+                        value: DNSEdnsOption::Nsid,
                     },
                 ])
             }
@@ -1025,6 +1315,208 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_dns_parse_rdata_opt_options() {
+        let data: &[u8] = &[
+            // client subnet: 192.0.2.0/24, scope 0
+            0x00, 0x08, 0x00, 0x07, 0x00, 0x01, 0x18, 0x00, 0xc0, 0x00, 0x02,
+            // extended error: DNSSEC bogus, "bad sig"
+            0x00, 0x0f, 0x00, 0x09, 0x00, 0x06, 0x62, 0x61, 0x64, 0x20, 0x73, 0x69, 0x67,
+            // padding
+            0x00, 0x0c, 0x00, 0x03, 0x00, 0x00, 0x00,
+            // cookie with a 7 byte client cookie
+            0x00, 0x0a, 0x00, 0x07, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+            // client subnet with an unknown family
+            0x00, 0x08, 0x00, 0x04, 0x00, 0x03, 0x00, 0x00,
+        ];
+        let (rem, rdata) = dns_parse_rdata_opt(data).unwrap();
+        assert_eq!(rem.len(), 0);
+
+        let opts = if let DNSRData::OPT(opts) = rdata {
+            opts
+        } else {
+            panic!("Expected DNSRData::OPT");
+        };
+        assert_eq!(opts.len(), 5);
+        assert_eq!(
+            opts[0].value,
+            DNSEdnsOption::ClientSubnet(DNSEdnsClientSubnet {
+                family: 1,
+                source_prefix: 24,
+                scope_prefix: 0,
+                address: vec![192, 0, 2],
+            })
+        );
+        assert_eq!(
+            opts[1].value,
+            DNSEdnsOption::ExtendedError {
+                info_code: DNSExtendedError::DnssecBogus as u16,
+                extra_text: b"bad sig".to_vec(),
+            }
+        );
+        assert_eq!(opts[2].value, DNSEdnsOption::Padding);
+        assert_eq!(opts[2].data.len(), 3);
+        assert_eq!(opts[3].value, DNSEdnsOption::Unknown);
+        assert_eq!(opts[4].value, DNSEdnsOption::Unknown);
+    }
+
+    #[test]
+    fn test_dns_parse_rdata_svcb() {
+        // 1 . alpn=h3,h2 ipv4hint=104.16.132.229 ech=AQID, followed by
+        // a truncated port and an unknown key.
+        let data: &[u8] = &[
+            0x00, 0x01, // priority
+            0x00, // target: root
+            0x00, 0x01, 0x00, 0x06, 0x02, 0x68, 0x33, 0x02, 0x68, 0x32, // alpn
+            0x00, 0x03, 0x00, 0x01, 0x50, // port (malformed)
+            0x00, 0x04, 0x00, 0x04, 0x68, 0x10, 0x84, 0xe5, // ipv4hint
+            0x00, 0x05, 0x00, 0x03, 0x01, 0x02, 0x03, // ech
+            0xff, 0x00, 0x00, 0x01, 0x2a, // unknown key
+        ];
+        let mut flags = DNSNameFlags::default();
+        let (rem, rdata) =
+            dns_parse_rdata(data, data, DNSRecordType::HTTPS as u16, &mut flags).unwrap();
+        assert_eq!(rem.len(), 0);
+
+        if let DNSRData::SVCB(svcb) = rdata {
+            assert_eq!(svcb.priority, 1);
+            assert!(svcb.target.value.is_empty());
+            assert_eq!(svcb.alpn, vec![b"h3".to_vec(), b"h2".to_vec()]);
+            assert!(!svcb.no_default_alpn);
+            assert_eq!(svcb.port, None);
+            assert_eq!(svcb.ipv4hint, vec![vec![104, 16, 132, 229]]);
+            assert_eq!(svcb.ech, Some(vec![1, 2, 3]));
+            assert!(svcb.ipv6hint.is_empty());
+            assert_eq!(
+                svcb.params,
+                vec![
+                    DNSSvcParam {
+                        key: 3,
+                        value: vec![0x50]
+                    },
+                    DNSSvcParam {
+                        key: 0xff00,
+                        value: vec![0x2a]
+                    },
+                ]
+            );
+        } else {
+            panic!("Expected DNSRData::SVCB");
+        }
+
+        // A truncated parameter is kept as unknown data.
+        let (_, rdata) =
+            dns_parse_rdata(&data[..16], data, DNSRecordType::SVCB as u16, &mut flags).unwrap();
+        assert_eq!(rdata, DNSRData::Unknown(data[..16].to_vec()));
+    }
+
+    #[test]
+    fn test_dns_parse_rdata_caa() {
+        // 0 issue "letsencrypt.org"
+        let data: &[u8] = b"\x00\x05issueletsencrypt.org";
+        let (rem, rdata) = dns_parse_rdata_caa(data).unwrap();
+        assert_eq!(rem.len(), 0);
+        assert_eq!(
+            rdata,
+            DNSRData::CAA(DNSRDataCAA {
+                flags: 0,
+                tag: b"issue".to_vec(),
+                value: b"letsencrypt.org".to_vec(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_dns_parse_rdata_dnskey() {
+        // Root zone KSK-2017.
+        let key = STANDARD
+            .decode(
+                "AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3+/4RgWOq7HrxRixHlFlExOLAJr5emLvN7SWXgnLh4+B5xQlNVz8Og8kvArMtNROxVQuCaSnIDdD5LKyWbRd2n9WGe2R8PzgCmr3EgVLrjyBxWezF0jLHwVN8efS3rCj/EWgvIWgb9tarpVUDK/b58Da+sqqls3eNbuv7pr+eoZG+SrDK6nWeL3c6H5Apxz7LjVc1uTIdsIXxuOLYA4/ilBmSVIzuDWfdRUfhHdY6+cn8HFRm+2hM8AnXGXws9555KrUB5qihylGa8subX2Nn6UwNR1AkUTV74bU=",
+            )
+            .unwrap();
+        let mut data = vec![0x01, 0x01, 0x03, 0x08];
+        data.extend_from_slice(&key);
+
+        let (rem, rdata) = dns_parse_rdata_dnskey(&data).unwrap();
+        assert_eq!(rem.len(), 0);
+        if let DNSRData::DNSKEY(dnskey) = rdata {
+            assert_eq!(dnskey.flags, 257);
+            assert_eq!(dnskey.protocol, 3);
+            assert_eq!(dnskey.algorithm, 8);
+            assert_eq!(dnskey.public_key, key);
+            assert_eq!(dnskey.key_tag, 20326);
+        } else {
+            panic!("Expected DNSRData::DNSKEY");
+        }
+    }
+
+    #[test]
+    fn test_dns_parse_rdata_ds() {
+        let data: &[u8] = &[0x4f, 0x66, 0x08, 0x02, 0xe0, 0x6d, 0x44, 0xb8];
+        let (rem, rdata) = dns_parse_rdata_ds(data).unwrap();
+        assert_eq!(rem.len(), 0);
+        assert_eq!(
+            rdata,
+            DNSRData::DS(DNSRDataDS {
+                key_tag: 20326,
+                algorithm: 8,
+                digest_type: 2,
+                digest: vec![0xe0, 0x6d, 0x44, 0xb8],
+            })
+        );
+    }
+
+    #[test]
+    fn test_dns_parse_rdata_rrsig() {
+        let data: &[u8] = &[
+            0x00, 0x30, // type covered: DNSKEY
+            0x08, // algorithm
+            0x00, // labels
+            0x00, 0x02, 0xa3, 0x00, // original ttl
+            0x68, 0x00, 0x00, 0x00, // expiration
+            0x67, 0x00, 0x00, 0x00, // inception
+            0x4f, 0x66, // key tag
+            0x00, // signer: root
+            0xde, 0xad, 0xbe, 0xef, // signature
+        ];
+        let mut flags = DNSNameFlags::default();
+        let (rem, rdata) = dns_parse_rdata_rrsig(data, data, &mut flags).unwrap();
+        assert_eq!(rem.len(), 0);
+        if let DNSRData::RRSIG(rrsig) = rdata {
+            assert_eq!(rrsig.type_covered, DNSRecordType::DNSKEY as u16);
+            assert_eq!(rrsig.algorithm, 8);
+            assert_eq!(rrsig.labels, 0);
+            assert_eq!(rrsig.original_ttl, 172800);
+            assert_eq!(rrsig.expiration, 0x68000000);
+            assert_eq!(rrsig.inception, 0x67000000);
+            assert_eq!(rrsig.key_tag, 20326);
+            assert!(rrsig.signer.value.is_empty());
+            assert_eq!(rrsig.signature, vec![0xde, 0xad, 0xbe, 0xef]);
+        } else {
+            panic!("Expected DNSRData::RRSIG");
+        }
+    }
+
+    #[test]
+    fn test_dns_parse_rdata_naptr() {
+        // 100 10 "S" "SIP+D2U" "" _sip._udp.example.com.
+        let data: &[u8] =
+            b"\x00\x64\x00\x0a\x01S\x07SIP+D2U\x00\x04_sip\x04_udp\x07example\x03com\x00";
+        let mut flags = DNSNameFlags::default();
+        let (rem, rdata) = dns_parse_rdata_naptr(data, data, &mut flags).unwrap();
+        assert_eq!(rem.len(), 0);
+        if let DNSRData::NAPTR(naptr) = rdata {
+            assert_eq!(naptr.order, 100);
+            assert_eq!(naptr.preference, 10);
+            assert_eq!(naptr.flags, b"S");
+            assert_eq!(naptr.services, b"SIP+D2U");
+            assert!(naptr.regexp.is_empty());
+            assert_eq!(naptr.replacement.value, b"_sip._udp.example.com");
+        } else {
+            panic!("Expected DNSRData::NAPTR");
+        }
+    }
+
     #[test]
     fn test_dns_parse_rdata_srv() {
         /*  ; <<>> DiG 9.11.5-P4-5.1+deb10u2-Debian <<>> _sip._udp.sip.voice.google.com SRV
@@ -24,10 +24,19 @@ use crate::core::*;
 
 // Defined in util-file.h
 /// cbindgen:ignore
+#[cfg(not(test))]
 extern "C" {
     pub fn FileFlowFlagsToFlags(flow_file_flags: u16, flags: u8) -> u16;
 }
 
+/// A variation of `FileFlowFlagsToFlags` for use when running Rust unit
+/// tests as the C function is not available for linkage.
+#[cfg(test)]
+#[allow(non_snake_case)]
+pub unsafe fn FileFlowFlagsToFlags(_flow_file_flags: u16, _flags: u8) -> u16 {
+    0
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct FileContainer {
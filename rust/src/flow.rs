@@ -15,6 +15,8 @@
  * 02110-1301, USA.
  */
 
+use suricata_sys::sys::AppProto;
+
 /// Flow API from C.
 /// cbindgen:ignore
 extern "C" {
@@ -22,6 +24,7 @@ extern "C" {
     fn FlowGetFlags(flow: &Flow) -> u32;
     fn FlowGetSourcePort(flow: &Flow) -> u16;
     fn FlowGetDestinationPort(flow: &Flow) -> u16;
+    fn FlowGetAlproto(flow: &Flow) -> AppProto;
 }
 
 // Flow flags
@@ -50,3 +53,8 @@ pub fn flow_get_flags(flow: &Flow) -> u32 {
 pub fn flow_get_ports(flow: &Flow) -> (u16, u16) {
     unsafe { (FlowGetSourcePort(flow), FlowGetDestinationPort(flow)) }
 }
+
+/// Return the application layer protocol of the flow.
+pub fn flow_get_alproto(flow: &Flow) -> AppProto {
+    unsafe { FlowGetAlproto(flow) }
+}
@@ -0,0 +1,270 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Rate tracking of the frames of a connection to find HTTP/2 abuse:
+//! rapid reset, CONTINUATION floods, SETTINGS, PING and WINDOW_UPDATE
+//! floods, and zero window stalls.
+
+use super::http2::{HTTP2Event, HTTP2FrameTypeData};
+use super::parser;
+use crate::conf::conf_get;
+use std::str::FromStr;
+
+// A limit of 0 disables its check.
+
+/// Length in seconds of the window the frames are counted in.
+static mut HTTP2_ABUSE_WINDOW: u64 = 1;
+/// Streams reset by the client before a response, per window.
+static mut HTTP2_ABUSE_RAPID_RESET: u32 = 100;
+/// CONTINUATION frames in a header block.
+static mut HTTP2_ABUSE_CONTINUATION: u32 = 64;
+/// SETTINGS frames, per window.
+static mut HTTP2_ABUSE_SETTINGS: u32 = 32;
+/// PING frames, per window.
+static mut HTTP2_ABUSE_PING: u32 = 64;
+/// WINDOW_UPDATE frames, per window.
+static mut HTTP2_ABUSE_WINDOW_UPDATE: u32 = 1024;
+/// Seconds a zero initial window size may be left unopened.
+static mut HTTP2_ABUSE_ZERO_WINDOW_TIMEOUT: u64 = 30;
+
+fn abuse_conf<T: FromStr>(name: &str) -> Option<T> {
+    let key = format!("app-layer.protocols.http2.abuse.{}", name);
+    let val = conf_get(&key)?;
+    if let Ok(v) = val.parse::<T>() {
+        return Some(v);
+    }
+    SCLogError!("Invalid value for http2.abuse.{}", name);
+    None
+}
+
+/// Read the limits from the configuration.
+pub(super) unsafe fn http2_abuse_config() {
+    if let Some(v) = abuse_conf::<u64>("window") {
+        HTTP2_ABUSE_WINDOW = std::cmp::max(v, 1);
+    }
+    if let Some(v) = abuse_conf("rapid-reset") {
+        HTTP2_ABUSE_RAPID_RESET = v;
+    }
+    if let Some(v) = abuse_conf("continuation") {
+        HTTP2_ABUSE_CONTINUATION = v;
+    }
+    if let Some(v) = abuse_conf("settings") {
+        HTTP2_ABUSE_SETTINGS = v;
+    }
+    if let Some(v) = abuse_conf("ping") {
+        HTTP2_ABUSE_PING = v;
+    }
+    if let Some(v) = abuse_conf("window-update") {
+        HTTP2_ABUSE_WINDOW_UPDATE = v;
+    }
+    if let Some(v) = abuse_conf("zero-window-timeout") {
+        HTTP2_ABUSE_ZERO_WINDOW_TIMEOUT = v;
+    }
+}
+
+#[derive(Debug, Default)]
+struct HTTP2RateCounter {
+    start: u64,
+    count: u32,
+}
+
+impl HTTP2RateCounter {
+    /// Count an occurrence at time now, in seconds, adding the event
+    /// when the count of the current window goes over max, once per
+    /// window.
+    fn count(&mut self, now: u64, max: u32, event: HTTP2Event, events: &mut Vec<HTTP2Event>) {
+        let window = unsafe { HTTP2_ABUSE_WINDOW };
+        if now < self.start || now - self.start >= window {
+            self.start = now;
+            self.count = 0;
+        }
+        self.count = self.count.saturating_add(1);
+        if max > 0 && self.count == max.saturating_add(1) {
+            events.push(event);
+        }
+    }
+}
+
+/// Frames sent by one side of a connection.
+#[derive(Debug, Default)]
+pub(super) struct HTTP2AbuseTracker {
+    resets: HTTP2RateCounter,
+    settings: HTTP2RateCounter,
+    pings: HTTP2RateCounter,
+    window_updates: HTTP2RateCounter,
+    /// CONTINUATION frames of the current header block
+    continuations: u32,
+    /// time the side advertised a zero initial window size, until it
+    /// opens a window
+    zero_window_since: Option<u64>,
+}
+
+impl HTTP2AbuseTracker {
+    /// Account for a frame sent by this side at time now, in seconds,
+    /// adding the events of the limits it goes over. `early_reset` tells
+    /// if the frame resets a stream of the client before a response.
+    pub(super) fn frame(
+        &mut self, now: u64, head: &parser::HTTP2FrameHeader, data: &HTTP2FrameTypeData,
+        early_reset: bool, events: &mut Vec<HTTP2Event>,
+    ) {
+        let end_headers = head.flags & parser::HTTP2_FLAG_HEADER_END_HEADERS != 0;
+        match data {
+            HTTP2FrameTypeData::HEADERS(_) | HTTP2FrameTypeData::PUSHPROMISE(_) => {
+                self.continuations = 0;
+            }
+            HTTP2FrameTypeData::CONTINUATION(_) => {
+                self.continuations = self.continuations.saturating_add(1);
+                let max = unsafe { HTTP2_ABUSE_CONTINUATION };
+                if max > 0 && self.continuations == max.saturating_add(1) {
+                    events.push(HTTP2Event::ContinuationFlood);
+                }
+                if end_headers {
+                    self.continuations = 0;
+                }
+            }
+            HTTP2FrameTypeData::RSTSTREAM(_) if early_reset => {
+                let max = unsafe { HTTP2_ABUSE_RAPID_RESET };
+                self.resets.count(now, max, HTTP2Event::RapidReset, events);
+            }
+            HTTP2FrameTypeData::SETTINGS(set) => {
+                let max = unsafe { HTTP2_ABUSE_SETTINGS };
+                self.settings
+                    .count(now, max, HTTP2Event::SettingsFlood, events);
+                for e in set {
+                    if e.id == parser::HTTP2SettingsId::InitialWindowSize {
+                        if e.value > 0 {
+                            self.zero_window_since = None;
+                        } else if self.zero_window_since.is_none() {
+                            self.zero_window_since = Some(now);
+                        }
+                    }
+                }
+            }
+            HTTP2FrameTypeData::PING => {
+                let max = unsafe { HTTP2_ABUSE_PING };
+                self.pings.count(now, max, HTTP2Event::PingFlood, events);
+            }
+            HTTP2FrameTypeData::WINDOWUPDATE(wu) => {
+                let max = unsafe { HTTP2_ABUSE_WINDOW_UPDATE };
+                self.window_updates
+                    .count(now, max, HTTP2Event::WindowUpdateFlood, events);
+                if wu.sizeinc > 0 {
+                    self.zero_window_since = None;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Check at time now, in seconds, if this side has kept a zero
+    /// window for too long. Returns true once per stall.
+    pub(super) fn stalled(&mut self, now: u64) -> bool {
+        let timeout = unsafe { HTTP2_ABUSE_ZERO_WINDOW_TIMEOUT };
+        if let Some(since) = self.zero_window_since {
+            if timeout > 0 && now.saturating_sub(since) >= timeout {
+                self.zero_window_since = None;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(ftype: parser::HTTP2FrameType, flags: u8) -> parser::HTTP2FrameHeader {
+        parser::HTTP2FrameHeader {
+            length: 0,
+            ftype: ftype as u8,
+            flags,
+            reserved: 0,
+            stream_id: 1,
+        }
+    }
+
+    #[test]
+    fn test_http2_abuse_rapid_reset() {
+        let mut tracker = HTTP2AbuseTracker::default();
+        let mut events = Vec::new();
+        let head = header(parser::HTTP2FrameType::RstStream, 0);
+        let rst = HTTP2FrameTypeData::RSTSTREAM(parser::HTTP2FrameRstStream { errorcode: 8 });
+        for _ in 0..200 {
+            // resets after a response do not count
+            tracker.frame(10, &head, &rst, false, &mut events);
+        }
+        assert!(events.is_empty());
+        for _ in 0..200 {
+            tracker.frame(10, &head, &rst, true, &mut events);
+        }
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], HTTP2Event::RapidReset));
+        // a new window
+        for _ in 0..100 {
+            tracker.frame(11, &head, &rst, true, &mut events);
+        }
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn test_http2_abuse_continuation() {
+        let mut tracker = HTTP2AbuseTracker::default();
+        let mut events = Vec::new();
+        let cont =
+            HTTP2FrameTypeData::CONTINUATION(parser::HTTP2FrameContinuation { blocks: Vec::new() });
+        let head = header(parser::HTTP2FrameType::Continuation, 0);
+        for _ in 0..64 {
+            tracker.frame(0, &head, &cont, false, &mut events);
+        }
+        let end = header(
+            parser::HTTP2FrameType::Continuation,
+            parser::HTTP2_FLAG_HEADER_END_HEADERS,
+        );
+        tracker.frame(0, &end, &cont, false, &mut events);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], HTTP2Event::ContinuationFlood));
+        // the header block ended
+        tracker.frame(0, &head, &cont, false, &mut events);
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn test_http2_abuse_zero_window() {
+        let mut tracker = HTTP2AbuseTracker::default();
+        let mut events = Vec::new();
+        let head = header(parser::HTTP2FrameType::Settings, 0);
+        let set = HTTP2FrameTypeData::SETTINGS(vec![parser::HTTP2FrameSettings {
+            id: parser::HTTP2SettingsId::InitialWindowSize,
+            value: 0,
+        }]);
+        tracker.frame(100, &head, &set, false, &mut events);
+        assert!(!tracker.stalled(110));
+        assert!(tracker.stalled(130));
+        assert!(!tracker.stalled(140));
+
+        tracker.frame(200, &head, &set, false, &mut events);
+        let head = header(parser::HTTP2FrameType::WindowUpdate, 0);
+        let wu = HTTP2FrameTypeData::WINDOWUPDATE(parser::HTTP2FrameWindowUpdate {
+            reserved: 0,
+            sizeinc: 65535,
+        });
+        tracker.frame(201, &head, &wu, false, &mut events);
+        assert!(!tracker.stalled(300));
+        assert!(events.is_empty());
+    }
+}
@@ -24,6 +24,14 @@ use std::io::{Cursor, Read, Write};
 
 pub const HTTP2_DECOMPRESSION_CHUNK_SIZE: usize = 0x1000; // 4096
 
+/// Window size that zstd content-encoding is limited to, cf RFC 8878
+const HTTP2_ZSTD_WINDOW_SIZE_MAX: u64 = 1 << 23;
+/// Maximum size of a zstd block, which bounds the compressed data kept
+/// by the decoder
+const ZSTD_BLOCK_SIZE_MAX: usize = 0x20000; // 128 KiB
+/// Maximum size of a zstd frame header
+const ZSTD_FRAME_HEADER_MAX: usize = 18;
+
 #[repr(u8)]
 #[derive(Copy, Clone, PartialOrd, PartialEq, Eq, Debug)]
 pub enum HTTP2ContentEncoding {
@@ -32,6 +40,19 @@ pub enum HTTP2ContentEncoding {
     Br = 2,
     Deflate = 3,
     Unrecognized = 4,
+    Zstd = 5,
+}
+
+impl HTTP2ContentEncoding {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            HTTP2ContentEncoding::Gzip => "gzip",
+            HTTP2ContentEncoding::Br => "br",
+            HTTP2ContentEncoding::Deflate => "deflate",
+            HTTP2ContentEncoding::Zstd => "zstd",
+            HTTP2ContentEncoding::Unknown | HTTP2ContentEncoding::Unrecognized => "unknown",
+        }
+    }
 }
 
 //a cursor turning EOF into blocking errors
@@ -88,6 +109,8 @@ pub enum HTTP2Decompresser {
     // This one is not so large, at 88 bytes as of doing this, but box
     // for consistency.
     Deflate(Box<DeflateDecoder<HTTP2cursor>>),
+    // Box because large.
+    Zstd(Box<HTTP2ZstdDecoder>),
 }
 
 impl std::fmt::Debug for HTTP2Decompresser {
@@ -97,6 +120,7 @@ impl std::fmt::Debug for HTTP2Decompresser {
             HTTP2Decompresser::Gzip(_) => write!(f, "GZIP"),
             HTTP2Decompresser::Brotli(_) => write!(f, "BROTLI"),
             HTTP2Decompresser::Deflate(_) => write!(f, "DEFLATE"),
+            HTTP2Decompresser::Zstd(_) => write!(f, "ZSTD"),
         }
     }
 }
@@ -105,6 +129,12 @@ impl std::fmt::Debug for HTTP2Decompresser {
 struct HTTP2DecoderHalf {
     encoding: HTTP2ContentEncoding,
     decoder: HTTP2Decompresser,
+    /// bytes given to the decoder
+    compressed: u64,
+    /// bytes out of the decoder
+    decompressed: u64,
+    /// the compression ratio went over the limit
+    ratio_exceeded: bool,
 }
 
 pub trait GetMutCursor {
@@ -165,11 +195,152 @@ fn http2_decompress<'a>(
     return Ok(&output[..offset]);
 }
 
+/// Get the size and checksum flag of the zstd frame header at the start
+/// of the input, or None if more data is needed.
+fn zstd_frame_header(input: &[u8]) -> io::Result<Option<(usize, bool)>> {
+    let (frame, size) = match ruzstd::frame::read_frame_header(input) {
+        Ok(r) => r,
+        Err(ruzstd::frame::ReadFrameHeaderError::BadMagicNumber(_)) => {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        Err(_) => {
+            if input.len() < ZSTD_FRAME_HEADER_MAX {
+                return Ok(None);
+            }
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+    };
+    match frame.header.window_size() {
+        Ok(window_size) if window_size <= HTTP2_ZSTD_WINDOW_SIZE_MAX => {}
+        _ => {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+    }
+    return Ok(Some((
+        size as usize,
+        frame.header.descriptor.content_checksum_flag(),
+    )));
+}
+
+/// Get the size of the zstd block at the start of the input, with the
+/// checksum following the last block, or None if more data is needed.
+fn zstd_block_len(input: &[u8], checksum: bool) -> io::Result<Option<usize>> {
+    if input.len() < 3 {
+        return Ok(None);
+    }
+    let header = u32::from_le_bytes([input[0], input[1], input[2], 0]);
+    let last = header & 1 != 0;
+    let size = (header >> 3) as usize;
+    if size > ZSTD_BLOCK_SIZE_MAX {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    let mut len = 3 + match (header >> 1) & 3 {
+        // raw and compressed blocks
+        0 | 2 => size,
+        // rle block
+        1 => 1,
+        _ => {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+    };
+    if last && checksum {
+        len += 4;
+    }
+    if input.len() < len {
+        return Ok(None);
+    }
+    return Ok(Some(len));
+}
+
+/// zstd decoder, given one full block at a time as ruzstd cannot stop in
+/// the middle of a block.
+pub struct HTTP2ZstdDecoder {
+    decoder: ruzstd::FrameDecoder,
+    /// a frame header was read, and the frame is not fully output
+    in_frame: bool,
+    /// the frame has a checksum after its last block
+    checksum: bool,
+    /// compressed data not decoded yet
+    pending: Vec<u8>,
+}
+
+impl HTTP2ZstdDecoder {
+    fn new() -> HTTP2ZstdDecoder {
+        HTTP2ZstdDecoder {
+            decoder: ruzstd::FrameDecoder::new(),
+            in_frame: false,
+            checksum: false,
+            pending: Vec::new(),
+        }
+    }
+}
+
+fn http2_decompress_zstd<'a>(
+    decoder: &mut HTTP2ZstdDecoder, input: &'a [u8], output: &'a mut Vec<u8>,
+) -> io::Result<&'a [u8]> {
+    decoder.pending.extend_from_slice(input);
+    output.clear();
+    let mut offset = 0;
+    loop {
+        // first output what was decoded
+        loop {
+            let start = output.len();
+            output.resize(start + HTTP2_DECOMPRESSION_CHUNK_SIZE, 0);
+            let n = decoder.decoder.read(&mut output[start..])?;
+            output.truncate(start + n);
+            if n == 0 {
+                break;
+            }
+        }
+        if decoder.in_frame && decoder.decoder.is_finished() {
+            decoder.in_frame = false;
+        }
+        let data = &decoder.pending[offset..];
+        if !decoder.in_frame {
+            match zstd_frame_header(data)? {
+                Some((size, checksum)) => {
+                    if decoder.decoder.reset(&data[..size]).is_err() {
+                        return Err(io::ErrorKind::InvalidData.into());
+                    }
+                    decoder.in_frame = true;
+                    decoder.checksum = checksum;
+                    offset += size;
+                }
+                None => {
+                    break;
+                }
+            }
+            continue;
+        }
+        match zstd_block_len(data, decoder.checksum)? {
+            Some(len) => {
+                let mut block = &data[..len];
+                if decoder
+                    .decoder
+                    .decode_blocks(&mut block, ruzstd::BlockDecodingStrategy::UptoBlocks(1))
+                    .is_err()
+                {
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+                offset += len;
+            }
+            None => {
+                break;
+            }
+        }
+    }
+    decoder.pending.drain(..offset);
+    return Ok(&output[..]);
+}
+
 impl HTTP2DecoderHalf {
     pub fn new() -> HTTP2DecoderHalf {
         HTTP2DecoderHalf {
             encoding: HTTP2ContentEncoding::Unknown,
             decoder: HTTP2Decompresser::Unassigned,
+            compressed: 0,
+            decompressed: 0,
+            ratio_exceeded: false,
         }
     }
 
@@ -190,6 +361,9 @@ impl HTTP2DecoderHalf {
                     HTTP2cursor::new(),
                     HTTP2_DECOMPRESSION_CHUNK_SIZE,
                 )));
+            } else if input == b"zstd" {
+                self.encoding = HTTP2ContentEncoding::Zstd;
+                self.decoder = HTTP2Decompresser::Zstd(Box::new(HTTP2ZstdDecoder::new()));
             } else {
                 self.encoding = HTTP2ContentEncoding::Unrecognized;
             }
@@ -198,6 +372,19 @@ impl HTTP2DecoderHalf {
 
     pub fn decompress<'a>(
         &mut self, input: &'a [u8], output: &'a mut Vec<u8>,
+    ) -> io::Result<&'a [u8]> {
+        let r = self.decompress_inner(input, output);
+        if !matches!(self.decoder, HTTP2Decompresser::Unassigned) {
+            if let Ok(decompressed) = &r {
+                self.compressed += input.len() as u64;
+                self.decompressed += decompressed.len() as u64;
+            }
+        }
+        return r;
+    }
+
+    fn decompress_inner<'a>(
+        &mut self, input: &'a [u8], output: &'a mut Vec<u8>,
     ) -> io::Result<&'a [u8]> {
         match self.decoder {
             HTTP2Decompresser::Gzip(ref mut gzip_decoder) => {
@@ -221,10 +408,49 @@ impl HTTP2DecoderHalf {
                 }
                 return r;
             }
+            HTTP2Decompresser::Zstd(ref mut zstd_decoder) => {
+                let r = http2_decompress_zstd(zstd_decoder.as_mut(), input, output);
+                if r.is_err() {
+                    self.decoder = HTTP2Decompresser::Unassigned;
+                }
+                return r;
+            }
             _ => {}
         }
         return Ok(input);
     }
+
+    /// Ratio of the decompressed size to the compressed size, if any
+    /// data was decompressed.
+    fn ratio(&self) -> Option<f64> {
+        if self.compressed == 0 {
+            return None;
+        }
+        return Some(self.decompressed as f64 / self.compressed as f64);
+    }
+
+    /// Check the ratio once min_size bytes were decompressed, returning
+    /// true the first time it goes over max.
+    fn check_ratio(&mut self, max: u32, min_size: u64) -> bool {
+        if self.ratio_exceeded || max == 0 || self.decompressed < min_size {
+            return false;
+        }
+        if let Some(ratio) = self.ratio() {
+            if ratio > max as f64 {
+                self.ratio_exceeded = true;
+                return true;
+            }
+        }
+        return false;
+    }
+}
+
+/// Compression accounting of one direction of a transaction.
+pub struct HTTP2CompressionInfo {
+    pub encoding: HTTP2ContentEncoding,
+    pub compressed: u64,
+    pub decompressed: u64,
+    pub ratio: f64,
 }
 
 #[derive(Debug)]
@@ -258,4 +484,135 @@ impl HTTP2Decoder {
             return self.decoder_ts.decompress(input, output);
         }
     }
+
+    /// Check if the compression ratio of a direction went over max,
+    /// once min_size bytes were decompressed. Returns true only once.
+    pub fn check_ratio(&mut self, dir: Direction, max: u32, min_size: u64) -> bool {
+        if dir == Direction::ToClient {
+            return self.decoder_tc.check_ratio(max, min_size);
+        } else {
+            return self.decoder_ts.check_ratio(max, min_size);
+        }
+    }
+
+    /// Get the compression accounting of a direction, if it has
+    /// decompressed data.
+    pub fn compression_info(&self, dir: Direction) -> Option<HTTP2CompressionInfo> {
+        let half = if dir == Direction::ToClient {
+            &self.decoder_tc
+        } else {
+            &self.decoder_ts
+        };
+        let ratio = half.ratio()?;
+        return Some(HTTP2CompressionInfo {
+            encoding: half.encoding,
+            compressed: half.compressed,
+            decompressed: half.decompressed,
+            ratio,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http2_decompress_zstd() {
+        // 2 MiB of 'a' compressed with zstd -3, with a checksum
+        #[rustfmt::skip]
+        let compressed: &[u8] = &[
+            0x28, 0xb5, 0x2f, 0xfd, 0xa4, 0x00, 0x00, 0x20,
+            0x00, 0x54, 0x00, 0x00, 0x10, 0x61, 0x61, 0x01,
+            0x00, 0xfb, 0xff, 0x39, 0xc0, 0x02, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x03, 0x00,
+            0x10, 0x61, 0xff, 0x45, 0xb0, 0x95,
+        ];
+        let mut decoder = HTTP2Decoder::new();
+        decoder.http2_encoding_fromvec(b"zstd", Direction::ToClient);
+        let mut total = 0;
+        for chunk in compressed.chunks(5) {
+            let mut output = Vec::new();
+            let r = decoder
+                .decompress(chunk, &mut output, Direction::ToClient)
+                .unwrap();
+            assert!(r.iter().all(|c| *c == b'a'));
+            total += r.len();
+        }
+        assert_eq!(total, 2 * 1048576);
+
+        let info = decoder.compression_info(Direction::ToClient).unwrap();
+        assert_eq!(info.encoding, HTTP2ContentEncoding::Zstd);
+        assert_eq!(info.compressed, compressed.len() as u64);
+        assert_eq!(info.decompressed, 2 * 1048576);
+        assert!(decoder.compression_info(Direction::ToServer).is_none());
+
+        assert!(!decoder.check_ratio(Direction::ToClient, 500, 4 * 1048576));
+        assert!(decoder.check_ratio(Direction::ToClient, 500, 1048576));
+        // only once
+        assert!(!decoder.check_ratio(Direction::ToClient, 500, 1048576));
+    }
+
+    #[test]
+    fn test_http2_decompress_zstd_one_frame() {
+        // 2 MiB of 'a' compressed with zstd -3, in a single DATA frame
+        #[rustfmt::skip]
+        let compressed: &[u8] = &[
+            0x28, 0xb5, 0x2f, 0xfd, 0xa4, 0x00, 0x00, 0x20,
+            0x00, 0x54, 0x00, 0x00, 0x10, 0x61, 0x61, 0x01,
+            0x00, 0xfb, 0xff, 0x39, 0xc0, 0x02, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x02, 0x00,
+            0x10, 0x61, 0x02, 0x00, 0x10, 0x61, 0x03, 0x00,
+            0x10, 0x61, 0xff, 0x45, 0xb0, 0x95,
+        ];
+        let mut decoder = HTTP2Decoder::new();
+        decoder.http2_encoding_fromvec(b"zstd", Direction::ToClient);
+        let mut output = Vec::new();
+        let r = decoder
+            .decompress(compressed, &mut output, Direction::ToClient)
+            .unwrap();
+        assert_eq!(r.len(), 2 * 1048576);
+        assert!(r.iter().all(|c| *c == b'a'));
+    }
+
+    #[test]
+    fn test_http2_decompress_zstd_block_size() {
+        // frame header with a window of 1 MiB, then a raw block of 2 MiB
+        let mut decoder = HTTP2Decoder::new();
+        decoder.http2_encoding_fromvec(b"zstd", Direction::ToClient);
+        let mut output = Vec::new();
+        assert!(decoder
+            .decompress(
+                &[0x28, 0xb5, 0x2f, 0xfd, 0x00, 0x50, 0xf8, 0xff, 0xff],
+                &mut output,
+                Direction::ToClient
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_http2_decompress_zstd_window() {
+        // frame header with a window of 16 MiB, over the limit
+        let mut decoder = HTTP2Decoder::new();
+        decoder.http2_encoding_fromvec(b"zstd", Direction::ToClient);
+        let mut output = Vec::new();
+        assert!(decoder
+            .decompress(
+                &[0x28, 0xb5, 0x2f, 0xfd, 0x00, 0x70, 0x01, 0x00, 0x00],
+                &mut output,
+                Direction::ToClient
+            )
+            .is_err());
+    }
 }
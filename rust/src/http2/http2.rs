@@ -15,6 +15,7 @@
  * 02110-1301, USA.
  */
 
+use super::abuse::{http2_abuse_config, HTTP2AbuseTracker};
 use super::decompression;
 use super::detect;
 use super::parser;
@@ -27,7 +28,7 @@ use crate::direction::Direction;
 use crate::dns::dns::DnsVariant;
 use crate::filecontainer::*;
 use crate::filetracker::*;
-use crate::flow::Flow;
+use crate::flow::{flow_get_last_time, Flow};
 use crate::frames::Frame;
 
 use crate::dns::dns::{dns_parse_request, dns_parse_response, DNSTransaction};
@@ -74,6 +75,11 @@ pub static mut HTTP2_MAX_TABLESIZE: u32 = 65536; // 0x10000
 static mut HTTP2_MAX_REASS: usize = 102400;
 static mut HTTP2_MAX_STREAMS: usize = 4096; // 0x1000
 
+/// Maximum ratio of decompressed to compressed size of a stream body.
+static mut HTTP2_MAX_COMPRESSION_RATIO: u32 = 500;
+/// The ratio is only checked past this decompressed size.
+const HTTP2_COMPRESSION_RATIO_MIN_SIZE: u64 = 1048576;
+
 #[derive(AppLayerFrameType)]
 pub enum Http2FrameType {
     Hdr,
@@ -236,6 +242,11 @@ impl HTTP2Transaction {
         self.tx_data.set_event(event as u8);
     }
 
+    /// Get the compression accounting of the body in a direction.
+    pub fn compression_info(&self, dir: Direction) -> Option<decompression::HTTP2CompressionInfo> {
+        self.decoder.compression_info(dir)
+    }
+
     fn handle_headers(
         &mut self, blocks: &[parser::HTTP2FrameHeaderBlock], dir: Direction,
     ) -> Option<Vec<u8>> {
@@ -304,6 +315,13 @@ impl HTTP2Transaction {
         sfcm: &'static SuricataFileContext, over: bool, flow: *const Flow,
     ) -> io::Result<()> {
         let decompressed = self.decoder.decompress(input, output, dir)?;
+        if self.decoder.check_ratio(
+            dir,
+            unsafe { HTTP2_MAX_COMPRESSION_RATIO },
+            HTTP2_COMPRESSION_RATIO_MIN_SIZE,
+        ) {
+            self.set_event(HTTP2Event::CompressionRatioExceeded);
+        }
         let xid: u32 = self.tx_id as u32;
         if dir == Direction::ToClient {
             self.ft_tc.tx_id = self.tx_id - 1;
@@ -473,14 +491,18 @@ impl HTTP2Transaction {
         if let Some(doh) = &mut self.doh {
             if !doh.data_buf[dir.index()].is_empty() {
                 if dir.is_to_client() {
-                    if let Ok(mut dtx) = dns_parse_response(&doh.data_buf[dir.index()]) {
+                    if let Ok(mut dtx) =
+                        dns_parse_response(&doh.data_buf[dir.index()], &DnsVariant::DnsOverHttps)
+                    {
                         dtx.id = 1;
                         doh.dns_response_tx = Some(dtx);
                         unsafe {
                             AppLayerForceProtocolChange(flow, ALPROTO_DOH2);
                         }
                     }
-                } else if let Ok(mut dtx) = dns_parse_request(&doh.data_buf[dir.index()], &DnsVariant::Dns) {
+                } else if let Ok(mut dtx) =
+                    dns_parse_request(&doh.data_buf[dir.index()], &DnsVariant::DnsOverHttps)
+                {
                     dtx.id = 1;
                     doh.dns_request_tx = Some(dtx);
                     unsafe {
@@ -522,6 +544,13 @@ pub enum HTTP2Event {
     ReassemblyLimitReached,
     DnsRequestTooLong,
     DnsResponseTooLong,
+    RapidReset,
+    ContinuationFlood,
+    SettingsFlood,
+    PingFlood,
+    WindowUpdateFlood,
+    ZeroWindowStall,
+    CompressionRatioExceeded,
 }
 
 pub struct HTTP2DynTable {
@@ -566,6 +595,11 @@ pub struct HTTP2State {
 
     c2s_buf: HTTP2HeaderReassemblyBuffer,
     s2c_buf: HTTP2HeaderReassemblyBuffer,
+
+    /// time of the data being parsed, in seconds
+    time: u64,
+    abuse_ts: HTTP2AbuseTracker,
+    abuse_tc: HTTP2AbuseTracker,
 }
 
 impl State<HTTP2Transaction> for HTTP2State {
@@ -600,6 +634,9 @@ impl HTTP2State {
             progress: HTTP2ConnectionState::Http2StateInit,
             c2s_buf: HTTP2HeaderReassemblyBuffer::default(),
             s2c_buf: HTTP2HeaderReassemblyBuffer::default(),
+            time: 0,
+            abuse_ts: HTTP2AbuseTracker::default(),
+            abuse_tc: HTTP2AbuseTracker::default(),
         }
     }
 
@@ -1120,6 +1157,38 @@ impl HTTP2State {
         }
     }
 
+    /// Account for a frame in the abuse tracking of its direction,
+    /// returning the events of the limits it goes over.
+    fn check_abuse(
+        &mut self, head: &parser::HTTP2FrameHeader, data: &HTTP2FrameTypeData, dir: Direction,
+    ) -> Vec<HTTP2Event> {
+        let mut events = Vec::new();
+        // a stream reset by the client before any response headers
+        let mut early_reset = false;
+        if dir == Direction::ToServer && head.stream_id > 0 {
+            if let HTTP2FrameTypeData::RSTSTREAM(_) = data {
+                let index = self.find_tx_index(head.stream_id);
+                early_reset = index > 0
+                    && !self.transactions[index - 1]
+                        .frames_tc
+                        .iter()
+                        .any(|f| matches!(f.data, HTTP2FrameTypeData::HEADERS(_)));
+            }
+        }
+        let (tracker, other) = if dir == Direction::ToServer {
+            (&mut self.abuse_ts, &mut self.abuse_tc)
+        } else {
+            (&mut self.abuse_tc, &mut self.abuse_ts)
+        };
+        tracker.frame(self.time, head, data, early_reset, &mut events);
+        // the stall of a side is noticed on the frames of both sides
+        let stalled = tracker.stalled(self.time);
+        if other.stalled(self.time) || stalled {
+            events.push(HTTP2Event::ZeroWindowStall);
+        }
+        events
+    }
+
     fn parse_frames(
         &mut self, mut input: &[u8], il: usize, dir: Direction, flow: *const Flow,
         stream_slice: &StreamSlice,
@@ -1180,6 +1249,10 @@ impl HTTP2State {
                         None,
                     );
                     if head.length == 0 && head.ftype == parser::HTTP2FrameType::Settings as u8 {
+                        let settings = HTTP2FrameTypeData::SETTINGS(Vec::new());
+                        for event in self.check_abuse(&head, &settings, dir) {
+                            self.set_event(event);
+                        }
                         input = &rem[hlsafe..];
                         continue;
                     }
@@ -1191,12 +1264,16 @@ impl HTTP2State {
                         dir,
                         &mut reass_limit_reached,
                     );
+                    let abuse_events = self.check_abuse(&head, &txdata, dir);
 
                     let tx = self.find_or_create_tx(&head, &txdata, dir);
                     if tx.is_none() {
                         return AppLayerResult::err();
                     }
                     let tx = tx.unwrap();
+                    for event in abuse_events {
+                        tx.tx_data.set_event(event as u8);
+                    }
                     if let Some(frame) = frame_hdr {
                         frame.set_tx(flow, tx.tx_id);
                     }
@@ -1207,7 +1284,9 @@ impl HTTP2State {
                         frame.set_tx(flow, tx.tx_id);
                     }
                     if let Some(doh_req_buf) = tx.handle_frame(&head, &txdata, dir) {
-                        if let Ok(mut dtx) = dns_parse_request(&doh_req_buf, &DnsVariant::Dns) {
+                        if let Ok(mut dtx) =
+                            dns_parse_request(&doh_req_buf, &DnsVariant::DnsOverHttps)
+                        {
                             dtx.id = 1;
                             unsafe {
                                 AppLayerForceProtocolChange(flow, ALPROTO_DOH2);
@@ -1463,6 +1542,9 @@ unsafe extern "C" fn http2_parse_ts(
     stream_slice: StreamSlice, _data: *const std::os::raw::c_void,
 ) -> AppLayerResult {
     let state = cast_pointer!(state, HTTP2State);
+    if let Some(f) = flow.as_ref() {
+        state.time = flow_get_last_time(f).as_secs();
+    }
     return state.parse_ts(flow, stream_slice);
 }
 
@@ -1471,6 +1553,9 @@ unsafe extern "C" fn http2_parse_tc(
     stream_slice: StreamSlice, _data: *const std::os::raw::c_void,
 ) -> AppLayerResult {
     let state = cast_pointer!(state, HTTP2State);
+    if let Some(f) = flow.as_ref() {
+        state.time = flow_get_last_time(f).as_secs();
+    }
     return state.parse_tc(flow, stream_slice);
 }
 
@@ -1593,6 +1678,14 @@ pub unsafe extern "C" fn SCRegisterHttp2Parser() {
                 SCLogError!("Invalid value for http2.max-reassembly-size");
             }
         }
+        if let Some(val) = conf_get("app-layer.protocols.http2.max-compression-ratio") {
+            if let Ok(v) = val.parse::<u32>() {
+                HTTP2_MAX_COMPRESSION_RATIO = v;
+            } else {
+                SCLogError!("Invalid value for http2.max-compression-ratio");
+            }
+        }
+        http2_abuse_config();
         AppLayerParserRegisterLogger(IPPROTO_TCP, ALPROTO_HTTP2);
         SCLogDebug!("Rust http2 parser registered.");
     } else {
@@ -17,6 +17,7 @@
 
 use super::http2::{HTTP2Frame, HTTP2FrameTypeData, HTTP2Transaction};
 use super::parser;
+use crate::direction::Direction;
 use crate::jsonbuilder::{JsonBuilder, JsonError};
 use std;
 use std::collections::{HashMap, HashSet};
@@ -207,9 +208,11 @@ fn log_http2_frames(frames: &[HTTP2Frame], js: &mut JsonBuilder) -> Result<bool,
     return Ok(has_settings || has_error_code || has_priority);
 }
 
-fn log_http2(tx: &HTTP2Transaction, js: &mut JsonBuilder) -> Result<bool, JsonError> {
-    js.open_object("http")?;
-    js.set_string("version", "2")?;
+/// Log the fields of the "http" object common to HTTP/2 and HTTP/3.
+pub(crate) fn log_http_headers(
+    tx: &HTTP2Transaction, version: &str, js: &mut JsonBuilder,
+) -> Result<bool, JsonError> {
+    js.set_string("version", version)?;
 
     let mut common: HashMap<HeaderName, &Vec<u8>> = HashMap::new();
 
@@ -266,16 +269,42 @@ fn log_http2(tx: &HTTP2Transaction, js: &mut JsonBuilder) -> Result<bool, JsonEr
         }
     }
 
+    return Ok(has_headers);
+}
+
+/// Log the content encoding of the body of a direction, with its sizes
+/// and compression ratio.
+fn log_http2_compression(
+    tx: &HTTP2Transaction, dir: Direction, js: &mut JsonBuilder,
+) -> Result<bool, JsonError> {
+    if let Some(info) = tx.compression_info(dir) {
+        js.open_object("compression")?;
+        js.set_string("encoding", info.encoding.to_str())?;
+        js.set_uint("compressed", info.compressed)?;
+        js.set_uint("decompressed", info.decompressed)?;
+        js.set_float("ratio", info.ratio)?;
+        js.close()?;
+        return Ok(true);
+    }
+    return Ok(false);
+}
+
+fn log_http2(tx: &HTTP2Transaction, js: &mut JsonBuilder) -> Result<bool, JsonError> {
+    js.open_object("http")?;
+    let has_headers = log_http_headers(tx, "2", js)?;
+
     // The rest of http2 logging is placed in an "http2" object.
     js.open_object("http2")?;
 
     js.set_uint("stream_id", tx.stream_id as u64)?;
     js.open_object("request")?;
     let has_request = log_http2_frames(&tx.frames_ts, js)?;
+    let has_request = log_http2_compression(tx, Direction::ToServer, js)? || has_request;
     js.close()?;
 
     js.open_object("response")?;
     let has_response = log_http2_frames(&tx.frames_tc, js)?;
+    let has_response = log_http2_compression(tx, Direction::ToClient, js)? || has_response;
     js.close()?;
 
     js.close()?; // http2
@@ -19,10 +19,11 @@
 
 #![allow(clippy::result_unit_err)]
 
+mod abuse;
 mod decompression;
 pub mod detect;
 pub mod http2;
-mod huffman;
+pub mod huffman;
 pub mod logger;
-mod parser;
+pub mod parser;
 mod range;
@@ -510,7 +510,7 @@ fn http2_parse_headers_block_literal_neverindex<'a>(
     return r;
 }
 
-fn http2_parse_var_uint(input: &[u8], value: u64, max: u64) -> IResult<&[u8], u64> {
+pub(crate) fn http2_parse_var_uint(input: &[u8], value: u64, max: u64) -> IResult<&[u8], u64> {
     if value < max {
         return Ok((input, value));
     }
@@ -17,7 +17,8 @@
 
 use crate::dns::dns::*;
 use crate::dns::log::{
-    dns_log_opt, dns_log_soa, dns_log_srv, dns_log_sshfp, dns_print_addr, dns_rrtype_string,
+    dns_log_caa, dns_log_dnskey, dns_log_ds, dns_log_naptr, dns_log_opt, dns_log_rrsig,
+    dns_log_soa, dns_log_srv, dns_log_sshfp, dns_log_svcb, dns_print_addr, dns_rrtype_string,
 };
 use crate::jsonbuilder::{JsonBuilder, JsonError};
 
@@ -59,6 +60,24 @@ fn mdns_log_json_answer_detail(answer: &DNSAnswerEntry) -> Result<JsonBuilder, J
         DNSRData::SRV(srv) => {
             jsa.set_object(&rrtype, &dns_log_srv(srv)?)?;
         }
+        DNSRData::SVCB(svcb) => {
+            jsa.set_object(&rrtype, &dns_log_svcb(svcb)?)?;
+        }
+        DNSRData::CAA(caa) => {
+            jsa.set_object(&rrtype, &dns_log_caa(caa)?)?;
+        }
+        DNSRData::DNSKEY(dnskey) => {
+            jsa.set_object(&rrtype, &dns_log_dnskey(dnskey)?)?;
+        }
+        DNSRData::RRSIG(rrsig) => {
+            jsa.set_object(&rrtype, &dns_log_rrsig(rrsig)?)?;
+        }
+        DNSRData::DS(ds) => {
+            jsa.set_object(&rrtype, &dns_log_ds(ds)?)?;
+        }
+        DNSRData::NAPTR(naptr) => {
+            jsa.set_object(&rrtype, &dns_log_naptr(naptr)?)?;
+        }
         DNSRData::OPT(opt) => {
             jsa.open_array(&rrtype)?;
             for val in opt {
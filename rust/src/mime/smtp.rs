@@ -76,7 +76,7 @@ pub struct MimeStateSMTP<'a> {
     pub(crate) state_flag: MimeSmtpParserState,
     pub(crate) headers: Vec<MimeHeader>,
     pub(crate) main_headers_nb: usize,
-    filename: Vec<u8>,
+    pub(crate) filename: Vec<u8>,
     pub(crate) attachments: Vec<Vec<u8>>,
     pub(crate) urls: Vec<Vec<u8>>,
     boundaries: Vec<Vec<u8>>,
@@ -88,7 +88,7 @@ pub struct MimeStateSMTP<'a> {
     // waiting to see if it is part of the boundary
     bufeol: [u8; 2],
     bufeolen: u8,
-    files: &'a mut FileContainer,
+    pub(crate) files: &'a mut FileContainer,
     sbcfg: *const StreamingBufferConfig,
     md5: md5::Md5,
     pub(crate) md5_state: MimeSmtpMd5State,
@@ -258,6 +258,7 @@ fn mime_smtp_process_headers(ctx: &mut MimeStateSMTP) -> (u32, bool) {
     return (warnings, encap);
 }
 
+#[cfg(not(test))]
 extern "C" {
     // Defined in util-file.h
     pub fn FileAppendData(
@@ -270,6 +271,34 @@ extern "C" {
     ) -> u32;
 }
 
+/// A variation of `FileAppendData` for use when running Rust unit tests
+/// as the C function is not available for linkage.
+#[cfg(test)]
+#[allow(non_snake_case)]
+pub unsafe fn FileAppendData(
+    _c: *mut FileContainer, _sbcfg: *const StreamingBufferConfig, _data: *const c_uchar,
+    _data_len: u32,
+) -> std::os::raw::c_int {
+    0
+}
+
+/// A variation of `BasicSearchNocaseIndex` for use when running Rust unit
+/// tests as the C function is not available for linkage.
+#[cfg(test)]
+#[allow(non_snake_case)]
+pub unsafe fn BasicSearchNocaseIndex(
+    data: *const c_uchar, data_len: u32, needle: *const c_uchar, needle_len: u16,
+) -> u32 {
+    if needle_len == 0 || needle_len as u32 > data_len {
+        return data_len;
+    }
+    let data = std::slice::from_raw_parts(data, data_len as usize);
+    let needle = std::slice::from_raw_parts(needle, needle_len as usize);
+    data.windows(needle.len())
+        .position(|w| w.eq_ignore_ascii_case(needle))
+        .map_or(data_len, |i| i as u32)
+}
+
 fn hex(i: u8) -> Option<u8> {
     if i.is_ascii_digit() {
         return Some(i - b'0');
@@ -361,13 +390,13 @@ fn mime_smtp_find_url_strings(ctx: &mut MimeStateSMTP, input_new: &[u8]) {
     }
 }
 
-const MAX_LINE_LEN: u32 = 998; // Def in RFC 2045, excluding CRLF sequence
+pub(crate) const MAX_LINE_LEN: u32 = 998; // Def in RFC 2045, excluding CRLF sequence
 const MAX_ENC_LINE_LEN: usize = 76; /* Def in RFC 2045, excluding CRLF sequence */
 const MAX_HEADER_NAME: usize = 75; /* 75 + ":" = 76 */
 const MAX_HEADER_VALUE: usize = 2000; /* Default - arbitrary limit */
 const MAX_BOUNDARY_LEN: usize = 254;
 
-fn mime_smtp_parse_line(
+pub(crate) fn mime_smtp_parse_line(
     ctx: &mut MimeStateSMTP, i: &[u8], full: &[u8],
 ) -> (MimeSmtpParserResult, u32) {
     if ctx.md5_state == MimeSmtpMd5State::MimeSmtpMd5Started {
@@ -635,7 +664,7 @@ pub unsafe extern "C" fn SCSmtpMimeParseLine(
     return r;
 }
 
-fn mime_smtp_complete(ctx: &mut MimeStateSMTP) {
+pub(crate) fn mime_smtp_complete(ctx: &mut MimeStateSMTP) {
     if ctx.md5_state == MimeSmtpMd5State::MimeSmtpMd5Started {
         ctx.md5_state = MimeSmtpMd5State::MimeSmtpMd5Completed;
         ctx.md5_result = ctx.md5.finalize_reset();
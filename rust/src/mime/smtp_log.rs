@@ -17,7 +17,7 @@
 
 use super::mime;
 use crate::jsonbuilder::{JsonBuilder, JsonError};
-use crate::mime::smtp::{MimeSmtpMd5State, MimeStateSMTP};
+use crate::mime::smtp::{MimeHeader, MimeSmtpMd5State, MimeStateSMTP};
 use digest::Digest;
 use digest::Update;
 use md5::Md5;
@@ -99,9 +99,9 @@ enum FieldCommaState {
 }
 
 fn log_field_comma(
-    js: &mut JsonBuilder, ctx: &MimeStateSMTP, c: &str, e: &str,
+    js: &mut JsonBuilder, headers: &[MimeHeader], c: &str, e: &str,
 ) -> Result<(), JsonError> {
-    for h in &ctx.headers[..ctx.main_headers_nb] {
+    for h in headers {
         if mime::slice_equals_lowercase(&h.name, e.as_bytes()) {
             let mark = js.get_mark();
             let mut has_not_empty_field = false;
@@ -163,7 +163,8 @@ pub unsafe extern "C" fn SCMimeSmtpLogFieldComma(
     if let Ok(email_field) = e.to_str() {
         let c: &CStr = CStr::from_ptr(config); //unsafe
         if let Ok(config_field) = c.to_str() {
-            return log_field_comma(js, ctx, config_field, email_field).is_ok();
+            let headers = &ctx.headers[..ctx.main_headers_nb];
+            return log_field_comma(js, headers, config_field, email_field).is_ok();
         }
     }
     return false;
@@ -197,9 +198,9 @@ pub unsafe extern "C" fn SCMimeSmtpLogFieldString(
 }
 
 fn log_data_header(
-    js: &mut JsonBuilder, ctx: &MimeStateSMTP, hname: &str,
+    js: &mut JsonBuilder, headers: &[MimeHeader], hname: &str,
 ) -> Result<(), JsonError> {
-    for h in &ctx.headers[..ctx.main_headers_nb] {
+    for h in headers {
         if mime::slice_equals_lowercase(&h.name, hname.as_bytes()) {
             js.set_string(hname, &String::from_utf8_lossy(&h.value))?;
             break;
@@ -208,23 +209,27 @@ fn log_data_header(
     return Ok(());
 }
 
-fn log_data(js: &mut JsonBuilder, ctx: &MimeStateSMTP) -> Result<(), JsonError> {
-    log_data_header(js, ctx, "from")?;
-    log_field_comma(js, ctx, "to", "to")?;
-    log_field_comma(js, ctx, "cc", "cc")?;
+/// Log the main headers, attachments and urls of a message, the way
+/// the email object of SMTP is logged.
+pub(crate) fn log_email(
+    js: &mut JsonBuilder, headers: &[MimeHeader], attachments: &[Vec<u8>], urls: &[Vec<u8>],
+) -> Result<(), JsonError> {
+    log_data_header(js, headers, "from")?;
+    log_field_comma(js, headers, "to", "to")?;
+    log_field_comma(js, headers, "cc", "cc")?;
 
     js.set_string("status", "PARSE_DONE")?;
 
-    if !ctx.attachments.is_empty() {
+    if !attachments.is_empty() {
         js.open_array("attachment")?;
-        for a in &ctx.attachments {
+        for a in attachments {
             js.append_string(&String::from_utf8_lossy(a))?;
         }
         js.close()?;
     }
-    if !ctx.urls.is_empty() {
+    if !urls.is_empty() {
         js.open_array("url")?;
-        for a in ctx.urls.iter().rev() {
+        for a in urls.iter().rev() {
             js.append_string(&String::from_utf8_lossy(a))?;
         }
         js.close()?;
@@ -235,5 +240,6 @@ fn log_data(js: &mut JsonBuilder, ctx: &MimeStateSMTP) -> Result<(), JsonError>
 
 #[no_mangle]
 pub unsafe extern "C" fn SCMimeSmtpLogData(js: &mut JsonBuilder, ctx: &MimeStateSMTP) -> bool {
-    return log_data(js, ctx).is_ok();
+    let headers = &ctx.headers[..ctx.main_headers_nb];
+    return log_email(js, headers, &ctx.attachments, &ctx.urls).is_ok();
 }
@@ -0,0 +1,387 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::ntp::{NTPTransaction, ALPROTO_NTP};
+use super::parser::NtpModeCode;
+use crate::core::{STREAM_TOCLIENT, STREAM_TOSERVER};
+use crate::detect::uint::{
+    detect_match_uint, detect_parse_uint_enum, DetectUintData, SCDetectI8Free, SCDetectI8Parse,
+    SCDetectU8Free, SCDetectU8Parse,
+};
+use crate::detect::{helper_keyword_register_sticky_buffer, SigTableElmtStickyBuffer};
+use std::ffi::CStr;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use suricata_sys::sys::{
+    DetectEngineCtx, DetectEngineThreadCtx, Flow, SCDetectBufferSetActiveList,
+    SCDetectHelperBufferMpmRegister, SCDetectHelperBufferRegister, SCDetectHelperKeywordRegister,
+    SCDetectSignatureSetAppProto, SCSigMatchAppendSMToList, SCSigTableAppLiteElmt, SigMatchCtx,
+    Signature,
+};
+
+static mut G_NTP_MODE_KW_ID: u16 = 0;
+static mut G_NTP_MODE_BUFFER_ID: c_int = 0;
+static mut G_NTP_VERSION_KW_ID: u16 = 0;
+static mut G_NTP_VERSION_BUFFER_ID: c_int = 0;
+static mut G_NTP_STRATUM_KW_ID: u16 = 0;
+static mut G_NTP_STRATUM_BUFFER_ID: c_int = 0;
+static mut G_NTP_POLL_KW_ID: u16 = 0;
+static mut G_NTP_POLL_BUFFER_ID: c_int = 0;
+static mut G_NTP_PRECISION_KW_ID: u16 = 0;
+static mut G_NTP_PRECISION_BUFFER_ID: c_int = 0;
+static mut G_NTP_REFID_BUFFER_ID: c_int = 0;
+
+unsafe extern "C" fn ntp_refid_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, NTPTransaction);
+    if let Some(ref h) = tx.header {
+        *buffer = h.ref_id_bytes.as_ptr();
+        *buffer_len = h.ref_id_bytes.len() as u32;
+        return true;
+    }
+
+    *buffer = ptr::null();
+    *buffer_len = 0;
+    return false;
+}
+
+unsafe extern "C" fn ntp_refid_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_NTP) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_NTP_REFID_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn ntp_parse_mode(ustr: *const std::os::raw::c_char) -> *mut DetectUintData<u8> {
+    let ft_name: &CStr = CStr::from_ptr(ustr); //unsafe
+    if let Ok(s) = ft_name.to_str() {
+        if let Some(ctx) = detect_parse_uint_enum::<u8, NtpModeCode>(s) {
+            let boxed = Box::new(ctx);
+            return Box::into_raw(boxed) as *mut _;
+        }
+    }
+    return std::ptr::null_mut();
+}
+
+unsafe extern "C" fn ntp_mode_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_NTP) != 0 {
+        return -1;
+    }
+    let ctx = ntp_parse_mode(raw) as *mut c_void;
+    if ctx.is_null() {
+        return -1;
+    }
+    if SCSigMatchAppendSMToList(
+        de,
+        s,
+        G_NTP_MODE_KW_ID,
+        ctx as *mut SigMatchCtx,
+        G_NTP_MODE_BUFFER_ID,
+    )
+    .is_null()
+    {
+        ntp_u8_free(std::ptr::null_mut(), ctx);
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn ntp_mode_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, NTPTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u8>);
+    if detect_match_uint(ctx, tx.mode) {
+        return 1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn ntp_version_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_NTP) != 0 {
+        return -1;
+    }
+    let ctx = SCDetectU8Parse(raw) as *mut c_void;
+    if ctx.is_null() {
+        return -1;
+    }
+    if SCSigMatchAppendSMToList(
+        de,
+        s,
+        G_NTP_VERSION_KW_ID,
+        ctx as *mut SigMatchCtx,
+        G_NTP_VERSION_BUFFER_ID,
+    )
+    .is_null()
+    {
+        ntp_u8_free(std::ptr::null_mut(), ctx);
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn ntp_version_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, NTPTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u8>);
+    if detect_match_uint(ctx, tx.version) {
+        return 1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn ntp_stratum_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_NTP) != 0 {
+        return -1;
+    }
+    let ctx = SCDetectU8Parse(raw) as *mut c_void;
+    if ctx.is_null() {
+        return -1;
+    }
+    if SCSigMatchAppendSMToList(
+        de,
+        s,
+        G_NTP_STRATUM_KW_ID,
+        ctx as *mut SigMatchCtx,
+        G_NTP_STRATUM_BUFFER_ID,
+    )
+    .is_null()
+    {
+        ntp_u8_free(std::ptr::null_mut(), ctx);
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn ntp_stratum_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, NTPTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u8>);
+    if let Some(ref h) = tx.header {
+        if detect_match_uint(ctx, h.stratum) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+unsafe extern "C" fn ntp_u8_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    let ctx = cast_pointer!(ctx, DetectUintData<u8>);
+    SCDetectU8Free(ctx);
+}
+
+// Poll and precision are signed log2 values, so they are matched as i8 to
+// allow negative arguments like `ntp.precision:<-20;`.
+unsafe fn ntp_log2_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char, kw_id: u16,
+    buffer_id: c_int,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_NTP) != 0 {
+        return -1;
+    }
+    let ctx = SCDetectI8Parse(raw) as *mut c_void;
+    if ctx.is_null() {
+        return -1;
+    }
+    if SCSigMatchAppendSMToList(de, s, kw_id, ctx as *mut SigMatchCtx, buffer_id).is_null() {
+        ntp_log2_free(std::ptr::null_mut(), ctx);
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn ntp_poll_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    ntp_log2_setup(de, s, raw, G_NTP_POLL_KW_ID, G_NTP_POLL_BUFFER_ID)
+}
+
+unsafe extern "C" fn ntp_poll_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, NTPTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<i8>);
+    if let Some(ref h) = tx.header {
+        if detect_match_uint(ctx, h.poll) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+unsafe extern "C" fn ntp_precision_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    ntp_log2_setup(de, s, raw, G_NTP_PRECISION_KW_ID, G_NTP_PRECISION_BUFFER_ID)
+}
+
+unsafe extern "C" fn ntp_precision_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, NTPTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<i8>);
+    if let Some(ref h) = tx.header {
+        if detect_match_uint(ctx, h.precision) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+unsafe extern "C" fn ntp_log2_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    let ctx = cast_pointer!(ctx, DetectUintData<i8>);
+    SCDetectI8Free(ctx);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCDetectNtpRegister() {
+    let kw = SCSigTableAppLiteElmt {
+        name: b"ntp.mode\0".as_ptr() as *const libc::c_char,
+        desc: b"match NTP association mode\0".as_ptr() as *const libc::c_char,
+        url: b"/rules/ntp-keywords.html#ntp-mode\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(ntp_mode_match),
+        Setup: Some(ntp_mode_setup),
+        Free: Some(ntp_u8_free),
+        flags: 0,
+    };
+    G_NTP_MODE_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_NTP_MODE_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"ntp.mode\0".as_ptr() as *const libc::c_char,
+        ALPROTO_NTP,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"ntp.version\0".as_ptr() as *const libc::c_char,
+        desc: b"match NTP protocol version\0".as_ptr() as *const libc::c_char,
+        url: b"/rules/ntp-keywords.html#ntp-version\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(ntp_version_match),
+        Setup: Some(ntp_version_setup),
+        Free: Some(ntp_u8_free),
+        flags: 0,
+    };
+    G_NTP_VERSION_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_NTP_VERSION_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"ntp.version\0".as_ptr() as *const libc::c_char,
+        ALPROTO_NTP,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"ntp.stratum\0".as_ptr() as *const libc::c_char,
+        desc: b"match NTP stratum\0".as_ptr() as *const libc::c_char,
+        url: b"/rules/ntp-keywords.html#ntp-stratum\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(ntp_stratum_match),
+        Setup: Some(ntp_stratum_setup),
+        Free: Some(ntp_u8_free),
+        flags: 0,
+    };
+    G_NTP_STRATUM_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_NTP_STRATUM_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"ntp.stratum\0".as_ptr() as *const libc::c_char,
+        ALPROTO_NTP,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"ntp.poll\0".as_ptr() as *const libc::c_char,
+        desc: b"match NTP poll interval exponent\0".as_ptr() as *const libc::c_char,
+        url: b"/rules/ntp-keywords.html#ntp-poll\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(ntp_poll_match),
+        Setup: Some(ntp_poll_setup),
+        Free: Some(ntp_log2_free),
+        flags: 0,
+    };
+    G_NTP_POLL_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_NTP_POLL_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"ntp.poll\0".as_ptr() as *const libc::c_char,
+        ALPROTO_NTP,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"ntp.precision\0".as_ptr() as *const libc::c_char,
+        desc: b"match NTP clock precision exponent\0".as_ptr() as *const libc::c_char,
+        url: b"/rules/ntp-keywords.html#ntp-precision\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(ntp_precision_match),
+        Setup: Some(ntp_precision_setup),
+        Free: Some(ntp_log2_free),
+        flags: 0,
+    };
+    G_NTP_PRECISION_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_NTP_PRECISION_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"ntp.precision\0".as_ptr() as *const libc::c_char,
+        ALPROTO_NTP,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("ntp.refid"),
+        desc: String::from("sticky buffer to match on the NTP reference id"),
+        url: String::from("/rules/ntp-keywords.html#ntp-refid"),
+        setup: ntp_refid_setup,
+    };
+    let _g_ntp_refid_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_NTP_REFID_BUFFER_ID = SCDetectHelperBufferMpmRegister(
+        b"ntp.refid\0".as_ptr() as *const libc::c_char,
+        b"ntp reference id\0".as_ptr() as *const libc::c_char,
+        ALPROTO_NTP,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(ntp_refid_get),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::detect::uint::{detect_parse_uint, DetectUintMode};
+
+    #[test]
+    fn test_ntp_parse_mode() {
+        let ctx = detect_parse_uint_enum::<u8, NtpModeCode>("private").unwrap();
+        assert_eq!(ctx.arg1, 7);
+        assert_eq!(ctx.mode, DetectUintMode::DetectUintModeEqual);
+        let ctx = detect_parse_uint_enum::<u8, NtpModeCode>("!symmetric_active").unwrap();
+        assert_eq!(ctx.arg1, 1);
+        assert_eq!(ctx.mode, DetectUintMode::DetectUintModeNe);
+        let ctx = detect_parse_uint_enum::<u8, NtpModeCode>("3").unwrap();
+        assert_eq!(ctx.arg1, 3);
+        assert!(detect_parse_uint_enum::<u8, NtpModeCode>("peer").is_none());
+    }
+
+    #[test]
+    fn test_ntp_match_precision() {
+        let (_, ctx) = detect_parse_uint::<i8>("<-20").unwrap();
+        assert!(detect_match_uint(&ctx, -23));
+        assert!(!detect_match_uint(&ctx, -6));
+        assert!(detect_parse_uint::<i8>(">200").is_err());
+    }
+}
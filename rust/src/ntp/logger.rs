@@ -0,0 +1,135 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::ntp::{NTPTransaction, NtpHeader};
+use super::parser::*;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use std;
+use std::net::Ipv4Addr;
+
+/// Format the reference id. Stratum 0 and 1 servers use a four
+/// character code (kiss code or clock source), others the IPv4 address
+/// of their upstream server (or a hash of the IPv6 one).
+pub fn ntp_refid_string(stratum: u8, ref_id: u32) -> String {
+    if stratum <= 1 {
+        let b = ref_id.to_be_bytes();
+        let end = b.iter().position(|&c| c == 0).unwrap_or(b.len());
+        String::from_utf8_lossy(&b[..end]).to_string()
+    } else {
+        Ipv4Addr::from(ref_id).to_string()
+    }
+}
+
+/// Convert a 16.16 fixed point NTP short format value to seconds.
+fn ntp_short_to_secs(v: u32) -> f64 {
+    v as f64 / 65536.0
+}
+
+fn log_ntp_header(h: &NtpHeader, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.set_uint("leap", h.leap)?;
+    js.set_uint("stratum", h.stratum)?;
+    js.set_int("poll", h.poll as i64)?;
+    js.set_int("precision", h.precision as i64)?;
+    js.set_float("root_delay", ntp_short_to_secs(h.root_delay))?;
+    js.set_float("root_dispersion", ntp_short_to_secs(h.root_dispersion))?;
+    js.set_string("reference_id", &ntp_refid_string(h.stratum, h.ref_id))?;
+    for (key, ts) in [
+        ("reference_ts", h.ts_ref),
+        ("origin_ts", h.ts_orig),
+        ("receive_ts", h.ts_recv),
+        ("transmit_ts", h.ts_xmit),
+    ] {
+        if let Some(secs) = ntp_ts_to_unix(ts) {
+            js.set_uint(key, secs)?;
+        }
+    }
+    Ok(())
+}
+
+fn log_ntp(tx: &NTPTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("ntp")?;
+    js.set_uint("version", tx.version)?;
+    js.set_string("mode", ntp_mode_name(tx.mode))?;
+    js.set_bool("response", tx.is_response)?;
+    js.set_uint("length", tx.len)?;
+    if let Some(ref h) = tx.header {
+        log_ntp_header(h, js)?;
+    }
+    if let Some(ref c) = tx.control {
+        js.open_object("control")?;
+        js.set_uint("opcode", c.opcode)?;
+        if let Some(name) = ntp_control_opcode_name(c.opcode) {
+            js.set_string("command", name)?;
+        }
+        js.set_uint("sequence", c.sequence)?;
+        js.set_uint("status", c.status)?;
+        js.set_uint("association_id", c.association_id)?;
+        js.set_uint("offset", c.offset)?;
+        js.set_uint("count", c.count)?;
+        js.set_bool("error", c.error)?;
+        js.set_bool("more", c.more)?;
+        js.close()?;
+    }
+    if let Some(ref p) = tx.private {
+        js.open_object("private")?;
+        js.set_uint("implementation", p.implementation)?;
+        js.set_uint("request_code", p.request_code)?;
+        if let Some(name) = ntp_private_request_name(p.request_code) {
+            js.set_string("request", name)?;
+        }
+        js.set_uint("sequence", p.sequence)?;
+        js.set_uint("error", p.error)?;
+        js.set_uint("item_count", p.item_count)?;
+        js.set_uint("item_size", p.item_size)?;
+        js.set_bool("authenticated", p.authenticated)?;
+        js.set_bool("more", p.more)?;
+        js.close()?;
+    }
+    if let Some(ref e) = tx.exchange {
+        js.open_object("exchange")?;
+        js.set_uint("request_bytes", e.request_len)?;
+        js.set_uint("response_bytes", e.response_len)?;
+        js.set_uint("response_count", e.response_count)?;
+        if e.request_len > 0 {
+            js.set_float("ratio", e.response_len as f64 / e.request_len as f64)?;
+        }
+        js.close()?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCNtpLoggerLog(
+    tx: *const std::os::raw::c_void, js: *mut std::os::raw::c_void,
+) -> bool {
+    let tx = cast_pointer!(tx, NTPTransaction);
+    let js = cast_pointer!(js, JsonBuilder);
+    log_ntp(tx, js).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ntp_refid_string() {
+        assert_eq!(ntp_refid_string(1, 0x47505300), "GPS");
+        assert_eq!(ntp_refid_string(0, 0x52415445), "RATE");
+        assert_eq!(ntp_refid_string(2, 0xc0a80001), "192.168.0.1");
+    }
+}
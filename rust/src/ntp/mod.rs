@@ -19,4 +19,7 @@
 
 // written by Pierre Chifflier  <chifflier@wzdftpd.net>
 
+pub mod detect;
+pub mod logger;
 pub mod ntp;
+pub mod parser;
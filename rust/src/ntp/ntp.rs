@@ -19,12 +19,14 @@
 
 extern crate ntp_parser;
 use self::ntp_parser::*;
+use super::parser::*;
 use crate::applayer::{self, *};
 use crate::core;
 use crate::core::{ALPROTO_FAILED, ALPROTO_UNKNOWN};
 use crate::direction::Direction;
 use crate::flow::Flow;
 use std;
+use std::collections::VecDeque;
 use std::ffi::CString;
 
 use nom7::Err;
@@ -38,6 +40,28 @@ pub enum NTPEvent {
     NotResponse,
 }
 
+/// Maximum number of requests waiting for a response in a flow.
+const NTP_MAX_PENDING_EXCHANGES: usize = 64;
+
+/// Identifies the request a response belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NtpExchangeKey {
+    /// Transmit timestamp of the request, echoed as origin timestamp.
+    Timestamp(u64),
+    /// Control message sequence number.
+    Control(u16),
+    /// Private message implementation and request code.
+    Private(u8, u8),
+}
+
+#[derive(Debug)]
+struct NtpExchange {
+    key: NtpExchangeKey,
+    request_len: u32,
+    response_len: u32,
+    response_count: u32,
+}
+
 #[derive(Default)]
 pub struct NTPState {
     state_data: AppLayerStateData,
@@ -45,6 +69,9 @@ pub struct NTPState {
     /// List of transactions for this session
     transactions: Vec<NTPTransaction>,
 
+    /// Requests that may still be answered
+    exchanges: VecDeque<NtpExchange>,
+
     /// Events counter
     events: u16,
 
@@ -52,11 +79,49 @@ pub struct NTPState {
     tx_id: u64,
 }
 
+/// Header fields of the time synchronization modes (1 to 5).
+#[derive(Debug, Default)]
+pub struct NtpHeader {
+    pub leap: u8,
+    pub stratum: u8,
+    pub poll: i8,
+    pub precision: i8,
+    pub root_delay: u32,
+    pub root_dispersion: u32,
+    pub ref_id: u32,
+    /// Reference id as sent on the wire, for the ntp.refid buffer.
+    pub ref_id_bytes: [u8; 4],
+    pub ts_ref: u64,
+    pub ts_orig: u64,
+    pub ts_recv: u64,
+    pub ts_xmit: u64,
+}
+
+/// Sizes of a request and of all the responses seen for it so far.
+#[derive(Debug, Default)]
+pub struct NtpExchangeSizes {
+    pub request_len: u32,
+    pub response_len: u32,
+    pub response_count: u32,
+}
+
 #[derive(Debug, Default)]
 pub struct NTPTransaction {
     /// The NTP reference ID
     pub xid: u32,
 
+    pub version: u8,
+    pub mode: u8,
+    /// Size of the NTP message
+    pub len: u32,
+    pub is_response: bool,
+
+    pub header: Option<NtpHeader>,
+    pub control: Option<NtpControlMessage>,
+    pub private: Option<NtpPrivateMessage>,
+    /// Set on responses that could be matched to a request.
+    pub exchange: Option<NtpExchangeSizes>,
+
     /// The internal transaction id
     id: u64,
 
@@ -90,19 +155,73 @@ impl NTPState {
     ///
     /// Returns 0 if successful, or -1 on error
     fn parse(&mut self, i: &[u8], direction: Direction) -> i32 {
+        if let Some(&b) = i.first() {
+            match ntp_version_mode(b).1 {
+                NTP_MODE_CONTROL => return self.parse_control(i, direction),
+                NTP_MODE_PRIVATE => return self.parse_private(i, direction),
+                _ => {}
+            }
+        }
         match parse_ntp(i) {
             Ok((_, ref msg)) => {
                 // SCLogDebug!("parse_ntp: {:?}",msg);
-                let (mode, ref_id) = match msg {
-                    NtpPacket::V3(pkt) => (pkt.mode, pkt.ref_id),
-                    NtpPacket::V4(pkt) => (pkt.mode, pkt.ref_id),
+                let (version, mode, header) = match msg {
+                    NtpPacket::V3(pkt) => (
+                        pkt.version,
+                        pkt.mode,
+                        NtpHeader {
+                            leap: pkt.li,
+                            stratum: pkt.stratum,
+                            poll: pkt.poll,
+                            precision: pkt.precision,
+                            root_delay: pkt.root_delay,
+                            root_dispersion: pkt.root_dispersion,
+                            ref_id: pkt.ref_id,
+                            ref_id_bytes: pkt.ref_id.to_be_bytes(),
+                            ts_ref: pkt.ts_ref,
+                            ts_orig: pkt.ts_orig,
+                            ts_recv: pkt.ts_recv,
+                            ts_xmit: pkt.ts_xmit,
+                        },
+                    ),
+                    NtpPacket::V4(pkt) => (
+                        pkt.version,
+                        pkt.mode,
+                        NtpHeader {
+                            leap: pkt.li,
+                            stratum: pkt.stratum,
+                            poll: pkt.poll,
+                            precision: pkt.precision,
+                            root_delay: pkt.root_delay,
+                            root_dispersion: pkt.root_dispersion,
+                            ref_id: pkt.ref_id,
+                            ref_id_bytes: pkt.ref_id.to_be_bytes(),
+                            ts_ref: pkt.ts_ref,
+                            ts_orig: pkt.ts_orig,
+                            ts_recv: pkt.ts_recv,
+                            ts_xmit: pkt.ts_xmit,
+                        },
+                    ),
                 };
+                let mut tx = self.new_tx(direction);
+                // use the reference id as identifier
+                tx.xid = header.ref_id;
+                tx.version = version;
+                tx.mode = mode.0;
+                tx.len = i.len() as u32;
                 if mode == NtpMode::SymmetricActive || mode == NtpMode::Client {
-                    let mut tx = self.new_tx(direction);
-                    // use the reference id as identifier
-                    tx.xid = ref_id;
-                    self.transactions.push(tx);
+                    self.add_exchange(NtpExchangeKey::Timestamp(header.ts_xmit), i.len());
+                } else if mode == NtpMode::SymmetricPassive || mode == NtpMode::Server {
+                    tx.is_response = true;
+                    tx.exchange =
+                        self.match_exchange(NtpExchangeKey::Timestamp(header.ts_orig), i.len());
+                    if tx.exchange.is_none() && mode == NtpMode::Server {
+                        tx.tx_data.set_event(NTPEvent::UnsolicitedResponse as u8);
+                        self.events += 1;
+                    }
                 }
+                tx.header = Some(header);
+                self.transactions.push(tx);
                 0
             }
             Err(Err::Incomplete(_)) => {
@@ -118,6 +237,94 @@ impl NTPState {
         }
     }
 
+    fn parse_control(&mut self, i: &[u8], direction: Direction) -> i32 {
+        match parse_ntp_control(i) {
+            Ok((_, msg)) => {
+                let mut tx = self.new_tx(direction);
+                tx.version = msg.version;
+                tx.mode = NTP_MODE_CONTROL;
+                tx.len = i.len() as u32;
+                let key = NtpExchangeKey::Control(msg.sequence);
+                if msg.response {
+                    tx.is_response = true;
+                    tx.exchange = self.match_exchange(key, i.len());
+                    if tx.exchange.is_none() {
+                        tx.tx_data.set_event(NTPEvent::UnsolicitedResponse as u8);
+                        self.events += 1;
+                    }
+                } else {
+                    self.add_exchange(key, i.len());
+                }
+                tx.control = Some(msg);
+                self.transactions.push(tx);
+                0
+            }
+            Err(_) => {
+                SCLogDebug!("Error while parsing NTP control message");
+                self.set_event(NTPEvent::MalformedData);
+                -1
+            }
+        }
+    }
+
+    fn parse_private(&mut self, i: &[u8], direction: Direction) -> i32 {
+        match parse_ntp_private(i) {
+            Ok((_, msg)) => {
+                let mut tx = self.new_tx(direction);
+                tx.version = msg.version;
+                tx.mode = NTP_MODE_PRIVATE;
+                tx.len = i.len() as u32;
+                let key = NtpExchangeKey::Private(msg.implementation, msg.request_code);
+                if msg.response {
+                    tx.is_response = true;
+                    tx.exchange = self.match_exchange(key, i.len());
+                    if tx.exchange.is_none() {
+                        tx.tx_data.set_event(NTPEvent::UnsolicitedResponse as u8);
+                        self.events += 1;
+                    }
+                } else {
+                    self.add_exchange(key, i.len());
+                }
+                tx.private = Some(msg);
+                self.transactions.push(tx);
+                0
+            }
+            Err(_) => {
+                SCLogDebug!("Error while parsing NTP private message");
+                self.set_event(NTPEvent::MalformedData);
+                -1
+            }
+        }
+    }
+
+    fn add_exchange(&mut self, key: NtpExchangeKey, len: usize) {
+        // a retransmitted request replaces the previous one
+        self.exchanges.retain(|e| e.key != key);
+        if self.exchanges.len() >= NTP_MAX_PENDING_EXCHANGES {
+            self.exchanges.pop_front();
+        }
+        self.exchanges.push_back(NtpExchange {
+            key,
+            request_len: len as u32,
+            response_len: 0,
+            response_count: 0,
+        });
+    }
+
+    /// Account a response to its request, returning the sizes of the
+    /// exchange so far. Responses can span multiple messages, so the
+    /// request is kept around until it is evicted by newer requests.
+    fn match_exchange(&mut self, key: NtpExchangeKey, len: usize) -> Option<NtpExchangeSizes> {
+        let e = self.exchanges.iter_mut().find(|e| e.key == key)?;
+        e.response_len = e.response_len.saturating_add(len as u32);
+        e.response_count += 1;
+        Some(NtpExchangeSizes {
+            request_len: e.request_len,
+            response_len: e.response_len,
+            response_count: e.response_count,
+        })
+    }
+
     fn free(&mut self) {
         // All transactions are freed when the `transactions` object is freed.
         // But let's be explicit
@@ -153,9 +360,9 @@ impl NTPState {
 impl NTPTransaction {
     pub fn new(direction: Direction, id: u64) -> NTPTransaction {
         NTPTransaction {
-            xid: 0,
             id,
             tx_data: applayer::AppLayerTxData::for_direction(direction),
+            ..Default::default()
         }
     }
 }
@@ -224,18 +431,23 @@ extern "C" fn ntp_tx_get_alstate_progress(
     1
 }
 
-static mut ALPROTO_NTP: AppProto = ALPROTO_UNKNOWN;
+pub(super) static mut ALPROTO_NTP: AppProto = ALPROTO_UNKNOWN;
 
 extern "C" fn ntp_probing_parser(
     _flow: *const Flow, _direction: u8, input: *const u8, input_len: u32, _rdir: *mut u8,
 ) -> AppProto {
-    if input.is_null() {
+    if input.is_null() || input_len == 0 {
         return ALPROTO_UNKNOWN;
     }
     let slice: &[u8] = unsafe { std::slice::from_raw_parts(input as *mut u8, input_len as usize) };
     let alproto = unsafe { ALPROTO_NTP };
-    match parse_ntp(slice) {
-        Ok((_, _)) => {
+    let r = match ntp_version_mode(slice[0]).1 {
+        NTP_MODE_CONTROL => parse_ntp_control(slice).map(|_| ()),
+        NTP_MODE_PRIVATE => parse_ntp_private(slice).map(|_| ()),
+        _ => parse_ntp(slice).map(|_| ()),
+    };
+    match r {
+        Ok(_) => {
             // parse_ntp already checks for supported version (3 or 4)
             return alproto;
         }
@@ -298,6 +510,7 @@ pub unsafe extern "C" fn SCRegisterNtpParser() {
         if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
             let _ = AppLayerRegisterParser(&parser, alproto);
         }
+        AppLayerParserRegisterLogger(core::IPPROTO_UDP, ALPROTO_NTP);
     } else {
         SCLogDebug!("Protocol detector and parser disabled for NTP.");
     }
@@ -319,5 +532,50 @@ mod tests {
 
         let mut state = NTPState::new();
         assert_eq!(0, state.parse(REQ, Direction::ToServer));
+        let tx = &state.transactions[0];
+        assert_eq!(tx.version, 4);
+        assert_eq!(tx.mode, 3);
+        assert!(!tx.is_response);
+        assert_eq!(tx.header.as_ref().unwrap().ts_xmit, 0x1857abc34a5f2cfe);
+    }
+
+    #[test]
+    fn test_ntp_parse_monlist_exchange() {
+        let mut req = vec![0x17, 0x00, 0x03, 0x2a];
+        req.extend_from_slice(&[0u8; 4]);
+        // response with 6 items of 72 bytes, more bit set
+        let mut resp = vec![0xd7, 0x00, 0x03, 0x2a, 0x00, 0x06, 0x00, 0x48];
+        resp.extend_from_slice(&[0u8; 6 * 72]);
+
+        let mut state = NTPState::new();
+        assert_eq!(0, state.parse(&req, Direction::ToServer));
+        assert_eq!(0, state.parse(&resp, Direction::ToClient));
+        assert_eq!(0, state.parse(&resp, Direction::ToClient));
+        assert_eq!(state.transactions.len(), 3);
+
+        let tx = &state.transactions[2];
+        assert!(tx.is_response);
+        let private = tx.private.as_ref().unwrap();
+        assert!(private.more);
+        assert_eq!(private.item_count, 6);
+        assert_eq!(private.item_size, 72);
+        let exchange = tx.exchange.as_ref().unwrap();
+        assert_eq!(exchange.request_len, 8);
+        assert_eq!(exchange.response_len, 2 * 440);
+        assert_eq!(exchange.response_count, 2);
+    }
+
+    #[test]
+    fn test_ntp_parse_unsolicited_response() {
+        let resp: &[u8] = &[
+            0x96, 0x82, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut state = NTPState::new();
+        assert_eq!(0, state.parse(resp, Direction::ToServer));
+        let tx = &state.transactions[0];
+        assert_eq!(tx.mode, NTP_MODE_CONTROL);
+        assert!(tx.is_response);
+        assert!(tx.exchange.is_none());
+        assert_eq!(state.events, 1);
     }
 }
@@ -0,0 +1,291 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Parsers for the NTP control (mode 6) and private (mode 7) messages
+//! that are not handled by the ntp-parser crate.
+
+use nom7::bytes::streaming::take;
+use nom7::combinator::{rest, verify};
+use nom7::number::streaming::{be_u16, be_u8};
+use nom7::IResult;
+use suricata_derive::EnumStringU8;
+
+use crate::detect::EnumString;
+
+pub const NTP_MODE_CONTROL: u8 = 6;
+pub const NTP_MODE_PRIVATE: u8 = 7;
+
+/// Private message request code for the monitor list, old and new format.
+pub const NTP_PRIVATE_MON_GETLIST: u8 = 20;
+pub const NTP_PRIVATE_MON_GETLIST_1: u8 = 42;
+
+/// Offset between the NTP era 0 epoch (1900) and the unix epoch.
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// Get the version and mode from the first byte of any NTP message.
+pub fn ntp_version_mode(b: u8) -> (u8, u8) {
+    ((b >> 3) & 0x7, b & 0x7)
+}
+
+fn is_valid_first_byte(b: u8, mode: u8) -> bool {
+    let (version, m) = ntp_version_mode(b);
+    m == mode && (1..=4).contains(&version)
+}
+
+/// NTP association modes, named as used in rules and logs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumStringU8)]
+#[repr(u8)]
+pub enum NtpModeCode {
+    SymmetricActive = 1,
+    SymmetricPassive = 2,
+    Client = 3,
+    Server = 4,
+    Broadcast = 5,
+    Control = 6,
+    Private = 7,
+}
+
+pub fn ntp_mode_name(mode: u8) -> &'static str {
+    NtpModeCode::from_u(mode).map_or("reserved", |m| m.to_str())
+}
+
+/// Convert the seconds of an NTP timestamp to seconds since the unix
+/// epoch. Timestamps before 1968 are taken to be in NTP era 1 (2036+),
+/// those between 1968 and 1970 have no unix time.
+pub fn ntp_ts_to_unix(ts: u64) -> Option<u64> {
+    let secs = ts >> 32;
+    if secs == 0 {
+        return None;
+    }
+    if secs >= 0x8000_0000 {
+        secs.checked_sub(NTP_UNIX_OFFSET)
+    } else {
+        Some(secs + (1 << 32) - NTP_UNIX_OFFSET)
+    }
+}
+
+/// NTP control message (RFC 1305 appendix B, RFC 9327).
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct NtpControlMessage {
+    pub version: u8,
+    pub response: bool,
+    pub error: bool,
+    pub more: bool,
+    pub opcode: u8,
+    pub sequence: u16,
+    pub status: u16,
+    pub association_id: u16,
+    pub offset: u16,
+    pub count: u16,
+}
+
+pub fn parse_ntp_control(i: &[u8]) -> IResult<&[u8], NtpControlMessage> {
+    let (i, b0) = verify(be_u8, |b| is_valid_first_byte(*b, NTP_MODE_CONTROL))(i)?;
+    let (i, b1) = be_u8(i)?;
+    let (i, sequence) = be_u16(i)?;
+    let (i, status) = be_u16(i)?;
+    let (i, association_id) = be_u16(i)?;
+    let (i, offset) = be_u16(i)?;
+    let (i, count) = be_u16(i)?;
+    let (i, _data) = take(count as usize)(i)?;
+    // padding and the optional authenticator
+    let (i, _) = rest(i)?;
+    let msg = NtpControlMessage {
+        version: ntp_version_mode(b0).0,
+        response: b1 & 0x80 != 0,
+        error: b1 & 0x40 != 0,
+        more: b1 & 0x20 != 0,
+        opcode: b1 & 0x1f,
+        sequence,
+        status,
+        association_id,
+        offset,
+        count,
+    };
+    Ok((i, msg))
+}
+
+/// NTP private message, as used by ntpdc (mode 7).
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct NtpPrivateMessage {
+    pub version: u8,
+    pub response: bool,
+    pub more: bool,
+    pub authenticated: bool,
+    pub sequence: u8,
+    pub implementation: u8,
+    pub request_code: u8,
+    pub error: u8,
+    pub item_count: u16,
+    pub item_size: u16,
+}
+
+pub fn parse_ntp_private(i: &[u8]) -> IResult<&[u8], NtpPrivateMessage> {
+    let (i, b0) = verify(be_u8, |b| is_valid_first_byte(*b, NTP_MODE_PRIVATE))(i)?;
+    let (i, b1) = be_u8(i)?;
+    let (i, implementation) = be_u8(i)?;
+    let (i, request_code) = be_u8(i)?;
+    let (i, err_items) = be_u16(i)?;
+    let (i, mbz_size) = be_u16(i)?;
+    // data items, padded to a fixed size in requests
+    let (i, _data) = rest(i)?;
+    let msg = NtpPrivateMessage {
+        version: ntp_version_mode(b0).0,
+        response: b0 & 0x80 != 0,
+        more: b0 & 0x40 != 0,
+        authenticated: b1 & 0x80 != 0,
+        sequence: b1 & 0x7f,
+        implementation,
+        request_code,
+        error: (err_items >> 12) as u8,
+        item_count: err_items & 0x0fff,
+        item_size: mbz_size & 0x0fff,
+    };
+    Ok((i, msg))
+}
+
+/// Name of a control message opcode.
+pub fn ntp_control_opcode_name(opcode: u8) -> Option<&'static str> {
+    let name = match opcode {
+        1 => "readstat",
+        2 => "readvar",
+        3 => "writevar",
+        4 => "readclock",
+        5 => "writeclock",
+        6 => "setrap",
+        7 => "asyncmsg",
+        8 => "configure",
+        9 => "saveconfig",
+        10 => "readmru",
+        11 => "readordlist",
+        12 => "reqnonce",
+        31 => "unsetrap",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Name of a private message request code, for the most common ones.
+pub fn ntp_private_request_name(code: u8) -> Option<&'static str> {
+    let name = match code {
+        0 => "peer_list",
+        1 => "peer_list_sum",
+        2 => "peer_info",
+        3 => "peer_stats",
+        4 => "sys_info",
+        5 => "sys_stats",
+        6 => "io_stats",
+        7 => "mem_stats",
+        8 => "loop_info",
+        9 => "timer_stats",
+        10 => "config",
+        11 => "unconfig",
+        12 => "set_sys_flag",
+        13 => "clr_sys_flag",
+        16 => "get_restrict",
+        17 => "resaddflags",
+        18 => "ressubflags",
+        19 => "unrestrict",
+        NTP_PRIVATE_MON_GETLIST => "mon_getlist",
+        21 => "reset_stats",
+        22 => "reset_peer",
+        23 => "reread_keys",
+        26 => "trust_key",
+        27 => "untrust_key",
+        28 => "auth_info",
+        29 => "traps",
+        30 => "add_trap",
+        31 => "clr_trap",
+        32 => "request_key",
+        33 => "control_key",
+        34 => "get_ctlstats",
+        36 => "get_clock_info",
+        37 => "set_clk_fudge",
+        38 => "get_kernel",
+        39 => "get_clkbug_info",
+        NTP_PRIVATE_MON_GETLIST_1 => "mon_getlist_1",
+        43 => "hostname_associd",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ntp_control_readvar() {
+        // ntpq -c rv request
+        let buf: &[u8] = &[
+            0x16, 0x02, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let (rem, msg) = parse_ntp_control(buf).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(msg.version, 2);
+        assert!(!msg.response);
+        assert_eq!(msg.opcode, 2);
+        assert_eq!(ntp_control_opcode_name(msg.opcode), Some("readvar"));
+        assert_eq!(msg.sequence, 1);
+        assert_eq!(msg.count, 0);
+    }
+
+    #[test]
+    fn test_parse_ntp_control_response() {
+        let buf: &[u8] = &[
+            0x16, 0x82, 0x00, 0x01, 0x06, 0x15, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, b'a', b'=',
+            b'1', b',',
+        ];
+        let (_, msg) = parse_ntp_control(buf).unwrap();
+        assert!(msg.response);
+        assert!(!msg.more);
+        assert_eq!(msg.status, 0x0615);
+        assert_eq!(msg.count, 4);
+    }
+
+    #[test]
+    fn test_parse_ntp_private_monlist() {
+        // ntpdc -c monlist request, version 2
+        let mut buf = vec![0x17, 0x00, 0x03, 0x2a];
+        buf.extend_from_slice(&[0u8; 44]);
+        let (_, msg) = parse_ntp_private(&buf).unwrap();
+        assert_eq!(msg.version, 2);
+        assert!(!msg.response);
+        assert_eq!(msg.implementation, 3);
+        assert_eq!(msg.request_code, NTP_PRIVATE_MON_GETLIST_1);
+        assert_eq!(msg.item_count, 0);
+    }
+
+    #[test]
+    fn test_ntp_ts_to_unix() {
+        assert_eq!(ntp_ts_to_unix(0), None);
+        // 2025-01-01T00:00:00Z
+        assert_eq!(ntp_ts_to_unix(0xeb1f_0400_0000_0000), Some(1_735_689_600));
+        // one second into era 1, 2036-02-07T06:28:17Z
+        assert_eq!(ntp_ts_to_unix(1 << 32), Some(2_085_978_497));
+        // 1968 to 1970, before the unix epoch
+        assert_eq!(ntp_ts_to_unix(0x8000_0000 << 32), None);
+        assert_eq!(ntp_ts_to_unix((NTP_UNIX_OFFSET - 1) << 32), None);
+        assert_eq!(ntp_ts_to_unix(NTP_UNIX_OFFSET << 32), Some(0));
+    }
+
+    #[test]
+    fn test_parse_ntp_private_wrong_mode() {
+        let buf: &[u8] = &[0x23, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert!(parse_ntp_private(buf).is_err());
+    }
+}
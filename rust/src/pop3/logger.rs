@@ -21,6 +21,7 @@
 
 use super::pop3::POP3Transaction;
 use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::mime::smtp_log::log_email;
 use std;
 
 fn log_pop3(tx: &POP3Transaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
@@ -51,6 +52,12 @@ fn log_pop3(tx: &POP3Transaction, js: &mut JsonBuilder) -> Result<(), JsonError>
     }
 
     js.close()?;
+
+    if let Some(ref email) = tx.email {
+        js.open_object("email")?;
+        log_email(js, &email.headers, &email.attachments, &email.urls)?;
+        js.close()?;
+    }
     Ok(())
 }
 
@@ -20,10 +20,17 @@
 //! POP3 parser
 
 use crate::applayer::*;
-use crate::conf::{conf_get, get_memval};
-use crate::core::{ALPROTO_FAILED, ALPROTO_UNKNOWN, IPPROTO_TCP, sc_app_layer_parser_trigger_raw_stream_inspection};
+use crate::conf::{conf_get, conf_get_bool, get_memval};
+use crate::core::{ALPROTO_FAILED, ALPROTO_UNKNOWN, IPPROTO_TCP, STREAM_TOCLIENT, SuricataFileContext, sc_app_layer_parser_trigger_raw_stream_inspection};
+use crate::filecontainer::{FileContainer, FileFlowFlagsToFlags};
 use crate::flow::Flow;
 use crate::direction;
+use crate::mime::smtp::{
+    mime_smtp_complete, mime_smtp_parse_line, mime_smtp_state_init, MimeHeader,
+    MimeSmtpParserResult, MAX_LINE_LEN, MIME_ANOM_INVALID_BASE64, MIME_ANOM_INVALID_QP,
+    MIME_ANOM_LONG_BOUNDARY, MIME_ANOM_LONG_ENC_LINE, MIME_ANOM_LONG_FILENAME,
+    MIME_ANOM_LONG_HEADER_NAME, MIME_ANOM_LONG_HEADER_VALUE, MIME_ANOM_LONG_LINE,
+};
 use std;
 use std::collections::VecDeque;
 use std::ffi::CString;
@@ -39,6 +46,16 @@ use sawp::probe::Status;
 use sawp_pop3::{self, Command, ErrorFlag, Flag, Flags, InnerMessage, Response};
 
 static mut POP3_MAX_TX: usize = 256;
+static mut POP3_DECODE_MIME: bool = true;
+
+pub static mut SURICATA_POP3_FILE_CONFIG: Option<&'static SuricataFileContext> = None;
+
+#[no_mangle]
+pub extern "C" fn SCPop3Init(context: &'static mut SuricataFileContext) {
+    unsafe {
+        SURICATA_POP3_FILE_CONFIG = Some(context);
+    }
+}
 
 pub(super) static mut ALPROTO_POP3: AppProto = ALPROTO_UNKNOWN;
 const POP3_PARSER: sawp_pop3::POP3 = sawp_pop3::POP3 {};
@@ -54,6 +71,18 @@ enum POP3Event {
     UnknownCommand,
     /// First line of server response + CRLF must not exceed 512 octets (RFC 2449)
     ResponseTooLong,
+    /// Server refused STLS
+    TlsRejected,
+    /// STLS succeeded, but the flow could not be handed to the TLS parser
+    FailedProtocolChange,
+    MimeInvalidBase64,
+    MimeInvalidQp,
+    MimeLongLine,
+    MimeLongEncLine,
+    MimeLongHeaderName,
+    MimeLongHeaderValue,
+    MimeLongBoundary,
+    MimeLongFilename,
 }
 
 impl From<ErrorFlag> for POP3Event {
@@ -67,26 +96,153 @@ impl From<ErrorFlag> for POP3Event {
     }
 }
 
+/// Message retrieved with RETR or TOP, decoded by the MIME parser.
+#[derive(Debug, Default)]
+pub struct POP3Email {
+    /// headers of the message, not of its parts
+    pub headers: Vec<MimeHeader>,
+    pub attachments: Vec<Vec<u8>>,
+    pub urls: Vec<Vec<u8>>,
+}
+
 pub struct POP3Transaction {
     tx_id: u64,
     pub request: Option<Command>,
     pub response: Option<Response>,
+    pub email: Option<POP3Email>,
     complete: bool,
 
+    files: FileContainer,
     tx_data: AppLayerTxData,
 }
 
+impl Drop for POP3Transaction {
+    fn drop(&mut self) {
+        if let Some(sfcm) = unsafe { SURICATA_POP3_FILE_CONFIG } {
+            self.files.free(sfcm);
+        }
+    }
+}
+
 impl POP3Transaction {
     pub fn new(tx_id: u64) -> POP3Transaction {
         Self {
             tx_id,
             request: None,
             response: None,
+            email: None,
             complete: false,
+            files: FileContainer::default(),
             tx_data: AppLayerTxData::new(),
         }
     }
 
+    fn mime_warnings_to_events(&mut self, warnings: u32) {
+        let events = [
+            (MIME_ANOM_INVALID_BASE64, POP3Event::MimeInvalidBase64),
+            (MIME_ANOM_INVALID_QP, POP3Event::MimeInvalidQp),
+            (MIME_ANOM_LONG_LINE, POP3Event::MimeLongLine),
+            (MIME_ANOM_LONG_ENC_LINE, POP3Event::MimeLongEncLine),
+            (MIME_ANOM_LONG_HEADER_NAME, POP3Event::MimeLongHeaderName),
+            (MIME_ANOM_LONG_HEADER_VALUE, POP3Event::MimeLongHeaderValue),
+            (MIME_ANOM_LONG_BOUNDARY, POP3Event::MimeLongBoundary),
+            (MIME_ANOM_LONG_FILENAME, POP3Event::MimeLongFilename),
+        ];
+        for (flag, event) in events {
+            if warnings & flag != 0 {
+                self.tx_data.set_event(event as u8);
+            }
+        }
+    }
+
+    /// Decode the lines of a RETR or TOP response as an email. The
+    /// attachments are extracted as files only when `extract_files` is
+    /// set, as TOP only returns the first lines of the body.
+    fn decode_message(&mut self, lines: &[Vec<u8>], extract_files: bool, file_flags: u16) {
+        let sfcm = if extract_files {
+            unsafe { SURICATA_POP3_FILE_CONFIG }
+        } else {
+            None
+        };
+        let sbcfg = sfcm.map_or(std::ptr::null(), |sfcm| sfcm.files_sbcfg as *const _);
+        let flags = if sfcm.is_some() {
+            self.tx_data.update_file_flags(file_flags);
+            unsafe { FileFlowFlagsToFlags(self.tx_data.file_flags, STREAM_TOCLIENT) }
+        } else {
+            0
+        };
+        let mut no_files = FileContainer::default();
+        let files = if sfcm.is_some() {
+            &mut self.files
+        } else {
+            &mut no_files
+        };
+        let mut ctx = match mime_smtp_state_init(files, sbcfg) {
+            Some(ctx) => ctx,
+            None => return,
+        };
+        let mut warnings = 0;
+        let mut track_id = 0;
+        let mut file_open = false;
+        let mut full = Vec::new();
+        for line in lines {
+            // Lines starting with the termination octet are byte-stuffed.
+            let line = if line.starts_with(b"..") {
+                &line[1..]
+            } else {
+                &line[..]
+            };
+            if line.len() > MAX_LINE_LEN as usize {
+                warnings |= MIME_ANOM_LONG_LINE;
+            }
+            full.clear();
+            full.extend_from_slice(line);
+            full.extend_from_slice(b"\r\n");
+            let (r, w) = mime_smtp_parse_line(&mut ctx, line, &full);
+            warnings |= w;
+            let sfcm = match sfcm {
+                Some(sfcm) => sfcm,
+                None => continue,
+            };
+            match r {
+                MimeSmtpParserResult::MimeSmtpFileOpen if !ctx.filename.is_empty() => {
+                    if file_open {
+                        ctx.files.file_close(sfcm, &track_id, flags);
+                        track_id += 1;
+                    }
+                    ctx.files.file_open(sfcm, track_id, &ctx.filename, flags);
+                    file_open = true;
+                    if self.tx_data.file_tx == 0 {
+                        self.tx_data.file_tx = STREAM_TOCLIENT;
+                        self.tx_data.init_files_opened();
+                    } else {
+                        self.tx_data.incr_files_opened();
+                    }
+                }
+                MimeSmtpParserResult::MimeSmtpFileClose if file_open => {
+                    ctx.files.file_close(sfcm, &track_id, flags);
+                    track_id += 1;
+                    file_open = false;
+                }
+                _ => {}
+            }
+        }
+        mime_smtp_complete(&mut ctx);
+        if let (Some(sfcm), true) = (sfcm, file_open) {
+            ctx.files.file_close(sfcm, &track_id, flags);
+        }
+        // Without an empty line, the message only has headers.
+        if ctx.main_headers_nb > 0 {
+            ctx.headers.truncate(ctx.main_headers_nb);
+        }
+        self.email = Some(POP3Email {
+            headers: std::mem::take(&mut ctx.headers),
+            attachments: std::mem::take(&mut ctx.attachments),
+            urls: std::mem::take(&mut ctx.urls),
+        });
+        self.mime_warnings_to_events(warnings);
+    }
+
     fn error_flags_to_events(&mut self, flags: Flags<ErrorFlag>) {
         for val in ErrorFlag::ITEMS
             .iter()
@@ -110,6 +266,8 @@ pub struct POP3State {
     transactions: VecDeque<POP3Transaction>,
     request_gap: bool,
     response_gap: bool,
+    /// STLS succeeded, what follows is TLS
+    tls_upgrade: bool,
 }
 
 impl State<POP3Transaction> for POP3State {
@@ -170,8 +328,9 @@ impl POP3State {
     }
 
     fn parse_request(&mut self, flow: *const Flow, input: &[u8]) -> AppLayerResult {
-        // We're not interested in empty requests.
-        if input.is_empty() {
+        // We're not interested in empty requests, nor in the TLS records
+        // following a successful STLS.
+        if input.is_empty() || self.tls_upgrade {
             return AppLayerResult::ok();
         }
 
@@ -240,8 +399,9 @@ impl POP3State {
     }
 
     fn parse_response(&mut self, input: &[u8], flow: *const Flow) -> AppLayerResult {
-        // We're not interested in empty responses.
-        if input.is_empty() {
+        // We're not interested in empty responses, nor in the TLS records
+        // following a successful STLS.
+        if input.is_empty() || self.tls_upgrade {
             return AppLayerResult::ok();
         }
 
@@ -263,6 +423,7 @@ impl POP3State {
             match POP3_PARSER.parse(start, Direction::ToClient) {
                 Ok((rem, Some(msg))) => {
                     if let InnerMessage::Response(mut response) = msg.inner {
+                        let file_flags = self.state_data.file_flags;
                         let tx = if let Some(tx) = self.find_request() {
                             tx
                         } else {
@@ -281,24 +442,31 @@ impl POP3State {
                         tx.complete = true;
                         sc_app_layer_parser_trigger_raw_stream_inspection(flow, direction::Direction::ToClient as i32);
 
-                        if response.status == sawp_pop3::Status::OK && tx.request.is_some() {
-                            let command = tx.request.as_ref().unwrap();
-                            match &command.keyword {
-                                sawp_pop3::Keyword::STLS => {
-                                    unsafe {
-                                        AppLayerRequestProtocolTLSUpgrade(flow);
-                                    };
-                                }
-                                sawp_pop3::Keyword::RETR => {
-                                    // Don't hold onto the whole email body
-
-                                    // TODO: pass off to mime parser
-                                    response.data.clear();
-                                }
-                                _ => {}
+                        let keyword = tx.request.as_ref().map(|command| &command.keyword);
+                        let stls = matches!(keyword, Some(sawp_pop3::Keyword::STLS));
+                        let retr = matches!(keyword, Some(sawp_pop3::Keyword::RETR));
+                        let top = matches!(keyword, Some(sawp_pop3::Keyword::TOP));
+                        let success = response.status == sawp_pop3::Status::OK;
+                        let mut tls_upgrade = false;
+                        if stls && success {
+                            if !unsafe { AppLayerRequestProtocolTLSUpgrade(flow) } {
+                                tx.tx_data.set_event(POP3Event::FailedProtocolChange as u8);
                             }
+                            tls_upgrade = true;
+                        } else if stls {
+                            tx.tx_data.set_event(POP3Event::TlsRejected as u8);
+                        } else if (retr || top) && success && unsafe { POP3_DECODE_MIME } {
+                            tx.decode_message(&response.data, retr, file_flags);
+                        }
+                        if retr {
+                            // Don't hold onto the whole email body
+                            response.data.clear();
                         }
                         tx.response = Some(response);
+                        if tls_upgrade {
+                            self.tls_upgrade = true;
+                            return AppLayerResult::ok();
+                        }
                     }
                     start = rem;
                 }
@@ -466,6 +634,19 @@ unsafe extern "C" fn pop3_tx_get_alstate_progress(tx: *mut c_void, direction: u8
     }
 }
 
+unsafe extern "C" fn pop3_get_tx_files(tx: *mut c_void, direction: u8) -> AppLayerGetFileState {
+    let tx = cast_pointer!(tx, POP3Transaction);
+    if tx.tx_data.file_tx & direction != 0 {
+        if let Some(sfcm) = { SURICATA_POP3_FILE_CONFIG } {
+            return AppLayerGetFileState {
+                fc: &mut tx.files,
+                cfg: sfcm.files_sbcfg,
+            };
+        }
+    }
+    AppLayerGetFileState::err()
+}
+
 export_tx_data_get!(pop3_get_tx_data, POP3Transaction);
 export_state_data_get!(pop3_get_state_data, POP3State);
 
@@ -497,7 +678,7 @@ pub unsafe extern "C" fn SCRegisterPop3Parser() {
         get_eventinfo_byid: Some(POP3Event::get_event_info_by_id),
         localstorage_new: None,
         localstorage_free: None,
-        get_tx_files: None,
+        get_tx_files: Some(pop3_get_tx_files),
         get_tx_iterator: Some(state_get_tx_iterator::<POP3State, POP3Transaction>),
         get_tx_data: pop3_get_tx_data,
         get_state_data: pop3_get_state_data,
@@ -536,9 +717,43 @@ pub unsafe extern "C" fn SCRegisterPop3Parser() {
                 SCLogError!("Invalid value for pop3.max-tx");
             }
         }
+        if conf_get("app-layer.protocols.pop3.decode-mime").is_some() {
+            POP3_DECODE_MIME = conf_get_bool("app-layer.protocols.pop3.decode-mime");
+        }
         AppLayerParserRegisterLogger(IPPROTO_TCP, ALPROTO_POP3);
         SCLogDebug!("Rust pop3 parser registered.");
     } else {
         SCLogDebug!("Protocol detector and parser disabled for POP3.");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pop3_decode_message() {
+        let lines: Vec<Vec<u8>> = [
+            "From: alice@example.com",
+            "To: bob@example.com, carol@example.com",
+            "Subject: hello",
+            "",
+            "..a line starting with a dot",
+            "bye",
+        ]
+        .iter()
+        .map(|l| l.as_bytes().to_vec())
+        .collect();
+        let mut tx = POP3Transaction::new(1);
+        tx.decode_message(&lines, false, 0);
+        let email = tx.email.as_ref().unwrap();
+        assert_eq!(email.headers.len(), 3);
+        assert_eq!(email.headers[0].name, b"From");
+        assert_eq!(
+            email.headers[1].value,
+            b"bob@example.com, carol@example.com"
+        );
+        assert!(email.attachments.is_empty());
+        assert_eq!(tx.tx_data.file_tx, 0);
+    }
+}
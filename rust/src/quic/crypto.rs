@@ -15,40 +15,108 @@
  * 02110-1301, USA.
  */
 
+use super::parser::quic_pkt_num;
 use aes::cipher::generic_array::GenericArray;
-use aes::Aes128;
 use aes::BlockEncrypt;
 use aes::NewBlockCipher;
+use aes::{Aes128, Aes256};
 use aes_gcm::AeadInPlace;
-use aes_gcm::Aes128Gcm;
 use aes_gcm::NewAead;
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
 use hkdf::Hkdf;
-use sha2::Sha256;
+use sha2::{Sha256, Sha384};
 
 pub const AES128_KEY_LEN: usize = 16;
 pub const AES128_TAG_LEN: usize = 16;
 pub const AES128_IV_LEN: usize = 12;
+const AES256_KEY_LEN: usize = 32;
+/// Length of the sample of the protected payload used for the header
+/// protection.
+const HP_SAMPLE_LEN: usize = 16;
+/// Offset of the sample from the start of the packet number.
+const HP_SAMPLE_OFFSET: usize = 4;
 
-pub struct HeaderProtectionKey(Aes128);
+/// TLS 1.3 cipher suites that can protect QUIC packets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuicCipherSuite {
+    Aes128GcmSha256,
+    Aes256GcmSha384,
+}
+
+impl QuicCipherSuite {
+    /// Get the suite from its TLS identifier. TLS_CHACHA20_POLY1305_SHA256
+    /// is not supported.
+    pub fn from_tls_id(id: u16) -> Option<Self> {
+        match id {
+            0x1301 => Some(QuicCipherSuite::Aes128GcmSha256),
+            0x1302 => Some(QuicCipherSuite::Aes256GcmSha384),
+            _ => None,
+        }
+    }
+
+    fn key_len(&self) -> usize {
+        match self {
+            QuicCipherSuite::Aes128GcmSha256 => AES128_KEY_LEN,
+            QuicCipherSuite::Aes256GcmSha384 => AES256_KEY_LEN,
+        }
+    }
+}
+
+enum QuicHkdf {
+    Sha256(Hkdf<Sha256>),
+    Sha384(Hkdf<Sha384>),
+}
+
+impl QuicHkdf {
+    fn from_prk(suite: QuicCipherSuite, secret: &[u8]) -> Option<Self> {
+        match suite {
+            QuicCipherSuite::Aes128GcmSha256 => {
+                Hkdf::<Sha256>::from_prk(secret).ok().map(QuicHkdf::Sha256)
+            }
+            QuicCipherSuite::Aes256GcmSha384 => {
+                Hkdf::<Sha384>::from_prk(secret).ok().map(QuicHkdf::Sha384)
+            }
+        }
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+enum HeaderProtectionCipher {
+    Aes128(Aes128),
+    Aes256(Aes256),
+}
+
+pub struct HeaderProtectionKey(HeaderProtectionCipher);
 
 impl HeaderProtectionKey {
-    fn new(secret: &[u8], version: u32) -> Self {
-        let hk = Hkdf::<Sha256>::from_prk(secret).unwrap();
-        let mut secret = [0u8; AES128_KEY_LEN];
+    fn new(suite: QuicCipherSuite, hk: &QuicHkdf, version: u32) -> Result<Self, ()> {
+        let mut secret = vec![0u8; suite.key_len()];
         let quichp = if version == 0x6b3343cf {
             b"quicv2 hp" as &[u8]
         } else {
             b"quic hp" as &[u8]
         };
-        hkdf_expand_label(&hk, quichp, &mut secret, AES128_KEY_LEN as u16);
-        return Self(Aes128::new(GenericArray::from_slice(&secret)));
+        hkdf_expand_label(hk, quichp, &mut secret)?;
+        let key = &secret[..];
+        let cipher = match suite {
+            QuicCipherSuite::Aes128GcmSha256 => {
+                HeaderProtectionCipher::Aes128(Aes128::new(GenericArray::from_slice(key)))
+            }
+            QuicCipherSuite::Aes256GcmSha384 => {
+                HeaderProtectionCipher::Aes256(Aes256::new(GenericArray::from_slice(key)))
+            }
+        };
+        return Ok(Self(cipher));
     }
 
     pub fn decrypt_in_place(
         &self, sample: &[u8], first: &mut u8, packet_number: &mut [u8],
     ) -> Result<(), ()> {
         let mut mask = GenericArray::clone_from_slice(sample);
-        self.0.encrypt_block(&mut mask);
+        match &self.0 {
+            HeaderProtectionCipher::Aes128(c) => c.encrypt_block(&mut mask),
+            HeaderProtectionCipher::Aes256(c) => c.encrypt_block(&mut mask),
+        }
 
         let (first_mask, pn_mask) = mask.split_first().unwrap();
 
@@ -69,22 +137,34 @@ impl HeaderProtectionKey {
     }
 }
 
+#[allow(clippy::large_enum_variant)]
+enum PacketCipher {
+    Aes128Gcm(Aes128Gcm),
+    Aes256Gcm(Aes256Gcm),
+}
+
 pub struct PacketKey {
-    key: Aes128Gcm,
+    key: PacketCipher,
     iv: [u8; AES128_IV_LEN],
 }
 
 impl PacketKey {
-    fn new(secret: &[u8], version: u32) -> Self {
-        let hk = Hkdf::<Sha256>::from_prk(secret).unwrap();
-        let mut secret = [0u8; AES128_KEY_LEN];
+    fn new(suite: QuicCipherSuite, hk: &QuicHkdf, version: u32) -> Result<Self, ()> {
+        let mut secret = vec![0u8; suite.key_len()];
         let quickey = if version == 0x6b3343cf {
             b"quicv2 key" as &[u8]
         } else {
             b"quic key" as &[u8]
         };
-        hkdf_expand_label(&hk, quickey, &mut secret, AES128_KEY_LEN as u16);
-        let key = Aes128Gcm::new(GenericArray::from_slice(&secret));
+        hkdf_expand_label(hk, quickey, &mut secret)?;
+        let key = match suite {
+            QuicCipherSuite::Aes128GcmSha256 => {
+                PacketCipher::Aes128Gcm(Aes128Gcm::new(GenericArray::from_slice(&secret)))
+            }
+            QuicCipherSuite::Aes256GcmSha384 => {
+                PacketCipher::Aes256Gcm(Aes256Gcm::new(GenericArray::from_slice(&secret)))
+            }
+        };
 
         let mut r = PacketKey {
             key,
@@ -95,8 +175,8 @@ impl PacketKey {
         } else {
             b"quic iv" as &[u8]
         };
-        hkdf_expand_label(&hk, quiciv, &mut r.iv, AES128_IV_LEN as u16);
-        return r;
+        hkdf_expand_label(hk, quiciv, &mut r.iv)?;
+        return Ok(r);
     }
 
     pub fn decrypt_in_place<'a>(
@@ -113,9 +193,16 @@ impl PacketKey {
         let tag_pos = payload.len() - AES128_TAG_LEN;
         let (buffer, tag) = payload.split_at_mut(tag_pos);
         let taga = GenericArray::from_slice(tag);
-        self.key
-            .decrypt_in_place_detached(GenericArray::from_slice(&nonce), header, buffer, taga)
-            .map_err(|_| ())?;
+        let nonce = GenericArray::from_slice(&nonce);
+        match &self.key {
+            PacketCipher::Aes128Gcm(key) => {
+                key.decrypt_in_place_detached(nonce, header, buffer, taga)
+            }
+            PacketCipher::Aes256Gcm(key) => {
+                key.decrypt_in_place_detached(nonce, header, buffer, taga)
+            }
+        }
+        .map_err(|_| ())?;
         Ok(&payload[..tag_pos])
     }
 }
@@ -126,12 +213,59 @@ pub struct DirectionalKeys {
 }
 
 impl DirectionalKeys {
-    fn new(secret: &[u8], version: u32) -> Self {
-        Self {
-            header: HeaderProtectionKey::new(secret, version),
-            packet: PacketKey::new(secret, version),
-        }
+    /// Derive the keys from a traffic secret of the TLS key schedule.
+    pub fn new(suite: QuicCipherSuite, secret: &[u8], version: u32) -> Result<Self, ()> {
+        let hk = QuicHkdf::from_prk(suite, secret).ok_or(())?;
+        Ok(Self {
+            header: HeaderProtectionKey::new(suite, &hk, version)?,
+            packet: PacketKey::new(suite, &hk, version)?,
+        })
+    }
+
+    /// Remove the header protection of a packet and decrypt its payload
+    /// into `output`. `packet` holds the whole packet, with its packet
+    /// number at `pn_offset`. `largest_pn` is the largest packet number
+    /// decrypted so far in the same packet number space, to expand the
+    /// truncated packet number. Returns the packet number.
+    pub fn decrypt_packet(
+        &self, packet: &[u8], pn_offset: usize, largest_pn: Option<u64>, output: &mut Vec<u8>,
+    ) -> Result<u64, ()> {
+        let sample_start = pn_offset + HP_SAMPLE_OFFSET;
+        let sample = packet
+            .get(sample_start..sample_start + HP_SAMPLE_LEN)
+            .ok_or(())?;
+        let mut first = packet[0];
+        let mut pn_buf = [0u8; HP_SAMPLE_OFFSET];
+        pn_buf.copy_from_slice(&packet[pn_offset..sample_start]);
+        self.header
+            .decrypt_in_place(sample, &mut first, &mut pn_buf)?;
+        let pn_len = (first & 3) as usize + 1;
+        let mut aad = Vec::with_capacity(pn_offset + pn_len);
+        aad.push(first);
+        aad.extend_from_slice(&packet[1..pn_offset]);
+        aad.extend_from_slice(&pn_buf[..pn_len]);
+        let pn = quic_expand_pkt_num(largest_pn, quic_pkt_num(&pn_buf[..pn_len]), pn_len);
+        output.clear();
+        output.extend_from_slice(&packet[pn_offset + pn_len..]);
+        let len = self.packet.decrypt_in_place(pn, &aad, output)?.len();
+        output.truncate(len);
+        Ok(pn)
+    }
+}
+
+/// Expand a truncated packet number, RFC 9000 appendix A.3.
+fn quic_expand_pkt_num(largest_pn: Option<u64>, truncated_pn: u64, pn_len: usize) -> u64 {
+    let expected = largest_pn.map_or(0, |pn| pn + 1);
+    let win = 1u64 << (pn_len * 8);
+    let hwin = win / 2;
+    let candidate = (expected & !(win - 1)) | truncated_pn;
+    if candidate + hwin <= expected && candidate < (1 << 62) - win {
+        return candidate + win;
+    }
+    if candidate > expected + hwin && candidate >= win {
+        return candidate - win;
     }
+    return candidate;
 }
 
 pub struct QuicKeys {
@@ -139,10 +273,10 @@ pub struct QuicKeys {
     pub remote: DirectionalKeys,
 }
 
-fn hkdf_expand_label(hk: &Hkdf<Sha256>, label: &[u8], okm: &mut [u8], olen: u16) {
+fn hkdf_expand_label(hk: &QuicHkdf, label: &[u8], okm: &mut [u8]) -> Result<(), ()> {
     const LABEL_PREFIX: &[u8] = b"tls13 ";
 
-    let output_len = u16::to_be_bytes(olen);
+    let output_len = u16::to_be_bytes(okm.len() as u16);
     let label_len = u8::to_be_bytes((LABEL_PREFIX.len() + label.len()) as u8);
     let context_len = u8::to_be_bytes(0);
 
@@ -154,10 +288,18 @@ fn hkdf_expand_label(hk: &Hkdf<Sha256>, label: &[u8], okm: &mut [u8], olen: u16)
         &context_len[..],
     ];
 
-    hk.expand_multi_info(info, okm).unwrap();
+    match hk {
+        QuicHkdf::Sha256(hk) => hk.expand_multi_info(info, okm),
+        QuicHkdf::Sha384(hk) => hk.expand_multi_info(info, okm),
+    }
+    .map_err(|_| ())
 }
 
-pub fn quic_keys_initial(version: u32, client_dst_connection_id: &[u8]) -> Option<QuicKeys> {
+/// Derive the keys of the Initial packets. Returns `None` for versions
+/// without a known salt.
+pub fn quic_keys_initial(
+    version: u32, client_dst_connection_id: &[u8],
+) -> Result<Option<QuicKeys>, ()> {
     let salt = match version {
         0x51303530 => &[
             0x50, 0x45, 0x74, 0xEF, 0xD0, 0x66, 0xFE, 0x2F, 0x9D, 0x94, 0x5C, 0xFC, 0xDB, 0xD3,
@@ -184,17 +326,61 @@ pub fn quic_keys_initial(version: u32, client_dst_connection_id: &[u8]) -> Optio
             0x9d, 0xcb, 0xf9, 0xbd, 0x2e, 0xd9,
         ],
         _ => {
-            return None;
+            return Ok(None);
         }
     };
-    let hk = Hkdf::<Sha256>::new(Some(salt), client_dst_connection_id);
+    let hk = QuicHkdf::Sha256(Hkdf::<Sha256>::new(Some(salt), client_dst_connection_id));
     let mut client_secret = [0u8; 32];
-    hkdf_expand_label(&hk, b"client in", &mut client_secret, 32);
+    hkdf_expand_label(&hk, b"client in", &mut client_secret)?;
     let mut server_secret = [0u8; 32];
-    hkdf_expand_label(&hk, b"server in", &mut server_secret, 32);
+    hkdf_expand_label(&hk, b"server in", &mut server_secret)?;
+
+    let suite = QuicCipherSuite::Aes128GcmSha256;
+    return Ok(Some(QuicKeys {
+        local: DirectionalKeys::new(suite, &server_secret, version)?,
+        remote: DirectionalKeys::new(suite, &client_secret, version)?,
+    }));
+}
 
-    return Some(QuicKeys {
-        local: DirectionalKeys::new(&server_secret, version),
-        remote: DirectionalKeys::new(&client_secret, version),
-    });
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quic_keys_initial() {
+        // RFC 9001 appendix A.1
+        let dcid = [0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08];
+        let keys = quic_keys_initial(1, &dcid).unwrap().unwrap();
+        assert_eq!(
+            keys.remote.packet.iv,
+            [0xfa, 0x04, 0x4b, 0x2f, 0x42, 0xa3, 0xfd, 0x3b, 0x46, 0xfb, 0x25, 0x5c]
+        );
+        assert_eq!(
+            keys.local.packet.iv,
+            [0x0a, 0xc1, 0x49, 0x3c, 0xa1, 0x90, 0x58, 0x53, 0xb0, 0xbb, 0xa0, 0x3e]
+        );
+    }
+
+    #[test]
+    fn test_quic_keys_from_secret() {
+        // RFC 9001 appendix A.1 client initial secret
+        let secret =
+            hex::decode("c00cf151ca5be075ed0ebfb5c80323c42d6b7db67881289af4008f1f6c357aea")
+                .unwrap();
+        let keys = DirectionalKeys::new(QuicCipherSuite::Aes128GcmSha256, &secret, 1).unwrap();
+        assert_eq!(
+            keys.packet.iv,
+            [0xfa, 0x04, 0x4b, 0x2f, 0x42, 0xa3, 0xfd, 0x3b, 0x46, 0xfb, 0x25, 0x5c]
+        );
+        // a secret of the wrong size for the hash
+        assert!(DirectionalKeys::new(QuicCipherSuite::Aes256GcmSha384, &secret, 1).is_err());
+    }
+
+    #[test]
+    fn test_quic_expand_pkt_num() {
+        // RFC 9000 appendix A.3
+        assert_eq!(quic_expand_pkt_num(Some(0xa82f30ea), 0x9b32, 2), 0xa82f9b32);
+        assert_eq!(quic_expand_pkt_num(None, 0, 1), 0);
+        assert_eq!(quic_expand_pkt_num(Some(0x1ff), 0x01, 1), 0x201);
+    }
 }
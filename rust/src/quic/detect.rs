@@ -16,10 +16,32 @@
  */
 
 use crate::core::{STREAM_TOCLIENT, STREAM_TOSERVER};
+use crate::detect::uint::{
+    detect_match_uint, DetectUintData, SCDetectU16Free, SCDetectU16Parse, SCDetectU32Free,
+    SCDetectU32Parse,
+};
+use crate::detect::{helper_keyword_register_sticky_buffer, SigTableElmtStickyBuffer};
 use crate::quic::quic::QuicTransaction;
-use std::os::raw::c_void;
+use std::os::raw::{c_int, c_void};
 use std::ptr;
-use suricata_sys::sys::DetectEngineThreadCtx;
+use suricata_sys::sys::{
+    AppProto, AppProtoEnum, DetectEngineCtx, DetectEngineThreadCtx, Flow,
+    SCDetectBufferSetActiveList, SCDetectHelperBufferMpmRegister, SCDetectHelperBufferRegister,
+    SCDetectHelperKeywordRegister, SCDetectSignatureSetAppProto, SCSigMatchAppendSMToList,
+    SCSigTableAppLiteElmt, SigMatchCtx, Signature,
+};
+
+const ALPROTO_QUIC: AppProto = AppProtoEnum::ALPROTO_QUIC as AppProto;
+
+static mut G_QUIC_SERVER_ALPN_BUFFER_ID: c_int = 0;
+static mut G_QUIC_SERVER_CIPHER_KW_ID: u16 = 0;
+static mut G_QUIC_SERVER_CIPHER_BUFFER_ID: c_int = 0;
+static mut G_QUIC_VN_VERSION_KW_ID: u16 = 0;
+static mut G_QUIC_VN_VERSION_BUFFER_ID: c_int = 0;
+static mut G_QUIC_RETRY_TOKEN_LEN_KW_ID: u16 = 0;
+static mut G_QUIC_RETRY_TOKEN_LEN_BUFFER_ID: c_int = 0;
+static mut G_QUIC_DCID_COUNT_KW_ID: u16 = 0;
+static mut G_QUIC_DCID_COUNT_BUFFER_ID: c_int = 0;
 
 #[no_mangle]
 pub unsafe extern "C" fn SCQuicTxGetUa(
@@ -145,3 +167,312 @@ pub unsafe extern "C" fn SCQuicTxGetCyuString(
         false
     }
 }
+
+/// Get the HTTP/2 transaction holding the headers of a HTTP/3 request,
+/// for the HTTP/2 detection engines.
+#[no_mangle]
+pub unsafe extern "C" fn SCQuicTxGetHttp2Tx(tx: &QuicTransaction) -> *mut c_void {
+    if let Some(h3) = &tx.http3 {
+        return &h3.tx as *const _ as *mut _;
+    }
+    ptr::null_mut()
+}
+
+/// Get the DNS transaction of a DNS over QUIC stream in the direction
+/// of the flags, for the DNS detection engines.
+#[no_mangle]
+pub unsafe extern "C" fn SCQuicTxGetDnsTx(tx: &QuicTransaction, flags: u8) -> *mut c_void {
+    if let Some(doq) = &tx.doq {
+        let dtx = if flags & STREAM_TOSERVER != 0 {
+            &doq.request
+        } else {
+            &doq.response
+        };
+        if let Some(dtx) = dtx {
+            return dtx as *const _ as *mut _;
+        }
+    }
+    ptr::null_mut()
+}
+
+unsafe extern "C" fn quic_server_alpn_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_QUIC) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_QUIC_SERVER_ALPN_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn quic_server_alpn_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, QuicTransaction);
+    if let Some(alpn) = &tx.server_alpn {
+        *buffer = alpn.as_ptr();
+        *buffer_len = alpn.len() as u32;
+        return true;
+    }
+    *buffer = ptr::null();
+    *buffer_len = 0;
+    return false;
+}
+
+unsafe fn quic_uint_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, ctx: *mut c_void, kw_id: u16, buffer_id: c_int,
+    free: unsafe extern "C" fn(*mut DetectEngineCtx, *mut c_void),
+) -> c_int {
+    if ctx.is_null() {
+        return -1;
+    }
+    if SCSigMatchAppendSMToList(de, s, kw_id, ctx as *mut SigMatchCtx, buffer_id).is_null() {
+        free(std::ptr::null_mut(), ctx);
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn quic_server_cipher_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_QUIC) != 0 {
+        return -1;
+    }
+    let ctx = SCDetectU16Parse(raw) as *mut c_void;
+    quic_uint_setup(
+        de,
+        s,
+        ctx,
+        G_QUIC_SERVER_CIPHER_KW_ID,
+        G_QUIC_SERVER_CIPHER_BUFFER_ID,
+        quic_u16_free,
+    )
+}
+
+unsafe extern "C" fn quic_server_cipher_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, QuicTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u16>);
+    if let Some(cipher) = tx.server_cipher {
+        if detect_match_uint(ctx, cipher) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+unsafe extern "C" fn quic_u16_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    let ctx = cast_pointer!(ctx, DetectUintData<u16>);
+    SCDetectU16Free(ctx);
+}
+
+unsafe extern "C" fn quic_u32_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    let ctx = cast_pointer!(ctx, DetectUintData<u32>);
+    SCDetectU32Free(ctx);
+}
+
+unsafe extern "C" fn quic_vn_version_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_QUIC) != 0 {
+        return -1;
+    }
+    let ctx = SCDetectU32Parse(raw) as *mut c_void;
+    quic_uint_setup(
+        de,
+        s,
+        ctx,
+        G_QUIC_VN_VERSION_KW_ID,
+        G_QUIC_VN_VERSION_BUFFER_ID,
+        quic_u32_free,
+    )
+}
+
+fn quic_vn_version_match_tx(tx: &QuicTransaction, ctx: &DetectUintData<u32>) -> bool {
+    tx.versions
+        .iter()
+        .any(|version| detect_match_uint(ctx, u32::from(*version)))
+}
+
+unsafe extern "C" fn quic_vn_version_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, QuicTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u32>);
+    if quic_vn_version_match_tx(tx, ctx) {
+        return 1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn quic_retry_token_len_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_QUIC) != 0 {
+        return -1;
+    }
+    let ctx = SCDetectU32Parse(raw) as *mut c_void;
+    quic_uint_setup(
+        de,
+        s,
+        ctx,
+        G_QUIC_RETRY_TOKEN_LEN_KW_ID,
+        G_QUIC_RETRY_TOKEN_LEN_BUFFER_ID,
+        quic_u32_free,
+    )
+}
+
+unsafe extern "C" fn quic_retry_token_len_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, QuicTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u32>);
+    if let Some(token) = &tx.retry_token {
+        if detect_match_uint(ctx, token.len() as u32) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+unsafe extern "C" fn quic_dcid_count_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_QUIC) != 0 {
+        return -1;
+    }
+    let ctx = SCDetectU32Parse(raw) as *mut c_void;
+    quic_uint_setup(
+        de,
+        s,
+        ctx,
+        G_QUIC_DCID_COUNT_KW_ID,
+        G_QUIC_DCID_COUNT_BUFFER_ID,
+        quic_u32_free,
+    )
+}
+
+unsafe extern "C" fn quic_dcid_count_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, QuicTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u32>);
+    if detect_match_uint(ctx, tx.dcid_count) {
+        return 1;
+    }
+    return 0;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCDetectQuicRegister() {
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("quic.server_alpn"),
+        desc: String::from("sticky buffer to match on the ALPN chosen by the QUIC server"),
+        url: String::from("/rules/quic-keywords.html#quic-server-alpn"),
+        setup: quic_server_alpn_setup,
+    };
+    let _g_quic_server_alpn_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_QUIC_SERVER_ALPN_BUFFER_ID = SCDetectHelperBufferMpmRegister(
+        b"quic.server_alpn\0".as_ptr() as *const libc::c_char,
+        b"quic server alpn\0".as_ptr() as *const libc::c_char,
+        ALPROTO_QUIC,
+        STREAM_TOCLIENT,
+        Some(quic_server_alpn_get),
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"quic.server_cipher\0".as_ptr() as *const libc::c_char,
+        desc: b"match the cipher suite chosen by the QUIC server\0".as_ptr() as *const libc::c_char,
+        url: b"/rules/quic-keywords.html#quic-server-cipher\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(quic_server_cipher_match),
+        Setup: Some(quic_server_cipher_setup),
+        Free: Some(quic_u16_free),
+        flags: 0,
+    };
+    G_QUIC_SERVER_CIPHER_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_QUIC_SERVER_CIPHER_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"quic.server_cipher\0".as_ptr() as *const libc::c_char,
+        ALPROTO_QUIC,
+        STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"quic.vn_version\0".as_ptr() as *const libc::c_char,
+        desc: b"match a version offered in a QUIC Version Negotiation\0".as_ptr()
+            as *const libc::c_char,
+        url: b"/rules/quic-keywords.html#quic-vn-version\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(quic_vn_version_match),
+        Setup: Some(quic_vn_version_setup),
+        Free: Some(quic_u32_free),
+        flags: 0,
+    };
+    G_QUIC_VN_VERSION_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_QUIC_VN_VERSION_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"quic.vn_version\0".as_ptr() as *const libc::c_char,
+        ALPROTO_QUIC,
+        STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"quic.retry_token_len\0".as_ptr() as *const libc::c_char,
+        desc: b"match the token length of a QUIC Retry\0".as_ptr() as *const libc::c_char,
+        url: b"/rules/quic-keywords.html#quic-retry-token-len\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(quic_retry_token_len_match),
+        Setup: Some(quic_retry_token_len_setup),
+        Free: Some(quic_u32_free),
+        flags: 0,
+    };
+    G_QUIC_RETRY_TOKEN_LEN_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_QUIC_RETRY_TOKEN_LEN_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"quic.retry_token_len\0".as_ptr() as *const libc::c_char,
+        ALPROTO_QUIC,
+        STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"quic.dcid_count\0".as_ptr() as *const libc::c_char,
+        desc: b"match the number of distinct QUIC destination connection ids\0".as_ptr()
+            as *const libc::c_char,
+        url: b"/rules/quic-keywords.html#quic-dcid-count\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(quic_dcid_count_match),
+        Setup: Some(quic_dcid_count_setup),
+        Free: Some(quic_u32_free),
+        flags: 0,
+    };
+    G_QUIC_DCID_COUNT_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_QUIC_DCID_COUNT_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"quic.dcid_count\0".as_ptr() as *const libc::c_char,
+        ALPROTO_QUIC,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::detect::uint::detect_parse_uint;
+    use crate::quic::parser::{PublicFlags, QuicHeader, QuicType, QuicVersion};
+
+    #[test]
+    fn test_quic_vn_version_match() {
+        let header = QuicHeader::new(
+            PublicFlags::new(0x80),
+            QuicType::VersionNegotiation,
+            QuicVersion(0),
+            Vec::new(),
+            Vec::new(),
+        );
+        let mut tx = QuicTransaction::new_empty(false, header);
+        tx.versions = vec![QuicVersion(1), QuicVersion::V2];
+        let (_, ctx) = detect_parse_uint::<u32>("0x6b3343cf").unwrap();
+        assert!(quic_vn_version_match_tx(&tx, &ctx));
+        let (_, ctx) = detect_parse_uint::<u32>(">2").unwrap();
+        assert!(quic_vn_version_match_tx(&tx, &ctx));
+        let (_, ctx) = detect_parse_uint::<u32>("2").unwrap();
+        assert!(!quic_vn_version_match_tx(&tx, &ctx));
+    }
+}
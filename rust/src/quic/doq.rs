@@ -0,0 +1,186 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! DNS over QUIC (RFC 9250) over the STREAM frames of the 1-RTT packets
+//! decrypted with the key log.
+
+use super::frames::StreamData;
+use super::reassembly::{CryptoReassembly, CryptoReassemblyError};
+use crate::direction::Direction;
+use crate::dns::dns::{dns_parse_framed, DNSParseError, DNSTransaction, DnsVariant};
+use crate::http2::http2::HTTP2TransactionState;
+use std::collections::HashMap;
+
+/// Maximum number of streams followed in each direction.
+const DOQ_MAX_STREAMS: usize = 1024;
+
+/// DNS query and response of a DoQ stream.
+#[derive(Debug)]
+pub struct DoqTransaction {
+    pub stream_id: u64,
+    pub request: Option<DNSTransaction>,
+    pub response: Option<DNSTransaction>,
+}
+
+impl DoqTransaction {
+    pub(super) fn new(stream_id: u64) -> Self {
+        Self {
+            stream_id,
+            request: None,
+            response: None,
+        }
+    }
+
+    /// Progress of the stream, with the states of a HTTP/2 stream as for
+    /// HTTP/3.
+    pub(super) fn progress(&self) -> HTTP2TransactionState {
+        if self.response.is_some() {
+            HTTP2TransactionState::HTTP2StateClosed
+        } else if self.request.is_some() {
+            HTTP2TransactionState::HTTP2StateHalfClosedClient
+        } else {
+            HTTP2TransactionState::HTTP2StateOpen
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(super) enum DoqMessage {
+    /// DNS message of a stream
+    Dns(u64, Box<DNSTransaction>),
+    /// malformed DoQ or DNS data, or beyond the limits
+    Error,
+}
+
+#[derive(Debug, Default)]
+struct DoqStream {
+    data: CryptoReassembly,
+    /// the message was parsed, or failed to
+    done: bool,
+}
+
+/// DNS over QUIC state of a connection.
+#[derive(Debug, Default)]
+pub(super) struct DoqState {
+    ts: HashMap<u64, DoqStream>,
+    tc: HashMap<u64, DoqStream>,
+}
+
+impl DoqState {
+    /// Handle the data of a STREAM frame. Each stream carries a single
+    /// message in each direction, prefixed with its length.
+    pub(super) fn handle_stream(
+        &mut self, data: &StreamData, to_server: bool, out: &mut Vec<DoqMessage>,
+    ) {
+        // messages are only sent on client-initiated bidirectional streams
+        if data.stream_id & 0x3 != 0 {
+            return;
+        }
+        let streams = if to_server {
+            &mut self.ts
+        } else {
+            &mut self.tc
+        };
+        if !streams.contains_key(&data.stream_id) && streams.len() >= DOQ_MAX_STREAMS {
+            streams.retain(|_, s| !s.done);
+            if streams.len() >= DOQ_MAX_STREAMS {
+                out.push(DoqMessage::Error);
+                return;
+            }
+        }
+        let stream = streams.entry(data.stream_id).or_default();
+        if stream.done {
+            return;
+        }
+        if let Err(CryptoReassemblyError::TooLong) = stream.data.insert(data.offset, &data.data) {
+            stream.done = true;
+            stream.data.reset();
+            out.push(DoqMessage::Error);
+            return;
+        }
+        let direction = if to_server {
+            Direction::ToServer
+        } else {
+            Direction::ToClient
+        };
+        match dns_parse_framed(
+            stream.data.contiguous(),
+            &DnsVariant::DnsOverQuic,
+            direction,
+        ) {
+            Ok((_, tx)) => {
+                out.push(DoqMessage::Dns(data.stream_id, Box::new(tx)));
+            }
+            Err(DNSParseError::Incomplete) => {
+                return;
+            }
+            Err(_) => {
+                out.push(DoqMessage::Error);
+            }
+        }
+        stream.done = true;
+        stream.data.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(stream_id: u64, offset: u64, data: &str) -> StreamData {
+        StreamData {
+            stream_id,
+            offset,
+            fin: false,
+            data: hex::decode(data).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_doq_query() {
+        let mut state = DoqState::default();
+        let mut out = Vec::new();
+        // query for example.com A, split in two out of order STREAM
+        // frames
+        state.handle_stream(
+            &stream(0, 14, "076578616d706c6503636f6d0000010001"),
+            true,
+            &mut out,
+        );
+        assert!(out.is_empty());
+        state.handle_stream(
+            &stream(0, 0, "001d000001000001000000000000"),
+            true,
+            &mut out,
+        );
+        assert_eq!(out.len(), 1);
+        match &out[0] {
+            DoqMessage::Dns(0, tx) => {
+                assert_eq!(tx.variant, DnsVariant::DnsOverQuic);
+                let request = tx.request.as_ref().unwrap();
+                assert_eq!(request.queries[0].name.value, b"example.com");
+            }
+            m => panic!("unexpected message {:?}", m),
+        }
+        // retransmission
+        state.handle_stream(&stream(0, 0, "001d"), true, &mut out);
+        assert_eq!(out.len(), 1);
+        // not a DNS message
+        state.handle_stream(&stream(4, 0, "0004ffffffff"), true, &mut out);
+        assert!(matches!(out[1], DoqMessage::Error));
+    }
+}
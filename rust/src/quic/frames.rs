@@ -16,13 +16,12 @@
  */
 
 use super::error::QuicError;
-use super::quic::QUIC_MAX_CRYPTO_FRAG_LEN;
 use crate::handshake::HandshakeParams;
 use crate::quic::parser::quic_var_uint;
 use nom7::bytes::complete::take;
 use nom7::combinator::{all_consuming, complete};
 use nom7::multi::{count, many0};
-use nom7::number::complete::{be_u16, be_u32, be_u8, le_u16, le_u32};
+use nom7::number::complete::{be_u16, be_u24, be_u32, be_u8, le_u16, le_u32};
 use nom7::sequence::pair;
 use nom7::IResult;
 use num::FromPrimitive;
@@ -140,12 +139,21 @@ pub(crate) struct Crypto {
     pub extv: Vec<QuicTlsExtension>,
     pub ja3: Option<String>,
     pub hs: Option<HandshakeParams>,
+    pub random: Vec<u8>,
 }
 
 #[derive(Debug, PartialEq)]
 pub(crate) struct CryptoFrag {
     pub offset: u64,
-    pub length: u64,
+    pub data: Vec<u8>,
+}
+
+/// A STREAM frame of an IETF QUIC packet protected by the handshake.
+#[derive(Debug, PartialEq)]
+pub(crate) struct StreamData {
+    pub stream_id: u64,
+    pub offset: u64,
+    pub fin: bool,
     pub data: Vec<u8>,
 }
 
@@ -154,12 +162,16 @@ pub(crate) enum Frame {
     Padding,
     Ping,
     Ack(Ack),
-    // this is more than a crypto frame : it contains a fully parsed tls hello
+    // this is more than a crypto frame : it contains a fully parsed tls hello,
+    // reassembled from the crypto fragments
     Crypto(Crypto),
-    // this is a regular quic crypto frame : they can be reassembled
+    // this is a regular quic crypto frame : they are reassembled
     // in order to parse a tls hello
     CryptoFrag(CryptoFrag),
     Stream(Stream),
+    StreamData(StreamData),
+    // a frame of a decrypted packet that is skipped, with its type
+    Skipped(u64),
     Unknown(Vec<u8>),
 }
 
@@ -316,6 +328,15 @@ fn quic_get_tls_extensions(
     return extv;
 }
 
+/// Rebuild the 32 bytes random of a hello, split by the parser into
+/// its first 4 bytes and the rest.
+fn tls_random(rand_time: u32, rand_data: &[u8]) -> Vec<u8> {
+    let mut random = Vec::with_capacity(32);
+    random.extend_from_slice(&rand_time.to_be_bytes());
+    random.extend_from_slice(rand_data);
+    return random;
+}
+
 fn parse_quic_handshake(msg: TlsMessage) -> Option<Frame> {
     if let Handshake(hs) = msg {
         match hs {
@@ -340,6 +361,7 @@ fn parse_quic_handshake(msg: TlsMessage) -> Option<Frame> {
                 return Some(Frame::Crypto(Crypto {
                     ciphers,
                     extv,
+                    random: tls_random(ch.rand_time, ch.rand_data),
                     ja3: if cfg!(feature = "ja3") {
                         Some(ja3)
                     } else {
@@ -363,6 +385,7 @@ fn parse_quic_handshake(msg: TlsMessage) -> Option<Frame> {
                 return Some(Frame::Crypto(Crypto {
                     ciphers,
                     extv,
+                    random: tls_random(sh.rand_time, sh.rand_data),
                     ja3: if cfg!(feature = "ja3") {
                         Some(ja3)
                     } else {
@@ -377,42 +400,113 @@ fn parse_quic_handshake(msg: TlsMessage) -> Option<Frame> {
     return None;
 }
 
+/// Parse a TLS hello reassembled from crypto fragments. `Ok(None)` means
+/// that more data is needed.
+pub(crate) fn parse_crypto_hello(data: &[u8]) -> Result<Option<Frame>, QuicError> {
+    match parse_tls_message_handshake(data) {
+        Ok((_, msg)) => parse_quic_handshake(msg)
+            .map(Some)
+            .ok_or(QuicError::InvalidPacket),
+        Err(nom7::Err::Incomplete(_)) => Ok(None),
+        Err(_) => Err(QuicError::InvalidPacket),
+    }
+}
+
 fn parse_crypto_frame(input: &[u8]) -> IResult<&[u8], Frame, QuicError> {
     let (rest, offset) = quic_var_uint(input)?;
     let (rest, length) = quic_var_uint(rest)?;
     let (rest, data) = take(length as usize)(rest)?;
+    Ok((
+        rest,
+        Frame::CryptoFrag(CryptoFrag {
+            offset,
+            data: data.to_vec(),
+        }),
+    ))
+}
 
-    if offset > 0 {
-        return Ok((
-            rest,
-            Frame::CryptoFrag(CryptoFrag {
-                offset,
-                length,
-                data: data.to_vec(),
-            }),
-        ));
-    }
-    // if we have offset 0, try quick path : parse directly
-    match parse_tls_message_handshake(data) {
-        Ok((_, msg)) => {
-            if let Some(c) = parse_quic_handshake(msg) {
-                return Ok((rest, c));
-            }
+const TLS_HS_ENCRYPTED_EXTENSIONS: u8 = 8;
+const TLS_HS_CERTIFICATE: u8 = 11;
+const TLS_HS_FINISHED: u8 = 20;
+
+/// What the server sends in the TLS 1.3 handshake messages of the
+/// Handshake packets.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Tls13ServerHandshake {
+    /// protocol chosen by the server, from its EncryptedExtensions
+    pub alpn: Option<Vec<u8>>,
+    /// certificate chain
+    pub certs: Vec<Vec<u8>>,
+}
+
+fn parse_tls13_certificate_entry(input: &[u8]) -> IResult<&[u8], Vec<u8>, QuicError> {
+    let (rest, cert_len) = be_u24(input)?;
+    let (rest, cert) = take(cert_len as usize)(rest)?;
+    let (rest, ext_len) = be_u16(rest)?;
+    let (rest, _extensions) = take(ext_len as usize)(rest)?;
+    Ok((rest, cert.to_vec()))
+}
+
+fn parse_tls13_certificate(input: &[u8]) -> IResult<&[u8], Vec<Vec<u8>>, QuicError> {
+    let (rest, context_len) = be_u8(input)?;
+    let (rest, _context) = take(context_len as usize)(rest)?;
+    let (rest, list_len) = be_u24(rest)?;
+    let (rest, list) = take(list_len as usize)(rest)?;
+    let (_, certs) = all_consuming(many0(complete(parse_tls13_certificate_entry)))(list)?;
+    Ok((rest, certs))
+}
+
+fn parse_tls13_encrypted_extensions(input: &[u8]) -> IResult<&[u8], Option<Vec<u8>>, QuicError> {
+    let (rest, ext_len) = be_u16(input)?;
+    let (rest, ext) = take(ext_len as usize)(rest)?;
+    let alpn = match parse_tls_extensions(ext) {
+        Ok((_, exts)) => exts.iter().find_map(|e| match e {
+            TlsExtension::ALPN(x) => x.first().map(|alpn| alpn.to_vec()),
+            _ => None,
+        }),
+        Err(_) => return Err(nom7::Err::Error(QuicError::InvalidPacket)),
+    };
+    Ok((rest, alpn))
+}
+
+/// Get the chosen ALPN and the certificate chain out of the TLS 1.3
+/// handshake messages sent by the server in Handshake packets,
+/// reassembled from the crypto fragments. `Ok(None)` means that more data
+/// is needed, an empty chain that the handshake has no Certificate message.
+pub(crate) fn parse_tls13_server_handshake(
+    data: &[u8],
+) -> Result<Option<Tls13ServerHandshake>, QuicError> {
+    let mut server = Tls13ServerHandshake::default();
+    let mut rest = data;
+    loop {
+        let (rest1, (msg_type, msg_len)) = match pair(be_u8, be_u24)(rest) {
+            Ok(r) => r,
+            Err(nom7::Err::Incomplete(_)) | Err(nom7::Err::Error(_)) => return Ok(None),
+            Err(nom7::Err::Failure(e)) => return Err(e),
+        };
+        if rest1.len() < msg_len as usize {
+            return Ok(None);
         }
-        Err(nom7::Err::Incomplete(_)) => {
-            // offset 0 but incomplete : save it as a fragment for later reassembly
-            return Ok((
-                rest,
-                Frame::CryptoFrag(CryptoFrag {
-                    offset,
-                    length,
-                    data: data.to_vec(),
-                }),
-            ));
+        let (body, rest2) = rest1.split_at(msg_len as usize);
+        match msg_type {
+            TLS_HS_ENCRYPTED_EXTENSIONS => {
+                let (_, alpn) = all_consuming(parse_tls13_encrypted_extensions)(body)
+                    .map_err(|_| QuicError::InvalidPacket)?;
+                server.alpn = alpn;
+            }
+            TLS_HS_CERTIFICATE => {
+                let (_, certs) = all_consuming(parse_tls13_certificate)(body)
+                    .map_err(|_| QuicError::InvalidPacket)?;
+                server.certs = certs;
+                return Ok(Some(server));
+            }
+            TLS_HS_FINISHED => {
+                return Ok(Some(server));
+            }
+            _ => {}
         }
-        _ => {}
+        rest = rest2;
     }
-    return Err(nom7::Err::Error(QuicError::InvalidPacket));
 }
 
 fn parse_tag(input: &[u8]) -> IResult<&[u8], StreamTag, QuicError> {
@@ -526,7 +620,95 @@ fn parse_crypto_stream_frame(input: &[u8]) -> IResult<&[u8], Frame, QuicError> {
     ))
 }
 
+fn parse_ietf_stream_frame(input: &[u8], frame_ty: u8) -> IResult<&[u8], Frame, QuicError> {
+    // 0b00001_off_len_fin
+    let (rest, stream_id) = quic_var_uint(input)?;
+    let (rest, offset) = if frame_ty & 0x04 != 0 {
+        quic_var_uint(rest)?
+    } else {
+        (rest, 0)
+    };
+    let (rest, data) = if frame_ty & 0x02 != 0 {
+        let (rest, length) = quic_var_uint(rest)?;
+        take(length as usize)(rest)?
+    } else {
+        take(rest.len())(rest)?
+    };
+    Ok((
+        rest,
+        Frame::StreamData(StreamData {
+            stream_id,
+            offset,
+            fin: frame_ty & 0x01 != 0,
+            data: data.to_vec(),
+        }),
+    ))
+}
+
+fn skip_var_uints(input: &[u8], nb: usize) -> IResult<&[u8], (), QuicError> {
+    let mut rest = input;
+    for _ in 0..nb {
+        let (rest1, _) = quic_var_uint(rest)?;
+        rest = rest1;
+    }
+    Ok((rest, ()))
+}
+
+fn skip_var_length(input: &[u8]) -> IResult<&[u8], (), QuicError> {
+    let (rest, length) = quic_var_uint(input)?;
+    let (rest, _) = take(length as usize)(rest)?;
+    Ok((rest, ()))
+}
+
 impl Frame {
+    /// Decode a frame of a Handshake or 1-RTT packet (RFC 9000 section 12.4).
+    fn decode_ietf_frame(input: &[u8]) -> IResult<&[u8], Frame, QuicError> {
+        let (rest, frame_ty) = quic_var_uint(input)?;
+        let rest = match frame_ty {
+            0x00 => return parse_padding_frame(rest),
+            0x01 => return Ok((rest, Frame::Ping)),
+            0x02 => return parse_ack_frame(rest),
+            0x03 => return parse_ack3_frame(rest),
+            0x06 => return parse_crypto_frame(rest),
+            0x08..=0x0f => return parse_ietf_stream_frame(rest, frame_ty as u8),
+            // RESET_STREAM
+            0x04 => skip_var_uints(rest, 3)?.0,
+            // STOP_SENDING, MAX_STREAM_DATA, STREAM_DATA_BLOCKED
+            0x05 | 0x11 | 0x15 => skip_var_uints(rest, 2)?.0,
+            // NEW_TOKEN
+            0x07 => skip_var_length(rest)?.0,
+            // MAX_DATA, MAX_STREAMS, DATA_BLOCKED, STREAMS_BLOCKED, RETIRE_CONNECTION_ID
+            0x10 | 0x12 | 0x13 | 0x14 | 0x16 | 0x17 | 0x19 => skip_var_uints(rest, 1)?.0,
+            // NEW_CONNECTION_ID
+            0x18 => {
+                let (rest, _) = skip_var_uints(rest, 2)?;
+                let (rest, cid_len) = be_u8(rest)?;
+                let (rest, _cid) = take(cid_len as usize)(rest)?;
+                let (rest, _reset_token) = take(16_usize)(rest)?;
+                rest
+            }
+            // PATH_CHALLENGE, PATH_RESPONSE
+            0x1a | 0x1b => take(8_usize)(rest)?.0,
+            // CONNECTION_CLOSE
+            0x1c => {
+                let (rest, _) = skip_var_uints(rest, 2)?;
+                skip_var_length(rest)?.0
+            }
+            0x1d => {
+                let (rest, _) = skip_var_uints(rest, 1)?;
+                skip_var_length(rest)?.0
+            }
+            // HANDSHAKE_DONE
+            0x1e => rest,
+            _ => return Ok(([].as_ref(), Frame::Unknown(rest.to_vec()))),
+        };
+        Ok((rest, Frame::Skipped(frame_ty)))
+    }
+
+    pub(crate) fn decode_ietf_frames(input: &[u8]) -> IResult<&[u8], Vec<Frame>, QuicError> {
+        all_consuming(many0(complete(Frame::decode_ietf_frame)))(input)
+    }
+
     fn decode_frame(input: &[u8]) -> IResult<&[u8], Frame, QuicError> {
         let (rest, frame_ty) = be_u8(input)?;
 
@@ -549,71 +731,79 @@ impl Frame {
         Ok((rest, value))
     }
 
-    pub(crate) fn decode_frames<'a>(
-        input: &'a [u8], past_frag: &'a [u8], past_fraglen: u32,
-    ) -> IResult<&'a [u8], Vec<Frame>, QuicError> {
-        let (rest, mut frames) = all_consuming(many0(complete(Frame::decode_frame)))(input)?;
-
-        // we use the already seen past fragment data
-        let mut crypto_max_size = past_frag.len() as u64;
-        let mut crypto_total_size = 0;
-        // reassemble crypto fragments : first find total size
-        for f in &frames {
-            if let Frame::CryptoFrag(c) = f {
-                if crypto_max_size < c.offset + c.length {
-                    crypto_max_size = c.offset + c.length;
-                }
-                crypto_total_size += c.length;
-            }
-        }
-        if crypto_max_size > 0 && crypto_max_size < QUIC_MAX_CRYPTO_FRAG_LEN {
-            // we have some, and no gaps from offset 0
-            let mut d = vec![0; crypto_max_size as usize];
-            d[..past_frag.len()].clone_from_slice(past_frag);
-            for f in &frames {
-                if let Frame::CryptoFrag(c) = f {
-                    d[c.offset as usize..(c.offset + c.length) as usize].clone_from_slice(&c.data);
-                }
-            }
-            // check that we have enough data, some new data, and data for the first byte
-            if crypto_total_size + past_fraglen as u64 >= crypto_max_size && crypto_total_size > 0 {
-                match parse_tls_message_handshake(&d) {
-                    Ok((_, msg)) => {
-                        if let Some(c) = parse_quic_handshake(msg) {
-                            // add a parsed crypto frame
-                            frames.push(c);
-                        }
-                    }
-                    Err(nom7::Err::Incomplete(_)) => {
-                        // this means the current packet does not have all the hanshake data yet
-                        let frag = CryptoFrag {
-                            offset: crypto_total_size + past_fraglen as u64,
-                            length: d.len() as u64,
-                            data: d.to_vec(),
-                        };
-                        frames.push(Frame::CryptoFrag(frag));
-                    }
-                    _ => {}
-                }
-            } else {
-                // pass in offset the number of bytes set in data
-                let frag = CryptoFrag {
-                    offset: crypto_total_size + past_fraglen as u64,
-                    length: d.len() as u64,
-                    data: d.to_vec(),
-                };
-                frames.push(Frame::CryptoFrag(frag));
-            }
-        } else if crypto_max_size >= QUIC_MAX_CRYPTO_FRAG_LEN {
-            // just notice the engine that we have a big crypto fragment without supplying data
-            let frag = CryptoFrag {
-                offset: 0,
-                length: crypto_max_size,
-                data: Vec::new(),
-            };
-            frames.push(Frame::CryptoFrag(frag));
-        }
+    pub(crate) fn decode_frames(input: &[u8]) -> IResult<&[u8], Vec<Frame>, QuicError> {
+        all_consuming(many0(complete(Frame::decode_frame)))(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_ietf_frames() {
+        let buf = hex::decode(concat!(
+            // MAX_DATA
+            "104400",
+            // NEW_CONNECTION_ID
+            "18010002aabb00000000000000000000000000000000",
+            // STREAM with offset, length and fin
+            "0f040203616263",
+            // STREAM without length
+            "08006465",
+        ))
+        .unwrap();
+        let (_, frames) = Frame::decode_ietf_frames(&buf).unwrap();
+        assert_eq!(
+            frames,
+            vec![
+                Frame::Skipped(0x10),
+                Frame::Skipped(0x18),
+                Frame::StreamData(StreamData {
+                    stream_id: 4,
+                    offset: 2,
+                    fin: true,
+                    data: b"abc".to_vec(),
+                }),
+                Frame::StreamData(StreamData {
+                    stream_id: 0,
+                    offset: 0,
+                    fin: false,
+                    data: b"de".to_vec(),
+                }),
+            ]
+        );
+    }
 
-        Ok((rest, frames))
+    #[test]
+    fn test_parse_tls13_server_handshake() {
+        let buf = hex::decode(concat!(
+            // EncryptedExtensions with ALPN h3
+            "080000",
+            "0b0009",
+            "0010000500030268",
+            "33",
+            // Certificate
+            "0b0000120000000e",
+            "00000230000000",
+            "00000230010000",
+        ))
+        .unwrap();
+        assert_eq!(parse_tls13_server_handshake(&buf[..12]), Ok(None));
+        assert_eq!(
+            parse_tls13_server_handshake(&buf),
+            Ok(Some(Tls13ServerHandshake {
+                alpn: Some(b"h3".to_vec()),
+                certs: vec![vec![0x30, 0x00], vec![0x30, 0x01]],
+            }))
+        );
+        // Finished without Certificate
+        let buf: &[u8] = &[
+            0x08, 0x00, 0x00, 0x02, 0x00, 0x00, 0x14, 0x00, 0x00, 0x01, 0x00,
+        ];
+        assert_eq!(
+            parse_tls13_server_handshake(buf),
+            Ok(Some(Tls13ServerHandshake::default()))
+        );
     }
 }
@@ -0,0 +1,504 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! HTTP/3 (RFC 9114) over the STREAM frames of the 1-RTT packets
+//! decrypted with the key log.
+
+use super::frames::StreamData;
+use super::parser::quic_var_uint;
+use super::qpack::QpackDecoder;
+use crate::http2::http2::{
+    HTTP2Frame, HTTP2FrameTypeData, HTTP2Transaction, HTTP2TransactionState,
+};
+use crate::http2::parser::{
+    HTTP2FrameHeader, HTTP2FrameHeaderBlock, HTTP2FrameHeaders, HTTP2FrameType,
+};
+use std::collections::HashMap;
+
+const H3_FRAME_HEADERS: u64 = 0x01;
+const H3_FRAME_SETTINGS: u64 = 0x04;
+
+const H3_STREAM_CONTROL: u64 = 0x00;
+const H3_STREAM_QPACK_ENCODER: u64 = 0x02;
+
+const H3_SETTINGS_QPACK_MAX_TABLE_CAPACITY: u64 = 0x01;
+
+/// Maximum size of a HEADERS or SETTINGS frame.
+const H3_MAX_FRAME_LEN: u64 = 65536;
+/// Maximum size of the data buffered for a stream, after a hole or in
+/// an incomplete frame.
+const H3_MAX_STREAM_BUFFER: usize = 131072;
+/// Maximum number of streams followed in each direction.
+const H3_MAX_STREAMS: usize = 1024;
+/// Maximum number of field sections waiting for the encoder stream.
+const H3_MAX_BLOCKED_SECTIONS: usize = 16;
+
+/// HTTP/3 request of a QUIC transaction, with its headers kept as
+/// HTTP/2 frames.
+#[derive(Debug)]
+pub struct Http3Transaction {
+    pub stream_id: u64,
+    pub tx: HTTP2Transaction,
+    pub fin_ts: bool,
+    pub fin_tc: bool,
+}
+
+impl Http3Transaction {
+    pub(super) fn new(stream_id: u64) -> Self {
+        Self {
+            stream_id,
+            tx: HTTP2Transaction::new(),
+            fin_ts: false,
+            fin_tc: false,
+        }
+    }
+
+    pub(super) fn add_headers(&mut self, blocks: Vec<HTTP2FrameHeaderBlock>, to_server: bool) {
+        let frame = HTTP2Frame {
+            header: HTTP2FrameHeader {
+                length: 0,
+                ftype: HTTP2FrameType::Headers as u8,
+                flags: 0,
+                reserved: 0,
+                stream_id: 0,
+            },
+            data: HTTP2FrameTypeData::HEADERS(HTTP2FrameHeaders {
+                padlength: None,
+                priority: None,
+                blocks,
+            }),
+        };
+        if to_server {
+            self.tx.frames_ts.push(frame);
+        } else {
+            self.tx.frames_tc.push(frame);
+        }
+    }
+
+    /// Progress of the request, with the states of a HTTP/2 stream for
+    /// the HTTP/2 detection engines.
+    pub(super) fn progress(&self) -> HTTP2TransactionState {
+        if self.fin_ts && self.fin_tc {
+            HTTP2TransactionState::HTTP2StateClosed
+        } else if !self.tx.frames_tc.is_empty() {
+            HTTP2TransactionState::HTTP2StateDataServer
+        } else if self.fin_ts {
+            HTTP2TransactionState::HTTP2StateHalfClosedClient
+        } else if !self.tx.frames_ts.is_empty() {
+            HTTP2TransactionState::HTTP2StateDataClient
+        } else {
+            HTTP2TransactionState::HTTP2StateOpen
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(super) enum Http3Message {
+    /// decoded field section of a request stream
+    Headers(u64, Vec<HTTP2FrameHeaderBlock>),
+    /// end of a request stream
+    Fin(u64),
+    /// malformed HTTP/3 data, or beyond the limits
+    Error,
+}
+
+#[derive(Debug, Default)]
+struct Http3Stream {
+    /// stream offset of the data not received yet
+    offset: u64,
+    /// data received up to `offset` and not parsed yet
+    buf: Vec<u8>,
+    /// data received after a hole, with its stream offset
+    pending: Vec<(u64, Vec<u8>)>,
+    /// stream offset of the end of the stream
+    fin: Option<u64>,
+    /// type of a unidirectional stream, once read
+    kind: Option<u64>,
+    /// bytes of a frame payload left to skip
+    skip: u64,
+    /// parsing stopped after an error
+    failed: bool,
+}
+
+impl Http3Stream {
+    /// Add the data of a STREAM frame. Returns false if too much data is
+    /// buffered.
+    fn insert(&mut self, offset: u64, data: &[u8], fin: bool) -> bool {
+        let end = offset + data.len() as u64;
+        if fin {
+            self.fin = Some(end);
+        }
+        if end <= self.offset {
+            // retransmission
+            return true;
+        }
+        if offset > self.offset {
+            self.pending.push((offset, data.to_vec()));
+        } else {
+            self.buf
+                .extend_from_slice(&data[(self.offset - offset) as usize..]);
+            self.offset = end;
+            while let Some(idx) = self.pending.iter().position(|(o, _)| *o <= self.offset) {
+                let (o, d) = self.pending.swap_remove(idx);
+                let dend = o + d.len() as u64;
+                if dend > self.offset {
+                    self.buf.extend_from_slice(&d[(self.offset - o) as usize..]);
+                    self.offset = dend;
+                }
+            }
+        }
+        let pending: usize = self.pending.iter().map(|(_, d)| d.len()).sum();
+        return self.buf.len() + pending <= H3_MAX_STREAM_BUFFER;
+    }
+
+    fn finished(&self) -> bool {
+        self.fin == Some(self.offset) && self.pending.is_empty()
+    }
+
+    /// Skip the rest of a frame payload being skipped.
+    fn consume_skip(&mut self) {
+        let n = std::cmp::min(self.skip, self.buf.len() as u64);
+        self.buf.drain(..n as usize);
+        self.skip -= n;
+    }
+
+    /// Get the next frame to handle out of the buffer, skipping the
+    /// payload of the frames not in `wanted`.
+    fn next_frame(&mut self, wanted: &[u64]) -> Result<Option<(u64, Vec<u8>)>, ()> {
+        loop {
+            self.consume_skip();
+            if self.skip > 0 {
+                return Ok(None);
+            }
+            let (rem, ftype) = match h3_var_uint(&self.buf) {
+                Some(r) => r,
+                None => return Ok(None),
+            };
+            let (rem, len) = match h3_var_uint(rem) {
+                Some(r) => r,
+                None => return Ok(None),
+            };
+            let hlen = self.buf.len() - rem.len();
+            if !wanted.contains(&ftype) {
+                self.buf.drain(..hlen);
+                self.skip = len;
+                continue;
+            }
+            if len > H3_MAX_FRAME_LEN {
+                return Err(());
+            }
+            if rem.len() < len as usize {
+                return Ok(None);
+            }
+            let payload = rem[..len as usize].to_vec();
+            self.buf.drain(..hlen + len as usize);
+            return Ok(Some((ftype, payload)));
+        }
+    }
+}
+
+/// Parse a QUIC variable-length integer, or None if incomplete.
+fn h3_var_uint(input: &[u8]) -> Option<(&[u8], u64)> {
+    let first = *input.first()?;
+    if input.len() < 1 << (first >> 6) {
+        return None;
+    }
+    quic_var_uint(input).ok()
+}
+
+/// Get the QPACK maximum table capacity out of a SETTINGS frame.
+fn h3_settings_max_table_capacity(mut input: &[u8]) -> Result<u64, ()> {
+    let mut capacity = 0;
+    while !input.is_empty() {
+        let (rem, id) = h3_var_uint(input).ok_or(())?;
+        let (rem, value) = h3_var_uint(rem).ok_or(())?;
+        if id == H3_SETTINGS_QPACK_MAX_TABLE_CAPACITY {
+            capacity = value;
+        }
+        input = rem;
+    }
+    return Ok(capacity);
+}
+
+#[derive(Debug, Default)]
+struct Http3Direction {
+    streams: HashMap<u64, Http3Stream>,
+    qpack: QpackDecoder,
+    /// field sections waiting for the encoder stream, by stream id
+    blocked: Vec<(u64, Vec<u8>)>,
+    /// QPACK maximum table capacity from the SETTINGS, for the peer
+    settings_capacity: Option<u64>,
+}
+
+impl Http3Direction {
+    fn get_stream(&mut self, stream_id: u64) -> Option<&mut Http3Stream> {
+        if !self.streams.contains_key(&stream_id) && self.streams.len() >= H3_MAX_STREAMS {
+            self.streams.retain(|_, s| !s.finished());
+            if self.streams.len() >= H3_MAX_STREAMS {
+                return None;
+            }
+        }
+        Some(self.streams.entry(stream_id).or_default())
+    }
+
+    /// Decode the field sections blocked until now, after instructions on
+    /// the encoder stream.
+    fn unblock(&mut self, out: &mut Vec<Http3Message>) {
+        let mut idx = 0;
+        while idx < self.blocked.len() {
+            let (stream_id, section) = &self.blocked[idx];
+            match self.qpack.decode_field_section(section) {
+                Some(blocks) => {
+                    let stream_id = *stream_id;
+                    self.blocked.remove(idx);
+                    out.push(Http3Message::Headers(stream_id, blocks));
+                    if self.streams.get(&stream_id).map_or(false, |s| s.finished())
+                        && !self.blocked.iter().any(|(id, _)| *id == stream_id)
+                    {
+                        out.push(Http3Message::Fin(stream_id));
+                    }
+                }
+                None => {
+                    idx += 1;
+                }
+            }
+        }
+    }
+
+    fn parse_request_stream(&mut self, stream_id: u64, out: &mut Vec<Http3Message>) {
+        let Http3Direction {
+            streams,
+            qpack,
+            blocked,
+            ..
+        } = self;
+        let stream = match streams.get_mut(&stream_id) {
+            Some(stream) => stream,
+            None => return,
+        };
+        loop {
+            match stream.next_frame(&[H3_FRAME_HEADERS]) {
+                Ok(Some((_, payload))) => match qpack.decode_field_section(&payload) {
+                    Some(blocks) => {
+                        out.push(Http3Message::Headers(stream_id, blocks));
+                    }
+                    None => {
+                        if blocked.len() >= H3_MAX_BLOCKED_SECTIONS {
+                            out.push(Http3Message::Error);
+                        } else {
+                            blocked.push((stream_id, payload));
+                        }
+                    }
+                },
+                Ok(None) => break,
+                Err(()) => {
+                    stream.failed = true;
+                    out.push(Http3Message::Error);
+                    break;
+                }
+            }
+        }
+        if stream.finished() && !blocked.iter().any(|(id, _)| *id == stream_id) {
+            out.push(Http3Message::Fin(stream_id));
+        }
+    }
+
+    fn parse_uni_stream(&mut self, stream_id: u64, out: &mut Vec<Http3Message>) {
+        let stream = match self.streams.get_mut(&stream_id) {
+            Some(stream) => stream,
+            None => return,
+        };
+        let kind = match stream.kind {
+            Some(kind) => kind,
+            None => match h3_var_uint(&stream.buf) {
+                Some((rem, kind)) => {
+                    let hlen = stream.buf.len() - rem.len();
+                    stream.buf.drain(..hlen);
+                    stream.kind = Some(kind);
+                    kind
+                }
+                None => return,
+            },
+        };
+        match kind {
+            H3_STREAM_CONTROL => loop {
+                match stream.next_frame(&[H3_FRAME_SETTINGS]) {
+                    Ok(Some((_, payload))) => match h3_settings_max_table_capacity(&payload) {
+                        Ok(capacity) => {
+                            self.settings_capacity = Some(capacity);
+                        }
+                        Err(()) => {
+                            out.push(Http3Message::Error);
+                        }
+                    },
+                    Ok(None) => break,
+                    Err(()) => {
+                        stream.failed = true;
+                        out.push(Http3Message::Error);
+                        break;
+                    }
+                }
+            },
+            H3_STREAM_QPACK_ENCODER => match self.qpack.parse_encoder_stream(&stream.buf) {
+                Ok(used) => {
+                    stream.buf.drain(..used);
+                    self.unblock(out);
+                }
+                Err(()) => {
+                    stream.failed = true;
+                    stream.buf.clear();
+                    out.push(Http3Message::Error);
+                }
+            },
+            _ => {
+                // push, decoder or unknown stream
+                stream.buf.clear();
+            }
+        }
+    }
+}
+
+/// HTTP/3 state of a connection.
+#[derive(Debug, Default)]
+pub(super) struct Http3State {
+    ts: Http3Direction,
+    tc: Http3Direction,
+}
+
+impl Http3State {
+    /// Handle the data of a STREAM frame.
+    pub(super) fn handle_stream(
+        &mut self, data: &StreamData, to_server: bool, out: &mut Vec<Http3Message>,
+    ) {
+        let dir = if to_server {
+            &mut self.ts
+        } else {
+            &mut self.tc
+        };
+        let stream = match dir.get_stream(data.stream_id) {
+            Some(stream) => stream,
+            None => {
+                out.push(Http3Message::Error);
+                return;
+            }
+        };
+        if stream.failed {
+            return;
+        }
+        if !stream.insert(data.offset, &data.data, data.fin) {
+            stream.failed = true;
+            stream.buf.clear();
+            stream.pending.clear();
+            out.push(Http3Message::Error);
+            return;
+        }
+        match data.stream_id & 0x3 {
+            // client-initiated bidirectional
+            0 => dir.parse_request_stream(data.stream_id, out),
+            // unidirectional
+            2 | 3 => dir.parse_uni_stream(data.stream_id, out),
+            _ => {}
+        }
+        // the SETTINGS of one side give the table capacity of the encoder
+        // of the other side
+        if let Some(capacity) = dir.settings_capacity.take() {
+            let other = if to_server {
+                &mut self.tc
+            } else {
+                &mut self.ts
+            };
+            other.qpack.set_max_capacity(capacity);
+            other.unblock(out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(stream_id: u64, offset: u64, data: &str, fin: bool) -> StreamData {
+        StreamData {
+            stream_id,
+            offset,
+            fin,
+            data: hex::decode(data).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_http3_request() {
+        let mut state = Http3State::default();
+        let mut out = Vec::new();
+        // HEADERS with :path /index.html, split in two out of order
+        // STREAM frames, then a DATA frame
+        state.handle_stream(
+            &stream(0, 6, "2f696e6465782e68746d6c", false),
+            true,
+            &mut out,
+        );
+        assert!(out.is_empty());
+        state.handle_stream(&stream(0, 0, "010f0000510b", false), true, &mut out);
+        state.handle_stream(&stream(0, 17, "0003616263", true), true, &mut out);
+        assert_eq!(out.len(), 2);
+        match &out[0] {
+            Http3Message::Headers(0, blocks) => {
+                assert_eq!(blocks.len(), 1);
+                assert_eq!(blocks[0].name.as_slice(), b":path");
+                assert_eq!(blocks[0].value.as_slice(), b"/index.html");
+            }
+            m => panic!("unexpected message {:?}", m),
+        }
+        assert!(matches!(out[1], Http3Message::Fin(0)));
+    }
+
+    #[test]
+    fn test_http3_dynamic_table() {
+        let mut state = Http3State::default();
+        let mut out = Vec::new();
+        // HEADERS referring to entries of the dynamic table
+        state.handle_stream(&stream(0, 0, "010403811011", true), true, &mut out);
+        assert!(out.is_empty());
+        // server control stream, SETTINGS with a table capacity of 220
+        state.handle_stream(&stream(3, 0, "0004030140dc", false), false, &mut out);
+        // client encoder stream
+        state.handle_stream(
+            &stream(
+                2,
+                0,
+                concat!(
+                    "02",
+                    "3fbd01",
+                    "c00f7777772e6578616d706c652e636f6d",
+                    "c10c2f73616d706c652f70617468",
+                ),
+                false,
+            ),
+            true,
+            &mut out,
+        );
+        assert_eq!(out.len(), 2);
+        match &out[0] {
+            Http3Message::Headers(0, blocks) => {
+                assert_eq!(blocks.len(), 2);
+                assert_eq!(blocks[1].value.as_slice(), b"/sample/path");
+            }
+            m => panic!("unexpected message {:?}", m),
+        }
+        assert!(matches!(out[1], Http3Message::Fin(0)));
+    }
+}
@@ -0,0 +1,101 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! NSS key log file (SSLKEYLOGFILE) support, giving the TLS 1.3 traffic
+//! secrets needed to decrypt the Handshake and 1-RTT packets.
+
+use std::collections::HashMap;
+
+/// Traffic secrets of one connection, found by the client random.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct QuicKeyLogSecrets {
+    pub client_handshake: Option<Vec<u8>>,
+    pub server_handshake: Option<Vec<u8>>,
+    pub client_traffic: Option<Vec<u8>>,
+    pub server_traffic: Option<Vec<u8>>,
+}
+
+static mut QUIC_KEYLOG: Option<HashMap<Vec<u8>, QuicKeyLogSecrets>> = None;
+
+fn quic_keylog_parse(content: &str) -> HashMap<Vec<u8>, QuicKeyLogSecrets> {
+    let mut secrets: HashMap<Vec<u8>, QuicKeyLogSecrets> = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_ascii_whitespace();
+        let (label, random, secret) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(label), Some(random), Some(secret)) => (label, random, secret),
+            _ => continue,
+        };
+        let (random, secret) = match (hex::decode(random), hex::decode(secret)) {
+            (Ok(random), Ok(secret)) => (random, secret),
+            _ => continue,
+        };
+        let entry = secrets.entry(random).or_default();
+        match label {
+            "CLIENT_HANDSHAKE_TRAFFIC_SECRET" => entry.client_handshake = Some(secret),
+            "SERVER_HANDSHAKE_TRAFFIC_SECRET" => entry.server_handshake = Some(secret),
+            "CLIENT_TRAFFIC_SECRET_0" => entry.client_traffic = Some(secret),
+            "SERVER_TRAFFIC_SECRET_0" => entry.server_traffic = Some(secret),
+            _ => {}
+        }
+    }
+    secrets.retain(|_, s| *s != QuicKeyLogSecrets::default());
+    return secrets;
+}
+
+/// Load the key log file. Returns the number of connections it has
+/// secrets for.
+pub(super) fn quic_keylog_load(path: &str) -> Result<usize, std::io::Error> {
+    let content = std::fs::read_to_string(path)?;
+    let secrets = quic_keylog_parse(&content);
+    let count = secrets.len();
+    unsafe {
+        QUIC_KEYLOG = Some(secrets);
+    }
+    Ok(count)
+}
+
+#[allow(static_mut_refs)]
+pub(super) fn quic_keylog_get(client_random: &[u8]) -> Option<&'static QuicKeyLogSecrets> {
+    unsafe { QUIC_KEYLOG.as_ref()?.get(client_random) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quic_keylog_parse() {
+        let content = "# SSL/TLS secrets log file\n\
+            CLIENT_HANDSHAKE_TRAFFIC_SECRET 0102 aabb\n\
+            SERVER_HANDSHAKE_TRAFFIC_SECRET 0102 ccdd\n\
+            CLIENT_TRAFFIC_SECRET_0 0102 eeff\n\
+            CLIENT_RANDOM 0304 0011\n\
+            SERVER_TRAFFIC_SECRET_0 0102 zz\n\
+            EXPORTER_SECRET 0506\n";
+        let secrets = quic_keylog_parse(content);
+        assert_eq!(secrets.len(), 1);
+        let s = &secrets[&vec![1, 2]];
+        assert_eq!(s.client_handshake, Some(vec![0xaa, 0xbb]));
+        assert_eq!(s.server_handshake, Some(vec![0xcc, 0xdd]));
+        assert_eq!(s.client_traffic, Some(vec![0xee, 0xff]));
+        assert_eq!(s.server_traffic, None);
+    }
+}
@@ -17,10 +17,16 @@
 
 use super::parser::QuicType;
 use super::quic::QuicTransaction;
+use crate::dns::log::dns_log_json_inner;
+use crate::http2::logger::log_http_headers;
 use crate::jsonbuilder::{JsonBuilder, JsonError};
 use digest::Digest;
 use digest::Update;
 use md5::Md5;
+use sha1::Sha1;
+use sha2::Sha256;
+use tls_parser::TlsCipherSuite;
+use x509_parser::prelude::{FromDer, X509Certificate};
 
 fn quic_tls_extension_name(e: u16) -> Option<String> {
     match e {
@@ -88,6 +94,30 @@ fn quic_tls_extension_name(e: u16) -> Option<String> {
     }
 }
 
+fn log_quic_certificate(der: &[u8], js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.start_object()?;
+    if let Ok((_, cert)) = X509Certificate::from_der(der) {
+        js.set_string("subject", &cert.tbs_certificate.subject.to_string())?;
+        js.set_string("issuerdn", &cert.tbs_certificate.issuer.to_string())?;
+        let serial: Vec<String> = cert
+            .tbs_certificate
+            .raw_serial()
+            .iter()
+            .map(|x| format!("{:02X}", x))
+            .collect();
+        js.set_string("serial", &serial.join(":"))?;
+    }
+    let fingerprint: Vec<String> = Sha1::new()
+        .chain(der)
+        .finalize()
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect();
+    js.set_string("fingerprint", &fingerprint.join(":"))?;
+    js.close()?;
+    Ok(())
+}
+
 fn log_quic(tx: &QuicTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
     js.open_object("quic")?;
     if tx.header.ty != QuicType::Short {
@@ -149,7 +179,75 @@ fn log_quic(tx: &QuicTransaction, js: &mut JsonBuilder) -> Result<(), JsonError>
         js.close()?;
     }
 
+    if !tx.certs.is_empty() {
+        js.open_array("certificates")?;
+        for cert in &tx.certs {
+            log_quic_certificate(cert, js)?;
+        }
+        js.close()?;
+    }
+
+    if !tx.versions.is_empty() {
+        js.open_array("versions")?;
+        for version in &tx.versions {
+            js.append_string(&String::from(*version))?;
+        }
+        js.close()?;
+    }
+
+    if let Some(token) = &tx.retry_token {
+        js.open_object("retry_token")?;
+        js.set_uint("length", token.len() as u64)?;
+        let hash = format!("{:x}", Sha256::new().chain(token).finalize());
+        js.set_string("sha256", &hash)?;
+        js.close()?;
+    }
+
+    if let Some(cipher) = tx.server_cipher {
+        match TlsCipherSuite::from_id(cipher) {
+            Some(suite) => js.set_string("server_cipher", suite.name)?,
+            None => js.set_string("server_cipher", &format!("0x{:04x}", cipher))?,
+        };
+    }
+
+    if let Some(alpn) = &tx.server_alpn {
+        js.set_string("server_alpn", &String::from_utf8_lossy(alpn))?;
+    }
+
+    if tx.dcid_count > 0 {
+        js.set_uint("dcid_count", tx.dcid_count as u64)?;
+    }
+
+    if !tx.streams.is_empty() {
+        js.open_array("streams")?;
+        for stream in &tx.streams {
+            js.start_object()?;
+            js.set_uint("id", stream.stream_id)?;
+            js.set_uint("offset", stream.offset)?;
+            js.set_uint("length", stream.data.len() as u64)?;
+            js.set_bool("fin", stream.fin)?;
+            js.close()?;
+        }
+        js.close()?;
+    }
+
     js.close()?;
+
+    if let Some(h3) = &tx.http3 {
+        js.open_object("http")?;
+        log_http_headers(&h3.tx, "3", js)?;
+        js.open_object("http3")?;
+        js.set_uint("stream_id", h3.stream_id)?;
+        js.close()?;
+        js.close()?;
+    }
+
+    if let Some(doq) = &tx.doq {
+        // log one DNS transaction, preferring the response
+        if let Some(dtx) = doq.response.as_ref().or(doq.request.as_ref()) {
+            dns_log_json_inner(dtx, js)?;
+        }
+    }
     Ok(())
 }
 
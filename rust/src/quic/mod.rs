@@ -20,8 +20,13 @@
 mod crypto;
 mod cyu;
 pub mod detect;
+mod doq;
 mod error;
 mod frames;
+mod http3;
+mod keylog;
 mod logger;
 mod parser;
+mod qpack;
 pub mod quic;
+mod reassembly;
@@ -17,7 +17,8 @@
 use super::error::QuicError;
 use super::frames::Frame;
 use nom7::bytes::complete::take;
-use nom7::combinator::map;
+use nom7::combinator::{all_consuming, complete, map};
+use nom7::multi::many0;
 use nom7::number::complete::{be_u24, be_u32, be_u8};
 use nom7::IResult;
 use std::convert::TryFrom;
@@ -79,7 +80,7 @@ impl From<u32> for QuicVersion {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum QuicType {
     Initial,
     Retry,
@@ -94,7 +95,7 @@ const QUIC_FLAG_DCID_LEN: u8 = 0x8;
 const QUIC_FLAG_NONCE: u8 = 0x4;
 const QUIC_FLAG_VERSION: u8 = 0x1;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PublicFlags {
     pub is_long: bool,
     pub raw: u8,
@@ -162,7 +163,7 @@ pub fn quic_var_uint(input: &[u8]) -> IResult<&[u8], u64, QuicError> {
 }
 
 /// A QUIC packet's header.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct QuicHeader {
     pub flags: PublicFlags,
     pub ty: QuicType,
@@ -358,8 +359,13 @@ impl QuicHeader {
                     }
                 }
                 QuicType::Retry => {
-                    // opaque retry token and 16 bytes retry integrity tag
-                    &rest[rest.len()..]
+                    // opaque retry token and 16 bytes retry integrity tag,
+                    // kept as the payload
+                    rest
+                }
+                QuicType::Handshake | QuicType::ZeroRTT => {
+                    has_length = !version.is_gquic();
+                    rest
                 }
                 _ => rest,
             };
@@ -395,11 +401,26 @@ impl QuicHeader {
     }
 }
 
+/// Length of the integrity tag at the end of a Retry packet.
+const QUIC_RETRY_TAG_LEN: usize = 16;
+
+/// Parse the versions supported by the server, in the payload of a
+/// Version Negotiation packet.
+pub(crate) fn quic_parse_versions(input: &[u8]) -> IResult<&[u8], Vec<QuicVersion>, QuicError> {
+    all_consuming(many0(complete(map(be_u32, QuicVersion))))(input)
+}
+
+/// Get the token out of the payload of a Retry packet.
+pub(crate) fn quic_retry_token(input: &[u8]) -> Option<&[u8]> {
+    if input.len() <= QUIC_RETRY_TAG_LEN {
+        return None;
+    }
+    Some(&input[..input.len() - QUIC_RETRY_TAG_LEN])
+}
+
 impl QuicData {
-    pub(crate) fn from_bytes(
-        input: &[u8], past_frag: &[u8], past_fraglen: u32,
-    ) -> Result<QuicData, QuicError> {
-        let (_, frames) = Frame::decode_frames(input, past_frag, past_fraglen)?;
+    pub(crate) fn from_bytes(input: &[u8]) -> Result<QuicData, QuicError> {
+        let (_, frames) = Frame::decode_frames(input)?;
         Ok(QuicData { frames })
     }
 }
@@ -451,6 +472,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_handshake_length() {
+        // Handshake packet followed by a coalesced packet
+        let buf: &[u8] = &[
+            0xe0, 0x00, 0x00, 0x00, 0x01, 0x01, 0xaa, 0x01, 0xbb, 0x02, 0x11, 0x22, 0x40, 0x33,
+        ];
+        let (rest, header) = QuicHeader::from_bytes(buf, TEST_DEFAULT_CID_LENGTH).unwrap();
+        assert_eq!(header.ty, QuicType::Handshake);
+        assert_eq!(header.scid, vec![0xbb]);
+        assert_eq!(header.length, 2);
+        assert_eq!(rest, &[0x11, 0x22, 0x40, 0x33]);
+    }
+
+    #[test]
+    fn test_parse_version_negotiation() {
+        let buf: &[u8] = &[
+            0x80, 0x00, 0x00, 0x00, 0x00, 0x01, 0xaa, 0x01, 0xbb, 0x00, 0x00, 0x00, 0x01, 0x6b,
+            0x33, 0x43, 0xcf,
+        ];
+        let (rest, header) = QuicHeader::from_bytes(buf, TEST_DEFAULT_CID_LENGTH).unwrap();
+        assert_eq!(header.ty, QuicType::VersionNegotiation);
+        assert_eq!(header.dcid, vec![0xaa]);
+        assert_eq!(header.length, 8);
+        let (_, versions) = quic_parse_versions(rest).unwrap();
+        assert_eq!(versions, vec![QuicVersion(1), QuicVersion::V2]);
+        assert!(quic_parse_versions(&rest[..6]).is_err());
+    }
+
+    #[test]
+    fn test_parse_retry() {
+        let mut buf = vec![0xf0, 0x00, 0x00, 0x00, 0x01, 0x00, 0x02, 0xbb, 0xcc];
+        buf.extend_from_slice(b"token");
+        buf.extend_from_slice(&[0x5a; 16]);
+        let (rest, header) = QuicHeader::from_bytes(&buf, TEST_DEFAULT_CID_LENGTH).unwrap();
+        assert_eq!(header.ty, QuicType::Retry);
+        assert_eq!(header.scid, vec![0xbb, 0xcc]);
+        assert_eq!(quic_retry_token(rest), Some(&b"token"[..]));
+        assert_eq!(quic_retry_token(&rest[5..]), None);
+    }
+
     #[test]
     fn test_parse_gquic_q044() {
         let test_data = hex::decode("ff513034345005cad2cc06c4d0e400000001afac230bc5b56fb89800171b800143484c4f09000000504144008f030000534e490098030000564552009c03000043435300ac03000050444d44b00300004943534cb40300004d494453b803000043464357bc03000053464357c003000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000003132372e302e302e310000000001e8816092921ae87eed8086a215829158353039803a09006400000000c0000000800000").unwrap();
@@ -473,8 +534,7 @@ mod tests {
             header
         );
 
-        let past_frag = Vec::new();
-        let data = QuicData::from_bytes(rest, &past_frag, 0).unwrap();
+        let data = QuicData::from_bytes(rest).unwrap();
         assert_eq!(
             QuicData {
                 frames: vec![Frame::Stream(Stream {
@@ -0,0 +1,548 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! QPACK (RFC 9204) decoding of the HTTP/3 field sections, into the
+//! header blocks of HTTP/2 so that its keywords and logging apply.
+
+use crate::common::nom7::bits;
+use crate::http2::huffman::http2_decode_huffman;
+use crate::http2::parser::{http2_parse_var_uint, HTTP2FrameHeaderBlock, HTTP2HeaderDecodeStatus};
+use nom7::bytes::streaming::take;
+use nom7::error::{make_error, ErrorKind};
+use nom7::multi::many0;
+use nom7::number::streaming::be_u8;
+use nom7::{Err, IResult};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// Maximum capacity of the dynamic table, whatever the peer allows.
+const QPACK_MAX_TABLE_CAPACITY: u64 = 65536;
+/// Overhead of an entry of the dynamic table, RFC 9204 section 3.2.1.
+const QPACK_ENTRY_OVERHEAD: u64 = 32;
+
+/// Static table, RFC 9204 appendix A.
+const QPACK_STATIC_TABLE: [(&str, &str); 99] = [
+    (":authority", ""),
+    (":path", "/"),
+    ("age", "0"),
+    ("content-disposition", ""),
+    ("content-length", "0"),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("referer", ""),
+    ("set-cookie", ""),
+    (":method", "CONNECT"),
+    (":method", "DELETE"),
+    (":method", "GET"),
+    (":method", "HEAD"),
+    (":method", "OPTIONS"),
+    (":method", "POST"),
+    (":method", "PUT"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "103"),
+    (":status", "200"),
+    (":status", "304"),
+    (":status", "404"),
+    (":status", "503"),
+    ("accept", "*/*"),
+    ("accept", "application/dns-message"),
+    ("accept-encoding", "gzip, deflate, br"),
+    ("accept-ranges", "bytes"),
+    ("access-control-allow-headers", "cache-control"),
+    ("access-control-allow-headers", "content-type"),
+    ("access-control-allow-origin", "*"),
+    ("cache-control", "max-age=0"),
+    ("cache-control", "max-age=2592000"),
+    ("cache-control", "max-age=604800"),
+    ("cache-control", "no-cache"),
+    ("cache-control", "no-store"),
+    ("cache-control", "public, max-age=31536000"),
+    ("content-encoding", "br"),
+    ("content-encoding", "gzip"),
+    ("content-type", "application/dns-message"),
+    ("content-type", "application/javascript"),
+    ("content-type", "application/json"),
+    ("content-type", "application/x-www-form-urlencoded"),
+    ("content-type", "image/gif"),
+    ("content-type", "image/jpeg"),
+    ("content-type", "image/png"),
+    ("content-type", "text/css"),
+    ("content-type", "text/html; charset=utf-8"),
+    ("content-type", "text/plain"),
+    ("content-type", "text/plain;charset=utf-8"),
+    ("range", "bytes=0-"),
+    ("strict-transport-security", "max-age=31536000"),
+    (
+        "strict-transport-security",
+        "max-age=31536000; includesubdomains",
+    ),
+    (
+        "strict-transport-security",
+        "max-age=31536000; includesubdomains; preload",
+    ),
+    ("vary", "accept-encoding"),
+    ("vary", "origin"),
+    ("x-content-type-options", "nosniff"),
+    ("x-xss-protection", "1; mode=block"),
+    (":status", "100"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "302"),
+    (":status", "400"),
+    (":status", "403"),
+    (":status", "421"),
+    (":status", "425"),
+    (":status", "500"),
+    ("accept-language", ""),
+    ("access-control-allow-credentials", "FALSE"),
+    ("access-control-allow-credentials", "TRUE"),
+    ("access-control-allow-headers", "*"),
+    ("access-control-allow-methods", "get"),
+    ("access-control-allow-methods", "get, post, options"),
+    ("access-control-allow-methods", "options"),
+    ("access-control-expose-headers", "content-length"),
+    ("access-control-request-headers", "content-type"),
+    ("access-control-request-method", "get"),
+    ("access-control-request-method", "post"),
+    ("alt-svc", "clear"),
+    ("authorization", ""),
+    (
+        "content-security-policy",
+        "script-src 'none'; object-src 'none'; base-uri 'none'",
+    ),
+    ("early-data", "1"),
+    ("expect-ct", ""),
+    ("forwarded", ""),
+    ("if-range", ""),
+    ("origin", ""),
+    ("purpose", "prefetch"),
+    ("server", ""),
+    ("timing-allow-origin", "*"),
+    ("upgrade-insecure-requests", "1"),
+    ("user-agent", ""),
+    ("x-forwarded-for", ""),
+    ("x-frame-options", "deny"),
+    ("x-frame-options", "sameorigin"),
+];
+
+type QpackEntry = (Rc<Vec<u8>>, Rc<Vec<u8>>);
+
+fn qpack_static_entry(index: u64) -> Option<QpackEntry> {
+    let (name, value) = QPACK_STATIC_TABLE.get(usize::try_from(index).ok()?)?;
+    Some((
+        Rc::new(name.as_bytes().to_vec()),
+        Rc::new(value.as_bytes().to_vec()),
+    ))
+}
+
+/// Parse an integer whose first byte has a prefix of `prefix` bits,
+/// RFC 9204 section 4.1.1.
+fn qpack_parse_int(input: &[u8], prefix: u8) -> IResult<&[u8], u64> {
+    let (i, first) = be_u8(input)?;
+    let max = (1u64 << prefix) - 1;
+    http2_parse_var_uint(i, u64::from(first) & max, max)
+}
+
+/// Parse a string literal whose length has a prefix of `prefix` bits,
+/// just after the Huffman flag, RFC 9204 section 4.1.2.
+fn qpack_parse_string(input: &[u8], prefix: u8) -> IResult<&[u8], Vec<u8>> {
+    let huffman = input.first().map_or(false, |&b| b & (1 << prefix) != 0);
+    let (i, len) = qpack_parse_int(input, prefix)?;
+    let (i, data) = take(len)(i)?;
+    if !huffman {
+        return Ok((i, data.to_vec()));
+    }
+    let (_, value) = bits(many0(http2_decode_huffman))(data)?;
+    return Ok((i, value));
+}
+
+fn qpack_header_block(entry: QpackEntry) -> HTTP2FrameHeaderBlock {
+    HTTP2FrameHeaderBlock {
+        name: entry.0,
+        value: entry.1,
+        error: HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeSuccess,
+        sizeupdate: 0,
+    }
+}
+
+fn qpack_error_block(error: HTTP2HeaderDecodeStatus) -> HTTP2FrameHeaderBlock {
+    HTTP2FrameHeaderBlock {
+        name: Rc::new(Vec::new()),
+        value: Rc::new(Vec::new()),
+        error,
+        sizeupdate: 0,
+    }
+}
+
+/// Decoder of the field sections of one direction, with the dynamic
+/// table built from the encoder stream of that direction.
+#[derive(Debug, Default)]
+pub(super) struct QpackDecoder {
+    entries: VecDeque<QpackEntry>,
+    /// sum of the sizes of the entries
+    size: u64,
+    capacity: u64,
+    /// maximum capacity, from the SETTINGS of the peer
+    max_capacity: Option<u64>,
+    /// number of insertions since the start of the connection
+    inserts: u64,
+}
+
+impl QpackDecoder {
+    pub(super) fn set_max_capacity(&mut self, max_capacity: u64) {
+        self.max_capacity = Some(std::cmp::min(max_capacity, QPACK_MAX_TABLE_CAPACITY));
+    }
+
+    fn evict(&mut self, capacity: u64) {
+        while self.size > capacity {
+            match self.entries.pop_front() {
+                Some((name, value)) => {
+                    self.size -= QPACK_ENTRY_OVERHEAD + (name.len() + value.len()) as u64;
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn set_capacity(&mut self, capacity: u64) -> bool {
+        if capacity > self.max_capacity.unwrap_or(0) {
+            return false;
+        }
+        self.evict(capacity);
+        self.capacity = capacity;
+        return true;
+    }
+
+    fn insert(&mut self, entry: QpackEntry) -> bool {
+        let size = QPACK_ENTRY_OVERHEAD + (entry.0.len() + entry.1.len()) as u64;
+        if size > self.capacity {
+            return false;
+        }
+        self.evict(self.capacity - size);
+        self.size += size;
+        self.entries.push_back(entry);
+        self.inserts += 1;
+        return true;
+    }
+
+    /// Get an entry of the dynamic table by its absolute index.
+    fn get(&self, index: u64) -> Option<QpackEntry> {
+        let first = self.inserts - self.entries.len() as u64;
+        if index < first || index >= self.inserts {
+            return None;
+        }
+        let (name, value) = &self.entries[(index - first) as usize];
+        Some((Rc::clone(name), Rc::clone(value)))
+    }
+
+    /// Get an entry of the dynamic table by an index relative to `base`.
+    fn get_relative(&self, base: u64, index: u64) -> Option<QpackEntry> {
+        self.get(base.checked_sub(index.checked_add(1)?)?)
+    }
+
+    /// Get an entry of the dynamic table by a post-base index.
+    fn get_post_base(&self, base: u64, index: u64) -> Option<QpackEntry> {
+        self.get(base.checked_add(index)?)
+    }
+
+    fn parse_encoder_instruction<'a>(&mut self, input: &'a [u8]) -> IResult<&'a [u8], ()> {
+        let first = input[0];
+        let (i, ok) = if first & 0x80 != 0 {
+            // insert with name reference
+            let (i, index) = qpack_parse_int(input, 6)?;
+            let (i, value) = qpack_parse_string(i, 7)?;
+            let entry = if first & 0x40 != 0 {
+                qpack_static_entry(index)
+            } else {
+                self.get_relative(self.inserts, index)
+            };
+            (
+                i,
+                entry.map_or(false, |(name, _)| self.insert((name, Rc::new(value)))),
+            )
+        } else if first & 0x40 != 0 {
+            // insert with literal name
+            let (i, name) = qpack_parse_string(input, 5)?;
+            let (i, value) = qpack_parse_string(i, 7)?;
+            (i, self.insert((Rc::new(name), Rc::new(value))))
+        } else if first & 0x20 != 0 {
+            // set dynamic table capacity
+            let (i, capacity) = qpack_parse_int(input, 5)?;
+            (i, self.set_capacity(capacity))
+        } else {
+            // duplicate
+            let (i, index) = qpack_parse_int(input, 5)?;
+            let entry = self.get_relative(self.inserts, index);
+            (i, entry.map_or(false, |entry| self.insert(entry)))
+        };
+        if !ok {
+            return Err(Err::Error(make_error(input, ErrorKind::Verify)));
+        }
+        return Ok((i, ()));
+    }
+
+    /// Apply the instructions of the encoder stream. Returns the number
+    /// of bytes used, the rest being an incomplete instruction, or an
+    /// error if the instructions are invalid.
+    pub(super) fn parse_encoder_stream(&mut self, input: &[u8]) -> Result<usize, ()> {
+        let mut i = input;
+        while !i.is_empty() {
+            match self.parse_encoder_instruction(i) {
+                Ok((rem, ())) => {
+                    i = rem;
+                }
+                Err(Err::Incomplete(_)) => {
+                    break;
+                }
+                Err(_) => {
+                    return Err(());
+                }
+            }
+        }
+        return Ok(input.len() - i.len());
+    }
+
+    /// Decode the Required Insert Count of a field section prefix,
+    /// RFC 9204 section 4.5.1.1.
+    fn required_insert_count(&self, encoded: u64, max_capacity: u64) -> Option<u64> {
+        if encoded == 0 {
+            return Some(0);
+        }
+        let max_entries = max_capacity / QPACK_ENTRY_OVERHEAD;
+        let full_range = 2 * max_entries;
+        if encoded > full_range {
+            return None;
+        }
+        let max_value = self.inserts + max_entries;
+        let max_wrapped = (max_value / full_range) * full_range;
+        let mut count = max_wrapped + encoded - 1;
+        if count > max_value {
+            if count <= full_range {
+                return None;
+            }
+            count -= full_range;
+        }
+        if count == 0 {
+            return None;
+        }
+        Some(count)
+    }
+
+    fn parse_field_line<'a>(
+        &self, input: &'a [u8], base: u64,
+    ) -> IResult<&'a [u8], HTTP2FrameHeaderBlock> {
+        let first = input[0];
+        let (i, entry) = if first & 0x80 != 0 {
+            // indexed field line
+            let (i, index) = qpack_parse_int(input, 6)?;
+            let entry = if first & 0x40 != 0 {
+                qpack_static_entry(index)
+            } else {
+                self.get_relative(base, index)
+            };
+            (i, entry)
+        } else if first & 0x40 != 0 {
+            // literal field line with name reference
+            let (i, index) = qpack_parse_int(input, 4)?;
+            let (i, value) = qpack_parse_string(i, 7)?;
+            let entry = if first & 0x10 != 0 {
+                qpack_static_entry(index)
+            } else {
+                self.get_relative(base, index)
+            };
+            (i, entry.map(|(name, _)| (name, Rc::new(value))))
+        } else if first & 0x20 != 0 {
+            // literal field line with literal name
+            let (i, name) = qpack_parse_string(input, 3)?;
+            let (i, value) = qpack_parse_string(i, 7)?;
+            (i, Some((Rc::new(name), Rc::new(value))))
+        } else if first & 0x10 != 0 {
+            // indexed field line with post-base index
+            let (i, index) = qpack_parse_int(input, 4)?;
+            (i, self.get_post_base(base, index))
+        } else {
+            // literal field line with post-base name reference
+            let (i, index) = qpack_parse_int(input, 3)?;
+            let (i, value) = qpack_parse_string(i, 7)?;
+            let entry = self.get_post_base(base, index);
+            (i, entry.map(|(name, _)| (name, Rc::new(value))))
+        };
+        match entry {
+            Some(entry) => Ok((i, qpack_header_block(entry))),
+            None => Err(Err::Error(make_error(i, ErrorKind::MapOpt))),
+        }
+    }
+
+    /// Decode a field section, RFC 9204 section 4.5. Returns None if it
+    /// refers to dynamic table entries not received yet on the encoder
+    /// stream, or before the SETTINGS of the peer. The headers decoded
+    /// before an error are kept, followed by a header block with the
+    /// error.
+    pub(super) fn decode_field_section(&self, input: &[u8]) -> Option<Vec<HTTP2FrameHeaderBlock>> {
+        let mut blocks = Vec::new();
+        let prefix = qpack_parse_int(input, 8).and_then(|(i, encoded)| {
+            let negative = i.first().map_or(false, |&b| b & 0x80 != 0);
+            let (i, delta) = qpack_parse_int(i, 7)?;
+            Ok((i, (encoded, negative, delta)))
+        });
+        let (mut i, (count, base)) = match prefix {
+            Ok((i, (encoded, negative, delta))) => {
+                let max_capacity = match self.max_capacity {
+                    Some(max_capacity) => max_capacity,
+                    None if encoded != 0 => return None,
+                    None => 0,
+                };
+                let count = self.required_insert_count(encoded, max_capacity);
+                let base = count.and_then(|count| {
+                    if negative {
+                        count.checked_sub(delta.checked_add(1)?)
+                    } else {
+                        count.checked_add(delta)
+                    }
+                });
+                match (count, base) {
+                    (Some(count), Some(base)) => (i, (count, base)),
+                    _ => {
+                        blocks.push(qpack_error_block(
+                            HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeError,
+                        ));
+                        return Some(blocks);
+                    }
+                }
+            }
+            Err(_) => {
+                blocks.push(qpack_error_block(
+                    HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeError,
+                ));
+                return Some(blocks);
+            }
+        };
+        if count > self.inserts {
+            return None;
+        }
+        while !i.is_empty() {
+            match self.parse_field_line(i, base) {
+                Ok((rem, block)) => {
+                    blocks.push(block);
+                    i = rem;
+                }
+                Err(Err::Error(err)) if err.code == ErrorKind::MapOpt => {
+                    blocks.push(qpack_error_block(
+                        HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeNotIndexed,
+                    ));
+                    break;
+                }
+                Err(Err::Error(err)) if err.code == ErrorKind::LengthValue => {
+                    blocks.push(qpack_error_block(
+                        HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeIntegerOverflow,
+                    ));
+                    break;
+                }
+                Err(_) => {
+                    blocks.push(qpack_error_block(
+                        HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeError,
+                    ));
+                    break;
+                }
+            }
+        }
+        return Some(blocks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(blocks: &[HTTP2FrameHeaderBlock]) -> Vec<(&[u8], &[u8])> {
+        blocks
+            .iter()
+            .map(|b| (b.name.as_slice(), b.value.as_slice()))
+            .collect()
+    }
+
+    #[test]
+    fn test_qpack_static() {
+        // RFC 9204 appendix B.1
+        let section = hex::decode("0000510b2f696e6465782e68746d6c").unwrap();
+        let decoder = QpackDecoder::default();
+        let blocks = decoder.decode_field_section(&section).unwrap();
+        assert_eq!(headers(&blocks), vec![(&b":path"[..], &b"/index.html"[..])]);
+    }
+
+    #[test]
+    fn test_qpack_dynamic() {
+        let mut decoder = QpackDecoder::default();
+        decoder.set_max_capacity(220);
+        // RFC 9204 appendix B.2, referring to entries not inserted yet
+        let section = hex::decode("03811011").unwrap();
+        assert!(decoder.decode_field_section(&section).is_none());
+        let encoder = hex::decode(concat!(
+            "3fbd01",
+            "c00f7777772e6578616d706c652e636f6d",
+            "c10c2f73616d706c652f70617468",
+        ))
+        .unwrap();
+        // incomplete instruction
+        assert_eq!(decoder.parse_encoder_stream(&encoder[..10]), Ok(3));
+        assert_eq!(
+            decoder.parse_encoder_stream(&encoder[3..]),
+            Ok(encoder.len() - 3)
+        );
+        let blocks = decoder.decode_field_section(&section).unwrap();
+        assert_eq!(
+            headers(&blocks),
+            vec![
+                (&b":authority"[..], &b"www.example.com"[..]),
+                (&b":path"[..], &b"/sample/path"[..]),
+            ]
+        );
+        // RFC 9204 appendix B.3, insert with literal name and duplicate
+        let encoder = hex::decode(concat!(
+            "4a637573746f6d2d6b65790c637573746f6d2d76616c7565",
+            "02",
+        ))
+        .unwrap();
+        assert_eq!(decoder.parse_encoder_stream(&encoder), Ok(encoder.len()));
+        let section = hex::decode("050080c181").unwrap();
+        let blocks = decoder.decode_field_section(&section).unwrap();
+        assert_eq!(
+            headers(&blocks),
+            vec![
+                (&b":authority"[..], &b"www.example.com"[..]),
+                (&b":path"[..], &b"/"[..]),
+                (&b"custom-key"[..], &b"custom-value"[..]),
+            ]
+        );
+        // capacity beyond the maximum
+        assert_eq!(decoder.parse_encoder_stream(&[0x3f, 0xbe, 0x01]), Err(()));
+        // unknown static index
+        let section = hex::decode("0000ff24").unwrap();
+        let blocks = decoder.decode_field_section(&section).unwrap();
+        assert_eq!(
+            blocks[0].error,
+            HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeNotIndexed
+        );
+    }
+}
@@ -16,15 +16,28 @@
  */
 
 use super::{
-    crypto::{quic_keys_initial, QuicKeys, AES128_KEY_LEN},
+    crypto::{quic_keys_initial, DirectionalKeys, QuicCipherSuite, QuicKeys, AES128_KEY_LEN},
     cyu::Cyu,
-    frames::{Frame, QuicTlsExtension, StreamTag},
-    parser::{quic_pkt_num, QuicData, QuicHeader, QuicType},
+    doq::{DoqMessage, DoqState, DoqTransaction},
+    frames::{
+        parse_crypto_hello, parse_tls13_server_handshake, Frame, QuicTlsExtension, StreamData,
+        StreamTag,
+    },
+    http3::{Http3Message, Http3State, Http3Transaction},
+    keylog::{quic_keylog_get, quic_keylog_load},
+    parser::{
+        quic_parse_versions, quic_pkt_num, quic_retry_token, QuicData, QuicHeader, QuicType,
+        QuicVersion,
+    },
+    reassembly::{CryptoReassembly, CryptoReassemblyError},
 };
 use crate::{
     applayer::{self, *},
+    conf::conf_get,
     direction::Direction,
     flow::Flow,
+    http2::http2::HTTP2TransactionState,
+    http2::parser::HTTP2HeaderDecodeStatus,
     ja4::JA4,
 };
 use crate::{
@@ -40,14 +53,20 @@ static mut ALPROTO_QUIC: AppProto = ALPROTO_UNKNOWN;
 
 const DEFAULT_DCID_LEN: usize = 16;
 const PKT_NUM_BUF_MAX_LEN: usize = 4;
-pub(super) const QUIC_MAX_CRYPTO_FRAG_LEN: u64 = 65535;
+/// Maximum number of distinct destination connection ids remembered.
+const QUIC_MAX_DCIDS: usize = 64;
 
-#[derive(FromPrimitive, Debug, AppLayerEvent)]
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq, AppLayerEvent)]
 pub enum QuicEvent {
     FailedDecrypt,
     ErrorOnData,
     ErrorOnHeader,
     CryptoFragTooLong,
+    CryptoFragOverlap,
+    Http3Error,
+    QpackDecodeError,
+    InvalidVersionNegotiation,
+    DoqError,
 }
 
 #[derive(Debug)]
@@ -61,6 +80,27 @@ pub struct QuicTransaction {
     pub ja3: Option<String>,
     pub ja4: Option<JA4>,
     pub client: bool,
+    /// server certificate chain, from Handshake packets decrypted with
+    /// the key log
+    pub certs: Vec<Vec<u8>>,
+    /// STREAM frames of a 1-RTT packet decrypted with the key log
+    pub(crate) streams: Vec<StreamData>,
+    /// HTTP/3 request, from the STREAM frames
+    pub http3: Option<Http3Transaction>,
+    /// DNS over QUIC query and response, from the STREAM frames
+    pub doq: Option<DoqTransaction>,
+    /// versions offered by the server in a Version Negotiation packet
+    pub versions: Vec<QuicVersion>,
+    /// token of a Retry packet
+    pub retry_token: Option<Vec<u8>>,
+    /// cipher suite chosen by the server in its hello
+    pub server_cipher: Option<u16>,
+    /// protocol chosen by the server, from Handshake packets decrypted
+    /// with the key log
+    pub server_alpn: Option<Vec<u8>>,
+    /// number of distinct destination connection ids seen in the flow
+    /// so far
+    pub dcid_count: u32,
     tx_data: AppLayerTxData,
 }
 
@@ -85,11 +125,20 @@ impl QuicTransaction {
             ja3,
             ja4,
             client,
+            certs: Vec::new(),
+            streams: Vec::new(),
+            http3: None,
+            doq: None,
+            versions: Vec::new(),
+            retry_token: None,
+            server_cipher: None,
+            server_alpn: None,
+            dcid_count: 0,
             tx_data: AppLayerTxData::for_direction(direction),
         }
     }
 
-    fn new_empty(client: bool, header: QuicHeader) -> Self {
+    pub(super) fn new_empty(client: bool, header: QuicHeader) -> Self {
         let direction = if client {
             Direction::ToServer
         } else {
@@ -105,26 +154,103 @@ impl QuicTransaction {
             ja3: None,
             ja4: None,
             client,
+            certs: Vec::new(),
+            streams: Vec::new(),
+            http3: None,
+            doq: None,
+            versions: Vec::new(),
+            retry_token: None,
+            server_cipher: None,
+            server_alpn: None,
+            dcid_count: 0,
             tx_data: AppLayerTxData::for_direction(direction),
         }
     }
 }
 
+/// Keys of a packet number space, with the largest packet number
+/// decrypted in each direction.
+struct QuicSpaceKeys {
+    keys: QuicKeys,
+    largest_pn_ts: Option<u64>,
+    largest_pn_tc: Option<u64>,
+}
+
+impl QuicSpaceKeys {
+    /// Returns `None` if the key log misses one of the secrets, and an
+    /// error if the keys could not be derived from them.
+    fn new(
+        suite: QuicCipherSuite, version: u32, client_secret: &Option<Vec<u8>>,
+        server_secret: &Option<Vec<u8>>,
+    ) -> Result<Option<Self>, ()> {
+        let (client_secret, server_secret) = match (client_secret, server_secret) {
+            (Some(client_secret), Some(server_secret)) => (client_secret, server_secret),
+            _ => return Ok(None),
+        };
+        let keys = QuicKeys {
+            local: DirectionalKeys::new(suite, server_secret, version)?,
+            remote: DirectionalKeys::new(suite, client_secret, version)?,
+        };
+        Ok(Some(Self {
+            keys,
+            largest_pn_ts: None,
+            largest_pn_tc: None,
+        }))
+    }
+
+    fn decrypt(
+        &mut self, packet: &[u8], pn_offset: usize, to_server: bool, output: &mut Vec<u8>,
+    ) -> Result<(), ()> {
+        let (keys, largest_pn) = if to_server {
+            (&self.keys.remote, &mut self.largest_pn_ts)
+        } else {
+            (&self.keys.local, &mut self.largest_pn_tc)
+        };
+        let pn = keys.decrypt_packet(packet, pn_offset, *largest_pn, output)?;
+        if largest_pn.map_or(true, |largest| pn > largest) {
+            *largest_pn = Some(pn);
+        }
+        Ok(())
+    }
+}
+
 pub struct QuicState {
     state_data: AppLayerStateData,
     max_tx_id: u64,
     keys: Option<QuicKeys>,
-    /// crypto fragment data already seen and reassembled to client
-    crypto_frag_tc: Vec<u8>,
-    /// number of bytes set in crypto fragment data to client
-    crypto_fraglen_tc: u32,
-    /// crypto fragment data already seen and reassembled to server
-    crypto_frag_ts: Vec<u8>,
-    /// number of bytes set in crypto fragment data to server
-    crypto_fraglen_ts: u32,
+    /// crypto fragments to client, until the server hello
+    crypto_tc: CryptoReassembly,
+    /// crypto fragments to server, until the client hello
+    crypto_ts: CryptoReassembly,
     hello_tc: bool,
     hello_ts: bool,
     has_retried: bool,
+    /// version of the first Initial packet of the client
+    client_version: Option<QuicVersion>,
+    /// distinct destination connection ids, up to QUIC_MAX_DCIDS
+    dcids: Vec<Vec<u8>>,
+    /// client random of the client hello, to find the key log secrets
+    client_random: Option<Vec<u8>>,
+    /// cipher suite of the server hello
+    suite: Option<QuicCipherSuite>,
+    /// keys of the Handshake packets, from the key log
+    hs_keys: Option<QuicSpaceKeys>,
+    /// keys of the 1-RTT packets, from the key log
+    app_keys: Option<QuicSpaceKeys>,
+    /// connection id chosen by the server, destination of the short
+    /// header packets to server
+    cid_ts: Vec<u8>,
+    /// connection id chosen by the client, destination of the short
+    /// header packets to client
+    cid_tc: Vec<u8>,
+    /// crypto fragments of the Handshake packets to client, until the
+    /// server certificate
+    crypto_hs_tc: CryptoReassembly,
+    certs_done: bool,
+    /// HTTP/3 parser, if the client offers it in its ALPN
+    http3: Option<Http3State>,
+    /// DNS over QUIC parser, if the client offers it in its ALPN
+    doq: Option<DoqState>,
     transactions: VecDeque<QuicTransaction>,
 }
 
@@ -134,13 +260,23 @@ impl Default for QuicState {
             state_data: AppLayerStateData::new(),
             max_tx_id: 0,
             keys: None,
-            crypto_frag_tc: Vec::new(),
-            crypto_frag_ts: Vec::new(),
-            crypto_fraglen_tc: 0,
-            crypto_fraglen_ts: 0,
+            crypto_tc: CryptoReassembly::default(),
+            crypto_ts: CryptoReassembly::default(),
             hello_tc: false,
             hello_ts: false,
             has_retried: false,
+            client_version: None,
+            dcids: Vec::new(),
+            client_random: None,
+            suite: None,
+            hs_keys: None,
+            app_keys: None,
+            cid_ts: Vec::new(),
+            cid_tc: Vec::new(),
+            crypto_hs_tc: CryptoReassembly::default(),
+            certs_done: false,
+            http3: None,
+            doq: None,
             transactions: VecDeque::new(),
         }
     }
@@ -166,20 +302,6 @@ impl QuicState {
         self.transactions.iter().find(|&tx| tx.tx_id == tx_id + 1)
     }
 
-    fn new_tx(
-        &mut self, header: QuicHeader, data: QuicData, sni: Option<Vec<u8>>, ua: Option<Vec<u8>>,
-        extb: Vec<QuicTlsExtension>, ja3: Option<String>, ja4: Option<JA4>, client: bool,
-        frag_long: bool,
-    ) {
-        let mut tx = QuicTransaction::new(header, data, sni, ua, extb, ja3, ja4, client);
-        self.max_tx_id += 1;
-        tx.tx_id = self.max_tx_id;
-        if frag_long {
-            tx.tx_data.set_event(QuicEvent::CryptoFragTooLong as u8);
-        }
-        self.transactions.push_back(tx);
-    }
-
     fn tx_iterator(
         &mut self, min_tx_id: u64, state: &mut u64,
     ) -> Option<(&QuicTransaction, u64, bool)> {
@@ -248,13 +370,294 @@ impl QuicState {
         return Err(());
     }
 
-    fn handle_frames(&mut self, data: QuicData, header: QuicHeader, to_server: bool) {
+    /// Reassemble the crypto fragments of a packet with the ones of the
+    /// previous packets, and add the TLS hello to the frames once it is
+    /// complete.
+    fn reassemble_crypto(
+        &mut self, data: &mut QuicData, to_server: bool, events: &mut Vec<QuicEvent>,
+    ) {
+        let crypto = if to_server {
+            &mut self.crypto_ts
+        } else {
+            &mut self.crypto_tc
+        };
+        if !quic_crypto_insert(crypto, &data.frames, events) {
+            return;
+        }
+        match parse_crypto_hello(crypto.contiguous()) {
+            Ok(Some(hello)) => {
+                crypto.reset();
+                data.frames.push(hello);
+            }
+            Ok(None) => {
+                // wait for the next packets
+            }
+            Err(_e) => {
+                crypto.reset();
+                events.push(QuicEvent::ErrorOnData);
+            }
+        }
+    }
+
+    /// Derive the keys of the Handshake and 1-RTT packets once both
+    /// hellos are seen, if the key log has the secrets of the connection.
+    fn setup_keylog_keys(&mut self, version: u32, events: &mut Vec<QuicEvent>) {
+        if self.hs_keys.is_some() || self.app_keys.is_some() {
+            return;
+        }
+        let (client_random, suite) = match (&self.client_random, self.suite) {
+            (Some(client_random), Some(suite)) => (client_random, suite),
+            _ => return,
+        };
+        if let Some(secrets) = quic_keylog_get(client_random) {
+            let hs_keys = QuicSpaceKeys::new(
+                suite,
+                version,
+                &secrets.client_handshake,
+                &secrets.server_handshake,
+            );
+            let app_keys = QuicSpaceKeys::new(
+                suite,
+                version,
+                &secrets.client_traffic,
+                &secrets.server_traffic,
+            );
+            match (hs_keys, app_keys) {
+                (Ok(hs_keys), Ok(app_keys)) => {
+                    self.hs_keys = hs_keys;
+                    self.app_keys = app_keys;
+                }
+                _ => {
+                    events.push(QuicEvent::FailedDecrypt);
+                }
+            }
+        }
+    }
+
+    /// Decrypt a Handshake packet with the key log keys, and get the
+    /// server certificate chain out of it.
+    fn parse_handshake(
+        &mut self, packet: &[u8], pn_offset: usize, header: QuicHeader, to_server: bool,
+    ) {
+        let keys = match &mut self.hs_keys {
+            Some(keys) => keys,
+            None => return,
+        };
+        let mut output = Vec::with_capacity(packet.len());
+        if keys
+            .decrypt(packet, pn_offset, to_server, &mut output)
+            .is_err()
+        {
+            // wrong secrets, do not try again
+            self.hs_keys = None;
+            self.set_event_notx(QuicEvent::FailedDecrypt, header, to_server);
+            return;
+        }
+        if to_server || self.certs_done {
+            return;
+        }
+        let frames = match Frame::decode_ietf_frames(&output) {
+            Ok((_, frames)) => frames,
+            Err(_) => {
+                self.set_event_notx(QuicEvent::ErrorOnData, header, to_server);
+                return;
+            }
+        };
+        let mut events = Vec::new();
+        if !quic_crypto_insert(&mut self.crypto_hs_tc, &frames, &mut events) {
+            return;
+        }
+        let mut server = None;
+        match parse_tls13_server_handshake(self.crypto_hs_tc.contiguous()) {
+            Ok(Some(hs)) => {
+                server = Some(hs);
+                self.certs_done = true;
+                self.crypto_hs_tc.reset();
+            }
+            Ok(None) => {
+                // wait for the next packets
+            }
+            Err(_e) => {
+                self.certs_done = true;
+                self.crypto_hs_tc.reset();
+                events.push(QuicEvent::ErrorOnData);
+            }
+        }
+        let empty = server
+            .as_ref()
+            .map_or(true, |hs| hs.alpn.is_none() && hs.certs.is_empty());
+        if empty && events.is_empty() {
+            return;
+        }
+        let mut tx = QuicTransaction::new_empty(to_server, header);
+        if let Some(server) = server {
+            tx.certs = server.certs;
+            tx.server_alpn = server.alpn;
+        }
+        self.push_tx(tx, &events);
+    }
+
+    /// Decrypt a 1-RTT packet with the key log keys, and get its STREAM
+    /// frames. Key updates are not supported.
+    fn parse_short(&mut self, packet: &[u8], header: QuicHeader, to_server: bool) {
+        let dcid_len = if to_server {
+            self.cid_ts.len()
+        } else {
+            self.cid_tc.len()
+        };
+        let keys = match &mut self.app_keys {
+            Some(keys) => keys,
+            None => return,
+        };
+        let mut output = Vec::with_capacity(packet.len());
+        if packet.len() <= 1 + dcid_len
+            || keys
+                .decrypt(packet, 1 + dcid_len, to_server, &mut output)
+                .is_err()
+        {
+            self.app_keys = None;
+            self.set_event_notx(QuicEvent::FailedDecrypt, header, to_server);
+            return;
+        }
+        let frames = match Frame::decode_ietf_frames(&output) {
+            Ok((_, frames)) => frames,
+            Err(_) => {
+                self.set_event_notx(QuicEvent::ErrorOnData, header, to_server);
+                return;
+            }
+        };
+        let streams: Vec<StreamData> = frames
+            .into_iter()
+            .filter_map(|frame| match frame {
+                Frame::StreamData(stream) => Some(stream),
+                _ => None,
+            })
+            .collect();
+        if streams.is_empty() {
+            return;
+        }
+        self.handle_http3(&streams, &header, to_server);
+        self.handle_doq(&streams, &header, to_server);
+        let mut tx = QuicTransaction::new_empty(to_server, header);
+        tx.streams = streams;
+        self.push_tx(tx, &[]);
+    }
+
+    fn get_http3_tx(&mut self, stream_id: u64) -> Option<&mut QuicTransaction> {
+        self.transactions.iter_mut().rev().find(|tx| {
+            tx.http3
+                .as_ref()
+                .map_or(false, |h3| h3.stream_id == stream_id)
+        })
+    }
+
+    /// Parse the STREAM frames as HTTP/3, with a transaction per request
+    /// stream.
+    fn handle_http3(&mut self, streams: &[StreamData], header: &QuicHeader, to_server: bool) {
+        let http3 = match &mut self.http3 {
+            Some(http3) => http3,
+            None => return,
+        };
+        let mut messages = Vec::new();
+        for stream in streams {
+            http3.handle_stream(stream, to_server, &mut messages);
+        }
+        for message in messages {
+            match message {
+                Http3Message::Headers(stream_id, blocks) => {
+                    let error = blocks
+                        .iter()
+                        .any(|b| b.error != HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeSuccess);
+                    if self.get_http3_tx(stream_id).is_none() {
+                        let mut tx = QuicTransaction::new_empty(to_server, header.clone());
+                        tx.http3 = Some(Http3Transaction::new(stream_id));
+                        tx.tx_data = AppLayerTxData::new();
+                        self.push_tx(tx, &[]);
+                    }
+                    if let Some(tx) = self.get_http3_tx(stream_id) {
+                        if error {
+                            tx.tx_data.set_event(QuicEvent::QpackDecodeError as u8);
+                        }
+                        if let Some(h3) = &mut tx.http3 {
+                            h3.add_headers(blocks, to_server);
+                        }
+                    }
+                }
+                Http3Message::Fin(stream_id) => {
+                    if let Some(h3) = self
+                        .get_http3_tx(stream_id)
+                        .and_then(|tx| tx.http3.as_mut())
+                    {
+                        if to_server {
+                            h3.fin_ts = true;
+                        } else {
+                            h3.fin_tc = true;
+                        }
+                    }
+                }
+                Http3Message::Error => {
+                    self.set_event_notx(QuicEvent::Http3Error, header.clone(), to_server);
+                }
+            }
+        }
+    }
+
+    fn get_doq_tx(&mut self, stream_id: u64) -> Option<&mut QuicTransaction> {
+        self.transactions.iter_mut().rev().find(|tx| {
+            tx.doq
+                .as_ref()
+                .map_or(false, |doq| doq.stream_id == stream_id)
+        })
+    }
+
+    /// Parse the STREAM frames as DNS over QUIC, with a transaction per
+    /// query stream holding the inner DNS transactions.
+    fn handle_doq(&mut self, streams: &[StreamData], header: &QuicHeader, to_server: bool) {
+        let doq = match &mut self.doq {
+            Some(doq) => doq,
+            None => return,
+        };
+        let mut messages = Vec::new();
+        for stream in streams {
+            doq.handle_stream(stream, to_server, &mut messages);
+        }
+        for message in messages {
+            match message {
+                DoqMessage::Dns(stream_id, mut dtx) => {
+                    if self.get_doq_tx(stream_id).is_none() {
+                        let mut tx = QuicTransaction::new_empty(to_server, header.clone());
+                        tx.doq = Some(DoqTransaction::new(stream_id));
+                        tx.tx_data = AppLayerTxData::new();
+                        self.push_tx(tx, &[]);
+                    }
+                    if let Some(tx) = self.get_doq_tx(stream_id) {
+                        // the DNS logger expects ids starting at 1
+                        dtx.id = tx.tx_id;
+                        if let Some(doq) = &mut tx.doq {
+                            if to_server {
+                                doq.request = Some(*dtx);
+                            } else {
+                                doq.response = Some(*dtx);
+                            }
+                        }
+                    }
+                }
+                DoqMessage::Error => {
+                    self.set_event_notx(QuicEvent::DoqError, header.clone(), to_server);
+                }
+            }
+        }
+    }
+
+    fn handle_frames(&mut self, mut data: QuicData, header: QuicHeader, to_server: bool) {
         let mut sni: Option<Vec<u8>> = None;
         let mut ua: Option<Vec<u8>> = None;
         let mut ja3: Option<String> = None;
         let mut ja4: Option<JA4> = None;
         let mut extv: Vec<QuicTlsExtension> = Vec::new();
-        let mut frag_long = false;
+        let mut server_cipher = None;
+        let mut events = Vec::new();
+        self.reassemble_crypto(&mut data, to_server, &mut events);
         for frame in &data.frames {
             match frame {
                 Frame::Stream(s) => {
@@ -271,24 +674,6 @@ impl QuicState {
                         }
                     }
                 }
-                Frame::CryptoFrag(frag) => {
-                    // means we had some fragments but not full TLS hello
-                    // save it for a later packet
-                    if to_server {
-                        // use a hardcoded limit to not grow indefinitely
-                        if frag.length < QUIC_MAX_CRYPTO_FRAG_LEN {
-                            self.crypto_frag_ts.clone_from(&frag.data);
-                            self.crypto_fraglen_ts = frag.offset as u32;
-                        } else {
-                            frag_long = true;
-                        }
-                    } else if frag.length < QUIC_MAX_CRYPTO_FRAG_LEN {
-                        self.crypto_frag_tc.clone_from(&frag.data);
-                        self.crypto_fraglen_tc = frag.offset as u32;
-                    } else {
-                        frag_long = true;
-                    }
-                }
                 Frame::Crypto(c) => {
                     if let Some(ja3str) = &c.ja3 {
                         ja3 = Some(ja3str.clone());
@@ -305,25 +690,96 @@ impl QuicState {
                         if e.etype == TlsExtensionType::ServerName && !e.values.is_empty() {
                             sni = Some(e.values[0].to_vec());
                         }
+                        if to_server
+                            && e.etype == TlsExtensionType::ApplicationLayerProtocolNegotiation
+                            && e.values.iter().any(|v| v.starts_with(b"h3"))
+                            && self.http3.is_none()
+                        {
+                            self.http3 = Some(Http3State::default());
+                        }
+                        if to_server
+                            && e.etype == TlsExtensionType::ApplicationLayerProtocolNegotiation
+                            && e.values.iter().any(|v| v.starts_with(b"doq"))
+                            && self.doq.is_none()
+                        {
+                            self.doq = Some(DoqState::default());
+                        }
                     }
                     extv.extend_from_slice(&c.extv);
                     if to_server {
-                        self.hello_ts = true
+                        self.hello_ts = true;
+                        self.client_random = Some(c.random.clone());
                     } else {
-                        self.hello_tc = true
+                        self.hello_tc = true;
+                        server_cipher = c.ciphers.first().map(|cipher| u16::from(*cipher));
+                        self.suite = server_cipher.and_then(QuicCipherSuite::from_tls_id);
                     }
                 }
                 _ => {}
             }
         }
-        self.new_tx(header, data, sni, ua, extv, ja3, ja4, to_server, frag_long);
+        self.setup_keylog_keys(u32::from(header.version), &mut events);
+        let mut tx = QuicTransaction::new(header, data, sni, ua, extv, ja3, ja4, to_server);
+        tx.server_cipher = server_cipher;
+        self.push_tx(tx, &events);
+    }
+
+    /// Handle the payload of a packet other than Initial, Handshake and
+    /// 1-RTT: the versions of a Version Negotiation, the token of a Retry.
+    fn handle_payload(&mut self, payload: &[u8], header: QuicHeader, to_server: bool) {
+        let mut tx = QuicTransaction::new_empty(to_server, header);
+        let mut events = Vec::new();
+        match tx.header.ty {
+            QuicType::VersionNegotiation => match quic_parse_versions(payload) {
+                Ok((_, versions)) => {
+                    // RFC 9000 6.2 a client discards a Version Negotiation
+                    // packet listing the version it selected
+                    if self.client_version.map_or(false, |v| versions.contains(&v)) {
+                        events.push(QuicEvent::InvalidVersionNegotiation);
+                    }
+                    tx.versions = versions;
+                }
+                Err(_) => {
+                    events.push(QuicEvent::ErrorOnData);
+                }
+            },
+            QuicType::Retry => {
+                if let Some(token) = quic_retry_token(payload) {
+                    tx.retry_token = Some(token.to_vec());
+                } else {
+                    events.push(QuicEvent::ErrorOnData);
+                }
+            }
+            _ => {}
+        }
+        self.push_tx(tx, &events);
+    }
+
+    /// Remember a destination connection id, returns true if it was not
+    /// seen before.
+    fn track_dcid(&mut self, dcid: &[u8]) -> bool {
+        if dcid.is_empty()
+            || self.dcids.len() >= QUIC_MAX_DCIDS
+            || self.dcids.iter().any(|d| d == dcid)
+        {
+            return false;
+        }
+        self.dcids.push(dcid.to_vec());
+        return true;
     }
 
     fn set_event_notx(&mut self, event: QuicEvent, header: QuicHeader, client: bool) {
-        let mut tx = QuicTransaction::new_empty(client, header);
+        let tx = QuicTransaction::new_empty(client, header);
+        self.push_tx(tx, &[event]);
+    }
+
+    fn push_tx(&mut self, mut tx: QuicTransaction, events: &[QuicEvent]) {
         self.max_tx_id += 1;
         tx.tx_id = self.max_tx_id;
-        tx.tx_data.set_event(event as u8);
+        tx.dcid_count = self.dcids.len() as u32;
+        for event in events {
+            tx.tx_data.set_event(*event as u8);
+        }
         self.transactions.push_back(tx);
     }
 
@@ -332,19 +788,65 @@ impl QuicState {
         let mut buf = input;
         while !buf.is_empty() {
             match QuicHeader::from_bytes(buf, DEFAULT_DCID_LEN) {
-                Ok((rest, header)) => {
-                    if (to_server && self.hello_ts) || (!to_server && self.hello_tc) {
-                        // payload is encrypted, stop parsing here
-                        return true;
-                    }
+                Ok((rest, mut header)) => {
                     if header.ty == QuicType::Short {
-                        // nothing to get
+                        let dcid_len = if to_server {
+                            self.cid_ts.len()
+                        } else {
+                            self.cid_tc.len()
+                        };
+                        if buf.len() > 1 + dcid_len {
+                            header.dcid = buf[1..1 + dcid_len].to_vec();
+                        }
+                        let new_dcid = self.track_dcid(&header.dcid);
+                        let max_tx_id = self.max_tx_id;
+                        // nothing to get without the key log keys
+                        self.parse_short(buf, header.clone(), to_server);
+                        if new_dcid && self.max_tx_id == max_tx_id {
+                            // log the connection id change on its own
+                            let tx = QuicTransaction::new_empty(to_server, header);
+                            self.push_tx(tx, &[]);
+                        }
                         return true;
                     }
+                    self.track_dcid(&header.dcid);
+                    if to_server && header.ty == QuicType::Initial && self.client_version.is_none()
+                    {
+                        self.client_version = Some(header.version);
+                    }
+                    if header.ty == QuicType::Initial || header.ty == QuicType::Handshake {
+                        if to_server {
+                            self.cid_tc = header.scid.clone();
+                        } else {
+                            self.cid_ts = header.scid.clone();
+                        }
+                    }
+                    if (to_server && self.hello_ts) || (!to_server && self.hello_tc) {
+                        if self.hs_keys.is_none() && self.app_keys.is_none() {
+                            // payload is encrypted, stop parsing here
+                            return true;
+                        }
+                        // header.length was checked against rest.len() during parsing
+                        let hlen = buf.len() - rest.len();
+                        let (packet, next_buf) = buf.split_at(hlen + usize::from(header.length));
+                        if header.ty == QuicType::Handshake {
+                            self.parse_handshake(packet, hlen, header, to_server);
+                        }
+                        buf = next_buf;
+                        continue;
+                    }
 
                     // unprotect/decrypt packet
                     if self.keys.is_none() && header.ty == QuicType::Initial {
-                        self.keys = quic_keys_initial(u32::from(header.version), &header.dcid);
+                        match quic_keys_initial(u32::from(header.version), &header.dcid) {
+                            Ok(keys) => {
+                                self.keys = keys;
+                            }
+                            Err(()) => {
+                                self.set_event_notx(QuicEvent::FailedDecrypt, header, to_server);
+                                return false;
+                            }
+                        }
                     } else if !to_server
                         && self.keys.is_some()
                         && header.ty == QuicType::Retry
@@ -352,6 +854,7 @@ impl QuicState {
                     {
                         // a retry packet discards the current keys, client will resend an initial packet with new keys
                         self.hello_ts = false;
+                        self.crypto_ts.reset();
                         self.keys = None;
                         // RFC 9000 17.2.5.2 After the client has received and processed an Initial or Retry packet
                         // from the server, it MUST discard any subsequent Retry packets that it receives.
@@ -360,18 +863,9 @@ impl QuicState {
                     // header.length was checked against rest.len() during parsing
                     let (mut framebuf, next_buf) = rest.split_at(header.length.into());
                     if header.ty != QuicType::Initial {
-                        // only version is interesting, no frames
-                        self.new_tx(
-                            header,
-                            QuicData { frames: Vec::new() },
-                            None,
-                            None,
-                            Vec::new(),
-                            None,
-                            None,
-                            to_server,
-                            false,
-                        );
+                        // no frames, only the version and the payload of
+                        // some types are interesting
+                        self.handle_payload(framebuf, header, to_server);
                         buf = next_buf;
                         continue;
                     }
@@ -391,26 +885,7 @@ impl QuicState {
                     }
                     buf = next_buf;
 
-                    let mut frag = Vec::new();
-                    // take the current fragment and reset it in the state
-                    let past_frag = if to_server {
-                        std::mem::swap(&mut self.crypto_frag_ts, &mut frag);
-                        &frag
-                    } else {
-                        std::mem::swap(&mut self.crypto_frag_tc, &mut frag);
-                        &frag
-                    };
-                    let past_fraglen = if to_server {
-                        self.crypto_fraglen_ts
-                    } else {
-                        self.crypto_fraglen_tc
-                    };
-                    if to_server {
-                        self.crypto_fraglen_ts = 0
-                    } else {
-                        self.crypto_fraglen_tc = 0
-                    }
-                    match QuicData::from_bytes(framebuf, past_frag, past_fraglen) {
+                    match QuicData::from_bytes(framebuf) {
                         Ok(data) => {
                             self.handle_frames(data, header, to_server);
                         }
@@ -430,6 +905,34 @@ impl QuicState {
     }
 }
 
+/// Add the crypto fragments of a packet to the reassembled crypto data.
+/// Returns false if there is nothing new.
+fn quic_crypto_insert(
+    crypto: &mut CryptoReassembly, frames: &[Frame], events: &mut Vec<QuicEvent>,
+) -> bool {
+    let mut added = false;
+    for frame in frames {
+        if let Frame::CryptoFrag(frag) = frame {
+            match crypto.insert(frag.offset, &frag.data) {
+                Ok(()) => {}
+                Err(CryptoReassemblyError::TooLong) => {
+                    if !events.contains(&QuicEvent::CryptoFragTooLong) {
+                        events.push(QuicEvent::CryptoFragTooLong);
+                    }
+                    continue;
+                }
+                Err(CryptoReassemblyError::Overlap) => {
+                    if !events.contains(&QuicEvent::CryptoFragOverlap) {
+                        events.push(QuicEvent::CryptoFragOverlap);
+                    }
+                }
+            }
+            added = true;
+        }
+    }
+    return added;
+}
+
 extern "C" fn quic_state_new(
     _orig_state: *mut std::os::raw::c_void, _orig_proto: AppProto,
 ) -> *mut std::os::raw::c_void {
@@ -513,8 +1016,15 @@ unsafe extern "C" fn quic_state_get_tx_count(state: *mut std::os::raw::c_void) -
 unsafe extern "C" fn quic_tx_get_alstate_progress(
     tx: *mut std::os::raw::c_void, _direction: u8,
 ) -> std::os::raw::c_int {
-    let _tx = cast_pointer!(tx, QuicTransaction);
-    return 1;
+    let tx = cast_pointer!(tx, QuicTransaction);
+    // states of HTTP/2 so that its detection engines apply to HTTP/3
+    if let Some(h3) = &tx.http3 {
+        return h3.progress() as i32;
+    }
+    if let Some(doq) = &tx.doq {
+        return doq.progress() as i32;
+    }
+    return HTTP2TransactionState::HTTP2StateClosed as i32;
 }
 
 unsafe extern "C" fn quic_state_get_tx_iterator(
@@ -558,8 +1068,8 @@ pub unsafe extern "C" fn SCRegisterQuicParser() {
         parse_tc: quic_parse_tc,
         get_tx_count: quic_state_get_tx_count,
         get_tx: quic_state_get_tx,
-        tx_comp_st_ts: 1,
-        tx_comp_st_tc: 1,
+        tx_comp_st_ts: HTTP2TransactionState::HTTP2StateClosed as i32,
+        tx_comp_st_tc: HTTP2TransactionState::HTTP2StateClosed as i32,
         tx_get_progress: quic_tx_get_alstate_progress,
         get_eventinfo: Some(QuicEvent::get_event_info),
         get_eventinfo_byid: Some(QuicEvent::get_event_info_by_id),
@@ -585,9 +1095,94 @@ pub unsafe extern "C" fn SCRegisterQuicParser() {
         if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
             let _ = AppLayerRegisterParser(&parser, alproto);
         }
+        if let Some(path) = conf_get("app-layer.protocols.quic.keylog-file") {
+            match quic_keylog_load(path) {
+                Ok(count) => {
+                    SCLogConfig!(
+                        "quic: loaded secrets of {} connections from {}",
+                        count,
+                        path
+                    );
+                }
+                Err(e) => {
+                    SCLogError!("quic: failed to read keylog-file {}: {}", path, e);
+                }
+            }
+        }
         SCLogDebug!("Rust quic parser registered.");
         AppLayerParserRegisterLogger(IPPROTO_UDP, ALPROTO_QUIC);
     } else {
         SCLogDebug!("Protocol detector and parser disabled for quic.");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quic::frames::CryptoFrag;
+    use crate::quic::parser::{PublicFlags, QuicVersion};
+
+    #[test]
+    fn test_quic_keylog_keys() {
+        let client_random = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+        // RFC 9001 appendix A.1 initial secrets, used as handshake secrets
+        let keylog = format!(
+            "CLIENT_HANDSHAKE_TRAFFIC_SECRET {} {}\nSERVER_HANDSHAKE_TRAFFIC_SECRET {} {}\n",
+            client_random,
+            "c00cf151ca5be075ed0ebfb5c80323c42d6b7db67881289af4008f1f6c357aea",
+            client_random,
+            "3c199828fd139efd216c155ad844cc81fb82fa8d7446fa7d78be803acdda951b",
+        );
+        let path = std::env::temp_dir().join("suricata-test-quic-keylog.txt");
+        std::fs::write(&path, keylog).unwrap();
+        let loaded = quic_keylog_load(path.to_str().unwrap());
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.unwrap(), 1);
+
+        let header = || {
+            QuicHeader::new(
+                PublicFlags::new(0xc0),
+                QuicType::Initial,
+                QuicVersion(1),
+                Vec::new(),
+                Vec::new(),
+            )
+        };
+        let mut state = QuicState::new();
+        // ClientHello offering TLS_AES_128_GCM_SHA256
+        let client_hello =
+            hex::decode(format!("0100002b0303{}000002130101000000", client_random)).unwrap();
+        let data = QuicData {
+            frames: vec![Frame::CryptoFrag(CryptoFrag {
+                offset: 0,
+                data: client_hello,
+            })],
+        };
+        state.handle_frames(data, header(), true);
+        assert_eq!(
+            state.client_random,
+            Some(hex::decode(client_random).unwrap())
+        );
+        assert!(state.hs_keys.is_none());
+
+        // ServerHello with another random, selecting the same suite
+        let server_hello = hex::decode(concat!(
+            "020000280303",
+            "202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f",
+            "00130100",
+            "0000",
+        ))
+        .unwrap();
+        let data = QuicData {
+            frames: vec![Frame::CryptoFrag(CryptoFrag {
+                offset: 0,
+                data: server_hello,
+            })],
+        };
+        state.handle_frames(data, header(), false);
+        assert_eq!(state.suite, Some(QuicCipherSuite::Aes128GcmSha256));
+        assert!(state.hs_keys.is_some());
+        // no 1-RTT secrets in the key log
+        assert!(state.app_keys.is_none());
+    }
+}
@@ -0,0 +1,156 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Reassembly of the CRYPTO frames, up to the TLS hello of the Initial
+//! packets or the server certificate of the Handshake packets.
+
+use std::cmp::{max, min};
+
+/// Maximum size of the reassembled crypto data.
+const QUIC_MAX_CRYPTO_FRAG_LEN: u64 = 65535;
+/// Maximum number of pieces of the reassembled crypto data.
+const QUIC_MAX_CRYPTO_FRAG_RANGES: usize = 64;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum CryptoReassemblyError {
+    /// The data goes beyond the size limit, or is split in too many
+    /// pieces.
+    TooLong,
+    /// The data overlaps already received data with different bytes.
+    /// The data seen first is kept.
+    Overlap,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct CryptoReassembly {
+    data: Vec<u8>,
+    /// Ranges of data received, sorted and not touching each other
+    ranges: Vec<(usize, usize)>,
+}
+
+impl CryptoReassembly {
+    pub(super) fn reset(&mut self) {
+        self.data.clear();
+        self.ranges.clear();
+    }
+
+    /// Add the data of a CRYPTO frame at its offset in the crypto stream.
+    pub(super) fn insert(&mut self, offset: u64, data: &[u8]) -> Result<(), CryptoReassemblyError> {
+        let end = offset.saturating_add(data.len() as u64);
+        if end > QUIC_MAX_CRYPTO_FRAG_LEN {
+            return Err(CryptoReassemblyError::TooLong);
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        if self.ranges.len() >= QUIC_MAX_CRYPTO_FRAG_RANGES {
+            return Err(CryptoReassemblyError::TooLong);
+        }
+        let (start, end) = (offset as usize, end as usize);
+        if self.data.len() < end {
+            self.data.resize(end, 0);
+        }
+        let mut overlap = false;
+        let mut pos = start;
+        for &(s, e) in &self.ranges {
+            if e <= pos {
+                continue;
+            }
+            if s >= end {
+                break;
+            }
+            if s > pos {
+                self.data[pos..s].copy_from_slice(&data[pos - start..s - start]);
+            }
+            let (os, oe) = (max(s, start), min(e, end));
+            if self.data[os..oe] != data[os - start..oe - start] {
+                overlap = true;
+            }
+            pos = max(pos, e);
+            if pos >= end {
+                break;
+            }
+        }
+        if pos < end {
+            self.data[pos..end].copy_from_slice(&data[pos - start..]);
+        }
+        self.add_range(start, end);
+        if overlap {
+            return Err(CryptoReassemblyError::Overlap);
+        }
+        Ok(())
+    }
+
+    fn add_range(&mut self, start: usize, end: usize) {
+        let idx = self.ranges.partition_point(|&(s, _)| s < start);
+        self.ranges.insert(idx, (start, end));
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(self.ranges.len());
+        for &(s, e) in &self.ranges {
+            match merged.last_mut() {
+                Some(last) if s <= last.1 => last.1 = max(last.1, e),
+                _ => merged.push((s, e)),
+            }
+        }
+        self.ranges = merged;
+    }
+
+    /// Get the data received without holes from the start of the stream.
+    pub(super) fn contiguous(&self) -> &[u8] {
+        match self.ranges.first() {
+            Some(&(0, end)) => &self.data[..end],
+            _ => &[],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quic_crypto_reassembly() {
+        let mut r = CryptoReassembly::default();
+        assert_eq!(r.insert(4, b"efgh"), Ok(()));
+        assert!(r.contiguous().is_empty());
+        assert_eq!(r.insert(10, b"kl"), Ok(()));
+        assert_eq!(r.insert(0, b"abcd"), Ok(()));
+        assert_eq!(r.contiguous(), b"abcdefgh");
+        // retransmission
+        assert_eq!(r.insert(2, b"cdef"), Ok(()));
+        assert_eq!(r.insert(6, b"ghij"), Ok(()));
+        assert_eq!(r.contiguous(), b"abcdefghijkl");
+        assert_eq!(r.ranges, vec![(0, 12)]);
+        // conflicting data, the first one is kept
+        assert_eq!(r.insert(11, b"XY"), Err(CryptoReassemblyError::Overlap));
+        assert_eq!(r.contiguous(), b"abcdefghijklY");
+        assert_eq!(
+            r.insert(QUIC_MAX_CRYPTO_FRAG_LEN - 1, b"ab"),
+            Err(CryptoReassemblyError::TooLong)
+        );
+        r.reset();
+        assert!(r.contiguous().is_empty());
+    }
+
+    #[test]
+    fn test_quic_crypto_reassembly_ranges() {
+        let mut r = CryptoReassembly::default();
+        for i in 0..QUIC_MAX_CRYPTO_FRAG_RANGES {
+            assert_eq!(r.insert(2 * i as u64 + 1, b"a"), Ok(()));
+        }
+        assert_eq!(r.insert(1000, b"a"), Err(CryptoReassemblyError::TooLong));
+    }
+}
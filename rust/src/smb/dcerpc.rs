@@ -23,6 +23,7 @@ use crate::smb::smb2::*;
 use crate::smb::dcerpc_records::*;
 use crate::smb::events::*;
 use crate::dcerpc::dcerpc::*;
+use crate::dcerpc::stub::*;
 use crate::smb::smb_status::*;
 
 impl SMBCommonHdr {
@@ -97,6 +98,8 @@ pub struct SMBTransactionDCERPC {
     pub frag_cnt_tc: u16,
     pub stub_data_ts: Vec<u8>,
     pub stub_data_tc: Vec<u8>,
+    /// decoded request, for the interfaces we know about
+    pub stub_call: Option<Box<DCERPCStubCall>>,
 }
 
 impl SMBTransactionDCERPC {
@@ -175,6 +178,45 @@ impl SMBState {
         }
         return None;
     }
+
+    /// Decode the stub data of a complete request, if it is for one of
+    /// the interfaces we know about.
+    fn dcerpc_decode_request(&mut self, tx_id: u64) {
+        let ifaces = match self.dcerpc_ifaces {
+            Some(ref x) => x,
+            None => { return; },
+        };
+        let handles = &self.dcerpc_handles;
+        for tx in self.transactions.iter_mut().rev() {
+            if tx.id != tx_id {
+                continue;
+            }
+            if let Some(SMBTransactionTypeData::DCERPC(ref mut tdn)) = tx.type_data {
+                if let Some(iface) = ifaces.iter().find(|i| i.context_id == tdn.context_id) {
+                    tdn.stub_call = dcerpc_stub_decode_request(&iface.uuid, tdn.opnum,
+                            &tdn.stub_data_ts, handles).map(Box::new);
+                }
+            }
+            break;
+        }
+    }
+
+    /// Name the service or registry key handle returned by a complete
+    /// response.
+    fn dcerpc_decode_response(&mut self, tx_id: u64) {
+        let handles = &mut self.dcerpc_handles;
+        for tx in self.transactions.iter().rev() {
+            if tx.id != tx_id {
+                continue;
+            }
+            if let Some(SMBTransactionTypeData::DCERPC(ref tdn)) = tx.type_data {
+                if let Some(ref call) = tdn.stub_call {
+                    handles.update(call, &tdn.stub_data_tc);
+                }
+            }
+            break;
+        }
+    }
 }
 
 /// Handle DCERPC request data from a WRITE, IOCTL or TRANS record.
@@ -187,6 +229,8 @@ pub fn smb_write_dcerpc_record(state: &mut SMBState,
 {
     let mut bind_ifaces : Option<Vec<DCERPCIface>> = None;
     let mut is_bind = false;
+    // request completed by this record, to decode once the tx is released
+    let mut done_tx_id : Option<u64> = None;
 
     SCLogDebug!("called for {} bytes of data", data.len());
     match parse_dcerpc_record(data) {
@@ -213,6 +257,9 @@ pub fn smb_write_dcerpc_record(state: &mut SMBState,
                                 if dcer.last_frag {
                                     SCLogDebug!("last frag set, so request side of DCERPC closed");
                                     tx.request_done = true;
+                                    if dcer.little_endian {
+                                        done_tx_id = Some(tx.id);
+                                    }
                                 } else {
                                     SCLogDebug!("NOT last frag, so request side of DCERPC remains open");
                                 }
@@ -223,6 +270,9 @@ pub fn smb_write_dcerpc_record(state: &mut SMBState,
                                 false
                             },
                         };
+                        if let Some(tx_id) = done_tx_id {
+                            state.dcerpc_decode_request(tx_id);
+                        }
                         return found;
                     },
                     _ => {
@@ -249,6 +299,9 @@ pub fn smb_write_dcerpc_record(state: &mut SMBState,
                             }
                             if dcer.last_frag {
                                 tx.request_done = true;
+                                if dcer.little_endian {
+                                    done_tx_id = Some(tx.id);
+                                }
                             } else {
                                 SCLogDebug!("NOT last frag, so request side of DCERPC remains open");
                             }
@@ -283,7 +336,8 @@ pub fn smb_write_dcerpc_record(state: &mut SMBState,
                                     };
                                     let uuid_str = uuid::Uuid::from_slice(&x.clone());
                                     let _uuid_str = uuid_str.map(|uuid_str| uuid_str.to_hyphenated().to_string()).unwrap();
-                                    let d = DCERPCIface::new(x,i.ver,i.ver_min);
+                                    let mut d = DCERPCIface::new(x,i.ver,i.ver_min);
+                                    d.context_id = i.ctx_id;
                                     SCLogDebug!("UUID {} version {}/{} bytes {:?}",
                                             _uuid_str,
                                             i.ver, i.ver_min,i.iface);
@@ -320,6 +374,9 @@ pub fn smb_write_dcerpc_record(state: &mut SMBState,
         // that is part of the state
         state.dcerpc_ifaces = bind_ifaces; // TODO store per ssn
     }
+    if let Some(tx_id) = done_tx_id {
+        state.dcerpc_decode_request(tx_id);
+    }
     return true;
 }
 
@@ -463,6 +520,8 @@ pub fn smb_read_dcerpc_record(state: &mut SMBState,
     let data = prevdata;
 
     let mut malformed = false;
+    // response completed by this record
+    let mut done_tx_id : Option<u64> = None;
 
     if data.is_empty() {
         SCLogDebug!("weird: no DCERPC data"); // TODO
@@ -490,6 +549,9 @@ pub fn smb_read_dcerpc_record(state: &mut SMBState,
                 let found = match state.get_dcerpc_tx(&hdr, &vercmd, dcer.call_id) {
                     Some(tx) => {
                         dcerpc_response_handle(tx, vercmd.clone(), &dcer);
+                        if tx.response_done && dcer.little_endian {
+                            done_tx_id = Some(tx.id);
+                        }
                         true
                     },
                     None => {
@@ -512,6 +574,9 @@ pub fn smb_read_dcerpc_record(state: &mut SMBState,
     if malformed {
         state.set_event(SMBEvent::MalformedData);
     }
+    if let Some(tx_id) = done_tx_id {
+        state.dcerpc_decode_response(tx_id);
+    }
 
     return true;
 }
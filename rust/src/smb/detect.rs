@@ -15,14 +15,27 @@
  * 02110-1301, USA.
  */
 
+use crate::core::STREAM_TOSERVER;
 use crate::dcerpc::dcerpc::DCERPC_TYPE_REQUEST;
 use crate::dcerpc::detect::{DCEIfaceData, DCEOpnumData, DETECT_DCE_OPNUM_RANGE_UNINITIALIZED};
-use crate::detect::uint::detect_match_uint;
+use crate::detect::uint::{
+    detect_match_uint, detect_parse_uint_enum, DetectUintData, SCDetectU8Free,
+};
+use crate::detect::{helper_keyword_register_sticky_buffer, SigTableElmtStickyBuffer};
 use crate::direction::Direction;
 use crate::smb::smb::*;
+use crate::smb::smb2::SMB2_COMMAND_GET_INFO;
+use crate::smb::smb2_records::SMB2_INFO_SECURITY;
 use std::ffi::CStr;
-use std::os::raw::{c_char, c_void};
+use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
+use suricata_derive::EnumStringU8;
+use suricata_sys::sys::{
+    AppProto, AppProtoEnum, DetectEngineCtx, DetectEngineThreadCtx, Flow,
+    SCDetectBufferSetActiveList, SCDetectHelperBufferRegister, SCDetectHelperKeywordRegister,
+    SCDetectHelperMultiBufferMpmRegister, SCDetectSignatureSetAppProto, SCSigMatchAppendSMToList,
+    SCSigTableAppLiteElmt, SigMatchCtx, Signature,
+};
 
 #[no_mangle]
 pub unsafe extern "C" fn SCSmbTxGetShare(
@@ -228,9 +241,202 @@ pub unsafe extern "C" fn SCSmbVersionFree(ptr: *mut c_void) {
     std::mem::drop(Box::from_raw(ptr as *mut u8));
 }
 
+const ALPROTO_SMB: AppProto = AppProtoEnum::ALPROTO_SMB as AppProto;
+
+static mut G_SMB_FILENAME_BUFFER_ID: c_int = 0;
+static mut G_SMB_OPERATION_KW_ID: u16 = 0;
+static mut G_SMB_OPERATION_BUFFER_ID: c_int = 0;
+
+/// File system operation of a transaction, for the smb.operation keyword.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumStringU8)]
+pub enum SMBOperation {
+    Create = 1,
+    Read = 2,
+    Write = 3,
+    Rename = 4,
+    Delete = 5,
+    SetInfo = 6,
+    SetSecurity = 7,
+    GetInfo = 8,
+    QueryDirectory = 9,
+}
+
+fn smb_tx_operation(tx: &SMBTransaction) -> Option<SMBOperation> {
+    match tx.type_data {
+        Some(SMBTransactionTypeData::CREATE(ref x)) => {
+            if x.delete_on_close {
+                Some(SMBOperation::Delete)
+            } else {
+                Some(SMBOperation::Create)
+            }
+        }
+        Some(SMBTransactionTypeData::FILE(ref x)) => {
+            if x.direction == Direction::ToServer {
+                Some(SMBOperation::Write)
+            } else {
+                Some(SMBOperation::Read)
+            }
+        }
+        Some(SMBTransactionTypeData::RENAME(_)) => Some(SMBOperation::Rename),
+        Some(SMBTransactionTypeData::SETFILEPATHINFO(ref x)) => {
+            if x.delete_on_close {
+                Some(SMBOperation::Delete)
+            } else {
+                Some(SMBOperation::SetInfo)
+            }
+        }
+        Some(SMBTransactionTypeData::FILEINFO(ref x)) => {
+            let (_, cmd) = tx.vercmd.get_smb2_cmd();
+            if cmd == SMB2_COMMAND_GET_INFO {
+                Some(SMBOperation::GetInfo)
+            } else if x.class == SMB2_INFO_SECURITY {
+                Some(SMBOperation::SetSecurity)
+            } else {
+                Some(SMBOperation::SetInfo)
+            }
+        }
+        Some(SMBTransactionTypeData::FIND(_)) => Some(SMBOperation::QueryDirectory),
+        _ => None,
+    }
+}
+
+/// Names of the file a transaction operates on. For renames these are the
+/// old and the new name.
+fn smb_tx_filenames(tx: &SMBTransaction) -> [&[u8]; 2] {
+    match tx.type_data {
+        Some(SMBTransactionTypeData::CREATE(ref x)) => [&x.filename, &[]],
+        Some(SMBTransactionTypeData::FILE(ref x)) => [&x.file_name, &[]],
+        Some(SMBTransactionTypeData::RENAME(ref x)) => [&x.oldname, &x.newname],
+        Some(SMBTransactionTypeData::SETFILEPATHINFO(ref x)) => [&x.filename, &[]],
+        Some(SMBTransactionTypeData::FILEINFO(ref x)) => [&x.filename, &[]],
+        Some(SMBTransactionTypeData::FIND(ref x)) => [&x.dirname, &[]],
+        _ => [&[], &[]],
+    }
+}
+
+unsafe extern "C" fn smb_filename_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_SMB) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_SMB_FILENAME_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn smb_tx_get_filename(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, _flags: u8, local_id: u32,
+    buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, SMBTransaction);
+    let names = smb_tx_filenames(tx);
+    if let Some(name) = names.get(local_id as usize) {
+        if !name.is_empty() {
+            *buffer = name.as_ptr();
+            *buffer_len = name.len() as u32;
+            return true;
+        }
+    }
+    *buffer = ptr::null();
+    *buffer_len = 0;
+    return false;
+}
+
+unsafe extern "C" fn smb_parse_operation(ustr: *const c_char) -> *mut DetectUintData<u8> {
+    let ft_name: &CStr = CStr::from_ptr(ustr); //unsafe
+    if let Ok(s) = ft_name.to_str() {
+        if let Some(ctx) = detect_parse_uint_enum::<u8, SMBOperation>(s) {
+            let boxed = Box::new(ctx);
+            return Box::into_raw(boxed) as *mut _;
+        }
+    }
+    return std::ptr::null_mut();
+}
+
+unsafe extern "C" fn smb_operation_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_SMB) != 0 {
+        return -1;
+    }
+    let ctx = smb_parse_operation(raw) as *mut c_void;
+    if ctx.is_null() {
+        return -1;
+    }
+    if SCSigMatchAppendSMToList(
+        de,
+        s,
+        G_SMB_OPERATION_KW_ID,
+        ctx as *mut SigMatchCtx,
+        G_SMB_OPERATION_BUFFER_ID,
+    )
+    .is_null()
+    {
+        smb_operation_free(std::ptr::null_mut(), ctx);
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn smb_operation_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, SMBTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u8>);
+    if let Some(op) = smb_tx_operation(tx) {
+        if detect_match_uint(ctx, op as u8) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+unsafe extern "C" fn smb_operation_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    let ctx = cast_pointer!(ctx, DetectUintData<u8>);
+    SCDetectU8Free(ctx);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCDetectSmbRegister() {
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("smb.filename"),
+        desc: String::from("sticky buffer to match on the file name of SMB file operations"),
+        url: String::from("/rules/smb-keywords.html#smb-filename"),
+        setup: smb_filename_setup,
+    };
+    let _g_smb_filename_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_SMB_FILENAME_BUFFER_ID = SCDetectHelperMultiBufferMpmRegister(
+        b"smb.filename\0".as_ptr() as *const c_char,
+        b"smb file name\0".as_ptr() as *const c_char,
+        ALPROTO_SMB,
+        STREAM_TOSERVER,
+        Some(smb_tx_get_filename),
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"smb.operation\0".as_ptr() as *const c_char,
+        desc: b"match on the file system operation of SMB transactions\0".as_ptr() as *const c_char,
+        url: b"/rules/smb-keywords.html#smb-operation\0".as_ptr() as *const c_char,
+        AppLayerTxMatch: Some(smb_operation_match),
+        Setup: Some(smb_operation_setup),
+        Free: Some(smb_operation_free),
+        flags: 0,
+    };
+    G_SMB_OPERATION_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_SMB_OPERATION_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"smb.operation\0".as_ptr() as *const c_char,
+        ALPROTO_SMB,
+        STREAM_TOSERVER,
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::detect::uint::DetectUintMode;
 
     #[test]
     fn test_parse_cmd_data() {
@@ -245,4 +451,15 @@ mod tests {
         assert_eq!(1u8, parse_version_data(" 1").unwrap());
         assert_eq!(2u8, parse_version_data(" 2 ").unwrap());
     }
+
+    #[test]
+    fn test_parse_operation() {
+        let ctx = detect_parse_uint_enum::<u8, SMBOperation>("rename").unwrap();
+        assert_eq!(ctx.arg1, SMBOperation::Rename as u8);
+        assert_eq!(ctx.mode, DetectUintMode::DetectUintModeEqual);
+        let ctx = detect_parse_uint_enum::<u8, SMBOperation>("!query_directory").unwrap();
+        assert_eq!(ctx.arg1, SMBOperation::QueryDirectory as u8);
+        assert_eq!(ctx.mode, DetectUintMode::DetectUintModeNe);
+        assert!(detect_parse_uint_enum::<u8, SMBOperation>("unlink").is_none());
+    }
 }
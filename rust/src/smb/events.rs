@@ -50,6 +50,12 @@ pub enum SMBEvent {
     UnusualNtlmsspOrder,
     /// Too many live transactions in one flow
     TooManyTransactions,
+    /// SMB3 transform record could not be decrypted with the session keys
+    DecryptionFailed,
+    /// SMB3 compression transform record that could not be decompressed
+    DecompressionFailed,
+    /// SMB3 compressed message bigger than `max-decompressed-size`
+    DecompressedSizeTooLarge,
 }
 
 impl SMBTransaction {
@@ -15,6 +15,10 @@
  * 02110-1301, USA.
  */
 
+use crate::smb::smb2_records::{
+    SMB2_INFO_FILE, SMB2_INFO_FILESYSTEM, SMB2_INFO_QUOTA, SMB2_INFO_SECURITY,
+};
+
 // Based on the list in Wiresharks packet-smb2.c
 // Names match names from Microsoft.
 
@@ -112,3 +116,90 @@ pub fn fsctl_func_to_string(f: u32) -> String {
         _ => { return (f).to_string(); },
     }.to_string()
 }
+
+pub fn smb2_info_type_to_string(t: u8) -> String {
+    match t {
+        SMB2_INFO_FILE => "FILE_INFO",
+        SMB2_INFO_FILESYSTEM => "FILESYSTEM_INFO",
+        SMB2_INFO_SECURITY => "SECURITY_INFO",
+        SMB2_INFO_QUOTA => "QUOTA_INFO",
+        _ => { return (t).to_string(); },
+    }.to_string()
+}
+
+// [MS-FSCC] section 2.4, also used for the QUERY_DIRECTORY classes
+pub fn smb2_file_info_level_to_string(l: u8) -> String {
+    match l {
+        0x01 => "SMB2_FILE_DIRECTORY_INFO",
+        0x02 => "SMB2_FILE_FULL_DIRECTORY_INFO",
+        0x03 => "SMB2_FILE_BOTH_DIRECTORY_INFO",
+        0x04 => "SMB2_FILE_BASIC_INFO",
+        0x05 => "SMB2_FILE_STANDARD_INFO",
+        0x06 => "SMB2_FILE_INTERNAL_INFO",
+        0x07 => "SMB2_FILE_EA_INFO",
+        0x08 => "SMB2_FILE_ACCESS_INFO",
+        0x09 => "SMB2_FILE_NAME_INFO",
+        0x0a => "SMB2_FILE_RENAME_INFO",
+        0x0b => "SMB2_FILE_LINK_INFO",
+        0x0c => "SMB2_FILE_NAMES_INFO",
+        0x0d => "SMB2_FILE_DISPOSITION_INFO",
+        0x0e => "SMB2_FILE_POSITION_INFO",
+        0x0f => "SMB2_FILE_FULL_EA_INFO",
+        0x10 => "SMB2_FILE_MODE_INFO",
+        0x11 => "SMB2_FILE_ALIGNMENT_INFO",
+        0x12 => "SMB2_FILE_ALL_INFO",
+        0x13 => "SMB2_FILE_ALLOCATION_INFO",
+        0x14 => "SMB2_FILE_ENDOFFILE_INFO",
+        0x15 => "SMB2_FILE_ALTERNATE_NAME_INFO",
+        0x16 => "SMB2_FILE_STREAM_INFO",
+        0x17 => "SMB2_FILE_PIPE_INFO",
+        0x1c => "SMB2_FILE_COMPRESSION_INFO",
+        0x22 => "SMB2_FILE_NETWORK_OPEN_INFO",
+        0x23 => "SMB2_FILE_ATTRIBUTE_TAG_INFO",
+        0x25 => "SMB2_FILE_ID_BOTH_DIRECTORY_INFO",
+        0x26 => "SMB2_FILE_ID_FULL_DIRECTORY_INFO",
+        0x27 => "SMB2_FILE_VALID_DATA_LENGTH_INFO",
+        0x28 => "SMB2_FILE_SHORT_NAME_INFO",
+        0x3c => "SMB2_FILE_ID_EXTD_DIRECTORY_INFO",
+        0x40 => "SMB2_FILE_DISPOSITION_INFO_EX",
+        0x41 => "SMB2_FILE_RENAME_INFO_EX",
+        _ => { return (l).to_string(); },
+    }.to_string()
+}
+
+pub fn smb2_fs_info_level_to_string(l: u8) -> String {
+    match l {
+        0x01 => "SMB2_FS_VOLUME_INFO",
+        0x03 => "SMB2_FS_SIZE_INFO",
+        0x04 => "SMB2_FS_DEVICE_INFO",
+        0x05 => "SMB2_FS_ATTRIBUTE_INFO",
+        0x06 => "SMB2_FS_CONTROL_INFO",
+        0x07 => "SMB2_FS_FULL_SIZE_INFO",
+        0x08 => "SMB2_FS_OBJECTID_INFO",
+        0x0b => "SMB2_FS_SECTOR_SIZE_INFO",
+        _ => { return (l).to_string(); },
+    }.to_string()
+}
+
+/// Names of the SECURITY_INFORMATION flags of security descriptor requests.
+pub fn smb2_security_info_flags(f: u32) -> Vec<&'static str> {
+    let names = [
+        (0x0000_0001, "OWNER"),
+        (0x0000_0002, "GROUP"),
+        (0x0000_0004, "DACL"),
+        (0x0000_0008, "SACL"),
+        (0x0000_0010, "LABEL"),
+        (0x0000_0020, "ATTRIBUTE"),
+        (0x0000_0040, "SCOPE"),
+        (0x0001_0000, "BACKUP"),
+    ];
+    names.iter().filter(|(flag, _)| f & flag != 0).map(|(_, name)| *name).collect()
+}
+
+pub fn smb2_info_level_to_string(class: u8, l: u8) -> String {
+    match class {
+        SMB2_INFO_FILE => smb2_file_info_level_to_string(l),
+        SMB2_INFO_FILESYSTEM => smb2_fs_info_level_to_string(l),
+        _ => l.to_string(),
+    }
+}
@@ -0,0 +1,127 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! SMB3 session keys file, giving the keys needed to decrypt the
+//! transform records of known sessions.
+
+use std::collections::HashMap;
+
+/// Keys of one session, found by the session id.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct SMBKeyLogKeys {
+    /// Session key, from which the decryption keys are derived.
+    pub session_key: Vec<u8>,
+    /// Already derived decryption keys, if given.
+    pub s2c_key: Option<Vec<u8>>,
+    pub c2s_key: Option<Vec<u8>>,
+}
+
+static mut SMB_KEYLOG: Option<HashMap<u64, SMBKeyLogKeys>> = None;
+
+fn smb_keylog_parse_session_id(s: &str) -> Option<u64> {
+    if let Some(h) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(h, 16).ok()
+    } else {
+        s.parse::<u64>().ok()
+    }
+}
+
+/// Parse the file. Each line has a session id, as a decimal number or as a
+/// 0x prefixed hex number, and the hex encoded session key. The server to
+/// client and client to server decryption keys can follow, in which case
+/// they are used as is.
+fn smb_keylog_parse(content: &str) -> HashMap<u64, SMBKeyLogKeys> {
+    let mut keys: HashMap<u64, SMBKeyLogKeys> = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_ascii_whitespace().collect();
+        if fields.len() != 2 && fields.len() != 4 {
+            continue;
+        }
+        let session_id = match smb_keylog_parse_session_id(fields[0]) {
+            Some(id) => id,
+            None => continue,
+        };
+        let session_key = match hex::decode(fields[1]) {
+            Ok(k) => k,
+            Err(_) => continue,
+        };
+        let mut entry = SMBKeyLogKeys {
+            session_key,
+            ..Default::default()
+        };
+        if fields.len() == 4 {
+            match (hex::decode(fields[2]), hex::decode(fields[3])) {
+                (Ok(s2c), Ok(c2s)) => {
+                    entry.s2c_key = Some(s2c);
+                    entry.c2s_key = Some(c2s);
+                }
+                _ => continue,
+            }
+        }
+        keys.insert(session_id, entry);
+    }
+    return keys;
+}
+
+/// Load the session keys file. Returns the number of sessions it has keys
+/// for.
+pub(super) fn smb_keylog_load(path: &str) -> Result<usize, std::io::Error> {
+    let content = std::fs::read_to_string(path)?;
+    let keys = smb_keylog_parse(&content);
+    let count = keys.len();
+    unsafe {
+        SMB_KEYLOG = Some(keys);
+    }
+    Ok(count)
+}
+
+/// Whether a session keys file was loaded, in which case the parser keeps
+/// track of what it needs to derive the keys.
+#[allow(static_mut_refs)]
+pub(super) fn smb_keylog_enabled() -> bool {
+    unsafe { SMB_KEYLOG.is_some() }
+}
+
+#[allow(static_mut_refs)]
+pub(super) fn smb_keylog_get(session_id: u64) -> Option<&'static SMBKeyLogKeys> {
+    unsafe { SMB_KEYLOG.as_ref()?.get(&session_id) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smb_keylog_parse() {
+        let content = "# session id, session key[, s2c key, c2s key]\n\
+            0x0000100000000015 00112233445566778899aabbccddeeff\n\
+            17592186044437 aabb ccdd eeff\n\
+            42 aabb ccdd\n\
+            0x2b zz\n\
+            43 aabb ccdd zz\n";
+        let keys = smb_keylog_parse(content);
+        assert_eq!(keys.len(), 1);
+        let k = &keys[&0x100000000015];
+        assert_eq!(k.session_key, vec![0xaa, 0xbb]);
+        assert_eq!(k.s2c_key, Some(vec![0xcc, 0xdd]));
+        assert_eq!(k.c2s_key, Some(vec![0xee, 0xff]));
+    }
+}
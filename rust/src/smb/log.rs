@@ -24,7 +24,9 @@ use crate::jsonbuilder::{JsonBuilder, JsonError};
 use crate::smb::smb::*;
 use crate::smb::smb1::*;
 use crate::smb::smb2::*;
+use crate::smb::smb2_records::SMB2_INFO_SECURITY;
 use crate::dcerpc::dcerpc::*;
+use crate::dcerpc::log::log_dcerpc_stub_call;
 use crate::smb::funcs::*;
 use crate::smb::smb_status::*;
 use std::error::Error;
@@ -412,6 +414,9 @@ fn smb_common_header(
                                 jsb.close()?;
                             }
                         }
+                        if let Some(ref call) = x.stub_call {
+                            log_dcerpc_stub_call(jsb, call)?;
+                        }
                     },
                     DCERPC_TYPE_BIND => {
                         if let Some(ref ifaces) = state.dcerpc_ifaces {
@@ -462,6 +467,12 @@ fn smb_common_header(
             if flags != SMB_LOG_DEFAULT_ALL && (flags & SMB_LOG_TYPE_SETFILEPATHINFO) == 0 {
                 return Err(SmbLogError::SkippedByConf);
             }
+            if tx.vercmd.get_version() == 2 {
+                jsb.open_object("set_info")?;
+                jsb.set_string("class", &smb2_info_type_to_string(x.subcmd as u8))?;
+                jsb.set_string("info_level", &smb2_info_level_to_string(x.subcmd as u8, x.loi as u8))?;
+                jsb.close()?;
+            }
             let mut name_raw = x.filename.to_vec();
             name_raw.retain(|&i|i != 0x00);
             if !name_raw.is_empty() {
@@ -498,6 +509,63 @@ fn smb_common_header(
             let gs = fuid_to_string(&x.fid);
             jsb.set_string("fuid", &gs)?;
         },
+        Some(SMBTransactionTypeData::FILEINFO(ref x)) => {
+            if flags != SMB_LOG_DEFAULT_ALL && (flags & SMB_LOG_TYPE_FILEINFO) == 0 {
+                return Err(SmbLogError::SkippedByConf);
+            }
+            let (_, cmd) = tx.vercmd.get_smb2_cmd();
+            if cmd == SMB2_COMMAND_SET_INFO {
+                jsb.open_object("set_info")?;
+            } else {
+                jsb.open_object("get_info")?;
+            }
+            jsb.set_string("class", &smb2_info_type_to_string(x.class))?;
+            if x.class == SMB2_INFO_SECURITY {
+                jsb.open_array("security_info")?;
+                for f in smb2_security_info_flags(x.additional_info) {
+                    jsb.append_string(f)?;
+                }
+                jsb.close()?;
+                if let Some(ref owner) = x.owner {
+                    jsb.set_string("owner", owner)?;
+                }
+                if let Some(ref group) = x.group {
+                    jsb.set_string("group", group)?;
+                }
+            } else {
+                jsb.set_string("info_level", &smb2_info_level_to_string(x.class, x.infolvl))?;
+            }
+            jsb.close()?;
+
+            if !x.filename.is_empty() {
+                let name = String::from_utf8_lossy(&x.filename);
+                jsb.set_string("filename", &name)?;
+            } else {
+                jsb.set_string("filename", "<share_root>")?;
+            }
+            let gs = fuid_to_string(&x.fuid);
+            jsb.set_string("fuid", &gs)?;
+        },
+        Some(SMBTransactionTypeData::FIND(ref x)) => {
+            if flags != SMB_LOG_DEFAULT_ALL && (flags & SMB_LOG_TYPE_FIND) == 0 {
+                return Err(SmbLogError::SkippedByConf);
+            }
+            jsb.open_object("find")?;
+            jsb.set_string("info_level", &smb2_file_info_level_to_string(x.infolvl))?;
+            let pattern = String::from_utf8_lossy(&x.pattern);
+            jsb.set_string("pattern", &pattern)?;
+            jsb.set_uint("entries", x.entries as u64)?;
+            jsb.close()?;
+
+            if !x.dirname.is_empty() {
+                let name = String::from_utf8_lossy(&x.dirname);
+                jsb.set_string("directory", &name)?;
+            } else {
+                jsb.set_string("directory", "<share_root>")?;
+            }
+            let gs = fuid_to_string(&x.fuid);
+            jsb.set_string("fuid", &gs)?;
+        },
         None => {
             if flags != SMB_LOG_DEFAULT_ALL && (flags & SMB_LOG_TYPE_GENERIC) == 0 {
                 return Err(SmbLogError::SkippedByConf);
@@ -525,6 +593,8 @@ const SMB_LOG_TYPE_IOCTL: u64 = BIT_U64!(6);
 const SMB_LOG_TYPE_RENAME: u64 = BIT_U64!(7);
 const SMB_LOG_TYPE_SETFILEPATHINFO: u64 = BIT_U64!(8);
 const SMB_LOG_TYPE_GENERIC: u64 = BIT_U64!(9);
+const SMB_LOG_TYPE_FILEINFO: u64 = BIT_U64!(10);
+const SMB_LOG_TYPE_FIND: u64 = BIT_U64!(11);
 const SMB_LOG_DEFAULT_ALL: u64 = 0;
 
 fn get_smb_log_type_from_str(s: &str) -> Option<u64> {
@@ -539,6 +609,8 @@ fn get_smb_log_type_from_str(s: &str) -> Option<u64> {
         "rename" => Some(SMB_LOG_TYPE_RENAME),
         "set_file_path_info" => Some(SMB_LOG_TYPE_SETFILEPATHINFO),
         "generic" => Some(SMB_LOG_TYPE_GENERIC),
+        "file_info" => Some(SMB_LOG_TYPE_FILEINFO),
+        "find" => Some(SMB_LOG_TYPE_FIND),
         _ => None,
     }
 }
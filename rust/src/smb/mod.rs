@@ -33,6 +33,9 @@ pub mod smb2;
 pub mod smb2_session;
 pub mod smb2_ioctl;
 pub mod smb3;
+pub mod smb3_crypto;
+pub mod smb3_compression;
+pub mod keylog;
 pub mod dcerpc;
 pub mod session;
 pub mod log;
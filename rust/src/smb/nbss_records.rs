@@ -53,7 +53,7 @@ impl NbssRecord<'_> {
     pub fn is_smb(&self) -> bool {
         let valid = self.is_valid();
         let smb = self.data.len() >= 4 &&
-            self.data[1] == b'S' && self.data[2] == b'M' && self.data[3] == b'B' && (self.data[0] == b'\xFE' || self.data[0] == b'\xFF' || self.data[0] == b'\xFD');
+            self.data[1] == b'S' && self.data[2] == b'M' && self.data[3] == b'B' && (self.data[0] == b'\xFE' || self.data[0] == b'\xFF' || self.data[0] == b'\xFD' || self.data[0] == b'\xFC');
 
         valid && smb
     }
@@ -54,10 +54,12 @@ use crate::smb::smb1::*;
 use crate::smb::smb2::*;
 use crate::smb::smb3::*;
 use crate::smb::dcerpc::*;
+use crate::dcerpc::stub::DCERPCHandleNames;
 use crate::smb::session::*;
 use crate::smb::events::*;
 use crate::smb::files::*;
 use crate::smb::smb2_ioctl::*;
+use crate::smb::keylog::smb_keylog_load;
 
 #[derive(AppLayerFrameType)]
 pub enum SMBFrameType {
@@ -84,6 +86,8 @@ pub static mut SMB_CFG_MAX_READ_QUEUE_CNT: u32 = 64;
 pub static mut SMB_CFG_MAX_WRITE_SIZE: u32 = 16777216;
 pub static mut SMB_CFG_MAX_WRITE_QUEUE_SIZE: u32 = 67108864;
 pub static mut SMB_CFG_MAX_WRITE_QUEUE_CNT: u32 = 64;
+/// Max size of a message once decompressed
+pub static mut SMB_CFG_MAX_DECOMPRESSED_SIZE: u32 = 16777216;
 /// max size of the per state guid2name cache
 pub static mut SMB_CFG_MAX_GUID_CACHE_SIZE: usize = 1024;
 /// SMBState::read_offset_cache
@@ -314,6 +318,8 @@ pub enum SMBTransactionTypeData {
     IOCTL(SMBTransactionIoctl),
     RENAME(SMBTransactionRename),
     SETFILEPATHINFO(SMBTransactionSetFilePathInfo),
+    FILEINFO(SMBTransactionFileInfo),
+    FIND(SMBTransactionFind),
 }
 
 // Used for Trans2 SET_PATH_INFO and SET_FILE_INFO
@@ -327,9 +333,10 @@ pub struct SMBTransactionSetFilePathInfo {
 }
 
 impl SMBTransactionSetFilePathInfo {
-    pub fn new(filename: Vec<u8>, fid: Vec<u8>, subcmd: u16, loi: u16, delete_on_close: bool)
+    pub fn new(mut filename: Vec<u8>, fid: Vec<u8>, subcmd: u16, loi: u16, delete_on_close: bool)
         -> Self
     {
+        filename.retain(|&i|i != 0x00);
         return Self {
             filename, fid,
             subcmd,
@@ -411,6 +418,74 @@ impl SMBState {
     }
 }
 
+// Used for SMB2 GET_INFO and SET_INFO, except for renames and deletes
+#[derive(Default, Debug)]
+pub struct SMBTransactionFileInfo {
+    pub class: u8,
+    pub infolvl: u8,
+    pub additional_info: u32,
+    pub filename: Vec<u8>,
+    pub fuid: Vec<u8>,
+
+    // SET_INFO of a security descriptor
+    pub owner: Option<String>,
+    pub group: Option<String>,
+}
+
+impl SMBTransactionFileInfo {
+    pub fn new(filename: Vec<u8>, fuid: Vec<u8>, class: u8, infolvl: u8, additional_info: u32)
+        -> Self
+    {
+        return Self {
+            class, infolvl, additional_info,
+            filename, fuid,
+            ..Default::default()
+        };
+    }
+}
+
+// Used for SMB2 QUERY_DIRECTORY
+#[derive(Default, Debug)]
+pub struct SMBTransactionFind {
+    pub infolvl: u8,
+    pub pattern: Vec<u8>,
+    pub dirname: Vec<u8>,
+    pub fuid: Vec<u8>,
+    pub entries: u32,
+}
+
+impl SMBState {
+    pub fn new_fileinfo_tx(&mut self, info: SMBTransactionFileInfo)
+        -> &mut SMBTransaction
+    {
+        let mut tx = self.new_tx();
+
+        tx.type_data = Some(SMBTransactionTypeData::FILEINFO(info));
+        tx.request_done = true;
+        tx.response_done = self.tc_trunc; // no response expected if tc is truncated
+
+        SCLogDebug!("SMB: TX FILEINFO created: ID {}", tx.id);
+        self.transactions.push_back(tx);
+        let tx_ref = self.transactions.back_mut();
+        return tx_ref.unwrap();
+    }
+
+    pub fn new_find_tx(&mut self, find: SMBTransactionFind)
+        -> &mut SMBTransaction
+    {
+        let mut tx = self.new_tx();
+
+        tx.type_data = Some(SMBTransactionTypeData::FIND(find));
+        tx.request_done = true;
+        tx.response_done = self.tc_trunc; // no response expected if tc is truncated
+
+        SCLogDebug!("SMB: TX FIND created: ID {}", tx.id);
+        self.transactions.push_back(tx);
+        let tx_ref = self.transactions.back_mut();
+        return tx_ref.unwrap();
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct SMBTransactionCreate {
     pub disposition: u32,
@@ -428,7 +503,8 @@ pub struct SMBTransactionCreate {
 }
 
 impl SMBTransactionCreate {
-    pub fn new(filename: Vec<u8>, disp: u32, del: bool, dir: bool) -> Self {
+    pub fn new(mut filename: Vec<u8>, disp: u32, del: bool, dir: bool) -> Self {
+        filename.retain(|&i|i != 0x00);
         return Self {
             disposition: disp,
             delete_on_close: del,
@@ -760,10 +836,18 @@ pub struct SMBState<> {
     /// dcerpc interfaces, stored here to be able to match
     /// them while inspecting DCERPC REQUEST txs
     pub dcerpc_ifaces: Option<Vec<DCERPCIface>>,
+    /// names of the service and registry key handles opened
+    /// over DCERPC
+    pub dcerpc_handles: DCERPCHandleNames,
 
     pub max_read_size: u32,
     pub max_write_size: u32,
 
+    /// SMB3 cipher negotiated by SMB 3.1.1, or 0
+    pub smb3_cipher: u16,
+    /// SMB3 decryption state, if a session keys file is loaded
+    pub smb3: Option<SMB3DecryptState>,
+
     /// Timestamp in seconds of last update. This is packet time,
     /// potentially coming from pcaps.
     ts: u64,
@@ -815,8 +899,11 @@ impl SMBState {
             dialect:0,
             dialect_vec: None,
             dcerpc_ifaces: None,
+            dcerpc_handles: DCERPCHandleNames::new(),
             max_read_size: 0,
             max_write_size: 0,
+            smb3_cipher: 0,
+            smb3: None,
             ts: 0,
         }
     }
@@ -1279,15 +1366,54 @@ impl SMBState {
         SCLogDebug!("SMBv3 PDU frame {:?}", smb_pdu);
         smb_pdu
     }
-    fn add_smb3_ts_hdr_data_frames(&mut self, flow: *const Flow, stream_slice: &StreamSlice, input: &[u8], nbss_len: i64) {
-        let _smb3_hdr = Frame::new(flow, stream_slice, input, 52_i64, SMBFrameType::SMB3Hdr as u8, None);
+    fn add_smb3_ts_hdr_data_frames(&mut self, flow: *const Flow, stream_slice: &StreamSlice, input: &[u8], nbss_len: i64, hdr_len: i64) {
+        let _smb3_hdr = Frame::new(flow, stream_slice, input, hdr_len, SMBFrameType::SMB3Hdr as u8, None);
         SCLogDebug!("SMBv3 HDR frame {:?}", _smb3_hdr);
-        if input.len() > 52 {
-            let _smb3_data = Frame::new(flow, stream_slice, &input[52..], nbss_len - 52, SMBFrameType::SMB3Data as u8, None);
+        if input.len() > hdr_len as usize {
+            let _smb3_data = Frame::new(flow, stream_slice, &input[hdr_len as usize..], nbss_len - hdr_len, SMBFrameType::SMB3Data as u8, None);
             SCLogDebug!("SMBv3 DATA frame {:?}", _smb3_data);
         }
     }
 
+    /// Handle the SMB2 records of a decrypted or decompressed transform
+    /// record.
+    fn smb2_decoded_records(&mut self, direction: Direction, mut data: &[u8]) {
+        while !data.is_empty() {
+            SCLogDebug!("SMBv2 record in SMBv3 transform record");
+            let res = if direction == Direction::ToServer {
+                parse_smb2_request_record(data)
+            } else {
+                parse_smb2_response_record(data)
+            };
+            match res {
+                Ok((rem, ref smb_record)) => {
+                    if direction == Direction::ToServer && smb_record.is_request() {
+                        smb2_request_record(self, smb_record);
+                    } else if direction == Direction::ToClient && smb_record.is_response() {
+                        smb2_response_record(self, smb_record);
+                    }
+                    data = rem;
+                },
+                _ => {
+                    self.set_event(SMBEvent::MalformedData);
+                    return;
+                },
+            }
+        }
+    }
+
+    /// Handle the content of a transform record: a compressed message,
+    /// or the SMB2 records of a decrypted message.
+    fn smb3_transformed_records(&mut self, direction: Direction, data: &[u8]) {
+        if data.starts_with(b"\xfcSMB") {
+            if let Some(plain) = self.smb3_decompress_record(data) {
+                self.smb2_decoded_records(direction, &plain);
+            }
+        } else {
+            self.smb2_decoded_records(direction, data);
+        }
+    }
+
     /// return bytes consumed
     pub fn parse_tcp_data_ts_partial(&mut self, flow: *const Flow, stream_slice: &StreamSlice, input: &[u8]) -> usize
     {
@@ -1479,6 +1605,7 @@ impl SMBState {
                                                 SCLogDebug!("nbss_data_rem {}", nbss_data_rem.len());
                                                 if smb_record.is_request() {
                                                     smb2_request_record(self, smb_record);
+                                                    self.smb3_preauth_update(smb_record, &nbss_data[..record_len as usize]);
                                                 } else {
                                                     // If we received a response when expecting a request, set an event
                                                     // on the PDU frame instead of handling the response.
@@ -1504,10 +1631,13 @@ impl SMBState {
                                     while !nbss_data.is_empty() {
                                         SCLogDebug!("SMBv3 transform record");
                                         match parse_smb3_transform_record(nbss_data) {
-                                            Ok((nbss_data_rem, ref _smb3_record)) => {
+                                            Ok((nbss_data_rem, ref smb3_record)) => {
                                                 let record_len = (nbss_data.len() - nbss_data_rem.len()) as i64;
                                                 self.add_smb3_ts_pdu_frame(flow, stream_slice, nbss_data, record_len);
-                                                self.add_smb3_ts_hdr_data_frames(flow, stream_slice, nbss_data, record_len);
+                                                self.add_smb3_ts_hdr_data_frames(flow, stream_slice, nbss_data, record_len, SMB3_TRANSFORM_HDR_LEN);
+                                                if let Some(plain) = self.smb3_decrypt_record(smb3_record, Direction::ToServer) {
+                                                    self.smb3_transformed_records(Direction::ToServer, &plain);
+                                                }
                                                 nbss_data = nbss_data_rem;
                                             },
                                             _ => {
@@ -1519,6 +1649,13 @@ impl SMBState {
                                             },
                                         }
                                     }
+                                } else if smb.version == 0xfc_u8 { // SMB3 compression transform
+                                    SCLogDebug!("SMBv3 compression transform record");
+                                    let nbss_data = nbss_hdr.data;
+                                    let record_len = nbss_data.len() as i64;
+                                    self.add_smb3_ts_pdu_frame(flow, stream_slice, nbss_data, record_len);
+                                    self.add_smb3_ts_hdr_data_frames(flow, stream_slice, nbss_data, record_len, smb3_compression_transform_hdr_len(nbss_data));
+                                    self.smb3_transformed_records(Direction::ToServer, nbss_data);
                                 }
                             },
                             _ => {
@@ -1615,11 +1752,11 @@ impl SMBState {
         let _smb_pdu = Frame::new(flow, stream_slice, input, nbss_len, SMBFrameType::SMB3Pdu as u8, None);
         SCLogDebug!("SMBv3 PDU frame {:?}", _smb_pdu);
     }
-    fn add_smb3_tc_hdr_data_frames(&mut self, flow: *const Flow, stream_slice: &StreamSlice, input: &[u8], nbss_len: i64) {
-        let _smb3_hdr = Frame::new(flow, stream_slice, input, 52_i64, SMBFrameType::SMB3Hdr as u8, None);
+    fn add_smb3_tc_hdr_data_frames(&mut self, flow: *const Flow, stream_slice: &StreamSlice, input: &[u8], nbss_len: i64, hdr_len: i64) {
+        let _smb3_hdr = Frame::new(flow, stream_slice, input, hdr_len, SMBFrameType::SMB3Hdr as u8, None);
         SCLogDebug!("SMBv3 HDR frame {:?}", _smb3_hdr);
-        if input.len() > 52 {
-            let _smb3_data = Frame::new(flow, stream_slice, &input[52..], nbss_len - 52, SMBFrameType::SMB3Data as u8, None);
+        if input.len() > hdr_len as usize {
+            let _smb3_data = Frame::new(flow, stream_slice, &input[hdr_len as usize..], nbss_len - hdr_len, SMBFrameType::SMB3Data as u8, None);
             SCLogDebug!("SMBv3 DATA frame {:?}", _smb3_data);
         }
     }
@@ -1809,6 +1946,7 @@ impl SMBState {
                                                 self.add_smb2_tc_hdr_data_frames(flow, stream_slice, nbss_data, record_len, smb_record.header_len as i64);
                                                 if smb_record.is_response() {
                                                     smb2_response_record(self, smb_record);
+                                                    self.smb3_preauth_update(smb_record, &nbss_data[..record_len as usize]);
                                                 } else {
                                                     SCLogDebug!("SMB2 request seen from server to client");
                                                     if let Some(frame) = pdu_frame {
@@ -1828,10 +1966,13 @@ impl SMBState {
                                     while !nbss_data.is_empty() {
                                         SCLogDebug!("SMBv3 transform record");
                                         match parse_smb3_transform_record(nbss_data) {
-                                            Ok((nbss_data_rem, ref _smb3_record)) => {
+                                            Ok((nbss_data_rem, ref smb3_record)) => {
                                                 let record_len = (nbss_data.len() - nbss_data_rem.len()) as i64;
                                                 self.add_smb3_tc_pdu_frame(flow, stream_slice, nbss_data, record_len);
-                                                self.add_smb3_tc_hdr_data_frames(flow, stream_slice, nbss_data, record_len);
+                                                self.add_smb3_tc_hdr_data_frames(flow, stream_slice, nbss_data, record_len, SMB3_TRANSFORM_HDR_LEN);
+                                                if let Some(plain) = self.smb3_decrypt_record(smb3_record, Direction::ToClient) {
+                                                    self.smb3_transformed_records(Direction::ToClient, &plain);
+                                                }
                                                 nbss_data = nbss_data_rem;
                                             },
                                             _ => {
@@ -1840,6 +1981,13 @@ impl SMBState {
                                             },
                                         }
                                     }
+                                } else if smb.version == 0xfc_u8 { // SMB3 compression transform
+                                    SCLogDebug!("SMBv3 compression transform record");
+                                    let nbss_data = nbss_hdr.data;
+                                    let record_len = nbss_data.len() as i64;
+                                    self.add_smb3_tc_pdu_frame(flow, stream_slice, nbss_data, record_len);
+                                    self.add_smb3_tc_hdr_data_frames(flow, stream_slice, nbss_data, record_len, smb3_compression_transform_hdr_len(nbss_data));
+                                    self.smb3_transformed_records(Direction::ToClient, nbss_data);
                                 }
                             },
                             Err(Err::Incomplete(_)) => {
@@ -2444,6 +2592,13 @@ pub unsafe extern "C" fn SCRegisterSmbParser() {
                 Err(_) => { SCLogError!("Invalid max-read-queue-cnt value"); }
             }
         }
+        let retval = conf_get("app-layer.protocols.smb.max-decompressed-size");
+        if let Some(val) = retval {
+            match get_memval(val) {
+                Ok(retval) => { SMB_CFG_MAX_DECOMPRESSED_SIZE = retval as u32; }
+                Err(_) => { SCLogError!("Invalid max-decompressed-size value"); }
+            }
+        }
         if let Some(val) = conf_get("app-layer.protocols.smb.max-tx") {
             if let Ok(v) = val.parse::<usize>() {
                 SMB_MAX_TX = v;
@@ -2515,6 +2670,16 @@ pub unsafe extern "C" fn SCRegisterSmbParser() {
                 cfg_max_read_size(), cfg_max_read_queue_cnt(), cfg_max_read_queue_size());
         SCLogConfig!("write: max record size: {}, max queued chunks {}, max queued size {}",
                 cfg_max_write_size(), cfg_max_write_queue_cnt(), cfg_max_write_queue_size());
+        if let Some(path) = conf_get("app-layer.protocols.smb.session-keys-file") {
+            match smb_keylog_load(path) {
+                Ok(count) => {
+                    SCLogConfig!("smb: loaded keys of {} sessions from {}", count, path);
+                }
+                Err(e) => {
+                    SCLogError!("smb: failed to read session-keys-file {}: {}", path, e);
+                }
+            }
+        }
         SCLogConfig!("guid: max cache size: {}", cfg_max_guid_cache_size());
     } else {
         SCLogDebug!("Protocol detector and parser disabled for SMB.");
@@ -407,6 +407,29 @@ pub fn smb2_write_request_record(state: &mut SMBState, r: &Smb2Record, nbss_rema
     }
 }
 
+/// Get the file name of a file id. With chained commands the file may have
+/// been opened by the previous command in the chain, in which case its
+/// name isn't mapped to the file id yet.
+fn smb2_guid_to_name(state: &mut SMBState, r: &Smb2Record, guid: &[u8]) -> Vec<u8>
+{
+    if let Some(n) = state.guid2name_cache.get(guid) {
+        return n.to_vec();
+    }
+    let mut guid_key = SMBCommonHdr::from2_notree(r, SMBHDR_TYPE_FILENAME);
+    if guid_key.msg_id == 0 {
+        return b"<unknown>".to_vec();
+    }
+    guid_key.msg_id -= 1;
+    match state.ssn2vec_cache.get(&guid_key) {
+        Some(n) => {
+            let mut name = n.to_vec();
+            name.retain(|&i|i != 0x00);
+            name
+        },
+        None => { b"<unknown>".to_vec() },
+    }
+}
+
 pub fn smb2_request_record(state: &mut SMBState, r: &Smb2Record)
 {
     SCLogDebug!("SMBv2 request record, command {} tree {} session {}",
@@ -421,47 +444,34 @@ pub fn smb2_request_record(state: &mut SMBState, r: &Smb2Record)
                 Ok((_, rd)) => {
                     SCLogDebug!("SMB2_COMMAND_SET_INFO: {:?}", rd);
 
-                    match rd.data {
+                    let tx_hdr = SMBCommonHdr::from2(r, SMBHDR_TYPE_GENERICTX);
+                    let fname = smb2_guid_to_name(state, r, rd.guid);
+                    let tx = match rd.data {
                         Smb2SetInfoRequestData::RENAME(ref ren) => {
-                            let tx_hdr = SMBCommonHdr::from2(r, SMBHDR_TYPE_GENERICTX);
                             let mut newname = ren.name.to_vec();
                             newname.retain(|&i|i != 0x00);
-                            let oldname = match state.guid2name_cache.get(rd.guid) {
-                                Some(n) => { n.to_vec() },
-                                None => { b"<unknown>".to_vec() },
-                            };
-                            let tx = state.new_rename_tx(rd.guid.to_vec(), oldname, newname);
-                            tx.hdr = tx_hdr;
-                            tx.request_done = true;
-                            tx.vercmd.set_smb2_cmd(SMB2_COMMAND_SET_INFO);
-                            true
+                            state.new_rename_tx(rd.guid.to_vec(), fname, newname)
                         }
                         Smb2SetInfoRequestData::DISPOSITION(ref dis) => {
-                            let tx_hdr = SMBCommonHdr::from2(r, SMBHDR_TYPE_GENERICTX);
-                            let fname = match state.guid2name_cache.get(rd.guid) {
-                                Some(n) => { n.to_vec() },
-                                None => {
-                                    // try to find latest created file in case of chained commands
-                                    let mut guid_key = SMBCommonHdr::from2_notree(r, SMBHDR_TYPE_FILENAME);
-                                    if guid_key.msg_id == 0 {
-                                        b"<unknown>".to_vec()
-                                    } else {
-                                        guid_key.msg_id -= 1;
-                                        match state.ssn2vec_cache.get(&guid_key) {
-                                            Some(n) => { n.to_vec() },
-                                            None => { b"<unknown>".to_vec()},
-                                        }
-                                    }
-                                },
-                            };
-                            let tx = state.new_setfileinfo_tx(fname, rd.guid.to_vec(), rd.class as u16, rd.infolvl as u16, dis.delete);
-                            tx.hdr = tx_hdr;
-                            tx.request_done = true;
-                            tx.vercmd.set_smb2_cmd(SMB2_COMMAND_SET_INFO);
-                            true
+                            state.new_setfileinfo_tx(fname, rd.guid.to_vec(), rd.class as u16, rd.infolvl as u16, dis.delete)
                         }
-                        _ => false,
-                    }
+                        Smb2SetInfoRequestData::SECURITY(sd) => {
+                            let mut info = SMBTransactionFileInfo::new(fname, rd.guid.to_vec(),
+                                    rd.class, rd.infolvl, rd.additional_info);
+                            info.owner = sd.owner;
+                            info.group = sd.group;
+                            state.new_fileinfo_tx(info)
+                        }
+                        Smb2SetInfoRequestData::UNHANDLED => {
+                            let info = SMBTransactionFileInfo::new(fname, rd.guid.to_vec(),
+                                    rd.class, rd.infolvl, rd.additional_info);
+                            state.new_fileinfo_tx(info)
+                        }
+                    };
+                    tx.hdr = tx_hdr;
+                    tx.request_done = true;
+                    tx.vercmd.set_smb2_cmd(SMB2_COMMAND_SET_INFO);
+                    true
                 },
                 Err(Err::Incomplete(_n)) => {
                     SCLogDebug!("SMB2_COMMAND_SET_INFO: {:?}", _n);
@@ -477,6 +487,44 @@ pub fn smb2_request_record(state: &mut SMBState, r: &Smb2Record)
             };
             have_si_tx
         },
+        SMB2_COMMAND_GET_INFO => {
+            if let Ok((_, rd)) = parse_smb2_request_getinfo(r.data) {
+                SCLogDebug!("SMB2_COMMAND_GET_INFO: {:?}", rd);
+                let tx_hdr = SMBCommonHdr::from2(r, SMBHDR_TYPE_GENERICTX);
+                let fname = smb2_guid_to_name(state, r, rd.guid);
+                let info = SMBTransactionFileInfo::new(fname, rd.guid.to_vec(),
+                        rd.class, rd.infolvl, rd.additional_info);
+                let tx = state.new_fileinfo_tx(info);
+                tx.hdr = tx_hdr;
+                tx.vercmd.set_smb2_cmd(SMB2_COMMAND_GET_INFO);
+                true
+            } else {
+                events.push(SMBEvent::MalformedData);
+                false
+            }
+        },
+        SMB2_COMMAND_FIND => {
+            if let Ok((_, rd)) = parse_smb2_request_query_directory(r.data) {
+                SCLogDebug!("SMB2_COMMAND_FIND: {:?}", rd);
+                let tx_hdr = SMBCommonHdr::from2(r, SMBHDR_TYPE_GENERICTX);
+                let mut pattern = rd.pattern.to_vec();
+                pattern.retain(|&i|i != 0x00);
+                let find = SMBTransactionFind {
+                    infolvl: rd.infolvl,
+                    pattern,
+                    dirname: smb2_guid_to_name(state, r, rd.guid),
+                    fuid: rd.guid.to_vec(),
+                    ..Default::default()
+                };
+                let tx = state.new_find_tx(find);
+                tx.hdr = tx_hdr;
+                tx.vercmd.set_smb2_cmd(SMB2_COMMAND_FIND);
+                true
+            } else {
+                events.push(SMBEvent::MalformedData);
+                false
+            }
+        },
         SMB2_COMMAND_IOCTL => {
             smb2_ioctl_request_record(state, r);
             true
@@ -723,6 +771,21 @@ pub fn smb2_response_record(state: &mut SMBState, r: &Smb2Record)
                 false
             }
         },
+        SMB2_COMMAND_FIND => {
+            if r.nt_status == SMB_NTSTATUS_SUCCESS {
+                let tx_hdr = SMBCommonHdr::from2(r, SMBHDR_TYPE_GENERICTX);
+                if let Some(tx) = state.get_generic_tx(2, r.command, &tx_hdr) {
+                    if let Some(SMBTransactionTypeData::FIND(ref mut tdf)) = tx.type_data {
+                        if let Ok((_, rd)) = parse_smb2_response_query_directory(r.data) {
+                            tdf.entries = rd.entries;
+                        } else {
+                            events.push(SMBEvent::MalformedData);
+                        }
+                    }
+                }
+            }
+            false // status is set by the generic handling below
+        },
         SMB2_COMMAND_TREE_DISCONNECT => {
             // normally removed when processing request,
             // but in case we missed that try again here
@@ -794,6 +857,7 @@ pub fn smb2_response_record(state: &mut SMBState, r: &Smb2Record)
                 state.dialect = rd.dialect;
                 state.max_read_size = rd.max_read_size;
                 state.max_write_size = rd.max_write_size;
+                state.smb3_cipher = rd.cipher;
 
                 let found2 = match state.get_negotiate_tx(2) {
                     Some(tx) => {
@@ -156,26 +156,89 @@ pub struct Smb2NegotiateProtocolResponseRecord<'a> {
     pub max_trans_size: u32,
     pub max_read_size: u32,
     pub max_write_size: u32,
+    /// cipher of the ENCRYPTION_CAPABILITIES context (SMB 3.1.1) or 0
+    pub cipher: u16,
+}
+
+const SMB2_HEADER_SIZE: usize = 64;
+const SMB2_ENCRYPTION_CAPABILITIES: u16 = 0x0002;
+
+fn parse_smb2_negotiate_context(i: &[u8]) -> IResult<&[u8], (u16, &[u8])> {
+    let (i, ctx_type) = le_u16(i)?;
+    let (i, ctx_len) = le_u16(i)?;
+    let (i, _reserved) = le_u32(i)?;
+    let (i, data) = take(ctx_len as usize)(i)?;
+    Ok((i, (ctx_type, data)))
+}
+
+fn parse_smb2_encryption_capabilities(i: &[u8]) -> IResult<&[u8], u16> {
+    let (i, _cipher_cnt) = le_u16(i)?;
+    let (i, cipher) = le_u16(i)?;
+    Ok((i, cipher))
+}
+
+/// Get the cipher selected by the server from the negotiate contexts.
+/// `body` is the negotiate response after the SMB2 header, while
+/// `ctx_offset` is from the start of the header.
+fn smb2_negotiate_context_cipher(body: &[u8], ctx_offset: u32, ctx_cnt: u16) -> u16 {
+    if (ctx_offset as usize) < SMB2_HEADER_SIZE {
+        return 0;
+    }
+    let mut offset = ctx_offset as usize - SMB2_HEADER_SIZE;
+    for _ in 0..ctx_cnt {
+        let ctx = match body.get(offset..) {
+            Some(ctx) => ctx,
+            None => return 0,
+        };
+        match parse_smb2_negotiate_context(ctx) {
+            Ok((rem, (ctx_type, data))) => {
+                if ctx_type == SMB2_ENCRYPTION_CAPABILITIES {
+                    return parse_smb2_encryption_capabilities(data)
+                        .map(|(_, cipher)| cipher)
+                        .unwrap_or(0);
+                }
+                // contexts are 8 byte aligned
+                offset += (ctx.len() - rem.len() + 7) & !7;
+            }
+            Err(_) => return 0,
+        }
+    }
+    0
 }
 
 pub fn parse_smb2_response_negotiate_protocol(
     i: &[u8],
 ) -> IResult<&[u8], Smb2NegotiateProtocolResponseRecord> {
+    let body = i;
     let (i, _struct_size) = take(2_usize)(i)?;
     let (i, _skip1) = take(2_usize)(i)?;
     let (i, dialect) = le_u16(i)?;
-    let (i, _ctx_cnt) = le_u16(i)?;
+    let (i, ctx_cnt) = le_u16(i)?;
     let (i, server_guid) = take(16_usize)(i)?;
     let (i, _capabilities) = le_u32(i)?;
     let (i, max_trans_size) = le_u32(i)?;
     let (i, max_read_size) = le_u32(i)?;
     let (i, max_write_size) = le_u32(i)?;
+    let cipher = if dialect == 0x0311 {
+        // system time, start time and security buffer offset and length
+        // precede the negotiate context offset
+        match i.get(20..24) {
+            Some(b) => {
+                let ctx_offset = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+                smb2_negotiate_context_cipher(body, ctx_offset, ctx_cnt)
+            }
+            None => 0,
+        }
+    } else {
+        0
+    };
     let record = Smb2NegotiateProtocolResponseRecord {
         dialect,
         server_guid,
         max_trans_size,
         max_read_size,
         max_write_size,
+        cipher,
     };
     Ok((i, record))
 }
@@ -191,6 +254,7 @@ pub fn parse_smb2_response_negotiate_protocol_error(
         max_trans_size: 0,
         max_read_size: 0,
         max_write_size: 0,
+        cipher: 0,
     };
     Ok((i, record))
 }
@@ -370,35 +434,110 @@ pub fn parse_smb2_request_setinfo_disposition(i: &[u8]) -> IResult<&[u8], Smb2Se
     Ok((i, record))
 }
 
+/// FileDispositionInformationEx, FILE_DISPOSITION_DELETE is bit 0 of the
+/// flags.
+pub fn parse_smb2_request_setinfo_disposition_ex(
+    i: &[u8],
+) -> IResult<&[u8], Smb2SetInfoRequestData> {
+    let (i, flags) = le_u32(i)?;
+    let record = Smb2SetInfoRequestData::DISPOSITION(Smb2SetInfoRequestDispoRecord {
+        delete: flags & 1 != 0,
+    });
+    Ok((i, record))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Smb2SecurityDescriptorRecord {
+    pub owner: Option<String>,
+    pub group: Option<String>,
+}
+
+/// Parse a SID into its string form, like S-1-5-32-544.
+pub fn parse_smb_sid(i: &[u8]) -> IResult<&[u8], String> {
+    let (i, revision) = le_u8(i)?;
+    let (i, sub_count) = le_u8(i)?;
+    let (i, authority) = take(6_usize)(i)?;
+    let (i, sub_authorities) = count(le_u32, sub_count as usize)(i)?;
+    let authority = authority
+        .iter()
+        .fold(0_u64, |acc, &b| (acc << 8) | b as u64);
+    let mut sid = format!("S-{}-{}", revision, authority);
+    for s in sub_authorities {
+        sid.push_str(&format!("-{}", s));
+    }
+    Ok((i, sid))
+}
+
+/// Self relative security descriptor, only the owner and group SIDs are
+/// extracted.
+pub fn parse_smb2_request_setinfo_security(i: &[u8]) -> IResult<&[u8], Smb2SetInfoRequestData> {
+    let (r, _revision) = le_u8(i)?;
+    let (r, _sbz1) = le_u8(r)?;
+    let (r, _control) = le_u16(r)?;
+    let (r, owner_offset) = le_u32(r)?;
+    let (r, group_offset) = le_u32(r)?;
+    let (r, _sacl_offset) = le_u32(r)?;
+    let (r, _dacl_offset) = le_u32(r)?;
+    let sid_at = |offset: u32| -> Option<String> {
+        if offset == 0 {
+            return None;
+        }
+        let d = i.get(offset as usize..)?;
+        parse_smb_sid(d).ok().map(|(_, sid)| sid)
+    };
+    let record = Smb2SetInfoRequestData::SECURITY(Smb2SecurityDescriptorRecord {
+        owner: sid_at(owner_offset),
+        group: sid_at(group_offset),
+    });
+    Ok((r, record))
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Smb2SetInfoRequestData<'a> {
     DISPOSITION(Smb2SetInfoRequestDispoRecord),
     RENAME(Smb2SetInfoRequestRenameRecord<'a>),
+    SECURITY(Smb2SecurityDescriptorRecord),
     UNHANDLED,
 }
 
+/// InfoType values of GET_INFO and SET_INFO requests.
+pub const SMB2_INFO_FILE: u8 = 1;
+pub const SMB2_INFO_FILESYSTEM: u8 = 2;
+pub const SMB2_INFO_SECURITY: u8 = 3;
+pub const SMB2_INFO_QUOTA: u8 = 4;
+
 #[derive(Debug)]
 pub struct Smb2SetInfoRequestRecord<'a> {
     pub guid: &'a [u8],
     pub class: u8,
     pub infolvl: u8,
+    pub additional_info: u32,
     pub data: Smb2SetInfoRequestData<'a>,
 }
 
 fn parse_smb2_request_setinfo_data(
     i: &[u8], class: u8, infolvl: u8,
 ) -> IResult<&[u8], Smb2SetInfoRequestData> {
-    if class == 1 {
-        // constants from [MS-FSCC] section 2.4
-        match infolvl {
-            10 => {
-                return parse_smb2_request_setinfo_rename(i);
-            }
-            0xd => {
-                return parse_smb2_request_setinfo_disposition(i);
+    match class {
+        SMB2_INFO_FILE => {
+            // constants from [MS-FSCC] section 2.4
+            match infolvl {
+                10 => {
+                    return parse_smb2_request_setinfo_rename(i);
+                }
+                0xd => {
+                    return parse_smb2_request_setinfo_disposition(i);
+                }
+                0x40 => {
+                    return parse_smb2_request_setinfo_disposition_ex(i);
+                }
+                _ => {}
             }
-            _ => {}
         }
+        SMB2_INFO_SECURITY => {
+            return parse_smb2_request_setinfo_security(i);
+        }
+        _ => {}
     }
     return Ok((i, Smb2SetInfoRequestData::UNHANDLED));
 }
@@ -410,7 +549,7 @@ pub fn parse_smb2_request_setinfo(i: &[u8]) -> IResult<&[u8], Smb2SetInfoRequest
     let (i, setinfo_size) = le_u32(i)?;
     let (i, _setinfo_offset) = le_u16(i)?;
     let (i, _reserved) = take(2_usize)(i)?;
-    let (i, _additional_info) = le_u32(i)?;
+    let (i, additional_info) = le_u32(i)?;
     let (i, guid) = take(16_usize)(i)?;
     let (i, data) = map_parser(take(setinfo_size), |b| {
         parse_smb2_request_setinfo_data(b, class, infolvl)
@@ -419,11 +558,97 @@ pub fn parse_smb2_request_setinfo(i: &[u8]) -> IResult<&[u8], Smb2SetInfoRequest
         guid,
         class,
         infolvl,
+        additional_info,
         data,
     };
     Ok((i, record))
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub struct Smb2GetInfoRequestRecord<'a> {
+    pub guid: &'a [u8],
+    pub class: u8,
+    pub infolvl: u8,
+    pub additional_info: u32,
+}
+
+pub fn parse_smb2_request_getinfo(i: &[u8]) -> IResult<&[u8], Smb2GetInfoRequestRecord> {
+    let (i, _struct_size) = le_u16(i)?;
+    let (i, class) = le_u8(i)?;
+    let (i, infolvl) = le_u8(i)?;
+    let (i, _output_buffer_len) = le_u32(i)?;
+    let (i, _input_buffer_offset) = le_u16(i)?;
+    let (i, _reserved) = take(2_usize)(i)?;
+    let (i, _input_buffer_len) = le_u32(i)?;
+    let (i, additional_info) = le_u32(i)?;
+    let (i, _flags) = le_u32(i)?;
+    let (i, guid) = take(16_usize)(i)?;
+    let record = Smb2GetInfoRequestRecord {
+        guid,
+        class,
+        infolvl,
+        additional_info,
+    };
+    Ok((i, record))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Smb2QueryDirectoryRequestRecord<'a> {
+    pub guid: &'a [u8],
+    pub infolvl: u8,
+    pub flags: u8,
+    pub pattern: &'a [u8],
+}
+
+pub fn parse_smb2_request_query_directory(
+    i: &[u8],
+) -> IResult<&[u8], Smb2QueryDirectoryRequestRecord> {
+    let (i, _struct_size) = le_u16(i)?;
+    let (i, infolvl) = le_u8(i)?;
+    let (i, flags) = le_u8(i)?;
+    let (i, _file_index) = le_u32(i)?;
+    let (i, guid) = take(16_usize)(i)?;
+    let (i, _pattern_offset) = le_u16(i)?;
+    let (i, pattern_len) = le_u16(i)?;
+    let (i, _output_buffer_len) = le_u32(i)?;
+    let (i, pattern) = take(pattern_len)(i)?;
+    let record = Smb2QueryDirectoryRequestRecord {
+        guid,
+        infolvl,
+        flags,
+        pattern,
+    };
+    Ok((i, record))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Smb2QueryDirectoryResponseRecord {
+    /// Number of directory entries in the data we have, the output buffer
+    /// may be truncated.
+    pub entries: u32,
+}
+
+pub fn parse_smb2_response_query_directory(
+    i: &[u8],
+) -> IResult<&[u8], Smb2QueryDirectoryResponseRecord> {
+    let (i, _struct_size) = le_u16(i)?;
+    let (i, _output_buffer_offset) = le_u16(i)?;
+    let (i, output_buffer_len) = le_u32(i)?;
+    let (i, data) = parse_smb2_data(i, output_buffer_len)?;
+    // entries are chained by their NextEntryOffset, which is 0 for the last
+    let mut entries = 0;
+    let mut offset = 0;
+    while let Ok((_, next)) = le_u32::<_, ()>(&data[offset..]) {
+        entries += 1;
+        if next == 0 || offset + next as usize >= data.len() {
+            break;
+        }
+        offset += next as usize;
+    }
+    let record = Smb2QueryDirectoryResponseRecord { entries };
+    Ok((i, record))
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct Smb2WriteRequestRecord<'a> {
     pub wr_len: u32,
@@ -607,7 +832,7 @@ pub fn search_smb_record(i: &[u8]) -> IResult<&[u8], &[u8]> {
         if index == 0 {
             return Err(Err::Error(make_error(d, ErrorKind::Eof)));
         }
-        if d[index - 1] == 0xfe || d[index - 1] == 0xff || d[index - 1] == 0xfd {
+        if d[index - 1] == 0xfe || d[index - 1] == 0xff || d[index - 1] == 0xfd || d[index - 1] == 0xfc {
             // if we have enough data, check nbss
             if index < 5 || d[index - 5] == NBSS_MSGTYPE_SESSION_MESSAGE {
                 return Ok((&d[index + 3..], &d[index - 1..]));
@@ -733,6 +958,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_smb2_request_setinfo_security() {
+        let data = hex::decode(concat!(
+            "2100030024000000600000000100000001000000000000000500000000000000",
+            "010004801400000000000000000000000000000001020000000000052000000020020000"
+        ))
+        .unwrap();
+        let (_, record) = parse_smb2_request_setinfo(&data).unwrap();
+        assert_eq!(record.class, SMB2_INFO_SECURITY);
+        assert_eq!(record.additional_info, 1);
+        assert_eq!(
+            record.data,
+            Smb2SetInfoRequestData::SECURITY(Smb2SecurityDescriptorRecord {
+                owner: Some("S-1-5-32-544".to_string()),
+                group: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_smb2_query_directory() {
+        let data = hex::decode(concat!(
+            "2100250100000000010000000000000005000000000000006000020000000100",
+            "2a00"
+        ))
+        .unwrap();
+        let (_, record) = parse_smb2_request_query_directory(&data).unwrap();
+        assert_eq!(record.infolvl, 0x25);
+        assert_eq!(record.flags, 1);
+        assert_eq!(record.pattern, b"*\x00");
+
+        let data = hex::decode(concat!(
+            "0900480018000000",
+            "100000000000000000000000000000000000000000000000"
+        ))
+        .unwrap();
+        let (_, record) = parse_smb2_response_query_directory(&data).unwrap();
+        assert_eq!(record.entries, 2);
+    }
+
     #[test]
     fn test_parse_smb2_request_read() {
         // https://raw.githubusercontent.com/bro/bro/master/testing/btest/Traces/smb/smb2.pcap
@@ -833,6 +1098,22 @@ mod tests {
         assert_eq!(neg_proto.max_trans_size, 0x800000);
         assert_eq!(neg_proto.max_read_size, 0x800000);
         assert_eq!(neg_proto.max_write_size, 0x800000);
+        assert_eq!(neg_proto.cipher, 0);
+    }
+
+    #[test]
+    fn test_parse_smb2_response_negotiate_protocol_311() {
+        // negotiate response body with a PREAUTH_INTEGRITY_CAPABILITIES and
+        // an ENCRYPTION_CAPABILITIES context selecting AES-128-GCM
+        let data = hex::decode("4100010011030200000102030405060708090a0b0c0d0e0f2f0000000000800000008000000080000000000000000000000000000000000080000000800000000100260000000000010020000100aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa0000020004000000000001000200").unwrap();
+        let result = parse_smb2_response_negotiate_protocol(&data);
+        assert!(result.is_ok());
+        let neg_proto = result.unwrap().1;
+        assert_eq!(neg_proto.dialect, 0x311);
+        assert_eq!(neg_proto.cipher, 2);
+        // truncated contexts
+        let result = parse_smb2_response_negotiate_protocol(&data[..100]);
+        assert_eq!(result.unwrap().1.cipher, 0);
     }
 
     #[test]
@@ -15,39 +15,376 @@
  * 02110-1301, USA.
  */
 
+use std::collections::HashMap;
+
 use nom7::bytes::streaming::{tag, take};
+use nom7::combinator::rest;
 use nom7::number::streaming::{le_u16, le_u32, le_u64};
 use nom7::IResult;
 
-#[derive(Debug,PartialEq, Eq)]
+use crate::direction::Direction;
+use crate::smb::events::*;
+use crate::smb::keylog::*;
+use crate::smb::smb::*;
+use crate::smb::smb2::*;
+use crate::smb::smb2_records::*;
+use crate::smb::smb3_compression::*;
+use crate::smb::smb3_crypto::*;
+use crate::smb::smb_status::*;
+
+/// Length of the header of a transform record.
+pub const SMB3_TRANSFORM_HDR_LEN: i64 = 52;
+
+#[derive(Debug, PartialEq, Eq)]
 pub struct Smb3TransformRecord<'a> {
+    pub signature: &'a [u8],
+    pub nonce: &'a [u8],
     pub session_id: u64,
     pub enc_algo: u16,
-    pub enc_data: &'a[u8],
+    /// header from the nonce up to the session id, authenticated
+    /// along with the encrypted data
+    pub aad: &'a [u8],
+    pub enc_data: &'a [u8],
 }
 
 pub fn parse_smb3_transform_record(i: &[u8]) -> IResult<&[u8], Smb3TransformRecord> {
     let (i, _) = tag(b"\xfdSMB")(i)?;
-    let (i, _signature) = take(16_usize)(i)?;
-    let (i, _nonce) = take(16_usize)(i)?;
+    let (i, signature) = take(16_usize)(i)?;
+    let (_, aad) = take(32_usize)(i)?;
+    let (i, nonce) = take(16_usize)(i)?;
     let (i, msg_size) = le_u32(i)?;
     let (i, _reserved) = le_u16(i)?;
     let (i, enc_algo) = le_u16(i)?;
     let (i, session_id) = le_u64(i)?;
     let (i, enc_data) = take(msg_size)(i)?;
     let record = Smb3TransformRecord {
+        signature,
+        nonce,
         session_id,
         enc_algo,
+        aad,
         enc_data,
     };
     Ok((i, record))
 }
 
+pub const SMB2_COMPRESSION_FLAG_CHAINED: u16 = 0x0001;
+
+/// Length of the header of a compression transform record. The header
+/// of a chained record is followed by the headers of its payloads, which
+/// are part of the data.
+pub fn smb3_compression_transform_hdr_len(i: &[u8]) -> i64 {
+    if i.len() >= 12 && u16::from_le_bytes([i[10], i[11]]) & SMB2_COMPRESSION_FLAG_CHAINED != 0 {
+        8
+    } else {
+        16
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Smb3CompressionPayload<'a> {
+    pub algo: u16,
+    /// size of the payload once decompressed
+    pub original_size: u32,
+    pub data: &'a [u8],
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Smb3CompressionTransformRecord<'a> {
+    /// size of the message once decompressed
+    pub original_size: u32,
+    pub chained: bool,
+    /// payloads, in order. An unchained record has an uncompressed
+    /// payload followed by a compressed one.
+    pub payloads: Vec<Smb3CompressionPayload<'a>>,
+}
+
+fn parse_smb3_compression_chained_payload(i: &[u8]) -> IResult<&[u8], Smb3CompressionPayload> {
+    let (i, algo) = le_u16(i)?;
+    let (i, _flags) = le_u16(i)?;
+    let (i, length) = le_u32(i)?;
+    let (i, data) = take(length)(i)?;
+    let (data, original_size) = match algo {
+        SMB2_COMPRESSION_LZNT1 | SMB2_COMPRESSION_LZ77 | SMB2_COMPRESSION_LZ77_HUFFMAN => {
+            le_u32(data)?
+        }
+        SMB2_COMPRESSION_PATTERN_V1 => {
+            // pattern, reserved, then the number of repetitions
+            let (_, repetitions) = le_u32(take(4_usize)(data)?.0)?;
+            (data, repetitions)
+        }
+        _ => (data, length),
+    };
+    let payload = Smb3CompressionPayload {
+        algo,
+        original_size,
+        data,
+    };
+    Ok((i, payload))
+}
+
+pub fn parse_smb3_compression_transform_record(
+    i: &[u8],
+) -> IResult<&[u8], Smb3CompressionTransformRecord> {
+    let (i, _) = tag(b"\xfcSMB")(i)?;
+    let (i, original_size) = le_u32(i)?;
+    let (_, flags) = le_u16(take(2_usize)(i)?.0)?;
+    if flags & SMB2_COMPRESSION_FLAG_CHAINED != 0 {
+        let mut payloads = Vec::new();
+        let mut i = i;
+        while !i.is_empty() {
+            let (rem, payload) = parse_smb3_compression_chained_payload(i)?;
+            payloads.push(payload);
+            i = rem;
+        }
+        let record = Smb3CompressionTransformRecord {
+            original_size,
+            chained: true,
+            payloads,
+        };
+        return Ok((i, record));
+    }
+    let (i, algo) = le_u16(i)?;
+    let (i, _flags) = le_u16(i)?;
+    let (i, offset) = le_u32(i)?;
+    let (i, uncompressed) = take(offset)(i)?;
+    let (i, compressed) = rest(i)?;
+    let record = Smb3CompressionTransformRecord {
+        original_size: original_size.saturating_add(offset),
+        chained: false,
+        payloads: vec![
+            Smb3CompressionPayload {
+                algo: SMB2_COMPRESSION_NONE,
+                original_size: offset,
+                data: uncompressed,
+            },
+            Smb3CompressionPayload {
+                algo,
+                original_size,
+                data: compressed,
+            },
+        ],
+    };
+    Ok((i, record))
+}
+
+/// Max number of sessions per flow for which the SMB3 key derivation
+/// state is kept.
+const SMB3_MAX_SESSIONS: usize = 64;
+
+#[derive(Debug, Default)]
+pub struct SMB3Session {
+    /// preauth integrity hash of the session setup (SMB 3.1.1)
+    pub preauth_hash: Vec<u8>,
+    /// session setup completed, so `preauth_hash` is final
+    pub established: bool,
+    /// decryption keys, derived on first use
+    pub keys: Option<SMB3Keys>,
+}
+
+/// State needed to decrypt the transform records of a flow, only kept
+/// when a session keys file is loaded.
+#[derive(Debug, Default)]
+pub struct SMB3DecryptState {
+    /// preauth integrity hash of the connection (SMB 3.1.1)
+    pub preauth_hash: Option<Vec<u8>>,
+    /// preauth integrity hash of a session setup that has not been
+    /// assigned a session id yet
+    pub preauth_pending: Option<Vec<u8>>,
+    pub sessions: HashMap<u64, SMB3Session>,
+}
+
+impl SMB3DecryptState {
+    fn add_session(&mut self, session_id: u64, ssn: SMB3Session) {
+        if self.sessions.len() < SMB3_MAX_SESSIONS || self.sessions.contains_key(&session_id) {
+            self.sessions.insert(session_id, ssn);
+        }
+    }
+}
+
+impl SMBState {
+    /// Update the SMB 3.1.1 preauth integrity hashes with a NEGOTIATE or
+    /// SESSION_SETUP message. `data` is the whole message, header included.
+    /// Called after the message is handled, so that the dialect of a
+    /// NEGOTIATE response is known.
+    pub fn smb3_preauth_update(&mut self, r: &Smb2Record, data: &[u8]) {
+        if !smb_keylog_enabled() {
+            return;
+        }
+        let dialect = self.dialect;
+        let st = self.smb3.get_or_insert_with(Default::default);
+        match r.command {
+            SMB2_COMMAND_NEGOTIATE_PROTOCOL => {
+                if r.is_request() {
+                    st.preauth_hash = Some(smb3_preauth_hash(&[0; 64], data));
+                } else {
+                    st.preauth_hash = match st.preauth_hash.take() {
+                        Some(h)
+                            if r.nt_status == SMB_NTSTATUS_SUCCESS
+                                && dialect == SMB2_DIALECT_311 =>
+                        {
+                            Some(smb3_preauth_hash(&h, data))
+                        }
+                        _ => None,
+                    };
+                }
+            }
+            SMB2_COMMAND_SESSION_SETUP => {
+                if dialect != SMB2_DIALECT_311 {
+                    return;
+                }
+                if r.is_request() {
+                    if r.session_id == 0 {
+                        if let Some(h) = &st.preauth_hash {
+                            st.preauth_pending = Some(smb3_preauth_hash(h, data));
+                        }
+                    } else if let Some(ssn) = st.sessions.get_mut(&r.session_id) {
+                        if !ssn.established {
+                            ssn.preauth_hash = smb3_preauth_hash(&ssn.preauth_hash, data);
+                        }
+                    }
+                } else if r.nt_status == SMB_NTSTATUS_MORE_PROCESSING_REQUIRED {
+                    if let Some(h) = st.preauth_pending.take() {
+                        let ssn = SMB3Session {
+                            preauth_hash: smb3_preauth_hash(&h, data),
+                            ..Default::default()
+                        };
+                        st.add_session(r.session_id, ssn);
+                    } else if let Some(ssn) = st.sessions.get_mut(&r.session_id) {
+                        if !ssn.established {
+                            ssn.preauth_hash = smb3_preauth_hash(&ssn.preauth_hash, data);
+                        }
+                    }
+                } else if r.nt_status == SMB_NTSTATUS_SUCCESS {
+                    // the final response is not part of the hash
+                    if let Some(h) = st.preauth_pending.take() {
+                        let ssn = SMB3Session {
+                            preauth_hash: h,
+                            established: true,
+                            ..Default::default()
+                        };
+                        st.add_session(r.session_id, ssn);
+                    } else if let Some(ssn) = st.sessions.get_mut(&r.session_id) {
+                        ssn.established = true;
+                    }
+                } else {
+                    st.preauth_pending = None;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn smb3_session_key(
+        &mut self, session_id: u64, direction: Direction, cipher: u16,
+    ) -> Option<Vec<u8>> {
+        let keylog = smb_keylog_get(session_id)?;
+        if let (Some(c2s), Some(s2c)) = (&keylog.c2s_key, &keylog.s2c_key) {
+            return Some(if direction == Direction::ToServer {
+                c2s.clone()
+            } else {
+                s2c.clone()
+            });
+        }
+        let dialect = self.dialect;
+        let st = self.smb3.get_or_insert_with(Default::default);
+        if !st.sessions.contains_key(&session_id) {
+            if dialect == SMB2_DIALECT_311 {
+                // keys depend on the session setup messages
+                SCLogDebug!("no SMB 3.1.1 session setup seen for session {}", session_id);
+                return None;
+            }
+            st.add_session(session_id, SMB3Session::default());
+        }
+        let ssn = st.sessions.get_mut(&session_id)?;
+        if dialect == SMB2_DIALECT_311 && !ssn.established {
+            return None;
+        }
+        if ssn.keys.is_none() {
+            ssn.keys = smb3_derive_keys(dialect, cipher, &keylog.session_key, &ssn.preauth_hash);
+        }
+        let keys = ssn.keys.as_ref()?;
+        Some(if direction == Direction::ToServer {
+            keys.c2s.clone()
+        } else {
+            keys.s2c.clone()
+        })
+    }
+
+    /// Decrypt a transform record with the keys of its session, from the
+    /// session keys file. Returns the SMB2 message(s) it holds.
+    pub fn smb3_decrypt_record(
+        &mut self, record: &Smb3TransformRecord, direction: Direction,
+    ) -> Option<Vec<u8>> {
+        if !smb_keylog_enabled() {
+            return None;
+        }
+        let cipher = if self.smb3_cipher != 0 {
+            self.smb3_cipher
+        } else {
+            SMB2_ENCRYPTION_AES128_CCM
+        };
+        let key = self.smb3_session_key(record.session_id, direction, cipher)?;
+        let plain = smb3_decrypt(cipher, &key, record);
+        if plain.is_none() {
+            SCLogDebug!(
+                "failed to decrypt SMB3 record of session {}",
+                record.session_id
+            );
+            self.set_event(SMBEvent::DecryptionFailed);
+        }
+        plain
+    }
+
+    /// Decompress a compression transform record. Returns the SMB2
+    /// message it holds.
+    pub fn smb3_decompress_record(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+        let record = match parse_smb3_compression_transform_record(data) {
+            Ok((_, record)) => record,
+            Err(_) => {
+                self.set_event(SMBEvent::DecompressionFailed);
+                return None;
+            }
+        };
+        let max_size = unsafe { SMB_CFG_MAX_DECOMPRESSED_SIZE };
+        if record.original_size > max_size {
+            SCLogDebug!(
+                "compressed message of {} bytes, max {}",
+                record.original_size,
+                max_size
+            );
+            self.set_event(SMBEvent::DecompressedSizeTooLarge);
+            return None;
+        }
+        let size = record.original_size as usize;
+        let mut out = Vec::with_capacity(size);
+        for payload in &record.payloads {
+            let payload_size = payload.original_size as usize;
+            if payload_size > size - out.len()
+                || smb3_decompress_payload(payload.algo, payload.data, payload_size, &mut out)
+                    .is_none()
+            {
+                SCLogDebug!(
+                    "failed to decompress payload with algorithm {}",
+                    payload.algo
+                );
+                self.set_event(SMBEvent::DecompressionFailed);
+                return None;
+            }
+        }
+        if out.len() != size {
+            self.set_event(SMBEvent::DecompressionFailed);
+            return None;
+        }
+        Some(out)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-	#[test]
-	fn test_parse_smb3_transform_record() {
+    #[test]
+    fn test_parse_smb3_transform_record() {
         // https://raw.githubusercontent.com/bro/bro/master/testing/btest/Traces/smb/smb3.pcap
         let data = hex::decode("fd534d42188d39cea4b1e3f640aff5d0b1569852c0bd665516dbb4b499507f000000000069000000000001003d00009400480000d9f8a66572b40c621bea6f5922a412a8eb2e3cc2af9ce26a277e75898cb523b9eb49ef660a6a1a09368fadd6a58e893e08eb3b7c068bdb74b6cd38e9ed1a2559cefb2ebc2172fd86c08a1a636eb851f20bf53a242f4cfaf7ab44e77291073ad492d6297c3d3a67757c").unwrap();
         let result = parse_smb3_transform_record(&data).unwrap();
@@ -55,5 +392,41 @@ mod tests {
         assert_eq!(record.session_id, 79167320227901);
         assert_eq!(record.enc_algo, 1);
         assert_eq!(record.enc_data.len(), 105);
+        assert_eq!(record.signature, &data[4..20]);
+        assert_eq!(record.nonce, &data[20..36]);
+        assert_eq!(record.aad, &data[20..52]);
+    }
+
+    #[test]
+    fn test_parse_smb3_compression_transform_record() {
+        // unchained: 4 uncompressed bytes then a LZ77 payload
+        let data =
+            hex::decode("fc534d421a00000002000000040000006162636400000000656667686173746ffc0f0000")
+                .unwrap();
+        let (_, record) = parse_smb3_compression_transform_record(&data).unwrap();
+        assert!(!record.chained);
+        assert_eq!(record.original_size, 30);
+        assert_eq!(record.payloads.len(), 2);
+        assert_eq!(record.payloads[0].algo, SMB2_COMPRESSION_NONE);
+        assert_eq!(record.payloads[0].data, b"abcd");
+        assert_eq!(record.payloads[1].algo, SMB2_COMPRESSION_LZ77);
+        assert_eq!(record.payloads[1].original_size, 26);
+        assert_eq!(smb3_compression_transform_hdr_len(&data), 16);
+
+        // chained: uncompressed payload, then a pattern
+        let data =
+            hex::decode("fc534d420c00000000000100040000006162636404000000080000002a00000008000000")
+                .unwrap();
+        let (_, record) = parse_smb3_compression_transform_record(&data).unwrap();
+        assert!(record.chained);
+        assert_eq!(record.original_size, 12);
+        assert_eq!(record.payloads.len(), 2);
+        assert_eq!(record.payloads[0].original_size, 4);
+        assert_eq!(record.payloads[1].algo, SMB2_COMPRESSION_PATTERN_V1);
+        assert_eq!(record.payloads[1].original_size, 8);
+        assert_eq!(smb3_compression_transform_hdr_len(&data), 8);
+
+        // truncated chained payload
+        assert!(parse_smb3_compression_transform_record(&data[..data.len() - 2]).is_err());
     }
 }
@@ -0,0 +1,368 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Decompression of SMB 3.1.1 compression transform payloads. The
+//! algorithms are described in MS-XCA.
+
+pub const SMB2_COMPRESSION_NONE: u16 = 0x0000;
+pub const SMB2_COMPRESSION_LZNT1: u16 = 0x0001;
+pub const SMB2_COMPRESSION_LZ77: u16 = 0x0002;
+pub const SMB2_COMPRESSION_LZ77_HUFFMAN: u16 = 0x0003;
+pub const SMB2_COMPRESSION_PATTERN_V1: u16 = 0x0004;
+
+const LZNT1_CHUNK_SIZE: usize = 4096;
+const HUFFMAN_SYMBOLS: usize = 512;
+const HUFFMAN_TABLE_BITS: u32 = 15;
+const HUFFMAN_BLOCK_SIZE: usize = 65536;
+
+fn read_u16(input: &[u8], pos: usize) -> Option<u16> {
+    let b = input.get(pos..pos + 2)?;
+    Some(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(input: &[u8], pos: usize) -> Option<u32> {
+    let b = input.get(pos..pos + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Copy a match of `len` bytes from `offset` bytes back. `start` is where
+/// the output of the current (sub)stream starts, matches can't reach
+/// before it.
+fn lz_copy(out: &mut Vec<u8>, start: usize, offset: usize, len: usize, max: usize) -> Option<()> {
+    if offset == 0 || offset > out.len() - start || len > max - out.len() {
+        return None;
+    }
+    let from = out.len() - offset;
+    for i in 0..len {
+        let b = out[from + i];
+        out.push(b);
+    }
+    Some(())
+}
+
+fn lznt1_decompress_chunk(mut chunk: &[u8], out: &mut Vec<u8>, max: usize) -> Option<()> {
+    let start = out.len();
+    while let Some((&flags, rest)) = chunk.split_first() {
+        chunk = rest;
+        for bit in 0..8 {
+            if chunk.is_empty() {
+                break;
+            }
+            if flags & (1 << bit) == 0 {
+                if out.len() >= max {
+                    return None;
+                }
+                out.push(chunk[0]);
+                chunk = &chunk[1..];
+            } else {
+                let token = read_u16(chunk, 0)?;
+                chunk = &chunk[2..];
+                // the split of the token between offset and length
+                // depends on the position in the chunk
+                let pos = out.len() - start;
+                if pos == 0 || pos > LZNT1_CHUNK_SIZE {
+                    return None;
+                }
+                let mut shift = 12;
+                let mut p = pos - 1;
+                while p >= 0x10 {
+                    p >>= 1;
+                    shift -= 1;
+                }
+                let len = (token & ((1 << shift) - 1)) as usize + 3;
+                let offset = (token >> shift) as usize + 1;
+                lz_copy(out, start, offset, len, max)?;
+            }
+        }
+    }
+    Some(())
+}
+
+/// LZNT1 decompression, MS-XCA 2.5.
+pub fn lznt1_decompress(mut input: &[u8], max: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    while input.len() >= 2 {
+        let header = read_u16(input, 0)?;
+        if header == 0 {
+            break;
+        }
+        let size = (header & 0x0fff) as usize + 1;
+        let chunk = input.get(2..2 + size)?;
+        input = &input[2 + size..];
+        if header & 0x8000 != 0 {
+            lznt1_decompress_chunk(chunk, &mut out, max)?;
+        } else {
+            if chunk.len() > max - out.len() {
+                return None;
+            }
+            out.extend_from_slice(chunk);
+        }
+    }
+    Some(out)
+}
+
+/// Plain LZ77 decompression, MS-XCA 2.4.
+pub fn lz77_decompress(input: &[u8], max: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    let mut flags: u32 = 0;
+    let mut flag_count = 0;
+    // position of the byte of which the high nibble is still to be
+    // used as a match length
+    let mut last_half_byte: Option<usize> = None;
+    loop {
+        if flag_count == 0 {
+            if pos == input.len() {
+                return Some(out);
+            }
+            flags = read_u32(input, pos)?;
+            pos += 4;
+            flag_count = 32;
+        }
+        flag_count -= 1;
+        if pos == input.len() {
+            return Some(out);
+        }
+        if flags & (1 << flag_count) == 0 {
+            if out.len() >= max {
+                return None;
+            }
+            out.push(input[pos]);
+            pos += 1;
+            continue;
+        }
+
+        let match_bytes = read_u16(input, pos)? as usize;
+        pos += 2;
+        let mut len = match_bytes % 8;
+        let offset = match_bytes / 8 + 1;
+        if len == 7 {
+            if let Some(h) = last_half_byte.take() {
+                len = (input[h] >> 4) as usize;
+            } else {
+                len = (*input.get(pos)? & 0x0f) as usize;
+                last_half_byte = Some(pos);
+                pos += 1;
+            }
+            if len == 15 {
+                len = *input.get(pos)? as usize;
+                pos += 1;
+                if len == 255 {
+                    len = read_u16(input, pos)? as usize;
+                    pos += 2;
+                    if len == 0 {
+                        len = read_u32(input, pos)? as usize;
+                        pos += 4;
+                    }
+                    if len < 15 + 7 {
+                        return None;
+                    }
+                    len -= 15 + 7;
+                }
+                len += 15;
+            }
+            len += 7;
+        }
+        len += 3;
+        lz_copy(&mut out, 0, offset, len, max)?;
+    }
+}
+
+/// Build the decoding table of a LZ77+Huffman block from the 4 bit
+/// lengths of its 512 symbols. Each entry is indexed by the next 15 bits
+/// of the stream.
+fn huffman_decoding_table(lengths: &[u8]) -> Option<Vec<u16>> {
+    let size = 1 << HUFFMAN_TABLE_BITS;
+    let mut table = Vec::with_capacity(size);
+    for bit_len in 1..=HUFFMAN_TABLE_BITS {
+        for symbol in 0..HUFFMAN_SYMBOLS {
+            let sym_len = (lengths[symbol / 2] >> (4 * (symbol % 2))) & 0x0f;
+            if sym_len as u32 == bit_len {
+                let count = 1 << (HUFFMAN_TABLE_BITS - bit_len);
+                if table.len() + count > size {
+                    return None;
+                }
+                table.resize(table.len() + count, symbol as u16);
+            }
+        }
+    }
+    if table.len() != size {
+        return None;
+    }
+    Some(table)
+}
+
+/// LZ77+Huffman decompression, MS-XCA 2.2. The output size has to be
+/// known, as the stream has no end marker of its own.
+pub fn lz77_huffman_decompress(input: &[u8], out_size: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(out_size);
+    let mut in_pos = 0;
+    while out.len() < out_size {
+        let lengths = input.get(in_pos..in_pos + HUFFMAN_SYMBOLS / 2)?;
+        let table = huffman_decoding_table(lengths)?;
+        let mut pos = in_pos + HUFFMAN_SYMBOLS / 2;
+        let mut next_bits = (read_u16(input, pos)? as u32) << 16 | read_u16(input, pos + 2)? as u32;
+        pos += 4;
+        let mut extra_bits: i32 = 16;
+        let block_end = std::cmp::min(out.len() + HUFFMAN_BLOCK_SIZE, out_size);
+        while out.len() < block_end {
+            let symbol = table[(next_bits >> (32 - HUFFMAN_TABLE_BITS)) as usize] as usize;
+            let sym_len = (lengths[symbol / 2] >> (4 * (symbol % 2))) & 0x0f;
+            next_bits <<= sym_len;
+            extra_bits -= sym_len as i32;
+            if extra_bits < 0 {
+                next_bits |= (read_u16(input, pos)? as u32) << -extra_bits;
+                extra_bits += 16;
+                pos += 2;
+            }
+            if symbol < 256 {
+                out.push(symbol as u8);
+                continue;
+            }
+
+            let symbol = symbol - 256;
+            let mut len = symbol % 16;
+            let offset_bits = (symbol / 16) as u32;
+            if len == 15 {
+                len = *input.get(pos)? as usize;
+                pos += 1;
+                if len == 255 {
+                    len = read_u16(input, pos)? as usize;
+                    pos += 2;
+                    if len < 15 {
+                        return None;
+                    }
+                    len -= 15;
+                }
+                len += 15;
+            }
+            len += 3;
+            let offset = ((next_bits as u64) >> (32 - offset_bits)) as usize + (1 << offset_bits);
+            next_bits <<= offset_bits;
+            extra_bits -= offset_bits as i32;
+            if extra_bits < 0 {
+                next_bits |= (read_u16(input, pos)? as u32) << -extra_bits;
+                extra_bits += 16;
+                pos += 2;
+            }
+            lz_copy(&mut out, 0, offset, len, out_size)?;
+        }
+        in_pos = pos;
+    }
+    Some(out)
+}
+
+/// Pattern_V1 payload: a byte repeated a number of times.
+pub fn pattern_v1_decompress(input: &[u8], max: usize) -> Option<Vec<u8>> {
+    let pattern = *input.first()?;
+    let repetitions = read_u32(input, 4)? as usize;
+    if repetitions > max {
+        return None;
+    }
+    Some(vec![pattern; repetitions])
+}
+
+/// Decompress a payload into `out`, which has to grow by `size` bytes.
+pub fn smb3_decompress_payload(
+    algo: u16, input: &[u8], size: usize, out: &mut Vec<u8>,
+) -> Option<()> {
+    let data = match algo {
+        SMB2_COMPRESSION_NONE => input.to_vec(),
+        SMB2_COMPRESSION_LZNT1 => lznt1_decompress(input, size)?,
+        SMB2_COMPRESSION_LZ77 => lz77_decompress(input, size)?,
+        SMB2_COMPRESSION_LZ77_HUFFMAN => lz77_huffman_decompress(input, size)?,
+        SMB2_COMPRESSION_PATTERN_V1 => pattern_v1_decompress(input, size)?,
+        _ => return None,
+    };
+    if data.len() != size {
+        return None;
+    }
+    out.extend_from_slice(&data);
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lz77_decompress() {
+        // MS-XCA 3.1 examples
+        let data =
+            hex::decode("3f0000006162636465666768696a6b6c6d6e6f707172737475767778797a").unwrap();
+        let out = lz77_decompress(&data, 1024).unwrap();
+        assert_eq!(out, b"abcdefghijklmnopqrstuvwxyz");
+        let data = hex::decode("ffffff1f61626317000fff2601").unwrap();
+        let out = lz77_decompress(&data, 1024).unwrap();
+        assert_eq!(out, b"abc".repeat(100));
+        assert!(lz77_decompress(&data, 299).is_none());
+        // match before the start of the output
+        let data = hex::decode("ffffff1f61626327000fff2601").unwrap();
+        assert!(lz77_decompress(&data, 1024).is_none());
+    }
+
+    #[test]
+    fn test_lznt1_decompress() {
+        let data = hex::decode("67b0007468652071756963006b2062726f776e2000666f78206a756d708073206f7665722001f0006c617a7920646f67462e023453b061626318020000010203040506070800090a0b0c0d0e0f1000111213141516171800191a1b1c1d1e1f2080212223242526271072").unwrap();
+        let mut expected = b"the quick brown fox jumps over the lazy dog. ".repeat(3);
+        expected.extend_from_slice(&b"abc".repeat(10));
+        expected.extend(0..40);
+        expected.extend_from_slice(b"the quick brown fox");
+        let out = lznt1_decompress(&data, 1024).unwrap();
+        assert_eq!(out, expected);
+        assert!(lznt1_decompress(&data, 100).is_none());
+        assert!(lznt1_decompress(&data[..50], 1024).is_none());
+    }
+
+    #[test]
+    fn test_lz77_huffman_decompress() {
+        // all symbols have a 9 bits code, except 'a' with 8 bits and 0xff
+        // that is not used
+        let mut data = vec![0x99; 256];
+        data[0x61 / 2] = 0x89;
+        data[0xff / 2] = 0x09;
+        data.extend_from_slice(&hex::decode("31000c9963a6a03901d23a0f4c9aff540223c2d89164d40836678c8133dee033220f9c2322d85971dc083a078e317345ccf3410168f4ec32220d9c2b13615fb668ff3d03af5c00bc00000c0000").unwrap());
+        let fox = b"the quick brown fox jumps over the lazy dog ";
+        let mut expected = b"abcdefgh".to_vec();
+        expected.extend_from_slice(&[b'a'; 600]);
+        expected.extend_from_slice(&fox.repeat(20));
+        expected.extend_from_slice(b"XYZ");
+        expected.extend_from_slice(&fox[..30]);
+        let out = lz77_huffman_decompress(&data, expected.len()).unwrap();
+        assert_eq!(out, expected);
+        assert!(lz77_huffman_decompress(&data[..300], expected.len()).is_none());
+        // incomplete code
+        data[0] = 0xa9;
+        assert!(lz77_huffman_decompress(&data, expected.len()).is_none());
+    }
+
+    #[test]
+    fn test_smb3_decompress_payload() {
+        let mut out = Vec::new();
+        assert!(smb3_decompress_payload(SMB2_COMPRESSION_NONE, b"abc", 3, &mut out).is_some());
+        let pattern = hex::decode("2a00000005000000").unwrap();
+        assert!(
+            smb3_decompress_payload(SMB2_COMPRESSION_PATTERN_V1, &pattern, 5, &mut out).is_some()
+        );
+        assert_eq!(out, b"abc*****");
+        assert!(
+            smb3_decompress_payload(SMB2_COMPRESSION_PATTERN_V1, &pattern, 4, &mut out).is_none()
+        );
+        assert!(smb3_decompress_payload(5, b"abc", 3, &mut out).is_none());
+        assert_eq!(out.len(), 8);
+    }
+}
@@ -0,0 +1,307 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! SMB3 key derivation and decryption of transform records, MS-SMB2
+//! sections 3.1.4.2 and 3.1.4.3.
+
+use crate::smb::smb3::Smb3TransformRecord;
+use aes::cipher::generic_array::GenericArray;
+use aes::BlockEncrypt;
+use aes::NewBlockCipher;
+use aes::{Aes128, Aes256};
+use aes_gcm::AeadInPlace;
+use aes_gcm::NewAead;
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use hkdf::hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+
+pub const SMB2_ENCRYPTION_AES128_CCM: u16 = 0x0001;
+pub const SMB2_ENCRYPTION_AES128_GCM: u16 = 0x0002;
+pub const SMB2_ENCRYPTION_AES256_CCM: u16 = 0x0003;
+pub const SMB2_ENCRYPTION_AES256_GCM: u16 = 0x0004;
+
+pub const SMB2_DIALECT_311: u16 = 0x0311;
+
+const AES128_KEY_LEN: usize = 16;
+const AES256_KEY_LEN: usize = 32;
+const AES_BLOCK_LEN: usize = 16;
+const SMB3_SIGNATURE_LEN: usize = 16;
+const SMB3_CCM_NONCE_LEN: usize = 11;
+const SMB3_GCM_NONCE_LEN: usize = 12;
+
+/// Decryption keys of a session.
+#[derive(Debug)]
+pub struct SMB3Keys {
+    pub c2s: Vec<u8>,
+    pub s2c: Vec<u8>,
+}
+
+/// SP800-108 KDF in counter mode, with HMAC-SHA256 as PRF.
+fn smb3_kdf(key: &[u8], label: &[u8], context: &[u8], len: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(len);
+    let mut i: u32 = 1;
+    while out.len() < len {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).ok()?;
+        mac.update(&i.to_be_bytes());
+        mac.update(label);
+        mac.update(&[0]);
+        mac.update(context);
+        mac.update(&((len * 8) as u32).to_be_bytes());
+        out.extend_from_slice(&mac.finalize().into_bytes());
+        i += 1;
+    }
+    out.truncate(len);
+    Some(out)
+}
+
+/// Derive the decryption keys of a session from its session key. For
+/// SMB 3.1.1 `preauth_hash` is the preauth integrity hash of the session
+/// setup, earlier dialects don't use it.
+pub fn smb3_derive_keys(
+    dialect: u16, cipher: u16, session_key: &[u8], preauth_hash: &[u8],
+) -> Option<SMB3Keys> {
+    // the AES-256 ciphers use the full session key, which may be shorter
+    // than their keys, and the others the first 16 bytes of it
+    let (key, len) = if matches!(
+        cipher,
+        SMB2_ENCRYPTION_AES256_CCM | SMB2_ENCRYPTION_AES256_GCM
+    ) {
+        if session_key.is_empty() {
+            return None;
+        }
+        (session_key.to_vec(), AES256_KEY_LEN)
+    } else {
+        // padded with zeros if shorter
+        let mut k = session_key.to_vec();
+        k.resize(AES128_KEY_LEN, 0);
+        (k, AES128_KEY_LEN)
+    };
+    let (c2s, s2c) = if dialect == SMB2_DIALECT_311 {
+        (
+            smb3_kdf(&key, b"SMBC2SCipherKey\0", preauth_hash, len)?,
+            smb3_kdf(&key, b"SMBS2CCipherKey\0", preauth_hash, len)?,
+        )
+    } else {
+        (
+            smb3_kdf(&key, b"SMB2AESCCM\0", b"ServerIn \0", len)?,
+            smb3_kdf(&key, b"SMB2AESCCM\0", b"ServerOut\0", len)?,
+        )
+    };
+    Some(SMB3Keys { c2s, s2c })
+}
+
+/// Update a preauth integrity hash with a message, MS-SMB2 3.2.5.2.
+pub fn smb3_preauth_hash(hash: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut h = Sha512::new();
+    h.update(hash);
+    h.update(message);
+    h.finalize().to_vec()
+}
+
+/// AES-CCM decryption (RFC 3610) in place, with a 16 bytes tag and a 11
+/// bytes nonce, so a 4 bytes message length. `encrypt` encrypts a block
+/// with the key. Returns true if the tag matches.
+fn aes_ccm_decrypt<F: Fn(&mut [u8; AES_BLOCK_LEN])>(
+    encrypt: F, nonce: &[u8], aad: &[u8], data: &mut [u8], tag: &[u8],
+) -> bool {
+    let mut ctr = [0u8; AES_BLOCK_LEN];
+    ctr[0] = 0x03;
+    ctr[1..12].copy_from_slice(nonce);
+    for (i, chunk) in data.chunks_mut(AES_BLOCK_LEN).enumerate() {
+        ctr[12..].copy_from_slice(&(i as u32 + 1).to_be_bytes());
+        let mut s = ctr;
+        encrypt(&mut s);
+        for (d, s) in chunk.iter_mut().zip(s.iter()) {
+            *d ^= s;
+        }
+    }
+
+    // CBC-MAC over the B0 block, the length prefixed aad and the
+    // plaintext, each zero padded to the block size
+    let mut x = [0u8; AES_BLOCK_LEN];
+    x[0] = 0x7b;
+    x[1..12].copy_from_slice(nonce);
+    x[12..].copy_from_slice(&(data.len() as u32).to_be_bytes());
+    encrypt(&mut x);
+    let mut adata = Vec::with_capacity(2 + aad.len());
+    adata.extend_from_slice(&(aad.len() as u16).to_be_bytes());
+    adata.extend_from_slice(aad);
+    for chunk in adata
+        .chunks(AES_BLOCK_LEN)
+        .chain(data.chunks(AES_BLOCK_LEN))
+    {
+        for (x, c) in x.iter_mut().zip(chunk.iter()) {
+            *x ^= c;
+        }
+        encrypt(&mut x);
+    }
+
+    ctr[12..].copy_from_slice(&[0, 0, 0, 0]);
+    encrypt(&mut ctr);
+    let mut diff = 0;
+    for ((t, s), x) in tag.iter().zip(ctr.iter()).zip(x.iter()) {
+        diff |= t ^ s ^ x;
+    }
+    diff == 0
+}
+
+/// Decrypt a transform record. Returns the plaintext SMB2 message(s), or
+/// None if the key doesn't fit the cipher or the signature doesn't match.
+pub fn smb3_decrypt(cipher: u16, key: &[u8], record: &Smb3TransformRecord) -> Option<Vec<u8>> {
+    if record.signature.len() != SMB3_SIGNATURE_LEN {
+        return None;
+    }
+    let mut data = record.enc_data.to_vec();
+    let ok = match cipher {
+        SMB2_ENCRYPTION_AES128_CCM if key.len() == AES128_KEY_LEN => {
+            let c = Aes128::new(GenericArray::from_slice(key));
+            aes_ccm_decrypt(
+                |b| c.encrypt_block(GenericArray::from_mut_slice(b)),
+                &record.nonce[..SMB3_CCM_NONCE_LEN],
+                record.aad,
+                &mut data,
+                record.signature,
+            )
+        }
+        SMB2_ENCRYPTION_AES256_CCM if key.len() == AES256_KEY_LEN => {
+            let c = Aes256::new(GenericArray::from_slice(key));
+            aes_ccm_decrypt(
+                |b| c.encrypt_block(GenericArray::from_mut_slice(b)),
+                &record.nonce[..SMB3_CCM_NONCE_LEN],
+                record.aad,
+                &mut data,
+                record.signature,
+            )
+        }
+        SMB2_ENCRYPTION_AES128_GCM if key.len() == AES128_KEY_LEN => {
+            Aes128Gcm::new(GenericArray::from_slice(key))
+                .decrypt_in_place_detached(
+                    GenericArray::from_slice(&record.nonce[..SMB3_GCM_NONCE_LEN]),
+                    record.aad,
+                    &mut data,
+                    GenericArray::from_slice(record.signature),
+                )
+                .is_ok()
+        }
+        SMB2_ENCRYPTION_AES256_GCM if key.len() == AES256_KEY_LEN => {
+            Aes256Gcm::new(GenericArray::from_slice(key))
+                .decrypt_in_place_detached(
+                    GenericArray::from_slice(&record.nonce[..SMB3_GCM_NONCE_LEN]),
+                    record.aad,
+                    &mut data,
+                    GenericArray::from_slice(record.signature),
+                )
+                .is_ok()
+        }
+        _ => false,
+    };
+    if ok {
+        Some(data)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smb::smb3::parse_smb3_transform_record;
+
+    #[test]
+    fn test_smb3_derive_keys() {
+        let session_key = hex::decode("270e1ba896585eeb7af3472d3b4c75a7").unwrap();
+        let keys = smb3_derive_keys(0x0300, SMB2_ENCRYPTION_AES128_CCM, &session_key, &[]).unwrap();
+        assert_eq!(hex::encode(keys.c2s), "cd25f0bccd79f6add9487f1d8778aaf8");
+        assert_eq!(hex::encode(keys.s2c), "e13c79ad34090979428b6dc42347b8fd");
+
+        let session_key: Vec<u8> = (0..32).collect();
+        let preauth_hash = Sha512::digest(b"abc");
+        let keys = smb3_derive_keys(
+            SMB2_DIALECT_311,
+            SMB2_ENCRYPTION_AES256_GCM,
+            &session_key,
+            &preauth_hash,
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(keys.c2s),
+            "08726acccf5127d5e5610a3748bfd7318e56d790aea9e6b3d6f1f2ba6dac42ca"
+        );
+        assert_eq!(
+            hex::encode(keys.s2c),
+            "680d55515e3c33d11d3274809075dc44acfd2b195cb37c44a0b05367609afd51"
+        );
+
+        // a 16 bytes session key still gives 32 bytes keys for AES-256
+        let keys = smb3_derive_keys(
+            SMB2_DIALECT_311,
+            SMB2_ENCRYPTION_AES256_GCM,
+            &session_key[..16],
+            &preauth_hash,
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(keys.c2s),
+            "71fd68c9b6e595dae904e49d6735a24962a5519fa31f4438a6f401d157ba6bbd"
+        );
+        assert_eq!(
+            hex::encode(keys.s2c),
+            "1d58d6dd46cd19606ee206d737253da735130d0623b4cd31aa16916a269458a6"
+        );
+        assert!(smb3_derive_keys(
+            SMB2_DIALECT_311,
+            SMB2_ENCRYPTION_AES256_CCM,
+            &[],
+            &preauth_hash
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_smb3_decrypt() {
+        let records = [
+            (SMB2_ENCRYPTION_AES128_CCM, "fd534d420a9bafb41647d667a006dcee1ca149fa0102030405060708090a0b00000000006f0000000000010015000000001000003f1ba99227c1b65833e0bfcd8262e31085686c97c56578d12f7f70efaf301d28f8d0368a21801b737637f9a250cc9a827563820744adb32b6ae42b889b0e1ef1d4ca6b534005094c62052cd1a95c6e840ca2771044e6b5f7157972f1e6120a22ab9066e6bc385325127c1ca67704d8"),
+            (SMB2_ENCRYPTION_AES128_GCM, "fd534d422ffa65f406f2a30126ed1121749769eb0102030405060708090a0b0c000000006f000000000001001500000000100000f856256433f55fc5385c0d45add86d35c1cbe75deea1ec6c5da53b57e4896c5cc56791476d933deeec417079dc8fec760790fe9c7270ed239123b0793748eac0a9aaebf585cbac506e3b8b0ba864d36860705261ff6768ffcfa57bbb72edd7b21ae27da47c21e2b64365c57983d812"),
+            (SMB2_ENCRYPTION_AES256_CCM, "fd534d42e90966f40c7300811ad58846238caa630102030405060708090a0b00000000006f0000000000010015000000001000000ba17ccfdaa93a57d4a5857aae1c09a564113bb0a5fb8099493856a1f66f223108cc55b5dd70656a841aaf85049f25f5ccfb748f9ad5fa7bbb24c523ddde65312c9d4a662d9cdf6b5872b781f276719a63c017fee7bd5d38c502960944f1dc1cb47ddde3aae07d8017a4883ae349b1"),
+            (SMB2_ENCRYPTION_AES256_GCM, "fd534d42a6b230810d44eb4221ee13d86179bd9c0102030405060708090a0b0c000000006f000000000001001500000000100000fbb91797ec94f0864ca263471013ea284243e1fe976d50c5a15ff9249eb676c584e49bdf6eb5ec9fb0acbae5c5dbf85d9ef64fe0d4993671367d0ccd6f01340c1f18674fd0dd7d972460dc1eda1877cfb443104191099fe00ef3e57f00914603c48b2e4e9f21600687afe151c29976"),
+        ];
+        for (cipher, record) in records {
+            let key_len =
+                if cipher == SMB2_ENCRYPTION_AES256_CCM || cipher == SMB2_ENCRYPTION_AES256_GCM {
+                    32
+                } else {
+                    16
+                };
+            let key: Vec<u8> = (0..key_len).collect();
+            let mut data = hex::decode(record).unwrap();
+            let (_, r) = parse_smb3_transform_record(&data).unwrap();
+            assert_eq!(r.session_id, 0x100000000015);
+            let plain = smb3_decrypt(cipher, &key, &r).unwrap();
+            assert_eq!(&plain[..4], b"\xfeSMB");
+            assert_eq!(
+                &plain[64..],
+                b"hello, world! this is a test of SMB3 encryption"
+            );
+            assert!(smb3_decrypt(cipher, &key[..8], &r).is_none());
+
+            let last = data.len() - 1;
+            data[last] ^= 1;
+            let (_, r) = parse_smb3_transform_record(&data).unwrap();
+            assert!(smb3_decrypt(cipher, &key, &r).is_none());
+        }
+    }
+}
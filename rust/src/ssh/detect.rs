@@ -15,9 +15,23 @@
  * 02110-1301, USA.
  */
 
-use super::ssh::SSHTransaction;
+use super::ssh::{SSHConnectionState, SSHTransaction, SshNegotiatedDirection, ALPROTO_SSH};
+use crate::core::{STREAM_TOCLIENT, STREAM_TOSERVER};
+use crate::detect::{helper_keyword_register_sticky_buffer, SigTableElmtStickyBuffer};
 use crate::direction::Direction;
+use std::os::raw::{c_int, c_void};
 use std::ptr;
+use suricata_sys::sys::{
+    DetectEngineCtx, SCDetectBufferSetActiveList, SCDetectHelperBufferProgressMpmRegister,
+    SCDetectSignatureSetAppProto, Signature,
+};
+
+static mut G_SSH_KEX_ALGO_BUFFER_ID: c_int = 0;
+static mut G_SSH_HOSTKEY_ALGO_BUFFER_ID: c_int = 0;
+static mut G_SSH_CIPHER_BUFFER_ID: c_int = 0;
+static mut G_SSH_MAC_BUFFER_ID: c_int = 0;
+static mut G_SSH_COMPRESSION_BUFFER_ID: c_int = 0;
+static mut G_SSH_HOSTKEY_FINGERPRINT_BUFFER_ID: c_int = 0;
 
 #[no_mangle]
 pub unsafe extern "C" fn SCSshTxGetProtocol(
@@ -134,3 +148,217 @@ pub unsafe extern "C" fn SCSshTxGetHasshString(
 
     return 0;
 }
+
+unsafe fn ssh_algo_setup(de: *mut DetectEngineCtx, s: *mut Signature, buffer_id: c_int) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_SSH) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, buffer_id) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe fn ssh_algo_set_buffer(
+    algo: Option<&[u8]>, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    if let Some(algo) = algo {
+        *buffer = algo.as_ptr();
+        *buffer_len = algo.len() as u32;
+        return true;
+    }
+    *buffer = ptr::null();
+    *buffer_len = 0;
+    return false;
+}
+
+unsafe extern "C" fn ssh_kex_algo_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    ssh_algo_setup(de, s, G_SSH_KEX_ALGO_BUFFER_ID)
+}
+
+unsafe extern "C" fn ssh_kex_algo_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, SSHTransaction);
+    let algo = tx.negotiated().and_then(|n| n.kex);
+    ssh_algo_set_buffer(algo, buffer, buffer_len)
+}
+
+unsafe extern "C" fn ssh_hostkey_algo_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    ssh_algo_setup(de, s, G_SSH_HOSTKEY_ALGO_BUFFER_ID)
+}
+
+unsafe extern "C" fn ssh_hostkey_algo_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, SSHTransaction);
+    let algo = tx.negotiated().and_then(|n| n.host_key);
+    ssh_algo_set_buffer(algo, buffer, buffer_len)
+}
+
+/// Get an algorithm of the direction the inspected packet goes in.
+unsafe fn ssh_direction_algo_get(
+    tx: *const c_void, flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+    get: for<'a> fn(&SshNegotiatedDirection<'a>) -> Option<&'a [u8]>,
+) -> bool {
+    let tx = cast_pointer!(tx, SSHTransaction);
+    let algo = tx.negotiated().and_then(|n| {
+        if flags & Direction::ToClient as u8 != 0 {
+            get(&n.server_to_client)
+        } else {
+            get(&n.client_to_server)
+        }
+    });
+    ssh_algo_set_buffer(algo, buffer, buffer_len)
+}
+
+unsafe extern "C" fn ssh_cipher_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    ssh_algo_setup(de, s, G_SSH_CIPHER_BUFFER_ID)
+}
+
+unsafe extern "C" fn ssh_cipher_get(
+    tx: *const c_void, flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    ssh_direction_algo_get(tx, flags, buffer, buffer_len, |d| d.cipher)
+}
+
+unsafe extern "C" fn ssh_mac_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    ssh_algo_setup(de, s, G_SSH_MAC_BUFFER_ID)
+}
+
+unsafe extern "C" fn ssh_mac_get(
+    tx: *const c_void, flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    ssh_direction_algo_get(tx, flags, buffer, buffer_len, |d| d.mac)
+}
+
+unsafe extern "C" fn ssh_compression_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    ssh_algo_setup(de, s, G_SSH_COMPRESSION_BUFFER_ID)
+}
+
+unsafe extern "C" fn ssh_compression_get(
+    tx: *const c_void, flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    ssh_direction_algo_get(tx, flags, buffer, buffer_len, |d| d.compression)
+}
+
+unsafe extern "C" fn ssh_hostkey_fingerprint_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    ssh_algo_setup(de, s, G_SSH_HOSTKEY_FINGERPRINT_BUFFER_ID)
+}
+
+unsafe extern "C" fn ssh_hostkey_fingerprint_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, SSHTransaction);
+    let fingerprint = tx.srv_hdr.host_key.as_ref().map(|k| k.sha256.as_bytes());
+    ssh_algo_set_buffer(fingerprint, buffer, buffer_len)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCDetectSshRegister() {
+    // the algorithms are known once both KEXINIT are seen, the NEWKEYS
+    // of both sides follow them
+    let progress = SSHConnectionState::SshStateFinished as c_int;
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("ssh.kex_algo"),
+        desc: String::from("sticky buffer to match on the negotiated SSH key exchange algorithm"),
+        url: String::from("/rules/ssh-keywords.html#ssh-kex-algo"),
+        setup: ssh_kex_algo_setup,
+    };
+    let _g_ssh_kex_algo_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_SSH_KEX_ALGO_BUFFER_ID = SCDetectHelperBufferProgressMpmRegister(
+        b"ssh.kex_algo\0".as_ptr() as *const libc::c_char,
+        b"ssh negotiated key exchange algorithm\0".as_ptr() as *const libc::c_char,
+        ALPROTO_SSH,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(ssh_kex_algo_get),
+        progress,
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("ssh.hostkey_algo"),
+        desc: String::from("sticky buffer to match on the negotiated SSH host key algorithm"),
+        url: String::from("/rules/ssh-keywords.html#ssh-hostkey-algo"),
+        setup: ssh_hostkey_algo_setup,
+    };
+    let _g_ssh_hostkey_algo_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_SSH_HOSTKEY_ALGO_BUFFER_ID = SCDetectHelperBufferProgressMpmRegister(
+        b"ssh.hostkey_algo\0".as_ptr() as *const libc::c_char,
+        b"ssh negotiated host key algorithm\0".as_ptr() as *const libc::c_char,
+        ALPROTO_SSH,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(ssh_hostkey_algo_get),
+        progress,
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("ssh.cipher"),
+        desc: String::from("sticky buffer to match on the negotiated SSH cipher"),
+        url: String::from("/rules/ssh-keywords.html#ssh-cipher"),
+        setup: ssh_cipher_setup,
+    };
+    let _g_ssh_cipher_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_SSH_CIPHER_BUFFER_ID = SCDetectHelperBufferProgressMpmRegister(
+        b"ssh.cipher\0".as_ptr() as *const libc::c_char,
+        b"ssh negotiated cipher\0".as_ptr() as *const libc::c_char,
+        ALPROTO_SSH,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(ssh_cipher_get),
+        progress,
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("ssh.mac"),
+        desc: String::from("sticky buffer to match on the negotiated SSH MAC algorithm"),
+        url: String::from("/rules/ssh-keywords.html#ssh-mac"),
+        setup: ssh_mac_setup,
+    };
+    let _g_ssh_mac_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_SSH_MAC_BUFFER_ID = SCDetectHelperBufferProgressMpmRegister(
+        b"ssh.mac\0".as_ptr() as *const libc::c_char,
+        b"ssh negotiated mac algorithm\0".as_ptr() as *const libc::c_char,
+        ALPROTO_SSH,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(ssh_mac_get),
+        progress,
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("ssh.compression"),
+        desc: String::from("sticky buffer to match on the negotiated SSH compression algorithm"),
+        url: String::from("/rules/ssh-keywords.html#ssh-compression"),
+        setup: ssh_compression_setup,
+    };
+    let _g_ssh_compression_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_SSH_COMPRESSION_BUFFER_ID = SCDetectHelperBufferProgressMpmRegister(
+        b"ssh.compression\0".as_ptr() as *const libc::c_char,
+        b"ssh negotiated compression algorithm\0".as_ptr() as *const libc::c_char,
+        ALPROTO_SSH,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(ssh_compression_get),
+        progress,
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("ssh.hostkey_fingerprint"),
+        desc: String::from("sticky buffer to match on the SHA256 fingerprint of the SSH host key"),
+        url: String::from("/rules/ssh-keywords.html#ssh-hostkey-fingerprint"),
+        setup: ssh_hostkey_fingerprint_setup,
+    };
+    let _g_ssh_hostkey_fingerprint_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_SSH_HOSTKEY_FINGERPRINT_BUFFER_ID = SCDetectHelperBufferProgressMpmRegister(
+        b"ssh.hostkey_fingerprint\0".as_ptr() as *const libc::c_char,
+        b"ssh host key fingerprint\0".as_ptr() as *const libc::c_char,
+        ALPROTO_SSH,
+        STREAM_TOCLIENT,
+        Some(ssh_hostkey_fingerprint_get),
+        progress,
+    );
+}
@@ -0,0 +1,345 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Metadata of the encrypted part of a SSH session.
+//!
+//! Nothing is decrypted: the sizes and directions of the encrypted
+//! records are used to guess the outcome of the authentication and the
+//! kind of session.
+
+use super::ssh::{SSHEvent, SshNegotiatedDirection};
+use crate::applayer::{AppLayerResult, AppLayerTxData};
+
+/// Records up to this size on the wire can hold a single keystroke or
+/// its echo.
+const SSH_KEYSTROKE_MAX_LEN: u32 = 100;
+/// Records from this size on the wire are part of a bulk transfer.
+const SSH_BULK_MIN_LEN: u32 = 1024;
+/// The SSH_MSG_SERVICE_ACCEPT of the server is smaller than this. The
+/// larger records before it are SSH_MSG_EXT_INFO.
+const SSH_SERVICE_ACCEPT_MAX_LEN: u32 = 128;
+/// SSH_MSG_USERAUTH_SUCCESS is one padding block smaller than
+/// SSH_MSG_SERVICE_ACCEPT, whatever the cipher.
+const SSH_AUTH_SUCCESS_DELTA: u32 = 16;
+/// Longest record length accepted when the lengths are sent in clear,
+/// the length is not trusted for framing beyond it.
+const SSH_MAX_RECORD_LEN: u32 = 256 * 1024;
+
+const SSH_AUTH_FAILURES_THRESHOLD: u32 = 3;
+const SSH_REVERSE_SHELL_KEYSTROKES: u64 = 10;
+const SSH_INTERACTIVE_KEYSTROKES: u64 = 8;
+const SSH_BULK_RECORDS: u64 = 16;
+
+/// Get the length of the MAC following each record if the cipher and
+/// MAC algorithms leave the record length in clear. These are the
+/// AES-GCM ciphers and the encrypt-then-mac MACs.
+pub fn ssh_clear_length_mac_len(algorithms: &SshNegotiatedDirection) -> Option<u32> {
+    match algorithms.cipher? {
+        b"aes128-gcm@openssh.com" | b"aes256-gcm@openssh.com" => return Some(16),
+        b"chacha20-poly1305@openssh.com" => return None,
+        _ => {}
+    }
+    match algorithms.mac? {
+        b"hmac-sha2-256-etm@openssh.com" => Some(32),
+        b"hmac-sha2-512-etm@openssh.com" => Some(64),
+        b"hmac-sha1-etm@openssh.com" => Some(20),
+        b"hmac-md5-etm@openssh.com" => Some(16),
+        b"hmac-sha1-96-etm@openssh.com" | b"hmac-md5-96-etm@openssh.com" => Some(12),
+        b"umac-64-etm@openssh.com" => Some(8),
+        b"umac-128-etm@openssh.com" => Some(16),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SshEncryptedDirection {
+    pub records: u64,
+    pub bytes: u64,
+    /// Small records answered by a small record of the other side
+    pub keystrokes: u64,
+    pub bulk_records: u64,
+    /// MAC length if the records can be delimited with their clear text
+    /// length. Otherwise each chunk of data counts as one record, which
+    /// usually is one TCP segment.
+    framing: Option<u32>,
+    record_left: u32,
+}
+
+impl SshEncryptedDirection {
+    pub fn set_framing(&mut self, mac_len: Option<u32>) {
+        self.framing = mac_len;
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SshAuthResult {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Default)]
+pub struct SshEncryptedSession {
+    pub client: SshEncryptedDirection,
+    pub server: SshEncryptedDirection,
+    /// Size of the SSH_MSG_SERVICE_ACCEPT record
+    accept_len: u32,
+    /// Size of the SSH_MSG_USERAUTH_FAILURE records. The first one is
+    /// the answer to the `none` method the client queries first.
+    failure_len: u32,
+    pub auth_failures: u32,
+    pub auth_success: bool,
+    /// Side of the last small record, if the last record was small
+    small_to_client: Option<bool>,
+    reverse_shell: bool,
+}
+
+impl SshEncryptedSession {
+    pub fn auth_result(&self) -> Option<SshAuthResult> {
+        if self.auth_success {
+            Some(SshAuthResult::Success)
+        } else if self.auth_failures > 0 {
+            Some(SshAuthResult::Failure)
+        } else {
+            None
+        }
+    }
+
+    /// Kind of the session once authenticated: "interactive" or "bulk".
+    pub fn session_type(&self) -> Option<&'static str> {
+        let keystrokes = self.client.keystrokes + self.server.keystrokes;
+        let bulk = std::cmp::max(self.client.bulk_records, self.server.bulk_records);
+        if bulk >= SSH_BULK_RECORDS && bulk > keystrokes {
+            Some("bulk")
+        } else if keystrokes >= SSH_INTERACTIVE_KEYSTROKES {
+            Some("interactive")
+        } else {
+            None
+        }
+    }
+
+    pub fn parse(
+        &mut self, mut input: &[u8], to_client: bool, offset: u32, tx_data: &mut AppLayerTxData,
+    ) -> AppLayerResult {
+        let il = input.len();
+        while !input.is_empty() {
+            let dir = if to_client {
+                &mut self.server
+            } else {
+                &mut self.client
+            };
+            let mac_len = match dir.framing {
+                Some(mac_len) => mac_len,
+                None => {
+                    self.record(input.len() as u32, to_client, tx_data);
+                    break;
+                }
+            };
+            if dir.record_left > 0 {
+                let skip = std::cmp::min(dir.record_left as usize, input.len());
+                dir.record_left -= skip as u32;
+                input = &input[skip..];
+                continue;
+            }
+            if input.len() < 4 {
+                return AppLayerResult::incomplete(offset + (il - input.len()) as u32, 4);
+            }
+            let len = u32::from_be_bytes([input[0], input[1], input[2], input[3]]);
+            if !(5..=SSH_MAX_RECORD_LEN).contains(&len) {
+                SCLogDebug!("SSH encrypted record length {} out of range", len);
+                dir.framing = None;
+                continue;
+            }
+            dir.record_left = 4 + len + mac_len;
+            self.record(4 + len + mac_len, to_client, tx_data);
+        }
+        return AppLayerResult::ok();
+    }
+
+    fn record(&mut self, len: u32, to_client: bool, tx_data: &mut AppLayerTxData) {
+        let dir = if to_client {
+            &mut self.server
+        } else {
+            &mut self.client
+        };
+        dir.records += 1;
+        dir.bytes += len as u64;
+        if len >= SSH_BULK_MIN_LEN {
+            dir.bulk_records += 1;
+        }
+        if !self.auth_success {
+            if to_client {
+                self.auth_record(len, tx_data);
+            }
+            return;
+        }
+        if len > SSH_KEYSTROKE_MAX_LEN {
+            self.small_to_client = None;
+            return;
+        }
+        match self.small_to_client {
+            // a keystroke from the other side and its echo
+            Some(from_server) if from_server != to_client => {
+                if from_server {
+                    self.server.keystrokes += 1;
+                } else {
+                    self.client.keystrokes += 1;
+                }
+                self.small_to_client = None;
+                if !self.reverse_shell
+                    && self.server.keystrokes >= SSH_REVERSE_SHELL_KEYSTROKES
+                    && self.server.keystrokes > self.client.keystrokes
+                {
+                    self.reverse_shell = true;
+                    tx_data.set_event(SSHEvent::EncryptedReverseShell as u8);
+                }
+            }
+            _ => {
+                self.small_to_client = Some(to_client);
+            }
+        }
+    }
+
+    /// Guess the authentication outcome from the server records.
+    fn auth_record(&mut self, len: u32, tx_data: &mut AppLayerTxData) {
+        if self.accept_len == 0 {
+            if len < SSH_SERVICE_ACCEPT_MAX_LEN {
+                self.accept_len = len;
+            }
+            return;
+        }
+        if len + SSH_AUTH_SUCCESS_DELTA == self.accept_len {
+            self.auth_success = true;
+            if self.auth_failures >= SSH_AUTH_FAILURES_THRESHOLD {
+                tx_data.set_event(SSHEvent::EncryptedAuthSuccessAfterFailures as u8);
+            }
+            return;
+        }
+        if self.failure_len == 0 {
+            self.failure_len = len;
+        } else if len == self.failure_len {
+            self.auth_failures += 1;
+            if self.auth_failures == SSH_AUTH_FAILURES_THRESHOLD {
+                tx_data.set_event(SSHEvent::EncryptedAuthFailures as u8);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(session: &mut SshEncryptedSession, sizes: &[(bool, u32)]) {
+        let mut tx_data = AppLayerTxData::new();
+        for &(to_client, len) in sizes {
+            let data = vec![0; len as usize];
+            session.parse(&data, to_client, 0, &mut tx_data);
+        }
+    }
+
+    #[test]
+    fn test_ssh_encrypted_auth() {
+        let mut session = SshEncryptedSession::default();
+        records(
+            &mut session,
+            &[
+                // EXT_INFO, SERVICE_REQUEST, SERVICE_ACCEPT
+                (true, 276),
+                (false, 52),
+                (true, 52),
+                // none method
+                (false, 68),
+                (true, 68),
+                // two wrong passwords
+                (false, 100),
+                (true, 68),
+                (false, 100),
+                (true, 68),
+                (false, 100),
+                (true, 36),
+            ],
+        );
+        assert!(session.auth_success);
+        assert_eq!(session.auth_failures, 2);
+        assert_eq!(session.auth_result(), Some(SshAuthResult::Success));
+        assert_eq!(session.server.records, 6);
+        assert_eq!(session.client.bytes, 52 + 68 + 300);
+    }
+
+    #[test]
+    fn test_ssh_encrypted_keystrokes() {
+        let mut session = SshEncryptedSession {
+            auth_success: true,
+            ..Default::default()
+        };
+        for _ in 0..SSH_INTERACTIVE_KEYSTROKES {
+            records(&mut session, &[(false, 36), (true, 36)]);
+        }
+        // output of a command
+        records(&mut session, &[(false, 36), (true, 1500)]);
+        assert_eq!(session.client.keystrokes, SSH_INTERACTIVE_KEYSTROKES);
+        assert_eq!(session.server.keystrokes, 0);
+        assert_eq!(session.session_type(), Some("interactive"));
+        assert!(!session.reverse_shell);
+
+        let mut session = SshEncryptedSession {
+            auth_success: true,
+            ..Default::default()
+        };
+        for _ in 0..SSH_REVERSE_SHELL_KEYSTROKES {
+            records(&mut session, &[(true, 36), (false, 36)]);
+        }
+        assert_eq!(session.server.keystrokes, SSH_REVERSE_SHELL_KEYSTROKES);
+        assert!(session.reverse_shell);
+    }
+
+    #[test]
+    fn test_ssh_encrypted_framing() {
+        let mut session = SshEncryptedSession {
+            auth_success: true,
+            ..Default::default()
+        };
+        let mac_len = ssh_clear_length_mac_len(&SshNegotiatedDirection {
+            cipher: Some(b"aes128-gcm@openssh.com"),
+            ..Default::default()
+        });
+        assert_eq!(mac_len, Some(16));
+        session.client.set_framing(mac_len);
+        let mut tx_data = AppLayerTxData::new();
+        // two records of 16 bytes, then the start of a third one
+        let mut data = Vec::new();
+        for _ in 0..2 {
+            data.extend_from_slice(&[0, 0, 0, 16]);
+            data.extend_from_slice(&[0; 32]);
+        }
+        data.extend_from_slice(&[0, 0]);
+        let r = session.parse(&data, false, 10, &mut tx_data);
+        assert_eq!(r, AppLayerResult::incomplete(10 + 2 * 36, 4));
+        assert_eq!(session.client.records, 2);
+        assert_eq!(session.client.bytes, 72);
+        // invalid length, falls back to counting chunks
+        let r = session.parse(&[0xff, 0xff, 0xff, 0xff, 0], false, 0, &mut tx_data);
+        assert_eq!(r, AppLayerResult::ok());
+        assert_eq!(session.client.records, 3);
+        let algorithms = SshNegotiatedDirection {
+            cipher: Some(b"aes128-ctr"),
+            mac: Some(b"hmac-sha2-256"),
+            compression: None,
+        };
+        assert_eq!(ssh_clear_length_mac_len(&algorithms), None);
+    }
+}
@@ -15,9 +15,59 @@
  * 02110-1301, USA.
  */
 
-use super::ssh::{SSHTransaction, SSH_MAX_BANNER_LEN};
+use super::encrypted::{SshAuthResult, SshEncryptedDirection, SshEncryptedSession};
+use super::ssh::{SSHTransaction, SshNegotiatedDirection, SSH_MAX_BANNER_LEN};
 use crate::jsonbuilder::{JsonBuilder, JsonError};
 
+fn log_algorithm(js: &mut JsonBuilder, name: &str, algo: Option<&[u8]>) -> Result<(), JsonError> {
+    if let Some(algo) = algo {
+        js.set_string_from_bytes(name, algo)?;
+    }
+    Ok(())
+}
+
+fn log_direction(
+    js: &mut JsonBuilder, name: &str, algos: &SshNegotiatedDirection,
+) -> Result<(), JsonError> {
+    js.open_object(name)?;
+    log_algorithm(js, "cipher", algos.cipher)?;
+    log_algorithm(js, "mac", algos.mac)?;
+    log_algorithm(js, "compression", algos.compression)?;
+    js.close()?;
+    Ok(())
+}
+
+fn log_encrypted_direction(
+    js: &mut JsonBuilder, name: &str, dir: &SshEncryptedDirection,
+) -> Result<(), JsonError> {
+    js.open_object(name)?;
+    js.set_uint("records", dir.records)?;
+    js.set_uint("bytes", dir.bytes)?;
+    js.set_uint("keystrokes", dir.keystrokes)?;
+    js.close()?;
+    Ok(())
+}
+
+fn log_encrypted(js: &mut JsonBuilder, session: &SshEncryptedSession) -> Result<(), JsonError> {
+    js.open_object("encrypted")?;
+    log_encrypted_direction(js, "client", &session.client)?;
+    log_encrypted_direction(js, "server", &session.server)?;
+    if let Some(result) = session.auth_result() {
+        js.open_object("auth")?;
+        match result {
+            SshAuthResult::Success => js.set_string("result", "success")?,
+            SshAuthResult::Failure => js.set_string("result", "failure")?,
+        };
+        js.set_uint("failures", session.auth_failures as u64)?;
+        js.close()?;
+    }
+    if let Some(session_type) = session.session_type() {
+        js.set_string("session", session_type)?;
+    }
+    js.close()?;
+    Ok(())
+}
+
 fn log_ssh(tx: &SSHTransaction, js: &mut JsonBuilder) -> Result<bool, JsonError> {
     js.open_object("ssh")?;
     if tx.cli_hdr.protover.is_empty() && tx.srv_hdr.protover.is_empty() {
@@ -73,8 +123,26 @@ fn log_ssh(tx: &SSHTransaction, js: &mut JsonBuilder) -> Result<bool, JsonError>
             }
             js.close()?;
         }
+        if let Some(ref host_key) = tx.srv_hdr.host_key {
+            js.open_object("host_key")?;
+            js.set_string_from_bytes("type", &host_key.key_type)?;
+            js.set_string("fingerprint_sha256", &host_key.sha256)?;
+            js.set_string("fingerprint_md5", &host_key.md5)?;
+            js.close()?;
+        }
         js.close()?;
     }
+    if let Some(negotiated) = tx.negotiated() {
+        js.open_object("negotiated")?;
+        log_algorithm(js, "kex", negotiated.kex)?;
+        log_algorithm(js, "host_key", negotiated.host_key)?;
+        log_direction(js, "client_to_server", &negotiated.client_to_server)?;
+        log_direction(js, "server_to_client", &negotiated.server_to_client)?;
+        js.close()?;
+    }
+    if let Some(ref session) = tx.encrypted {
+        log_encrypted(js, session)?;
+    }
     js.close()?;
     return Ok(true);
 }
@@ -18,6 +18,7 @@
 //! SSH application layer, logger, detection and parser module.
 
 pub mod detect;
+mod encrypted;
 pub mod logger;
 mod parser;
 pub mod ssh;
@@ -15,6 +15,7 @@
  * 02110-1301, USA.
  */
 
+use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine};
 use digest::Digest;
 use digest::Update;
 use md5::Md5;
@@ -26,6 +27,7 @@ use nom7::multi::length_data;
 use nom7::number::streaming::{be_u32, be_u8};
 use nom7::sequence::terminated;
 use nom7::IResult;
+use sha2::Sha256;
 use std::fmt;
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
@@ -40,6 +42,7 @@ pub enum MessageCode {
     NewKeys,
     KexdhInit,
     KexdhReply,
+    KexdhGexReply,
 
     Undefined(u8),
 }
@@ -57,6 +60,7 @@ impl MessageCode {
             21 => MessageCode::NewKeys,
             30 => MessageCode::KexdhInit,
             31 => MessageCode::KexdhReply,
+            33 => MessageCode::KexdhGexReply,
             _ => MessageCode::Undefined(value),
         }
     }
@@ -201,6 +205,53 @@ impl SshPacketKeyExchange<'_> {
     }
 }
 
+/// Algorithm name-lists of a KEXINIT message, kept until the KEXINIT of
+/// the other side is seen.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SshKexAlgorithms {
+    pub kex: Vec<u8>,
+    pub host_key: Vec<u8>,
+    pub encr_client_to_server: Vec<u8>,
+    pub encr_server_to_client: Vec<u8>,
+    pub mac_client_to_server: Vec<u8>,
+    pub mac_server_to_client: Vec<u8>,
+    pub comp_client_to_server: Vec<u8>,
+    pub comp_server_to_client: Vec<u8>,
+}
+
+impl From<&SshPacketKeyExchange<'_>> for SshKexAlgorithms {
+    fn from(kex: &SshPacketKeyExchange<'_>) -> Self {
+        Self {
+            kex: kex.kex_algs.to_vec(),
+            host_key: kex.server_host_key_algs.to_vec(),
+            encr_client_to_server: kex.encr_algs_client_to_server.to_vec(),
+            encr_server_to_client: kex.encr_algs_server_to_client.to_vec(),
+            mac_client_to_server: kex.mac_algs_client_to_server.to_vec(),
+            mac_server_to_client: kex.mac_algs_server_to_client.to_vec(),
+            comp_client_to_server: kex.comp_algs_client_to_server.to_vec(),
+            comp_server_to_client: kex.comp_algs_server_to_client.to_vec(),
+        }
+    }
+}
+
+/// Get the algorithm chosen from two name-lists: the first algorithm of
+/// the client list that the server supports (RFC 4253 section 7.1).
+pub fn ssh_negotiate_algorithm<'a>(client: &'a [u8], server: &[u8]) -> Option<&'a [u8]> {
+    client
+        .split(|&c| c == b',')
+        .filter(|alg| !alg.is_empty())
+        .find(|alg| server.split(|&c| c == b',').any(|s| s == *alg))
+}
+
+/// Ciphers with built-in authentication, the MAC algorithm is not used
+/// with them.
+pub fn ssh_cipher_is_aead(cipher: &[u8]) -> bool {
+    matches!(
+        cipher,
+        b"chacha20-poly1305@openssh.com" | b"aes128-gcm@openssh.com" | b"aes256-gcm@openssh.com"
+    )
+}
+
 #[inline]
 fn parse_string(i: &[u8]) -> IResult<&[u8], &[u8]> {
     length_data(be_u32)(i)
@@ -240,6 +291,38 @@ pub fn ssh_parse_key_exchange(i: &[u8]) -> IResult<&[u8], SshPacketKeyExchange>
     ))
 }
 
+/// Get the host key blob from a key exchange reply. The DH, ECDH and
+/// DH group exchange replies all start with it.
+pub fn ssh_parse_kex_reply(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    parse_string(i)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SshHostKey {
+    pub key_type: Vec<u8>,
+    /// Fingerprints in the format of OpenSSH
+    pub sha256: String,
+    pub md5: String,
+}
+
+pub fn ssh_parse_host_key(i: &[u8]) -> Option<SshHostKey> {
+    let (_, key_type) = parse_string(i).ok()?;
+    if key_type.is_empty() {
+        return None;
+    }
+    let sha256 = format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(i)));
+    let md5 = Md5::digest(i)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<String>>()
+        .join(":");
+    Some(SshHostKey {
+        key_type: key_type.to_vec(),
+        sha256,
+        md5: format!("MD5:{}", md5),
+    })
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -769,4 +852,49 @@ mod tests {
             panic!("ssh_parse_key_exchange() parsed malicious key_exchange");
         }
     }
+
+    #[test]
+    fn test_negotiate_algorithm() {
+        let client = b"curve25519-sha256,ecdh-sha2-nistp256,ext-info-c";
+        let server = b"ecdh-sha2-nistp256,curve25519-sha256,kex-strict-s-v00@openssh.com";
+        assert_eq!(
+            ssh_negotiate_algorithm(client, server),
+            Some(&b"curve25519-sha256"[..])
+        );
+        assert_eq!(
+            ssh_negotiate_algorithm(b"aes128-ctr", b"aes128-cbc,aes128-ctr"),
+            Some(&b"aes128-ctr"[..])
+        );
+        assert_eq!(ssh_negotiate_algorithm(b"none", b"zlib"), None);
+        assert_eq!(ssh_negotiate_algorithm(b"", b""), None);
+        // names are matched as a whole
+        assert_eq!(ssh_negotiate_algorithm(b"aes128", b"aes128-ctr"), None);
+    }
+
+    #[test]
+    fn test_parse_host_key() {
+        let key: &[u8] = &[
+            0x00, 0x00, 0x00, 0x0b, 0x73, 0x73, 0x68, 0x2d, 0x65, 0x64, 0x32, 0x35, 0x35, 0x31,
+            0x39, 0x00, 0x00, 0x00, 0x20, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+            0x6a, 0x6b, 0x6c, 0x6d, 0x6e, 0x6f, 0x70, 0x71, 0x72, 0x73, 0x74, 0x75, 0x76, 0x77,
+            0x78, 0x79, 0x7a, 0x30, 0x31, 0x32, 0x33, 0x34, 0x35,
+        ];
+        let mut reply = vec![0x00, 0x00, 0x00, key.len() as u8];
+        reply.extend_from_slice(key);
+        reply.extend_from_slice(&[0x00, 0x00, 0x00, 0x01, 0x02]);
+        let (rem, blob) = ssh_parse_kex_reply(&reply).unwrap();
+        assert_eq!(blob, key);
+        assert_eq!(rem, &[0x00, 0x00, 0x00, 0x01, 0x02]);
+        let host_key = ssh_parse_host_key(blob).unwrap();
+        assert_eq!(host_key.key_type, b"ssh-ed25519");
+        assert_eq!(
+            host_key.sha256,
+            "SHA256:mTB0raVQ96HxbqU8+YC8O/zgPOeWu+1fqOI7c73T7bk"
+        );
+        assert_eq!(
+            host_key.md5,
+            "MD5:5f:bb:15:a2:ed:de:37:01:33:b5:92:06:37:4b:7f:e0"
+        );
+        assert!(ssh_parse_host_key(&[0x00, 0x00, 0x00, 0x00]).is_none());
+    }
 }
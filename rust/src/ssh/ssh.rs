@@ -15,6 +15,7 @@
  * 02110-1301, USA.
  */
 
+use super::encrypted::{ssh_clear_length_mac_len, SshEncryptedSession};
 use super::parser;
 use crate::applayer::*;
 use crate::core::*;
@@ -33,9 +34,10 @@ pub enum SshEncryptionHandling {
     SSH_HANDLE_ENCRYPTION_TRACK_ONLY = 0, // Disable raw content inspection, continue tracking
     SSH_HANDLE_ENCRYPTION_BYPASS = 1,     // Skip processing of flow, bypass if possible
     SSH_HANDLE_ENCRYPTION_FULL = 2,       // Handle fully like any other protocol
+    SSH_HANDLE_ENCRYPTION_METADATA = 3,   // Disable raw content inspection, track record sizes
 }
 
-static mut ALPROTO_SSH: AppProto = ALPROTO_UNKNOWN;
+pub(super) static mut ALPROTO_SSH: AppProto = ALPROTO_UNKNOWN;
 static HASSH_ENABLED: AtomicBool = AtomicBool::new(false);
 
 static mut ENCRYPTION_BYPASS_ENABLED: SshEncryptionHandling =
@@ -49,6 +51,10 @@ fn encryption_bypass_mode() -> SshEncryptionHandling {
     unsafe { ENCRYPTION_BYPASS_ENABLED }
 }
 
+fn encryption_metadata_enabled() -> bool {
+    encryption_bypass_mode() == SshEncryptionHandling::SSH_HANDLE_ENCRYPTION_METADATA
+}
+
 #[derive(AppLayerFrameType)]
 pub enum SshFrameType {
     RecordHdr,
@@ -62,6 +68,9 @@ pub enum SSHEvent {
     LongBanner,
     InvalidRecord,
     LongKexRecord,
+    EncryptedAuthFailures,
+    EncryptedAuthSuccessAfterFailures,
+    EncryptedReverseShell,
 }
 
 #[repr(u8)]
@@ -72,6 +81,9 @@ pub enum SSHConnectionState {
     SshStateBannerWaitEol = 1,
     SshStateBannerDone = 2,
     SshStateFinished = 3,
+    /// Completion state when the encrypted records are tracked: the
+    /// transaction is never done before the end of the flow.
+    SshStateClosed = 4,
 }
 
 pub const SSH_MAX_BANNER_LEN: usize = 256;
@@ -88,6 +100,11 @@ pub struct SshHeader {
 
     pub hassh: Vec<u8>,
     pub hassh_string: Vec<u8>,
+
+    /// Algorithms offered in the KEXINIT of this side
+    pub kex_algorithms: Option<parser::SshKexAlgorithms>,
+    /// Host key from the key exchange reply, server side only
+    pub host_key: Option<parser::SshHostKey>,
 }
 
 impl Default for SshHeader {
@@ -108,7 +125,78 @@ impl SshHeader {
 
             hassh: Vec::new(),
             hassh_string: Vec::new(),
+
+            kex_algorithms: None,
+            host_key: None,
+        }
+    }
+
+    fn handle_kexinit(&mut self, key_exchange: &parser::SshPacketKeyExchange, resp: bool) {
+        if hassh_is_enabled() {
+            key_exchange.generate_hassh(&mut self.hassh_string, &mut self.hassh, &resp);
         }
+        if self.kex_algorithms.is_none() {
+            self.kex_algorithms = Some(key_exchange.into());
+        }
+    }
+
+    fn handle_kex_reply(&mut self, input: &[u8]) {
+        if self.host_key.is_some() {
+            return;
+        }
+        if let Ok((_, host_key)) = parser::ssh_parse_kex_reply(input) {
+            self.host_key = parser::ssh_parse_host_key(host_key);
+        }
+    }
+}
+
+/// Get the message code of the server key exchange reply holding the
+/// host key. It depends on the negotiated key exchange method, GSSAPI
+/// methods do not send a host key this way.
+fn ssh_kex_reply_code(srv: &SshHeader, cli: &SshHeader) -> Option<parser::MessageCode> {
+    let cli = cli.kex_algorithms.as_ref()?;
+    let srv = srv.kex_algorithms.as_ref()?;
+    let kex = parser::ssh_negotiate_algorithm(&cli.kex, &srv.kex)?;
+    if kex.starts_with(b"diffie-hellman-group-exchange-") {
+        Some(parser::MessageCode::KexdhGexReply)
+    } else if kex.starts_with(b"gss-") {
+        None
+    } else {
+        Some(parser::MessageCode::KexdhReply)
+    }
+}
+
+/// Algorithms used for one direction of the connection.
+#[derive(Default)]
+pub struct SshNegotiatedDirection<'a> {
+    pub cipher: Option<&'a [u8]>,
+    /// Not set for ciphers with built-in authentication
+    pub mac: Option<&'a [u8]>,
+    pub compression: Option<&'a [u8]>,
+}
+
+/// Algorithms chosen by the key exchange. Each one is unset if client and
+/// server have no algorithm in common.
+pub struct SshNegotiated<'a> {
+    pub kex: Option<&'a [u8]>,
+    pub host_key: Option<&'a [u8]>,
+    pub client_to_server: SshNegotiatedDirection<'a>,
+    pub server_to_client: SshNegotiatedDirection<'a>,
+}
+
+fn ssh_negotiate_direction<'a>(
+    client_encr: &'a [u8], server_encr: &[u8], client_mac: &'a [u8], server_mac: &[u8],
+    client_comp: &'a [u8], server_comp: &[u8],
+) -> SshNegotiatedDirection<'a> {
+    let cipher = parser::ssh_negotiate_algorithm(client_encr, server_encr);
+    let mac = match cipher {
+        Some(cipher) if parser::ssh_cipher_is_aead(cipher) => None,
+        _ => parser::ssh_negotiate_algorithm(client_mac, server_mac),
+    };
+    SshNegotiatedDirection {
+        cipher,
+        mac,
+        compression: parser::ssh_negotiate_algorithm(client_comp, server_comp),
     }
 }
 
@@ -117,9 +205,61 @@ pub struct SSHTransaction {
     pub srv_hdr: SshHeader,
     pub cli_hdr: SshHeader,
 
+    /// Metadata of the encrypted session, with the `metadata` encryption
+    /// handling
+    pub encrypted: Option<SshEncryptedSession>,
+
     tx_data: AppLayerTxData,
 }
 
+impl SSHTransaction {
+    /// Get the algorithms chosen by the key exchange, once the KEXINIT
+    /// of both sides was seen.
+    pub fn negotiated(&self) -> Option<SshNegotiated<'_>> {
+        let cli = self.cli_hdr.kex_algorithms.as_ref()?;
+        let srv = self.srv_hdr.kex_algorithms.as_ref()?;
+        Some(SshNegotiated {
+            kex: parser::ssh_negotiate_algorithm(&cli.kex, &srv.kex),
+            host_key: parser::ssh_negotiate_algorithm(&cli.host_key, &srv.host_key),
+            client_to_server: ssh_negotiate_direction(
+                &cli.encr_client_to_server,
+                &srv.encr_client_to_server,
+                &cli.mac_client_to_server,
+                &srv.mac_client_to_server,
+                &cli.comp_client_to_server,
+                &srv.comp_client_to_server,
+            ),
+            server_to_client: ssh_negotiate_direction(
+                &cli.encr_server_to_client,
+                &srv.encr_server_to_client,
+                &cli.mac_server_to_client,
+                &srv.mac_server_to_client,
+                &cli.comp_server_to_client,
+                &srv.comp_server_to_client,
+            ),
+        })
+    }
+
+    fn parse_encrypted(&mut self, input: &[u8], resp: bool, offset: u32) -> AppLayerResult {
+        if self.encrypted.is_none() {
+            let mut session = SshEncryptedSession::default();
+            if let Some(negotiated) = self.negotiated() {
+                session
+                    .client
+                    .set_framing(ssh_clear_length_mac_len(&negotiated.client_to_server));
+                session
+                    .server
+                    .set_framing(ssh_clear_length_mac_len(&negotiated.server_to_client));
+            }
+            self.encrypted = Some(session);
+        }
+        if let Some(session) = self.encrypted.as_mut() {
+            return session.parse(input, resp, offset, &mut self.tx_data);
+        }
+        return AppLayerResult::ok();
+    }
+}
+
 #[derive(Default)]
 pub struct SSHState {
     state_data: AppLayerStateData,
@@ -156,24 +296,28 @@ impl SSHState {
                 let start = hdr.record_left as usize;
                 match hdr.record_left_msg {
                     // parse reassembled tcp segments
-                    parser::MessageCode::Kexinit if hassh_is_enabled() => {
+                    parser::MessageCode::Kexinit => {
                         if let Ok((_rem, key_exchange)) =
                             parser::ssh_parse_key_exchange(&input[..start])
                         {
-                            key_exchange.generate_hassh(
-                                &mut hdr.hassh_string,
-                                &mut hdr.hassh,
-                                &resp,
-                            );
+                            hdr.handle_kexinit(&key_exchange, resp);
                         }
                         hdr.record_left_msg = parser::MessageCode::Undefined(0);
                     }
+                    parser::MessageCode::KexdhReply | parser::MessageCode::KexdhGexReply => {
+                        hdr.handle_kex_reply(&input[..start]);
+                        hdr.record_left_msg = parser::MessageCode::Undefined(0);
+                    }
                     _ => {}
                 }
                 input = &input[start..];
                 hdr.record_left = 0;
             }
         }
+        if hdr.flags == SSHConnectionState::SshStateFinished && encryption_metadata_enabled() {
+            let offset = (il - input.len()) as u32;
+            return self.transaction.parse_encrypted(input, resp, offset);
+        }
         //parse records out of input
         while !input.is_empty() {
             match parser::ssh_parse_record(input) {
@@ -204,19 +348,19 @@ impl SSHState {
                     );
                     SCLogDebug!("SSH valid record {}", head);
                     match head.msg_code {
-                        parser::MessageCode::Kexinit if hassh_is_enabled() => {
+                        parser::MessageCode::Kexinit => {
                             //let endkex = SSH_RECORD_HEADER_LEN + head.pkt_len - 2;
                             let endkex = input.len() - rem.len();
                             if let Ok((_, key_exchange)) = parser::ssh_parse_key_exchange(
                                 &input[SSH_RECORD_HEADER_LEN..endkex],
                             ) {
-                                key_exchange.generate_hassh(
-                                    &mut hdr.hassh_string,
-                                    &mut hdr.hassh,
-                                    &resp,
-                                );
+                                hdr.handle_kexinit(&key_exchange, resp);
                             }
                         }
+                        code if resp && Some(code) == ssh_kex_reply_code(hdr, ohdr) => {
+                            let endkex = input.len() - rem.len();
+                            hdr.handle_kex_reply(&input[SSH_RECORD_HEADER_LEN..endkex]);
+                        }
                         parser::MessageCode::NewKeys => {
                             hdr.flags = SSHConnectionState::SshStateFinished;
                             if ohdr.flags >= SSHConnectionState::SshStateFinished {
@@ -231,6 +375,10 @@ impl SSHState {
                                     SshEncryptionHandling::SSH_HANDLE_ENCRYPTION_TRACK_ONLY => {
                                         flags |= APP_LAYER_PARSER_NO_INSPECTION;
                                     }
+                                    SshEncryptionHandling::SSH_HANDLE_ENCRYPTION_METADATA => {
+                                        // keep the transaction inspected for the events
+                                        flags |= APP_LAYER_PARSER_NO_INSPECTION_PAYLOAD;
+                                    }
                                     _ => {}
                                 }
 
@@ -246,6 +394,12 @@ impl SSHState {
 
                     input = rem;
                     //header and complete data (not returned)
+                    if hdr.flags == SSHConnectionState::SshStateFinished
+                        && encryption_metadata_enabled()
+                    {
+                        let offset = (il - input.len()) as u32;
+                        return self.transaction.parse_encrypted(input, resp, offset);
+                    }
                 }
                 Err(Err::Incomplete(_)) => {
                     match parser::ssh_parse_record_header(input) {
@@ -283,12 +437,14 @@ impl SSHState {
                                 parser::MessageCode::NewKeys => {
                                     hdr.flags = SSHConnectionState::SshStateFinished;
                                 }
-                                parser::MessageCode::Kexinit if hassh_is_enabled() => {
+                                code if code == parser::MessageCode::Kexinit
+                                    || (resp && Some(code) == ssh_kex_reply_code(hdr, ohdr)) =>
+                                {
                                     // check if buffer is bigger than maximum reassembled packet size
                                     hdr.record_left = head.pkt_len - 2;
                                     if hdr.record_left < SSH_MAX_REASSEMBLED_RECORD_LEN as u32 {
                                         // saving type of incomplete kex message
-                                        hdr.record_left_msg = parser::MessageCode::Kexinit;
+                                        hdr.record_left_msg = code;
                                         return AppLayerResult::incomplete(
                                             (il - rem.len()) as u32,
                                             head.pkt_len - 2,
@@ -530,6 +686,13 @@ pub unsafe extern "C" fn SCSshTxGetAlStateProgress(
     return SSHConnectionState::SshStateInProgress as i32;
 }
 
+fn ssh_tx_completion_state() -> i32 {
+    if encryption_metadata_enabled() {
+        return SSHConnectionState::SshStateClosed as i32;
+    }
+    return SSHConnectionState::SshStateFinished as i32;
+}
+
 // Parser name as a C style string.
 const PARSER_NAME: &[u8] = b"ssh\0";
 
@@ -551,8 +714,8 @@ pub unsafe extern "C" fn SCRegisterSshParser() {
         parse_tc: ssh_parse_response,
         get_tx_count: ssh_state_get_tx_count,
         get_tx: SCSshStateGetTx,
-        tx_comp_st_ts: SSHConnectionState::SshStateFinished as i32,
-        tx_comp_st_tc: SSHConnectionState::SshStateFinished as i32,
+        tx_comp_st_ts: ssh_tx_completion_state(),
+        tx_comp_st_tc: ssh_tx_completion_state(),
         tx_get_progress: SCSshTxGetAlStateProgress,
         get_eventinfo: Some(SSHEvent::get_event_info),
         get_eventinfo_byid: Some(SSHEvent::get_event_info_by_id),
@@ -606,16 +769,92 @@ pub extern "C" fn SCSshEnableBypass(mode: SshEncryptionHandling) {
 pub unsafe extern "C" fn SCSshTxGetLogCondition(tx: *mut std::os::raw::c_void) -> bool {
     let tx = cast_pointer!(tx, SSHTransaction);
 
-    if SCSshHasshIsEnabled() {
-        if tx.cli_hdr.flags == SSHConnectionState::SshStateFinished
-            && tx.srv_hdr.flags == SSHConnectionState::SshStateFinished
-        {
-            return true;
-        }
-    } else if tx.cli_hdr.flags == SSHConnectionState::SshStateBannerDone
-        && tx.srv_hdr.flags == SSHConnectionState::SshStateBannerDone
+    // the encrypted session is logged at the end of the flow
+    if encryption_metadata_enabled() {
+        return false;
+    }
+    // wait for the key exchange to log the negotiated algorithms
+    if tx.cli_hdr.flags == SSHConnectionState::SshStateFinished
+        && tx.srv_hdr.flags == SSHConnectionState::SshStateFinished
     {
         return true;
     }
     return false;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::SshKexAlgorithms;
+
+    fn kex_algorithms(kex: &[u8], encr: &[u8], mac: &[u8]) -> Option<SshKexAlgorithms> {
+        Some(SshKexAlgorithms {
+            kex: kex.to_vec(),
+            host_key: b"ssh-ed25519,rsa-sha2-512".to_vec(),
+            encr_client_to_server: encr.to_vec(),
+            encr_server_to_client: encr.to_vec(),
+            mac_client_to_server: mac.to_vec(),
+            mac_server_to_client: b"hmac-sha1".to_vec(),
+            comp_client_to_server: b"none,zlib@openssh.com".to_vec(),
+            comp_server_to_client: b"none".to_vec(),
+        })
+    }
+
+    #[test]
+    fn test_ssh_negotiated() {
+        let mut tx = SSHTransaction::default();
+        tx.cli_hdr.kex_algorithms = kex_algorithms(
+            b"curve25519-sha256,ext-info-c",
+            b"chacha20-poly1305@openssh.com,aes128-ctr",
+            b"hmac-sha2-256,hmac-sha1",
+        );
+        assert!(tx.negotiated().is_none());
+        tx.srv_hdr.kex_algorithms = kex_algorithms(
+            b"diffie-hellman-group14-sha1,curve25519-sha256",
+            b"aes128-ctr,chacha20-poly1305@openssh.com",
+            b"hmac-sha1,hmac-sha2-256",
+        );
+        let negotiated = tx.negotiated().unwrap();
+        assert_eq!(negotiated.kex, Some(&b"curve25519-sha256"[..]));
+        assert_eq!(negotiated.host_key, Some(&b"ssh-ed25519"[..]));
+        let c2s = &negotiated.client_to_server;
+        assert_eq!(c2s.cipher, Some(&b"chacha20-poly1305@openssh.com"[..]));
+        assert_eq!(c2s.mac, None);
+        assert_eq!(c2s.compression, Some(&b"none"[..]));
+        let s2c = &negotiated.server_to_client;
+        assert_eq!(s2c.compression, Some(&b"none"[..]));
+
+        tx.srv_hdr.kex_algorithms =
+            kex_algorithms(b"ecdh-sha2-nistp256", b"aes256-cbc", b"hmac-md5");
+        let negotiated = tx.negotiated().unwrap();
+        assert_eq!(negotiated.kex, None);
+        assert_eq!(negotiated.client_to_server.cipher, None);
+        assert_eq!(negotiated.client_to_server.mac, None);
+    }
+
+    #[test]
+    fn test_ssh_kex_reply_code() {
+        let mut cli = SshHeader::new();
+        let mut srv = SshHeader::new();
+        assert_eq!(ssh_kex_reply_code(&srv, &cli), None);
+        cli.kex_algorithms = kex_algorithms(
+            b"diffie-hellman-group-exchange-sha256,curve25519-sha256",
+            b"aes128-ctr",
+            b"hmac-sha1",
+        );
+        srv.kex_algorithms = kex_algorithms(b"curve25519-sha256", b"aes128-ctr", b"hmac-sha1");
+        assert_eq!(
+            ssh_kex_reply_code(&srv, &cli),
+            Some(parser::MessageCode::KexdhReply)
+        );
+        srv.kex_algorithms = kex_algorithms(
+            b"diffie-hellman-group-exchange-sha256",
+            b"aes128-ctr",
+            b"hmac-sha1",
+        );
+        assert_eq!(
+            ssh_kex_reply_code(&srv, &cli),
+            Some(parser::MessageCode::KexdhGexReply)
+        );
+    }
+}
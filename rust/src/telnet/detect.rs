@@ -0,0 +1,202 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser;
+use super::telnet::{TelnetTransaction, ALPROTO_TELNET};
+use crate::core::{STREAM_TOCLIENT, STREAM_TOSERVER};
+use crate::detect::{helper_keyword_register_sticky_buffer, SigTableElmtStickyBuffer};
+use crate::direction::Direction;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use suricata_sys::sys::{
+    DetectEngineCtx, DetectEngineThreadCtx, SCDetectBufferSetActiveList,
+    SCDetectHelperBufferMpmRegister, SCDetectHelperMultiBufferMpmRegister,
+    SCDetectSignatureSetAppProto, Signature,
+};
+
+static mut G_TELNET_USERNAME_BUFFER_ID: c_int = 0;
+static mut G_TELNET_COMMAND_BUFFER_ID: c_int = 0;
+static mut G_TELNET_BANNER_BUFFER_ID: c_int = 0;
+static mut G_TELNET_OPTION_BUFFER_ID: c_int = 0;
+
+unsafe fn telnet_set_buffer(
+    v: &Option<Vec<u8>>, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    if let Some(v) = v {
+        *buffer = v.as_ptr();
+        *buffer_len = v.len() as u32;
+        return true;
+    }
+    *buffer = ptr::null();
+    *buffer_len = 0;
+    return false;
+}
+
+unsafe extern "C" fn telnet_username_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_TELNET) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_TELNET_USERNAME_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn telnet_username_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, TelnetTransaction);
+    return telnet_set_buffer(&tx.username, buffer, buffer_len);
+}
+
+unsafe extern "C" fn telnet_command_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_TELNET) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_TELNET_COMMAND_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn telnet_command_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, TelnetTransaction);
+    return telnet_set_buffer(&tx.command, buffer, buffer_len);
+}
+
+unsafe extern "C" fn telnet_banner_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_TELNET) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_TELNET_BANNER_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn telnet_banner_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, TelnetTransaction);
+    return telnet_set_buffer(&tx.banner, buffer, buffer_len);
+}
+
+unsafe extern "C" fn telnet_option_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_TELNET) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_TELNET_OPTION_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+/// Get the data of the suboptions sent in the direction being inspected.
+unsafe extern "C" fn telnet_option_get(
+    _de: *mut DetectEngineThreadCtx, tx: *const c_void, flags: u8, local_id: u32,
+    buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, TelnetTransaction);
+    let direction = if flags & Direction::ToClient as u8 != 0 {
+        Direction::ToClient
+    } else {
+        Direction::ToServer
+    };
+    let option = tx
+        .options
+        .iter()
+        .filter(|o| o.cmd == parser::SB && o.direction == direction)
+        .nth(local_id as usize);
+    if let Some(o) = option {
+        *buffer = o.data.as_ptr();
+        *buffer_len = o.data.len() as u32;
+        return true;
+    }
+    *buffer = ptr::null();
+    *buffer_len = 0;
+    return false;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCDetectTelnetRegister() {
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("telnet.username"),
+        desc: String::from("sticky buffer to match on the telnet login username"),
+        url: String::from("/rules/telnet-keywords.html#telnet-username"),
+        setup: telnet_username_setup,
+    };
+    let _g_telnet_username_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_TELNET_USERNAME_BUFFER_ID = SCDetectHelperBufferMpmRegister(
+        b"telnet.username\0".as_ptr() as *const libc::c_char,
+        b"telnet login username\0".as_ptr() as *const libc::c_char,
+        ALPROTO_TELNET,
+        STREAM_TOSERVER,
+        Some(telnet_username_get),
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("telnet.command"),
+        desc: String::from("sticky buffer to match on a telnet command line after login"),
+        url: String::from("/rules/telnet-keywords.html#telnet-command"),
+        setup: telnet_command_setup,
+    };
+    let _g_telnet_command_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_TELNET_COMMAND_BUFFER_ID = SCDetectHelperBufferMpmRegister(
+        b"telnet.command\0".as_ptr() as *const libc::c_char,
+        b"telnet command line\0".as_ptr() as *const libc::c_char,
+        ALPROTO_TELNET,
+        STREAM_TOSERVER,
+        Some(telnet_command_get),
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("telnet.banner"),
+        desc: String::from("sticky buffer to match on the telnet server welcome message"),
+        url: String::from("/rules/telnet-keywords.html#telnet-banner"),
+        setup: telnet_banner_setup,
+    };
+    let _g_telnet_banner_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_TELNET_BANNER_BUFFER_ID = SCDetectHelperBufferMpmRegister(
+        b"telnet.banner\0".as_ptr() as *const libc::c_char,
+        b"telnet server banner\0".as_ptr() as *const libc::c_char,
+        ALPROTO_TELNET,
+        STREAM_TOCLIENT,
+        Some(telnet_banner_get),
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("telnet.option"),
+        desc: String::from("sticky buffer to match on telnet suboption data"),
+        url: String::from("/rules/telnet-keywords.html#telnet-option"),
+        setup: telnet_option_setup,
+    };
+    let _g_telnet_option_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_TELNET_OPTION_BUFFER_ID = SCDetectHelperMultiBufferMpmRegister(
+        b"telnet.option\0".as_ptr() as *const libc::c_char,
+        b"telnet suboption data\0".as_ptr() as *const libc::c_char,
+        ALPROTO_TELNET,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(telnet_option_get),
+    );
+}
@@ -0,0 +1,76 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser;
+use super::telnet::TelnetTransaction;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use std;
+
+fn log_telnet(tx: &TelnetTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("telnet")?;
+    if let Some(ref banner) = tx.banner {
+        js.set_string_from_bytes("banner", banner)?;
+    }
+    if !tx.options.is_empty() {
+        js.open_array("options")?;
+        for o in &tx.options {
+            js.start_object()?;
+            js.set_string("direction", &o.direction.to_string())?;
+            js.set_string("command", parser::negotiation_name(o.cmd))?;
+            js.set_uint("option", o.option)?;
+            if let Some(name) = parser::option_name(o.option) {
+                js.set_string("name", name)?;
+            }
+            js.close()?;
+        }
+        js.close()?;
+    }
+    if let Some(ref ttype) = tx.terminal_type {
+        js.set_string_from_bytes("terminal_type", ttype)?;
+    }
+    if let Some((width, height)) = tx.window_size {
+        js.open_object("window_size")?;
+        js.set_uint("width", width)?;
+        js.set_uint("height", height)?;
+        js.close()?;
+    }
+    if tx.username.is_some() || tx.password_prompt || tx.auth_success.is_some() {
+        js.open_object("login")?;
+        if let Some(ref username) = tx.username {
+            js.set_string_from_bytes("username", username)?;
+        }
+        js.set_bool("password_prompt", tx.password_prompt)?;
+        if let Some(success) = tx.auth_success {
+            js.set_bool("success", success)?;
+        }
+        js.close()?;
+    }
+    if let Some(ref command) = tx.command {
+        js.set_string_from_bytes("command", command)?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCTelnetLoggerLog(
+    tx: *const std::os::raw::c_void, js: *mut std::os::raw::c_void,
+) -> bool {
+    let tx = cast_pointer!(tx, TelnetTransaction);
+    let js = cast_pointer!(js, JsonBuilder);
+    log_telnet(tx, js).is_ok()
+}
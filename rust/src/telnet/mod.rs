@@ -18,4 +18,6 @@
 //! Telnet application layer and parser module.
 
 pub mod telnet;
+pub mod detect;
+pub mod logger;
 mod parser;
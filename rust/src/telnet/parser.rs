@@ -23,6 +23,20 @@ use nom7::number::streaming::le_u8;
 use nom7::bytes::streaming::tag;
 use nom7::bytes::streaming::{take_until};
 
+pub const IAC: u8 = 255;
+pub const SE: u8 = 240;
+pub const SB: u8 = 250;
+pub const WILL: u8 = 251;
+pub const WONT: u8 = 252;
+pub const DO: u8 = 253;
+pub const DONT: u8 = 254;
+
+pub const OPT_TERMINAL_TYPE: u8 = 24;
+pub const OPT_NAWS: u8 = 31;
+
+/// Terminal type suboption "IS" command (RFC 1091).
+const TTYPE_IS: u8 = 0;
+
 pub fn peek_message_is_ctl(i: &[u8]) -> IResult<&[u8], bool> {
     let (i, v) = peek(le_u8)(i)?;
     Ok((i, v == b'\xff'))
@@ -75,3 +89,146 @@ pub fn parse_welcome_message(i: &[u8]) -> IResult<&[u8], TelnetMessageType> {
         Ok((i, TelnetMessageType::Data(t)))
     }
 }
+
+/// A control message decoded from the raw bytes returned by
+/// `parse_ctl_message`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TelnetControl<'a> {
+    /// IAC WILL/WONT/DO/DONT <option>
+    Negotiation { cmd: u8, option: u8 },
+    /// IAC SB <option> <data> (the IAC SE is parsed as its own command)
+    Suboption { option: u8, data: &'a [u8] },
+    /// Two byte commands such as IAC SE, IAC NOP or IAC AYT.
+    Command(u8),
+    /// IAC followed by a byte that is not a telnet command.
+    Invalid(u8),
+}
+
+pub fn parse_control(c: &[u8]) -> Option<TelnetControl> {
+    if c.len() < 2 || c[0] != IAC {
+        return None;
+    }
+    match c[1] {
+        WILL..=DONT if c.len() >= 3 => Some(TelnetControl::Negotiation {
+            cmd: c[1],
+            option: c[2],
+        }),
+        SB if c.len() >= 3 => Some(TelnetControl::Suboption {
+            option: c[2],
+            data: &c[3..],
+        }),
+        SE..=249 | IAC => Some(TelnetControl::Command(c[1])),
+        cmd => Some(TelnetControl::Invalid(cmd)),
+    }
+}
+
+/// Get the terminal type from a TERMINAL-TYPE IS suboption.
+pub fn parse_terminal_type(data: &[u8]) -> Option<&[u8]> {
+    match data.split_first() {
+        Some((&TTYPE_IS, ttype)) => Some(ttype),
+        _ => None,
+    }
+}
+
+/// Get the width and height from a NAWS suboption (RFC 1073).
+pub fn parse_window_size(data: &[u8]) -> Option<(u16, u16)> {
+    // 255 values are doubled inside suboptions
+    let mut v = Vec::with_capacity(4);
+    let mut i = 0;
+    while i < data.len() && v.len() < 4 {
+        v.push(data[i]);
+        if data[i] == IAC && data.get(i + 1) == Some(&IAC) {
+            i += 1;
+        }
+        i += 1;
+    }
+    if v.len() != 4 {
+        return None;
+    }
+    let width = u16::from_be_bytes([v[0], v[1]]);
+    let height = u16::from_be_bytes([v[2], v[3]]);
+    Some((width, height))
+}
+
+/// Name of a telnet option, as assigned by IANA.
+pub fn option_name(option: u8) -> Option<&'static str> {
+    let name = match option {
+        0 => "binary",
+        1 => "echo",
+        3 => "suppress_go_ahead",
+        5 => "status",
+        6 => "timing_mark",
+        24 => "terminal_type",
+        31 => "naws",
+        32 => "terminal_speed",
+        33 => "remote_flow_control",
+        34 => "linemode",
+        35 => "x_display_location",
+        36 => "environment",
+        37 => "authentication",
+        38 => "encryption",
+        39 => "new_environment",
+        _ => return None,
+    };
+    Some(name)
+}
+
+pub fn negotiation_name(cmd: u8) -> &'static str {
+    match cmd {
+        WILL => "will",
+        WONT => "wont",
+        DO => "do",
+        DONT => "dont",
+        SB => "sb",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_negotiation() {
+        let buf = b"\xff\xfd\x18\xff\xfb\x1f";
+        let (rem, ctl) = parse_ctl_message(buf).unwrap();
+        assert_eq!(rem, b"\xff\xfb\x1f");
+        assert_eq!(
+            parse_control(ctl),
+            Some(TelnetControl::Negotiation {
+                cmd: DO,
+                option: OPT_TERMINAL_TYPE
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_suboption() {
+        let buf = b"\xff\xfa\x18\x00XTERM\xff\xf0";
+        let (rem, ctl) = parse_ctl_message(buf).unwrap();
+        assert_eq!(rem, b"\xff\xf0");
+        match parse_control(ctl) {
+            Some(TelnetControl::Suboption { option, data }) => {
+                assert_eq!(option, OPT_TERMINAL_TYPE);
+                assert_eq!(parse_terminal_type(data), Some(&b"XTERM"[..]));
+            }
+            _ => panic!("expected suboption"),
+        }
+        let (rem, ctl) = parse_ctl_message(rem).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(parse_control(ctl), Some(TelnetControl::Command(SE)));
+    }
+
+    #[test]
+    fn test_parse_window_size() {
+        assert_eq!(parse_window_size(b"\x00\x50\x00\x18"), Some((80, 24)));
+        assert_eq!(parse_window_size(b"\x00\xff\xff\x00\x18"), Some((255, 24)));
+        assert_eq!(parse_window_size(b"\x00\x50"), None);
+    }
+
+    #[test]
+    fn test_parse_invalid_command() {
+        let (_, ctl) = parse_ctl_message(b"\xff\x10").unwrap();
+        assert_eq!(parse_control(ctl), Some(TelnetControl::Invalid(0x10)));
+    }
+}
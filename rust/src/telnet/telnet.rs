@@ -18,6 +18,7 @@
 use std;
 use crate::core::{ALPROTO_UNKNOWN, IPPROTO_TCP};
 use crate::applayer::{self, *};
+use crate::direction::Direction;
 use crate::flow::Flow;
 use crate::frames::*;
 use std::ffi::CString;
@@ -25,10 +26,22 @@ use nom7::IResult;
 use suricata_sys::sys::AppProto;
 use super::parser;
 
-static mut ALPROTO_TELNET: AppProto = ALPROTO_UNKNOWN;
+pub(super) static mut ALPROTO_TELNET: AppProto = ALPROTO_UNKNOWN;
+
+/// Suboptions are buffered until IAC SE is seen, so put a bound on them.
+const TELNET_MAX_SUBOPTION_LEN: usize = 4096;
+/// Maximum number of negotiated options recorded per transaction.
+const TELNET_MAX_OPTIONS: usize = 256;
 
 #[derive(AppLayerEvent)]
-enum TelnetEvent {}
+enum TelnetEvent {
+    /// IAC followed by a byte that is not a telnet command
+    MalformedIac,
+    /// Suboption without IAC SE within the suboption length limit
+    OversizedSuboption,
+    /// More negotiated options than we record in a transaction
+    TooManyOptions,
+}
 
 #[derive(AppLayerFrameType)]
 pub enum TelnetFrameType {
@@ -37,18 +50,57 @@ pub enum TelnetFrameType {
     Data,
 }
 
+/// An option negotiation (IAC WILL/WONT/DO/DONT) or suboption (IAC SB).
+#[derive(Debug, PartialEq, Eq)]
+pub struct TelnetOption {
+    pub direction: Direction,
+    pub cmd: u8,
+    pub option: u8,
+    /// Suboption data, empty for negotiations.
+    pub data: Vec<u8>,
+}
+
 #[derive(Default)]
 pub struct TelnetTransaction {
     tx_id: u64,
+    pub options: Vec<TelnetOption>,
+    pub terminal_type: Option<Vec<u8>>,
+    pub window_size: Option<(u16, u16)>,
+    /// Server welcome message up to the login prompt.
+    pub banner: Option<Vec<u8>>,
+    pub username: Option<Vec<u8>>,
+    /// Set when the server prompted for a password during this transaction.
+    pub password_prompt: bool,
+    /// Outcome of the login, if this transaction saw one.
+    pub auth_success: Option<bool>,
+    /// Client command line after a successful login.
+    pub command: Option<Vec<u8>>,
+    complete: bool,
     tx_data: AppLayerTxData,
 }
 
+impl TelnetTransaction {
+    fn add_option(&mut self, direction: Direction, cmd: u8, option: u8, data: &[u8]) {
+        if self.options.len() >= TELNET_MAX_OPTIONS {
+            self.tx_data.set_event(TelnetEvent::TooManyOptions as u8);
+            return;
+        }
+        self.options.push(TelnetOption {
+            direction,
+            cmd,
+            option,
+            data: data.to_vec(),
+        });
+    }
+}
+
 impl Transaction for TelnetTransaction {
     fn id(&self) -> u64 {
         self.tx_id
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
 pub enum TelnetProtocolState {
     Idle,
     LoginSent,
@@ -130,9 +182,73 @@ impl TelnetState {
         self.transactions.iter().find(|tx| tx.tx_id == tx_id + 1)
     }
 
-    fn _find_request(&mut self) -> Option<&mut TelnetTransaction> {
-        // TODO
-        None
+    fn new_tx(&mut self) -> TelnetTransaction {
+        let mut tx = TelnetTransaction::default();
+        self.tx_id += 1;
+        tx.tx_id = self.tx_id;
+        return tx;
+    }
+
+    /// Get the open transaction, creating one if the last one is complete.
+    fn get_current_tx(&mut self) -> &mut TelnetTransaction {
+        if !matches!(self.transactions.last(), Some(tx) if !tx.complete) {
+            let tx = self.new_tx();
+            self.transactions.push(tx);
+        }
+        // unwrap is safe as we just made sure there is a transaction
+        return self.transactions.last_mut().unwrap();
+    }
+
+    fn handle_control(&mut self, direction: Direction, c: &[u8]) {
+        match parser::parse_control(c) {
+            Some(parser::TelnetControl::Negotiation { cmd, option }) => {
+                self.get_current_tx().add_option(direction, cmd, option, &[]);
+            }
+            Some(parser::TelnetControl::Suboption { option, data }) => {
+                let tx = self.get_current_tx();
+                tx.add_option(direction, parser::SB, option, data);
+                match option {
+                    parser::OPT_TERMINAL_TYPE => {
+                        if let Some(ttype) = parser::parse_terminal_type(data) {
+                            tx.terminal_type = Some(ttype.to_vec());
+                        }
+                    }
+                    parser::OPT_NAWS => {
+                        if let Some(size) = parser::parse_window_size(data) {
+                            tx.window_size = Some(size);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Some(parser::TelnetControl::Invalid(_cmd)) => {
+                SCLogDebug!("invalid command after IAC {}", _cmd);
+                self.get_current_tx()
+                    .tx_data
+                    .set_event(TelnetEvent::MalformedIac as u8);
+            }
+            _ => {}
+        }
+    }
+
+    /// Raise an event if the parser is stuck waiting for the end of
+    /// a suboption that exceeds our limit.
+    fn check_suboption_len(&mut self, input: &[u8]) -> bool {
+        if input.len() > TELNET_MAX_SUBOPTION_LEN
+            && input.starts_with(&[parser::IAC, parser::SB])
+        {
+            self.get_current_tx()
+                .tx_data
+                .set_event(TelnetEvent::OversizedSuboption as u8);
+            return false;
+        }
+        return true;
+    }
+
+    fn set_auth_result(&mut self, success: bool) {
+        let tx = self.get_current_tx();
+        tx.auth_success = Some(success);
+        tx.complete = true;
     }
 
     // app-layer-frame-documentation tag start: parse_request
@@ -217,23 +333,34 @@ impl TelnetState {
                         match self.state {
                             TelnetProtocolState::LoginSent => {
                                 self.state = TelnetProtocolState::LoginRecv;
+                                let username = trim_line(d);
+                                if !username.is_empty() {
+                                    self.get_current_tx().username = Some(username.to_vec());
+                                }
                             }
                             TelnetProtocolState::PasswdSent => {
                                 self.state = TelnetProtocolState::PasswdRecv;
                             }
                             TelnetProtocolState::AuthOk => {
-                                let _message = std::str::from_utf8(d);
-                                if let Ok(_message) = _message {
-                                    SCLogDebug!("=> {}", _message);
+                                let command = trim_line(d);
+                                if !command.is_empty() {
+                                    SCLogDebug!("=> {:?}", command);
+                                    let tx = self.get_current_tx();
+                                    tx.command = Some(command.to_vec());
+                                    tx.complete = true;
                                 }
                             }
                             _ => {}
                         }
-                    } else if let parser::TelnetMessageType::Control(_c) = request {
-                        SCLogDebug!("request {:?}", _c);
+                    } else if let parser::TelnetMessageType::Control(c) = request {
+                        SCLogDebug!("request {:?}", c);
+                        self.handle_control(Direction::ToServer, c);
                     }
                 }
                 Err(nom7::Err::Incomplete(_)) => {
+                    if !self.check_suboption_len(start) {
+                        return AppLayerResult::err();
+                    }
                     // Not enough data. This parser doesn't give us a good indication
                     // of how much data is missing so just ask for one more byte so the
                     // parse is called as soon as more data is received.
@@ -305,19 +432,27 @@ impl TelnetState {
 
                     if let parser::TelnetMessageType::Data(d) = response {
                         match self.state {
-                            TelnetProtocolState::Idle |
+                            TelnetProtocolState::Idle => {
+                                self.state = TelnetProtocolState::LoginSent;
+                                let banner = trim_banner(d);
+                                if !banner.is_empty() {
+                                    self.get_current_tx().banner = Some(banner.to_vec());
+                                }
+                            },
                             TelnetProtocolState::AuthFail => {
                                 self.state = TelnetProtocolState::LoginSent;
                             },
                             TelnetProtocolState::LoginRecv => {
                                 self.state = TelnetProtocolState::PasswdSent;
+                                self.get_current_tx().password_prompt = true;
                             },
                             TelnetProtocolState::PasswdRecv => {
-                                if let Ok(message) = std::str::from_utf8(d) {
+                                if let Ok(message) = std::str::from_utf8(trim_line(d)) {
                                     match message {
                                         "Login incorrect" => {
                                             SCLogDebug!("LOGIN FAILED");
                                             self.state = TelnetProtocolState::AuthFail;
+                                            self.set_auth_result(false);
                                         },
                                         "" => {
 
@@ -325,6 +460,7 @@ impl TelnetState {
                                         &_ => {
                                             SCLogDebug!("LOGIN OK");
                                             self.state = TelnetProtocolState::AuthOk;
+                                            self.set_auth_result(true);
                                         },
                                     }
                                 }
@@ -337,11 +473,15 @@ impl TelnetState {
                             },
                             _ => {},
                         }
-                    } else if let parser::TelnetMessageType::Control(_c) = response {
-                        SCLogDebug!("response {:?}", _c);
+                    } else if let parser::TelnetMessageType::Control(c) = response {
+                        SCLogDebug!("response {:?}", c);
+                        self.handle_control(Direction::ToClient, c);
                     }
                 }
                 Err(nom7::Err::Incomplete(_)) => {
+                    if !self.check_suboption_len(start) {
+                        return AppLayerResult::err();
+                    }
                     let consumed = input.len() - start.len();
                     let needed = start.len() + 1;
                     return AppLayerResult::incomplete(consumed as u32, needed as u32);
@@ -366,6 +506,23 @@ impl TelnetState {
     }
 }
 
+/// Strip the line ending and NUL padding from client data.
+fn trim_line(d: &[u8]) -> &[u8] {
+    let mut end = d.len();
+    while end > 0 && (d[end - 1] == b'\r' || d[end - 1] == b'\0') {
+        end -= 1;
+    }
+    &d[..end]
+}
+
+/// Strip the surrounding whitespace from the welcome message.
+fn trim_banner(d: &[u8]) -> &[u8] {
+    let is_space = |c: &u8| c.is_ascii_whitespace() || *c == b'\0';
+    let start = d.iter().position(|c| !is_space(c)).unwrap_or(d.len());
+    let end = d.iter().rposition(|c| !is_space(c)).map_or(start, |p| p + 1);
+    &d[start..end]
+}
+
 /// Probe for a valid header.
 ///
 fn probe(input: &[u8]) -> IResult<&[u8], ()> {
@@ -488,8 +645,10 @@ unsafe extern "C" fn telnet_tx_get_alstate_progress(
     tx: *mut std::os::raw::c_void,
     _direction: u8,
 ) -> std::os::raw::c_int {
-    let _tx = cast_pointer!(tx, TelnetTransaction);
-    // TODO
+    let tx = cast_pointer!(tx, TelnetTransaction);
+    if tx.complete {
+        return 1;
+    }
     return 0;
 }
 
@@ -553,8 +712,48 @@ pub unsafe extern "C" fn SCRegisterTelnetParser() {
         {
             let _ = AppLayerRegisterParser(&parser, alproto);
         }
+        AppLayerParserRegisterLogger(IPPROTO_TCP, ALPROTO_TELNET);
         SCLogDebug!("Rust telnet parser registered.");
     } else {
         SCLogDebug!("Protocol detector and parser disabled for TELNET.");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_telnet_login_command() {
+        let mut state = TelnetState::new();
+        let f = std::ptr::null();
+        let ss = StreamSlice::from_slice(&[], 0, 0);
+
+        let r = state.parse_response(f, &ss, b"\xff\xfd\x18\r\nrouter01\r\nlogin: ");
+        assert!(r.is_ok());
+        let r = state.parse_request(f, &ss, b"\xff\xfb\x18admin\r\n");
+        assert!(r.is_ok());
+        let r = state.parse_response(f, &ss, b"Password: ");
+        assert!(r.is_ok());
+        let r = state.parse_request(f, &ss, b"secret\r\n");
+        assert!(r.is_ok());
+        let r = state.parse_response(f, &ss, b"\r\nWelcome\r\n");
+        assert!(r.is_ok());
+        assert_eq!(state.state, TelnetProtocolState::AuthOk);
+        let r = state.parse_request(f, &ss, b"show running-config\r\n");
+        assert!(r.is_ok());
+
+        assert_eq!(state.transactions.len(), 2);
+        let tx = &state.transactions[0];
+        assert_eq!(tx.banner.as_deref(), Some(&b"router01\r\nlogin"[..]));
+        assert_eq!(tx.username.as_deref(), Some(&b"admin"[..]));
+        assert!(tx.password_prompt);
+        assert_eq!(tx.auth_success, Some(true));
+        assert_eq!(tx.options.len(), 2);
+        assert_eq!(tx.options[0].direction, Direction::ToClient);
+        assert_eq!(tx.options[1].cmd, parser::WILL);
+        let tx = &state.transactions[1];
+        assert_eq!(tx.command.as_deref(), Some(&b"show running-config"[..]));
+        assert!(tx.complete);
+    }
+}
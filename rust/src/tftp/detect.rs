@@ -0,0 +1,295 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::{TFTPOpcode, TFTP_OPCODE_OACK, TFTP_OPCODE_RRQ};
+use super::tftp::TFTPTransaction;
+use crate::core::{STREAM_TOCLIENT, STREAM_TOSERVER};
+use crate::detect::uint::{
+    detect_match_uint, detect_parse_uint_enum, DetectUintData, SCDetectU16Free, SCDetectU16Parse,
+    SCDetectU8Free,
+};
+use crate::detect::{helper_keyword_register_sticky_buffer, SigTableElmtStickyBuffer};
+use std::ffi::CStr;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use suricata_sys::sys::{
+    AppProto, AppProtoEnum, DetectEngineCtx, DetectEngineThreadCtx, Flow,
+    SCDetectBufferSetActiveList, SCDetectHelperBufferMpmRegister, SCDetectHelperBufferRegister,
+    SCDetectHelperKeywordRegister, SCDetectSignatureSetAppProto, SCSigMatchAppendSMToList,
+    SCSigTableAppLiteElmt, SigMatchCtx, Signature,
+};
+
+const ALPROTO_TFTP: AppProto = AppProtoEnum::ALPROTO_TFTP as AppProto;
+
+static mut G_TFTP_FILENAME_BUFFER_ID: c_int = 0;
+static mut G_TFTP_MODE_BUFFER_ID: c_int = 0;
+static mut G_TFTP_OPCODE_KW_ID: u16 = 0;
+static mut G_TFTP_OPCODE_BUFFER_ID: c_int = 0;
+static mut G_TFTP_ERROR_CODE_KW_ID: u16 = 0;
+static mut G_TFTP_ERROR_CODE_BUFFER_ID: c_int = 0;
+
+unsafe extern "C" fn tftp_filename_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_TFTP) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_TFTP_FILENAME_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn tftp_filename_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, TFTPTransaction);
+    if !tx.filename.is_empty() {
+        *buffer = tx.filename.as_ptr();
+        *buffer_len = tx.filename.len() as u32;
+        return true;
+    }
+
+    *buffer = ptr::null();
+    *buffer_len = 0;
+    return false;
+}
+
+unsafe extern "C" fn tftp_mode_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, _raw: *const std::os::raw::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_TFTP) != 0 {
+        return -1;
+    }
+    if SCDetectBufferSetActiveList(de, s, G_TFTP_MODE_BUFFER_ID) < 0 {
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn tftp_mode_get(
+    tx: *const c_void, _flags: u8, buffer: *mut *const u8, buffer_len: *mut u32,
+) -> bool {
+    let tx = cast_pointer!(tx, TFTPTransaction);
+    if !tx.mode.is_empty() {
+        *buffer = tx.mode.as_ptr();
+        *buffer_len = tx.mode.len() as u32;
+        return true;
+    }
+
+    *buffer = ptr::null();
+    *buffer_len = 0;
+    return false;
+}
+
+unsafe extern "C" fn tftp_parse_opcode(
+    ustr: *const std::os::raw::c_char,
+) -> *mut DetectUintData<u8> {
+    let ft_name: &CStr = CStr::from_ptr(ustr); //unsafe
+    if let Ok(s) = ft_name.to_str() {
+        if let Some(ctx) = detect_parse_uint_enum::<u8, TFTPOpcode>(s) {
+            let boxed = Box::new(ctx);
+            return Box::into_raw(boxed) as *mut _;
+        }
+    }
+    return std::ptr::null_mut();
+}
+
+unsafe extern "C" fn tftp_opcode_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_TFTP) != 0 {
+        return -1;
+    }
+    let ctx = tftp_parse_opcode(raw) as *mut c_void;
+    if ctx.is_null() {
+        return -1;
+    }
+    if SCSigMatchAppendSMToList(
+        de,
+        s,
+        G_TFTP_OPCODE_KW_ID,
+        ctx as *mut SigMatchCtx,
+        G_TFTP_OPCODE_BUFFER_ID,
+    )
+    .is_null()
+    {
+        tftp_opcode_free(std::ptr::null_mut(), ctx);
+        return -1;
+    }
+    return 0;
+}
+
+fn tftp_opcode_match_tx(tx: &TFTPTransaction, ctx: &DetectUintData<u8>) -> bool {
+    (TFTP_OPCODE_RRQ..=TFTP_OPCODE_OACK)
+        .any(|opcode| tx.has_opcode(opcode) && detect_match_uint(ctx, opcode))
+}
+
+unsafe extern "C" fn tftp_opcode_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, TFTPTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u8>);
+    if tftp_opcode_match_tx(tx, ctx) {
+        return 1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn tftp_opcode_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    let ctx = cast_pointer!(ctx, DetectUintData<u8>);
+    SCDetectU8Free(ctx);
+}
+
+unsafe extern "C" fn tftp_error_code_setup(
+    de: *mut DetectEngineCtx, s: *mut Signature, raw: *const libc::c_char,
+) -> c_int {
+    if SCDetectSignatureSetAppProto(s, ALPROTO_TFTP) != 0 {
+        return -1;
+    }
+    let ctx = SCDetectU16Parse(raw) as *mut c_void;
+    if ctx.is_null() {
+        return -1;
+    }
+    if SCSigMatchAppendSMToList(
+        de,
+        s,
+        G_TFTP_ERROR_CODE_KW_ID,
+        ctx as *mut SigMatchCtx,
+        G_TFTP_ERROR_CODE_BUFFER_ID,
+    )
+    .is_null()
+    {
+        tftp_error_code_free(std::ptr::null_mut(), ctx);
+        return -1;
+    }
+    return 0;
+}
+
+unsafe extern "C" fn tftp_error_code_match(
+    _de: *mut DetectEngineThreadCtx, _f: *mut Flow, _flags: u8, _state: *mut c_void,
+    tx: *mut c_void, _sig: *const Signature, ctx: *const SigMatchCtx,
+) -> c_int {
+    let tx = cast_pointer!(tx, TFTPTransaction);
+    let ctx = cast_pointer!(ctx, DetectUintData<u16>);
+    if let Some(ref error) = tx.error {
+        if detect_match_uint(ctx, error.code) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+unsafe extern "C" fn tftp_error_code_free(_de: *mut DetectEngineCtx, ctx: *mut c_void) {
+    let ctx = cast_pointer!(ctx, DetectUintData<u16>);
+    SCDetectU16Free(ctx);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCDetectTftpRegister() {
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("tftp.filename"),
+        desc: String::from("sticky buffer to match on the TFTP filename"),
+        url: String::from("/rules/tftp-keywords.html#tftp-filename"),
+        setup: tftp_filename_setup,
+    };
+    let _g_tftp_filename_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_TFTP_FILENAME_BUFFER_ID = SCDetectHelperBufferMpmRegister(
+        b"tftp.filename\0".as_ptr() as *const libc::c_char,
+        b"tftp filename\0".as_ptr() as *const libc::c_char,
+        ALPROTO_TFTP,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(tftp_filename_get),
+    );
+    let kw = SigTableElmtStickyBuffer {
+        name: String::from("tftp.mode"),
+        desc: String::from("sticky buffer to match on the TFTP transfer mode"),
+        url: String::from("/rules/tftp-keywords.html#tftp-mode"),
+        setup: tftp_mode_setup,
+    };
+    let _g_tftp_mode_kw_id = helper_keyword_register_sticky_buffer(&kw);
+    G_TFTP_MODE_BUFFER_ID = SCDetectHelperBufferMpmRegister(
+        b"tftp.mode\0".as_ptr() as *const libc::c_char,
+        b"tftp mode\0".as_ptr() as *const libc::c_char,
+        ALPROTO_TFTP,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+        Some(tftp_mode_get),
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"tftp.opcode\0".as_ptr() as *const libc::c_char,
+        desc: b"match TFTP opcode\0".as_ptr() as *const libc::c_char,
+        url: b"/rules/tftp-keywords.html#tftp-opcode\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(tftp_opcode_match),
+        Setup: Some(tftp_opcode_setup),
+        Free: Some(tftp_opcode_free),
+        flags: 0,
+    };
+    G_TFTP_OPCODE_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_TFTP_OPCODE_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"tftp.opcode\0".as_ptr() as *const libc::c_char,
+        ALPROTO_TFTP,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+    let kw = SCSigTableAppLiteElmt {
+        name: b"tftp.error_code\0".as_ptr() as *const libc::c_char,
+        desc: b"match TFTP error code\0".as_ptr() as *const libc::c_char,
+        url: b"/rules/tftp-keywords.html#tftp-error-code\0".as_ptr() as *const libc::c_char,
+        AppLayerTxMatch: Some(tftp_error_code_match),
+        Setup: Some(tftp_error_code_setup),
+        Free: Some(tftp_error_code_free),
+        flags: 0,
+    };
+    G_TFTP_ERROR_CODE_KW_ID = SCDetectHelperKeywordRegister(&kw);
+    G_TFTP_ERROR_CODE_BUFFER_ID = SCDetectHelperBufferRegister(
+        b"tftp.error_code\0".as_ptr() as *const libc::c_char,
+        ALPROTO_TFTP,
+        STREAM_TOSERVER | STREAM_TOCLIENT,
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::detect::uint::DetectUintMode;
+    use crate::tftp::parser::{TFTPRequest, TFTP_OPCODE_WRQ};
+
+    #[test]
+    fn test_tftp_parse_opcode() {
+        let ctx = detect_parse_uint_enum::<u8, TFTPOpcode>("oack").unwrap();
+        assert_eq!(ctx.arg1, 6);
+        assert_eq!(ctx.mode, DetectUintMode::DetectUintModeEqual);
+        let ctx = detect_parse_uint_enum::<u8, TFTPOpcode>("!rrq").unwrap();
+        assert_eq!(ctx.arg1, 1);
+        assert_eq!(ctx.mode, DetectUintMode::DetectUintModeNe);
+        assert!(detect_parse_uint_enum::<u8, TFTPOpcode>("read").is_none());
+    }
+
+    #[test]
+    fn test_tftp_match_opcode() {
+        let request = TFTPRequest {
+            opcode: TFTP_OPCODE_WRQ,
+            ..Default::default()
+        };
+        let mut tx = TFTPTransaction::new(&request);
+        tx.opcodes = 1 << TFTP_OPCODE_WRQ;
+        let ctx = detect_parse_uint_enum::<u8, TFTPOpcode>("wrq").unwrap();
+        assert!(tftp_opcode_match_tx(&tx, &ctx));
+        let ctx = detect_parse_uint_enum::<u8, TFTPOpcode>("data").unwrap();
+        assert!(!tftp_opcode_match_tx(&tx, &ctx));
+    }
+}
@@ -18,17 +18,63 @@
 // written by Clément Galland <clement.galland@epita.fr>
 
 use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::tftp::parser::TFTPOption;
 use crate::tftp::tftp::TFTPTransaction;
 
+fn tftp_log_options(
+    name: &str, options: &[TFTPOption], jb: &mut JsonBuilder,
+) -> Result<(), JsonError> {
+    // options can repeat and their names are not checked, so they are
+    // not used as keys
+    jb.open_array(name)?;
+    for option in options {
+        jb.start_object()?;
+        jb.set_string("name", &option.name)?;
+        jb.set_string("value", &option.value)?;
+        jb.close()?;
+    }
+    jb.close()?;
+    Ok(())
+}
+
 fn tftp_log_request(tx: &TFTPTransaction, jb: &mut JsonBuilder) -> Result<(), JsonError> {
     jb.open_object("tftp")?;
-    match tx.opcode {
-        1 => jb.set_string("packet", "read")?,
-        2 => jb.set_string("packet", "write")?,
-        _ => jb.set_string("packet", "error")?,
-    };
-    jb.set_string("file", tx.filename.as_str())?;
-    jb.set_string("mode", tx.mode.as_str())?;
+    // a transfer seen without its request has no request fields
+    if tx.opcode != 0 {
+        match tx.opcode {
+            1 => jb.set_string("packet", "read")?,
+            2 => jb.set_string("packet", "write")?,
+            _ => jb.set_string("packet", "error")?,
+        };
+        jb.set_string("file", tx.filename.as_str())?;
+        jb.set_string("mode", tx.mode.as_str())?;
+        if !tx.options.is_empty() {
+            tftp_log_options("options", &tx.options, jb)?;
+        }
+    }
+    if tx.transfer {
+        jb.open_object("transfer")?;
+        if let Some(ref oack) = tx.oack {
+            tftp_log_options("oack", oack, jb)?;
+        }
+        jb.set_uint("blksize", tx.blksize as u64)?;
+        if let Some(tsize) = tx.tsize {
+            jb.set_uint("tsize", tsize)?;
+        }
+        if let Some(timeout) = tx.timeout {
+            jb.set_uint("timeout", timeout)?;
+        }
+        jb.set_uint("blocks", tx.blocks)?;
+        jb.set_uint("bytes", tx.bytes)?;
+        jb.set_bool("complete", tx.complete && tx.error.is_none())?;
+        jb.close()?;
+    }
+    if let Some(ref error) = tx.error {
+        jb.open_object("error")?;
+        jb.set_uint("code", error.code as u64)?;
+        jb.set_string("message", &error.message)?;
+        jb.close()?;
+    }
     jb.close()?;
     Ok(())
 }
@@ -20,4 +20,6 @@
 // written by Clément Galland <clement.galland@epita.fr>
 
 pub mod tftp;
+pub mod detect;
 pub mod log;
+pub mod parser;
@@ -0,0 +1,226 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Parsers for the TFTP packets (RFC 1350) and the option extension
+//! (RFC 2347).
+
+use nom7::bytes::complete::{tag, take_while};
+use nom7::combinator::{map, map_res, opt, rest};
+use nom7::error::{make_error, ErrorKind};
+use nom7::multi::many0;
+use nom7::number::complete::{be_u16, be_u8};
+use nom7::sequence::terminated;
+use nom7::{Err, IResult};
+use std::str;
+use suricata_derive::EnumStringU8;
+
+pub const TFTP_OPCODE_RRQ: u8 = 1;
+pub const TFTP_OPCODE_WRQ: u8 = 2;
+pub const TFTP_OPCODE_DATA: u8 = 3;
+pub const TFTP_OPCODE_ACK: u8 = 4;
+pub const TFTP_OPCODE_ERROR: u8 = 5;
+pub const TFTP_OPCODE_OACK: u8 = 6;
+
+/// Opcode names, as used by the `tftp.opcode` keyword.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumStringU8)]
+#[repr(u8)]
+pub enum TFTPOpcode {
+    Rrq = 1,
+    Wrq = 2,
+    Data = 3,
+    Ack = 4,
+    Error = 5,
+    Oack = 6,
+}
+
+/// Block size used when no blksize option was acknowledged.
+pub const TFTP_DEFAULT_BLKSIZE: u16 = 512;
+
+/// An option from a request or an option acknowledgment. Names are
+/// case insensitive, they are stored in lower case.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TFTPOption {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TFTPRequest {
+    pub opcode: u8,
+    pub filename: String,
+    pub mode: String,
+    pub options: Vec<TFTPOption>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TFTPPacket<'a> {
+    Request(TFTPRequest),
+    Data(u16, &'a [u8]),
+    Ack(u16),
+    Error(u16, String),
+    OptionAck(Vec<TFTPOption>),
+}
+
+impl TFTPPacket<'_> {
+    pub fn opcode(&self) -> u8 {
+        match self {
+            TFTPPacket::Request(request) => request.opcode,
+            TFTPPacket::Data(_, _) => TFTP_OPCODE_DATA,
+            TFTPPacket::Ack(_) => TFTP_OPCODE_ACK,
+            TFTPPacket::Error(_, _) => TFTP_OPCODE_ERROR,
+            TFTPPacket::OptionAck(_) => TFTP_OPCODE_OACK,
+        }
+    }
+}
+
+fn getstr(i: &[u8]) -> IResult<&[u8], &str> {
+    map_res(take_while(|c| c != 0), str::from_utf8)(i)
+}
+
+fn tftp_option(i: &[u8]) -> IResult<&[u8], TFTPOption> {
+    let (i, name) = terminated(getstr, tag([0]))(i)?;
+    let (i, value) = terminated(getstr, tag([0]))(i)?;
+    let option = TFTPOption {
+        name: name.to_lowercase(),
+        value: String::from(value),
+    };
+    Ok((i, option))
+}
+
+fn tftp_options(i: &[u8]) -> IResult<&[u8], Vec<TFTPOption>> {
+    let (i, options) = many0(tftp_option)(i)?;
+    if !i.is_empty() {
+        return Err(Err::Error(make_error(i, ErrorKind::Eof)));
+    }
+    Ok((i, options))
+}
+
+fn tftp_request(i: &[u8], opcode: u8) -> IResult<&[u8], TFTPRequest> {
+    let (i, filename) = terminated(getstr, tag([0]))(i)?;
+    let (i, mode) = getstr(i)?;
+    let (i, _) = opt(tag([0]))(i)?;
+    let (i, options) = tftp_options(i)?;
+    let request = TFTPRequest {
+        opcode,
+        filename: String::from(filename),
+        mode: mode.to_lowercase(),
+        options,
+    };
+    Ok((i, request))
+}
+
+fn tftp_error(i: &[u8]) -> IResult<&[u8], TFTPPacket<'_>> {
+    let (i, code) = be_u16(i)?;
+    let (i, message) = take_while(|c| c != 0)(i)?;
+    let (i, _) = rest(i)?;
+    let message = String::from_utf8_lossy(message).to_string();
+    Ok((i, TFTPPacket::Error(code, message)))
+}
+
+pub fn parse_tftp_packet(i: &[u8]) -> IResult<&[u8], TFTPPacket<'_>> {
+    let (i, _) = tag([0])(i)?;
+    let (i, opcode) = be_u8(i)?;
+    match opcode {
+        TFTP_OPCODE_RRQ | TFTP_OPCODE_WRQ => {
+            map(|i| tftp_request(i, opcode), TFTPPacket::Request)(i)
+        }
+        TFTP_OPCODE_DATA => {
+            let (i, block) = be_u16(i)?;
+            let (i, data) = rest(i)?;
+            Ok((i, TFTPPacket::Data(block, data)))
+        }
+        TFTP_OPCODE_ACK => map(be_u16, TFTPPacket::Ack)(i),
+        TFTP_OPCODE_ERROR => tftp_error(i),
+        TFTP_OPCODE_OACK => map(tftp_options, TFTPPacket::OptionAck)(i),
+        _ => Err(Err::Error(make_error(i, ErrorKind::Switch))),
+    }
+}
+
+/// Get the value of the option `name` if it is a valid number in the
+/// range allowed for it.
+pub fn tftp_option_value(options: &[TFTPOption], name: &str, min: u64, max: u64) -> Option<u64> {
+    let option = options.iter().find(|o| o.name == name)?;
+    let value = option.value.parse::<u64>().ok()?;
+    if value < min || value > max {
+        return None;
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tftp_request_options() {
+        let buf: &[u8] = b"\x00\x01fw.bin\x00octet\x00blksize\x001428\x00TSIZE\x000\x00";
+        let (rem, pkt) = parse_tftp_packet(buf).unwrap();
+        assert!(rem.is_empty());
+        let req = match pkt {
+            TFTPPacket::Request(req) => req,
+            _ => panic!("not a request"),
+        };
+        assert_eq!(req.opcode, TFTP_OPCODE_RRQ);
+        assert_eq!(req.filename, "fw.bin");
+        assert_eq!(req.mode, "octet");
+        assert_eq!(req.options.len(), 2);
+        assert_eq!(req.options[1].name, "tsize");
+        assert_eq!(
+            tftp_option_value(&req.options, "blksize", 8, 65464),
+            Some(1428)
+        );
+        assert_eq!(tftp_option_value(&req.options, "timeout", 1, 255), None);
+    }
+
+    #[test]
+    fn test_parse_tftp_data_ack_error() {
+        let buf: &[u8] = &[0x00, 0x03, 0x00, 0x02, b'a', b'b'];
+        assert_eq!(
+            parse_tftp_packet(buf).unwrap().1,
+            TFTPPacket::Data(2, b"ab")
+        );
+        let buf: &[u8] = &[0x00, 0x04, 0x01, 0x00];
+        assert_eq!(parse_tftp_packet(buf).unwrap().1, TFTPPacket::Ack(256));
+        let buf: &[u8] = b"\x00\x05\x00\x01File not found\x00";
+        assert_eq!(
+            parse_tftp_packet(buf).unwrap().1,
+            TFTPPacket::Error(1, String::from("File not found"))
+        );
+    }
+
+    #[test]
+    fn test_parse_tftp_oack() {
+        let buf: &[u8] = b"\x00\x06blksize\x001024\x00";
+        let (_, pkt) = parse_tftp_packet(buf).unwrap();
+        assert_eq!(
+            pkt,
+            TFTPPacket::OptionAck(vec![TFTPOption {
+                name: String::from("blksize"),
+                value: String::from("1024"),
+            }])
+        );
+        // option value not terminated
+        let buf: &[u8] = b"\x00\x06blksize\x001024";
+        assert!(parse_tftp_packet(buf).is_err());
+    }
+
+    #[test]
+    fn test_parse_tftp_invalid_opcode() {
+        let buf: &[u8] = &[0x00, 0x07, 0x00, 0x00];
+        assert!(parse_tftp_packet(buf).is_err());
+    }
+}
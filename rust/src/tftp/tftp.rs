@@ -17,38 +17,104 @@
 
 // written by Clément Galland <clement.galland@epita.fr>
 
-use std::str;
 use std;
-use nom7::IResult;
-use nom7::combinator::map_res;
-use nom7::bytes::streaming::{tag, take_while};
-use nom7::number::streaming::be_u8;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::os::raw::{c_char, c_int, c_void};
 
-use crate::applayer::{AppLayerTxData,AppLayerStateData};
+use super::parser::*;
+use crate::applayer::{AppLayerGetFileState, AppLayerStateData, AppLayerTxData};
+use crate::core::{AppLayerEventType, SuricataFileContext, STREAM_TOCLIENT, STREAM_TOSERVER};
+use crate::direction::Direction;
+use crate::filecontainer::FileFlowFlagsToFlags;
+use crate::filetracker::FileTransferTracker;
 
-const READREQUEST:  u8 = 1;
-const WRITEREQUEST: u8 = 2;
-const DATA:         u8 = 3;
-const ACK:          u8 = 4;
-const ERROR:        u8 = 5;
+/// Number of out of order blocks that are queued before the file is
+/// truncated.
+const TFTP_MAX_PENDING_BLOCKS: usize = 64;
 
-#[derive(Debug, PartialEq, Eq)]
+/// Number of recent blocks for which a hash of the data is kept to
+/// tell retransmissions from blocks sent again with other data.
+const TFTP_MAX_BLOCK_HASHES: usize = 64;
+
+pub static mut SURICATA_TFTP_FILE_CONFIG: Option<&'static SuricataFileContext> = None;
+
+#[derive(Debug, PartialEq, Eq, AppLayerEvent)]
+pub enum TFTPEvent {
+    DuplicateBlock,
+    OutOfOrderBlock,
+}
+
+#[derive(Debug, Default)]
+pub struct TFTPError {
+    pub code: u16,
+    pub message: String,
+}
+
+#[derive(Debug)]
 pub struct TFTPTransaction {
     pub opcode : u8,
     pub filename : String,
     pub mode : String,
+    /// Options of the request
+    pub options: Vec<TFTPOption>,
+    /// Bit mask of the opcodes of the packets in this transaction
+    pub opcodes: u8,
+    /// Set if this transaction follows the DATA, ACK, OACK and ERROR
+    /// packets of a transfer, not set for the request itself.
+    pub transfer: bool,
+    /// Options acknowledged by the server
+    pub oack: Option<Vec<TFTPOption>>,
+    pub blksize: u16,
+    pub tsize: Option<u64>,
+    pub timeout: Option<u64>,
+    pub error: Option<TFTPError>,
+    /// Number of distinct DATA blocks and their total size
+    pub blocks: u64,
+    pub bytes: u64,
+    pub complete: bool,
+    /// Block numbers wrap around, these are counted from the first
+    /// block of the transfer.
+    next_block: u64,
+    last_block: Option<u64>,
+    /// Out of order blocks queued in the file tracker
+    pending: BTreeSet<u64>,
+    /// Block numbers and hashes of the data of the most recent blocks
+    block_hashes: VecDeque<(u64, u64)>,
+    file_tracker: FileTransferTracker,
     id: u64,
     tx_data: AppLayerTxData,
 }
 
+#[derive(Default)]
 pub struct TFTPState {
     state_data: AppLayerStateData,
     pub transactions : Vec<TFTPTransaction>,
     /// tx counter for assigning incrementing id's to tx's
     tx_id: u64,
+    /// Request the transfer on this flow belongs to. Set from the
+    /// expectation on the flow the server answers from.
+    request: Option<TFTPRequest>,
+    /// Request parsed by the last call, to set up the expectation
+    new_request: Option<TFTPRequest>,
+    transfer_tx_id: u64,
+    transfer_done: bool,
 }
 
 impl TFTPState {
+    pub fn new() -> Self {
+        Self {
+            state_data: AppLayerStateData::new(),
+            transactions: Vec::new(),
+            tx_id: 0,
+            request: None,
+            new_request: None,
+            transfer_tx_id: 0,
+            transfer_done: false,
+        }
+    }
+
     fn get_tx_by_id(&mut self, tx_id: u64) -> Option<&TFTPTransaction> {
         self.transactions.iter().find(|&tx| tx.id == tx_id + 1)
     }
@@ -60,35 +126,256 @@ impl TFTPState {
             let _ = self.transactions.remove(idx);
         }
     }
+
+    fn new_tx(&mut self, request: &TFTPRequest) -> &mut TFTPTransaction {
+        let mut tx = TFTPTransaction::new(request);
+        self.tx_id += 1;
+        tx.id = self.tx_id;
+        self.transactions.push(tx);
+        return self.transactions.last_mut().unwrap();
+    }
+
+    /// Get the transaction of the current transfer, creating it for the
+    /// first packet after the request.
+    fn get_transfer_tx(&mut self) -> &mut TFTPTransaction {
+        let file_flags = self.state_data.file_flags;
+        let id = self.transfer_tx_id;
+        let idx = match self.transactions.iter().position(|tx| tx.id == id) {
+            Some(idx) => idx,
+            None => {
+                let request = self.request.clone().unwrap_or_default();
+                let tx = self.new_tx(&request);
+                tx.transfer = true;
+                self.transfer_tx_id = self.tx_id;
+                self.transactions.len() - 1
+            }
+        };
+        let tx = &mut self.transactions[idx];
+        tx.tx_data.update_file_flags(file_flags);
+        tx.tx_data.updated_ts = true;
+        tx.tx_data.updated_tc = true;
+        return tx;
+    }
+
+    fn parse(&mut self, input: &[u8], direction: Direction) -> bool {
+        let pkt = match parse_tftp_packet(input) {
+            Ok((_, pkt)) => pkt,
+            Err(_) => return false,
+        };
+        if let TFTPPacket::Request(request) = pkt {
+            if !tftp_mode_ok(&request.mode) {
+                return false;
+            }
+            let tx = self.new_tx(&request);
+            tx.opcodes = 1 << request.opcode;
+            tx.complete = true;
+            self.request = Some(request.clone());
+            self.new_request = Some(request);
+            self.transfer_tx_id = 0;
+            self.transfer_done = false;
+            return true;
+        }
+        // late ACKs and retransmissions once the transfer is over
+        if self.transfer_done && !matches!(pkt, TFTPPacket::Error(_, _)) {
+            return true;
+        }
+        let tx = self.get_transfer_tx();
+        tx.opcodes |= 1 << pkt.opcode();
+        match pkt {
+            TFTPPacket::Data(block, data) => tx.handle_data(block, data, direction),
+            TFTPPacket::OptionAck(options) => tx.handle_oack(options),
+            TFTPPacket::Error(code, message) => {
+                tx.error = Some(TFTPError { code, message });
+                tx.complete = true;
+            }
+            _ => {}
+        }
+        if tx.complete {
+            tx.close_file();
+            self.transfer_done = true;
+        }
+        return true;
+    }
+}
+
+fn tftp_mode_ok(mode: &str) -> bool {
+    match mode {
+        "netascii" | "mail" | "octet" => true,
+        _ => false
+    }
+}
+
+fn tftp_block_hash(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
 }
 
 impl TFTPTransaction {
-    pub fn new(opcode : u8, filename : String, mode : String) -> TFTPTransaction {
+    pub fn new(request: &TFTPRequest) -> TFTPTransaction {
+        let tsize = if request.opcode == TFTP_OPCODE_WRQ {
+            tftp_option_value(&request.options, "tsize", 0, u64::MAX)
+        } else {
+            None
+        };
         TFTPTransaction {
-            opcode,
-            filename,
-            mode : mode.to_lowercase(),
+            opcode: request.opcode,
+            filename: request.filename.clone(),
+            mode: request.mode.clone(),
+            options: request.options.clone(),
+            opcodes: 0,
+            transfer: false,
+            oack: None,
+            blksize: TFTP_DEFAULT_BLKSIZE,
+            tsize,
+            timeout: None,
+            error: None,
+            blocks: 0,
+            bytes: 0,
+            complete: false,
+            next_block: 1,
+            last_block: None,
+            pending: BTreeSet::new(),
+            block_hashes: VecDeque::new(),
+            file_tracker: FileTransferTracker::new(),
             id : 0,
             tx_data: AppLayerTxData::new(),
         }
     }
-    pub fn is_mode_ok(&self) -> bool {
-        match self.mode.as_str() {
-            "netascii" | "mail" | "octet" => true,
-            _ => false
+
+    /// Check if a packet with `opcode` is part of this transaction.
+    pub fn has_opcode(&self, opcode: u8) -> bool {
+        opcode < 8 && self.opcodes & (1 << opcode) != 0
+    }
+
+    fn handle_oack(&mut self, options: Vec<TFTPOption>) {
+        if let Some(blksize) = tftp_option_value(&options, "blksize", 8, 65464) {
+            self.blksize = blksize as u16;
+        }
+        if let Some(tsize) = tftp_option_value(&options, "tsize", 0, u64::MAX) {
+            self.tsize = Some(tsize);
         }
+        self.timeout = tftp_option_value(&options, "timeout", 1, 255);
+        self.oack = Some(options);
     }
-    pub fn is_opcode_ok(&self) -> bool {
-        match self.opcode {
-            READREQUEST | WRITEREQUEST | ACK | DATA | ERROR => true,
-            _ => false
+
+    fn handle_data(&mut self, block: u16, data: &[u8], direction: Direction) {
+        // a larger block than expected means we missed the OACK
+        if self.oack.is_none() && self.blocks == 0 && data.len() > self.blksize as usize {
+            self.blksize = std::cmp::min(data.len(), u16::MAX as usize) as u16;
+        }
+        let delta = block.wrapping_sub(self.next_block as u16) as i16 as i64;
+        let abs = self.next_block as i64 + delta;
+        if abs < 1 {
+            return;
+        }
+        let abs = abs as u64;
+        let hash = tftp_block_hash(data);
+        if abs < self.next_block || self.pending.contains(&abs) {
+            // retransmissions after a lost ACK are expected, only flag
+            // a block that comes back with other data
+            if self.block_data_differs(abs, hash) {
+                self.tx_data.set_event(TFTPEvent::DuplicateBlock as u8);
+            }
+            return;
+        }
+        if self.block_hashes.len() >= TFTP_MAX_BLOCK_HASHES {
+            self.block_hashes.pop_front();
+        }
+        self.block_hashes.push_back((abs, hash));
+        if abs > self.next_block {
+            self.tx_data.set_event(TFTPEvent::OutOfOrderBlock as u8);
+            if self.pending.len() >= TFTP_MAX_PENDING_BLOCKS {
+                SCLogDebug!("too many out of order blocks, truncating file");
+                if let Some(sfcm) = unsafe { SURICATA_TFTP_FILE_CONFIG } {
+                    self.file_tracker.trunc(sfcm);
+                }
+            }
+            self.pending.insert(abs);
+        } else {
+            self.next_block += 1;
+            while self.pending.remove(&self.next_block) {
+                self.next_block += 1;
+            }
+        }
+        self.blocks += 1;
+        self.bytes += data.len() as u64;
+        let is_last = data.len() < self.blksize as usize;
+        if is_last {
+            self.last_block = Some(abs);
+        }
+        self.file_chunk(abs, data, is_last, direction);
+        if let Some(last) = self.last_block {
+            if self.next_block > last {
+                self.complete = true;
+            }
+        }
+    }
+
+    /// Check if a block seen before had other data than the one with
+    /// `hash`. Blocks that are no longer tracked are not checked.
+    fn block_data_differs(&self, block: u64, hash: u64) -> bool {
+        self.block_hashes
+            .iter()
+            .any(|&(b, h)| b == block && h != hash)
+    }
+
+    fn file_chunk(&mut self, block: u64, data: &[u8], is_last: bool, direction: Direction) {
+        if self.filename.is_empty() {
+            return;
+        }
+        let sfcm = match unsafe { SURICATA_TFTP_FILE_CONFIG } {
+            Some(sfcm) => sfcm,
+            None => return,
+        };
+        let dir_flag = if direction == Direction::ToServer {
+            STREAM_TOSERVER
+        } else {
+            STREAM_TOCLIENT
+        };
+        if self.tx_data.file_tx == 0 {
+            self.tx_data.file_tx = dir_flag;
+            self.tx_data.init_files_opened();
+        } else if self.tx_data.file_tx != dir_flag {
+            return;
+        }
+        self.file_tracker.file_flags =
+            unsafe { FileFlowFlagsToFlags(self.tx_data.file_flags, dir_flag) };
+        let offset = (block - 1) * self.blksize as u64;
+        let track_id = self.id as u32;
+        self.file_tracker.new_chunk(
+            sfcm,
+            self.filename.as_bytes(),
+            data,
+            offset,
+            data.len() as u32,
+            0,
+            is_last,
+            &track_id,
+        );
+    }
+
+    /// Truncate the file if the transfer ended before its last block.
+    fn close_file(&mut self) {
+        if self.file_tracker.file_open {
+            if let Some(sfcm) = unsafe { SURICATA_TFTP_FILE_CONFIG } {
+                self.file_tracker.trunc(sfcm);
+            }
+        }
+    }
+}
+
+impl Drop for TFTPTransaction {
+    fn drop(&mut self) {
+        if let Some(sfcm) = unsafe { SURICATA_TFTP_FILE_CONFIG } {
+            self.file_tracker.file.free(sfcm);
         }
     }
 }
 
 #[no_mangle]
 pub extern "C" fn SCTftpStateAlloc() -> *mut std::os::raw::c_void {
-    let state = TFTPState { state_data: AppLayerStateData::new(), transactions : Vec::new(), tx_id: 0, };
+    let state = TFTPState::new();
     let boxed = Box::new(state);
     return Box::into_raw(boxed) as *mut _;
 }
@@ -118,58 +405,91 @@ pub extern "C" fn SCTftpGetTxCnt(state: &mut TFTPState) -> u64 {
     return state.tx_id;
 }
 
-fn getstr(i: &[u8]) -> IResult<&[u8], &str> {
-    map_res(
-        take_while(|c| c != 0),
-        str::from_utf8
-    )(i)
+#[no_mangle]
+pub unsafe extern "C" fn SCTftpParseRequest(state: &mut TFTPState,
+                                  input: *const u8,
+                                  len: u32) -> i64 {
+    let buf = std::slice::from_raw_parts(input, len as usize);
+    if state.parse(buf, Direction::ToServer) {
+        0
+    } else {
+        -1
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCTftpParseResponse(state: &mut TFTPState,
+                                  input: *const u8,
+                                  len: u32) -> i64 {
+    let buf = std::slice::from_raw_parts(input, len as usize);
+    if state.parse(buf, Direction::ToClient) {
+        0
+    } else {
+        -1
+    }
 }
 
-fn tftp_request(slice: &[u8]) -> IResult<&[u8], TFTPTransaction> {
-    let (i, _) = tag([0])(slice)?;
-    let (i, opcode) = be_u8(i)?;
-    let (i, filename) = getstr(i)?;
-    let (i, _) = tag([0])(i)?;
-    let (i, mode) = getstr(i)?;
-    Ok((i,
-        TFTPTransaction::new(opcode, String::from(filename), String::from(mode))
-       )
-      )
+/// Take the request parsed by the last call to the request parser, if
+/// any. The caller owns the returned request.
+#[no_mangle]
+pub extern "C" fn SCTftpStateTakeRequest(state: &mut TFTPState) -> *mut TFTPRequest {
+    match state.new_request.take() {
+        Some(request) => Box::into_raw(Box::new(request)),
+        None => std::ptr::null_mut(),
+    }
 }
 
-fn parse_tftp_request(input: &[u8]) -> Option<TFTPTransaction> {
-    match tftp_request(input) {
-        Ok((_, tx)) => {
-            if !tx.is_mode_ok() {
-                return None;
-            }
-            if !tx.is_opcode_ok() {
-                return None;
-            }
-            return Some(tx);
-        }
-        Err(_) => {
-            return None;
-        }
+#[no_mangle]
+pub unsafe extern "C" fn SCTftpRequestFree(request: *mut TFTPRequest) {
+    std::mem::drop(Box::from_raw(request));
+}
+
+/// Set the request the transfer on this flow belongs to.
+#[no_mangle]
+pub extern "C" fn SCTftpStateSetRequest(state: &mut TFTPState, request: &TFTPRequest) {
+    state.request = Some(request.clone());
+}
+
+#[no_mangle]
+pub extern "C" fn SCTftpGetTxProgress(tx: &TFTPTransaction) -> c_int {
+    if tx.complete {
+        1
+    } else {
+        0
     }
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn SCTftpParseRequest(state: &mut TFTPState,
-                                  input: *const u8,
-                                  len: u32) -> i64 {
-    let buf = std::slice::from_raw_parts(input, len as usize);
-    match parse_tftp_request(buf) {
-        Some(mut tx) => {
-            state.tx_id += 1;
-            tx.id = state.tx_id;
-            state.transactions.push(tx);
-            0
-        },
-        None => {
-           -1
+pub unsafe extern "C" fn SCTftpGetTxFiles(tx: *mut c_void, direction: u8) -> AppLayerGetFileState {
+    let tx = cast_pointer!(tx, TFTPTransaction);
+    if tx.tx_data.file_tx & direction != 0 {
+        if let Some(sfcm) = { SURICATA_TFTP_FILE_CONFIG } {
+            return AppLayerGetFileState {
+                fc: &mut tx.file_tracker.file,
+                cfg: sfcm.files_sbcfg,
+            };
         }
     }
+    AppLayerGetFileState::err()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCTftpStateGetEventInfo(
+    event_name: *const c_char, event_id: *mut u8, event_type: *mut AppLayerEventType,
+) -> c_int {
+    crate::applayer::get_event_info::<TFTPEvent>(event_name, event_id, event_type)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCTftpStateGetEventInfoById(
+    event_id: u8, event_name: *mut *const c_char, event_type: *mut AppLayerEventType,
+) -> c_int {
+    crate::applayer::get_event_info_by_id::<TFTPEvent>(event_id, event_name, event_type)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCTftpInit(context: &'static mut SuricataFileContext) {
+    SURICATA_TFTP_FILE_CONFIG = Some(context);
 }
 
 #[no_mangle]
@@ -215,14 +535,27 @@ mod test {
             0x00, 0x01, 0x72, 0x66, 0x63, 0x31, 0x33, 0x35, 0x30, 0x2e, 0x74, 0x78, 0x74, 0x00, 0x63, 0x63, 0x63, 0x63, 0x63, 0x00,
     ];
 
+    fn parse_tftp_request(input: &[u8]) -> Option<TFTPRequest> {
+        match parse_tftp_packet(input) {
+            Ok((_, TFTPPacket::Request(request))) => {
+                if !tftp_mode_ok(&request.mode) {
+                    return None;
+                }
+                return Some(request);
+            }
+            _ => {
+                return None;
+            }
+        }
+    }
+
     #[test]
     pub fn test_parse_tftp_read_request_1() {
-        let tx = TFTPTransaction {
-            opcode: READREQUEST,
+        let tx = TFTPRequest {
+            opcode: TFTP_OPCODE_RRQ,
             filename: String::from("rfc1350.txt"),
             mode: String::from("octet"),
-            id: 0,
-            tx_data: AppLayerTxData::new(),
+            options: Vec::new(),
         };
 
         let txp = parse_tftp_request(&READ_REQUEST[..]).unwrap();
@@ -231,12 +564,11 @@ mod test {
 
     #[test]
     pub fn test_parse_tftp_write_request_1() {
-        let tx = TFTPTransaction {
-            opcode: WRITEREQUEST,
+        let tx = TFTPRequest {
+            opcode: TFTP_OPCODE_WRQ,
             filename: String::from("rfc1350.txt"),
             mode: String::from("octet"),
-            id: 0,
-            tx_data: AppLayerTxData::new(),
+            options: Vec::new(),
         };
 
         let txp = parse_tftp_request(&WRITE_REQUEST[..]).unwrap();
@@ -265,4 +597,80 @@ mod test {
 
         assert_eq!(None, parse_tftp_request(&INVALID_MODE[..]));
     }
+
+    #[test]
+    pub fn test_tftp_request_expectation() {
+        let mut state = TFTPState::new();
+        assert!(state.parse(&READ_REQUEST, Direction::ToServer));
+        assert_eq!(state.transactions.len(), 1);
+        assert!(state.transactions[0].complete);
+        assert!(!state.transactions[0].transfer);
+        let request = state.new_request.take().unwrap();
+        assert_eq!(request.filename, "rfc1350.txt");
+        assert!(state.new_request.is_none());
+    }
+
+    #[test]
+    pub fn test_tftp_transfer() {
+        let mut state = TFTPState::new();
+        state.request = parse_tftp_request(&READ_REQUEST);
+        assert!(state.parse(b"\x00\x06blksize\x008\x00", Direction::ToServer));
+        assert!(state.parse(b"\x00\x03\x00\x01abcdefgh", Direction::ToServer));
+        assert!(state.parse(b"\x00\x04\x00\x01", Direction::ToClient));
+        // retransmission of block 1, then block 3 before block 2
+        assert!(state.parse(b"\x00\x03\x00\x01abcdefgh", Direction::ToServer));
+        assert!(state.parse(b"\x00\x03\x00\x03qr", Direction::ToServer));
+        assert!(!state.transactions[0].complete);
+        assert!(state.parse(b"\x00\x03\x00\x02ijklmnop", Direction::ToServer));
+        assert_eq!(state.transactions.len(), 1);
+        let tx = &state.transactions[0];
+        assert!(tx.transfer);
+        assert_eq!(tx.filename, "rfc1350.txt");
+        assert_eq!(tx.blksize, 8);
+        assert_eq!(tx.blocks, 3);
+        assert_eq!(tx.bytes, 18);
+        assert!(tx.complete);
+        assert!(tx.has_opcode(TFTP_OPCODE_OACK));
+        assert!(tx.has_opcode(TFTP_OPCODE_ACK));
+        assert!(!tx.has_opcode(TFTP_OPCODE_RRQ));
+        // the final ACK does not start a new transfer
+        assert!(state.parse(b"\x00\x04\x00\x03", Direction::ToClient));
+        assert_eq!(state.transactions.len(), 1);
+    }
+
+    #[test]
+    pub fn test_tftp_transfer_error() {
+        let mut state = TFTPState::new();
+        assert!(state.parse(b"\x00\x05\x00\x01File not found\x00", Direction::ToServer));
+        let tx = &state.transactions[0];
+        assert!(tx.complete);
+        assert!(tx.filename.is_empty());
+        let error = tx.error.as_ref().unwrap();
+        assert_eq!(error.code, 1);
+        assert_eq!(error.message, "File not found");
+    }
+
+    #[test]
+    pub fn test_tftp_block_rollover() {
+        let mut tx = TFTPTransaction::new(&TFTPRequest::default());
+        tx.next_block = 65535;
+        tx.handle_data(65535, &[0; 512], Direction::ToServer);
+        tx.handle_data(0, &[0; 512], Direction::ToServer);
+        assert_eq!(tx.next_block, 65537);
+        assert_eq!(tx.blocks, 2);
+        assert!(!tx.complete);
+    }
+
+    #[test]
+    pub fn test_tftp_block_resent_data() {
+        let mut tx = TFTPTransaction::new(&TFTPRequest::default());
+        tx.handle_data(1, b"abcdefgh", Direction::ToServer);
+        assert!(!tx.block_data_differs(1, tftp_block_hash(b"abcdefgh")));
+        assert!(tx.block_data_differs(1, tftp_block_hash(b"abcdefgX")));
+        // block 2 was never seen
+        assert!(!tx.block_data_differs(2, tftp_block_hash(b"abcdefgh")));
+        tx.handle_data(1, b"abcdefgX", Direction::ToServer);
+        assert_eq!(tx.blocks, 1);
+        assert_eq!(tx.bytes, 8);
+    }
 }
@@ -368,6 +368,13 @@ extern "C" {
         alproto: AppProto, direction: u8, GetData: InspectionSingleBufferGetDataPtr,
     ) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn SCDetectHelperBufferProgressMpmRegister(
+        name: *const ::std::os::raw::c_char, desc: *const ::std::os::raw::c_char,
+        alproto: AppProto, direction: u8, GetData: InspectionSingleBufferGetDataPtr,
+        progress: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
 extern "C" {
     pub fn SCDetectHelperMultiBufferMpmRegister(
         name: *const ::std::os::raw::c_char, desc: *const ::std::os::raw::c_char,
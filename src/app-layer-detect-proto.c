@@ -2053,6 +2053,8 @@ void AppLayerProtoDetectSupportedIpprotos(AppProto alproto, uint8_t *ipprotos)
     if (alproto == ALPROTO_HTTP) {
         AppLayerProtoDetectSupportedIpprotos(ALPROTO_HTTP1, ipprotos);
         AppLayerProtoDetectSupportedIpprotos(ALPROTO_HTTP2, ipprotos);
+        // HTTP/3 requests are parsed by QUIC
+        AppLayerProtoDetectSupportedIpprotos(ALPROTO_QUIC, ipprotos);
     } else if (alproto == ALPROTO_DOH2) {
         // DOH2 is not detected, just HTTP2
         AppLayerProtoDetectSupportedIpprotos(ALPROTO_HTTP2, ipprotos);
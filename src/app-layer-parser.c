@@ -54,6 +54,7 @@
 #include "app-layer-ike.h"
 #include "app-layer-http2.h"
 #include "app-layer-imap.h"
+#include "app-layer-pop3.h"
 
 struct AppLayerParserThreadCtx_ {
     void *(*alproto_local_storage)[FLOW_PROTO_MAX];
@@ -1803,7 +1804,7 @@ void AppLayerParserRegisterProtocolParsers(void)
     SCRfbRegisterParser();
     SCMqttRegisterParser();
     SCRegisterPgsqlParser();
-    SCRegisterPop3Parser();
+    RegisterPOP3Parsers();
     SCRegisterRdpParser();
     RegisterHTTP2Parsers();
     SCRegisterTelnetParser();
@@ -0,0 +1,39 @@
+/* Copyright (C) 2025 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+/**
+ * \file
+ *
+ * Parser for POP3, RFC 1939. The protocol itself is handled in Rust,
+ * this only holds the configuration of the files extracted from the
+ * retrieved messages.
+ */
+
+#include "suricata-common.h"
+
+#include "app-layer-parser.h"
+#include "app-layer-pop3.h"
+#include "rust.h"
+
+static StreamingBufferConfig sbcfg = STREAMING_BUFFER_CONFIG_INITIALIZER;
+static SuricataFileContext sfc = { &sbcfg };
+
+void RegisterPOP3Parsers(void)
+{
+    SCPop3Init(&sfc);
+    SCRegisterPop3Parser();
+}
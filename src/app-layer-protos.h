@@ -99,17 +99,22 @@ static inline bool AppProtoEquals(AppProto sigproto, AppProto alproto)
     }
     switch (sigproto) {
         case ALPROTO_DNS:
-            // a DNS signature matches on either DNS or DOH2 flows
-            return (alproto == ALPROTO_DOH2) || (alproto == ALPROTO_DNS);
+            // a DNS signature matches on either DNS, DOH2 or QUIC flows,
+            // the latter for DNS over QUIC
+            return (alproto == ALPROTO_DOH2) || (alproto == ALPROTO_DNS) ||
+                   (alproto == ALPROTO_QUIC);
         case ALPROTO_HTTP2:
-            // a HTTP2 signature matches on either HTTP2 or DOH2 flows
-            return (alproto == ALPROTO_DOH2) || (alproto == ALPROTO_HTTP2);
+            // a HTTP2 signature matches on either HTTP2, DOH2 or QUIC flows,
+            // the latter for HTTP/3 requests
+            return (alproto == ALPROTO_DOH2) || (alproto == ALPROTO_HTTP2) ||
+                   (alproto == ALPROTO_QUIC);
         case ALPROTO_DOH2:
             // a DOH2 signature accepts dns, http2 or http generic keywords
             return (alproto == ALPROTO_DOH2) || (alproto == ALPROTO_HTTP2) ||
                    (alproto == ALPROTO_DNS) || (alproto == ALPROTO_HTTP);
         case ALPROTO_HTTP:
-            return (alproto == ALPROTO_HTTP1) || (alproto == ALPROTO_HTTP2);
+            return (alproto == ALPROTO_HTTP1) || (alproto == ALPROTO_HTTP2) ||
+                   (alproto == ALPROTO_QUIC);
         case ALPROTO_DCERPC:
             return (alproto == ALPROTO_SMB);
     }
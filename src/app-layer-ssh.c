@@ -115,12 +115,17 @@ void RegisterSSHParsers(void)
                 encryption_bypass = SSH_HANDLE_ENCRYPTION_TRACK_ONLY;
             } else if (strcmp(encryption_node->val, "bypass") == 0) {
                 encryption_bypass = SSH_HANDLE_ENCRYPTION_BYPASS;
+            } else if (strcmp(encryption_node->val, "metadata") == 0) {
+                encryption_bypass = SSH_HANDLE_ENCRYPTION_METADATA;
             } else {
                 encryption_bypass = SSH_CONFIG_DEFAULT_ENCRYPTION_BYPASS;
             }
         }
 
-        if (encryption_bypass) {
+        if (encryption_bypass == SSH_HANDLE_ENCRYPTION_METADATA) {
+            SCLogConfig("ssh: tracking of the encrypted records enabled");
+            SCSshEnableBypass(encryption_bypass);
+        } else if (encryption_bypass) {
             SCLogConfig("ssh: bypass on the start of encryption enabled");
             SCSshEnableBypass(encryption_bypass);
         }
@@ -31,6 +31,7 @@
 #include "app-layer.h"
 #include "app-layer-detect-proto.h"
 #include "app-layer-parser.h"
+#include "app-layer-expectation.h"
 
 #include "app-layer-tftp.h"
 
@@ -41,6 +42,57 @@
  * be the size of a header. */
 #define TFTP_MIN_FRAME_LEN 4
 
+static StreamingBufferConfig sbcfg = STREAMING_BUFFER_CONFIG_INITIALIZER;
+static SuricataFileContext sfc = { &sbcfg };
+
+/** Request handed over to the flow the server answers from. Starts
+ *  with the free function as required by the expectation API. */
+typedef struct TFTPExpectation_ {
+    void (*DFree)(void *);
+    TFTPRequest *request;
+} TFTPExpectation;
+
+static void TFTPExpectationFree(void *ptr)
+{
+    TFTPExpectation *data = (TFTPExpectation *)ptr;
+    SCTftpRequestFree(data->request);
+    SCFree(data);
+}
+
+/**
+ * \brief Set up the expectation for the transfer of a RRQ or WRQ.
+ *
+ * The server answers from a new port to the port of the client, so the
+ * flow of the transfer starts with a packet to that port.
+ */
+static void TFTPCreateExpectation(Flow *f, TFTPRequest *request)
+{
+    TFTPExpectation *data = SCCalloc(1, sizeof(*data));
+    if (data == NULL) {
+        SCTftpRequestFree(request);
+        return;
+    }
+    data->DFree = TFTPExpectationFree;
+    data->request = request;
+
+    if (AppLayerExpectationCreate(f, STREAM_TOSERVER, 0, f->sp, ALPROTO_TFTP, data) < 0) {
+        SCLogDebug("No expectation created.");
+        TFTPExpectationFree(data);
+    }
+}
+
+/**
+ * \brief Take over the request of a flow created from an expectation.
+ */
+static void TFTPHandleExpectation(Flow *f, void *state)
+{
+    TFTPExpectation *data = (TFTPExpectation *)FlowGetStorageById(f, AppLayerExpectationGetFlowId());
+    if (data != NULL) {
+        SCTftpStateSetRequest(state, data->request);
+        FlowFreeStorageById(f, AppLayerExpectationGetFlowId());
+    }
+}
+
 static void *TFTPStateAlloc(void *orig_state, AppProto proto_orig)
 {
     return SCTftpStateAlloc();
@@ -62,12 +114,6 @@ static void TFTPStateTxFree(void *state, uint64_t tx_id)
     SCTftpStateTxFree(state, tx_id);
 }
 
-static int TFTPStateGetEventInfo(
-        const char *event_name, uint8_t *event_id, AppLayerEventType *event_type)
-{
-    return -1;
-}
-
 /**
  * \brief Probe the input to see if it looks like tftp.
  *
@@ -108,19 +154,38 @@ static AppLayerResult TFTPParseRequest(Flow *f, void *state, AppLayerParserState
         SCReturnStruct(APP_LAYER_OK);
     }
 
+    TFTPHandleExpectation(f, state);
+
     int64_t res = SCTftpParseRequest(state, input, input_len);
     if (res < 0) {
         SCReturnStruct(APP_LAYER_ERROR);
     }
+
+    TFTPRequest *request = SCTftpStateTakeRequest(state);
+    if (request != NULL) {
+        TFTPCreateExpectation(f, request);
+    }
     SCReturnStruct(APP_LAYER_OK);
 }
 
-/**
- * \brief Response parsing is not implemented
- */
 static AppLayerResult TFTPParseResponse(Flow *f, void *state, AppLayerParserState *pstate,
         StreamSlice stream_slice, void *local_data)
 {
+    const uint8_t *input = StreamSliceGetData(&stream_slice);
+    uint32_t input_len = StreamSliceGetDataLen(&stream_slice);
+
+    SCLogDebug("Parsing tftp response: len=%" PRIu32, input_len);
+
+    if (input == NULL || input_len == 0) {
+        SCReturnStruct(APP_LAYER_OK);
+    }
+
+    TFTPHandleExpectation(f, state);
+
+    int64_t res = SCTftpParseResponse(state, input, input_len);
+    if (res < 0) {
+        SCReturnStruct(APP_LAYER_ERROR);
+    }
     SCReturnStruct(APP_LAYER_OK);
 }
 
@@ -137,25 +202,20 @@ static void *TFTPGetTx(void *state, uint64_t tx_id)
 /**
  * \brief Return the state of a transaction in a given direction.
  *
- * In the case of the tftp protocol, the existence of a transaction
- * means that the request is done. However, some protocols that may
- * need multiple chunks of data to complete the request may need more
- * than just the existence of a transaction for the request to be
- * considered complete.
- *
- * For the response to be considered done, the response for a request
- * needs to be seen.  The response_done flag is set on response for
- * checking here.
+ * A request transaction is done as soon as it is parsed. A transfer
+ * transaction is done once the last DATA block or an ERROR was seen.
  */
 static int TFTPGetStateProgress(void *tx, uint8_t direction)
 {
-    return 1;
+    return SCTftpGetTxProgress(tx);
 }
 
 void RegisterTFTPParsers(void)
 {
     const char *proto_name = "tftp";
 
+    SCTftpInit(&sfc);
+
     /* Check if TFTP UDP detection is enabled. If it does not exist in
      * the configuration file then it will be enabled by default. */
     if (AppLayerProtoDetectConfProtoDetectionEnabled("udp", proto_name)) {
@@ -225,11 +285,17 @@ void RegisterTFTPParsers(void)
         AppLayerParserRegisterGetTx(IPPROTO_UDP, ALPROTO_TFTP,
                                     TFTPGetTx);
 
-        AppLayerParserRegisterGetEventInfo(IPPROTO_UDP, ALPROTO_TFTP,
-                                           TFTPStateGetEventInfo);
+        AppLayerParserRegisterGetEventInfo(IPPROTO_UDP, ALPROTO_TFTP, SCTftpStateGetEventInfo);
+        AppLayerParserRegisterGetEventInfoById(
+                IPPROTO_UDP, ALPROTO_TFTP, SCTftpStateGetEventInfoById);
+        AppLayerParserRegisterGetTxFilesFunc(IPPROTO_UDP, ALPROTO_TFTP, SCTftpGetTxFiles);
 
         AppLayerParserRegisterTxDataFunc(IPPROTO_UDP, ALPROTO_TFTP, SCTftpGetTxData);
         AppLayerParserRegisterStateDataFunc(IPPROTO_UDP, ALPROTO_TFTP, SCTftpGetStateData);
+
+        /* The transfers are on the flows the server answers from, these
+         * are found through the expectations set by the requests. */
+        AppLayerRegisterExpectationProto(IPPROTO_UDP, ALPROTO_TFTP);
     }
     else {
         SCLogDebug("TFTP protocol parsing disabled.");
@@ -44,25 +44,31 @@ int SCDetectHelperBufferRegister(const char *name, AppProto alproto, uint8_t dir
     return DetectBufferTypeRegister(name);
 }
 
-int SCDetectHelperBufferMpmRegister(const char *name, const char *desc, AppProto alproto,
-        uint8_t direction, InspectionSingleBufferGetDataPtr GetData)
+int SCDetectHelperBufferProgressMpmRegister(const char *name, const char *desc, AppProto alproto,
+        uint8_t direction, InspectionSingleBufferGetDataPtr GetData, int progress)
 {
     if (direction & STREAM_TOSERVER) {
-        DetectAppLayerInspectEngineRegisterSingle(
-                name, alproto, SIG_FLAG_TOSERVER, 0, DetectEngineInspectBufferSingle, GetData);
-        DetectAppLayerMpmRegisterSingle(
-                name, SIG_FLAG_TOSERVER, 2, PrefilterSingleMpmRegister, GetData, alproto, 0);
+        DetectAppLayerInspectEngineRegisterSingle(name, alproto, SIG_FLAG_TOSERVER, progress,
+                DetectEngineInspectBufferSingle, GetData);
+        DetectAppLayerMpmRegisterSingle(name, SIG_FLAG_TOSERVER, 2, PrefilterSingleMpmRegister,
+                GetData, alproto, progress);
     }
     if (direction & STREAM_TOCLIENT) {
-        DetectAppLayerInspectEngineRegisterSingle(
-                name, alproto, SIG_FLAG_TOCLIENT, 0, DetectEngineInspectBufferSingle, GetData);
-        DetectAppLayerMpmRegisterSingle(
-                name, SIG_FLAG_TOCLIENT, 2, PrefilterSingleMpmRegister, GetData, alproto, 0);
+        DetectAppLayerInspectEngineRegisterSingle(name, alproto, SIG_FLAG_TOCLIENT, progress,
+                DetectEngineInspectBufferSingle, GetData);
+        DetectAppLayerMpmRegisterSingle(name, SIG_FLAG_TOCLIENT, 2, PrefilterSingleMpmRegister,
+                GetData, alproto, progress);
     }
     DetectBufferTypeSetDescriptionByName(name, desc);
     return DetectBufferTypeGetByName(name);
 }
 
+int SCDetectHelperBufferMpmRegister(const char *name, const char *desc, AppProto alproto,
+        uint8_t direction, InspectionSingleBufferGetDataPtr GetData)
+{
+    return SCDetectHelperBufferProgressMpmRegister(name, desc, alproto, direction, GetData, 0);
+}
+
 int SCDetectHelperMultiBufferProgressMpmRegister(const char *name, const char *desc,
         AppProto alproto, uint8_t direction, InspectionMultiBufferGetDataPtr GetData, int progress)
 {
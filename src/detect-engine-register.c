@@ -676,6 +676,7 @@ void SigTableSetup(void)
     DetectSmbNtlmsspUserRegister();
     DetectSmbNtlmsspDomainRegister();
     DetectSmbVersionRegister();
+    SCDetectSmbRegister();
     DetectTlsRegister();
     DetectTlsValidityRegister();
     DetectTlsVersionRegister();
@@ -773,6 +774,12 @@ void SigTableSetup(void)
     SCDetectSdpRegister();
     SCDetectDNSRegister();
     SCDetectPgsqlRegister();
+    SCDetectTelnetRegister();
+    SCDetectNtpRegister();
+    SCDetectTftpRegister();
+    SCDetectSshRegister();
+    SCDetectQuicRegister();
+    SCDetectDcerpcRegister();
 
     for (size_t i = 0; i < preregistered_callbacks_nb; i++) {
         PreregisteredCallbacks[i]();
@@ -1110,6 +1110,17 @@ void *DetectGetInnerTx(void *tx_ptr, AppProto alproto, AppProto engine_alproto,
             // incompatible engine->alproto with flow alproto
             tx_ptr = NULL;
         }
+    } else if (unlikely(alproto == ALPROTO_QUIC)) {
+        if (engine_alproto == ALPROTO_HTTP2) {
+            // need to get the http2 tx pointer of the http3 request
+            tx_ptr = SCQuicTxGetHttp2Tx(tx_ptr);
+        } else if (engine_alproto == ALPROTO_DNS) {
+            // need to get the dns tx pointer of the dns over quic stream
+            tx_ptr = SCQuicTxGetDnsTx(tx_ptr, flow_flags);
+        } else if (engine_alproto != ALPROTO_QUIC && engine_alproto != ALPROTO_UNKNOWN) {
+            // incompatible engine->alproto with flow alproto
+            tx_ptr = NULL;
+        }
     } else if (engine_alproto != alproto && engine_alproto != ALPROTO_UNKNOWN) {
         // incompatible engine->alproto with flow alproto
         tx_ptr = NULL;
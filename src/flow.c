@@ -1228,6 +1228,17 @@ uint32_t FlowGetFlags(Flow *flow)
 {
     return flow->flags;
 }
+
+/**
+ * \brief Get flow application layer protocol.
+ *
+ * A function to get the flow alproto useful when the caller only has an
+ * opaque pointer to the flow structure.
+ */
+AppProto FlowGetAlproto(Flow *flow)
+{
+    return flow->alproto;
+}
 /************************************Unittests*******************************/
 
 #ifdef UNITTESTS
@@ -103,6 +103,7 @@
 #define LOG_FORMAT_GROUPED     BIT_U64(60)
 #define LOG_FORMAT_DETAILED    BIT_U64(61)
 #define LOG_HTTPS              BIT_U64(62)
+#define LOG_CAA                BIT_U64(63)
 
 #define LOG_FORMAT_ALL (LOG_FORMAT_GROUPED|LOG_FORMAT_DETAILED)
 #define LOG_ALL_RRTYPES (~(uint64_t)(LOG_QUERIES|LOG_ANSWERS|LOG_FORMAT_DETAILED|LOG_FORMAT_GROUPED))
@@ -167,6 +168,8 @@ typedef enum {
     DNS_RRTYPE_MAILA,
     DNS_RRTYPE_ANY,
     DNS_RRTYPE_URI,
+    DNS_RRTYPE_SVCB,
+    DNS_RRTYPE_CAA,
     DNS_RRTYPE_MAX,
 } DnsRRTypes;
 
@@ -233,7 +236,9 @@ static struct {
    { "tsig", LOG_TSIG },
    { "maila", LOG_MAILA },
    { "any", LOG_ANY },
-   { "uri", LOG_URI }
+   { "uri", LOG_URI },
+   { "svcb", LOG_HTTPS },
+   { "caa", LOG_CAA }
     // clang-format on
 };
 
@@ -241,6 +246,8 @@ typedef struct LogDnsFileCtx_ {
     uint64_t flags; /** Store mode */
     OutputJsonCtx *eve_ctx;
     uint8_t version;
+    /** Log tunneling and DGA features */
+    bool features;
 } LogDnsFileCtx;
 
 typedef struct LogDnsLogThread_ {
@@ -250,8 +257,8 @@ typedef struct LogDnsLogThread_ {
 
 bool AlertJsonDns(void *txptr, SCJsonBuilder *js)
 {
-    return SCDnsLogJson(
-            txptr, LOG_FORMAT_DETAILED | LOG_QUERIES | LOG_ANSWERS | LOG_ALL_RRTYPES, js);
+    return SCDnsLogJson(txptr, LOG_FORMAT_DETAILED | LOG_QUERIES | LOG_ANSWERS | LOG_ALL_RRTYPES,
+            false, js);
 }
 
 bool AlertJsonDoh2(void *txptr, SCJsonBuilder *js)
@@ -324,7 +331,7 @@ static int JsonDoh2Logger(ThreadVars *tv, void *thread_data, const Packet *p, Fl
 
         SCJbGetMark(jb, &mark);
         // log DOH2 with DNS config
-        r2 = SCDnsLogJson(tx_dns, td->dnslog_ctx->flags, jb);
+        r2 = SCDnsLogJson(tx_dns, td->dnslog_ctx->flags, td->dnslog_ctx->features, jb);
         if (!r2) {
             SCJbRestoreMark(jb, &mark);
         }
@@ -431,7 +438,7 @@ static int JsonDnsLogger(ThreadVars *tv, void *thread_data, const Packet *p, Flo
             return TM_ECODE_OK;
         }
 
-        if (SCDnsLogJson(txptr, td->dnslog_ctx->flags, jb)) {
+        if (SCDnsLogJson(txptr, td->dnslog_ctx->flags, td->dnslog_ctx->features, jb)) {
             OutputJsonBuilderBuffer(tv, p, p->flow, jb, td->ctx);
         }
         SCJbFree(jb);
@@ -439,6 +446,21 @@ static int JsonDnsLogger(ThreadVars *tv, void *thread_data, const Packet *p, Flo
     return TM_ECODE_OK;
 }
 
+static int JsonDoqLogger(ThreadVars *tv, void *thread_data, const Packet *p, Flow *f, void *alstate,
+        void *txptr, uint64_t tx_id)
+{
+    // log the query and then the answer of a dns over quic stream
+    void *tx_dns = DetectGetInnerTx(txptr, ALPROTO_QUIC, ALPROTO_DNS, STREAM_TOSERVER);
+    if (tx_dns) {
+        JsonDnsLogger(tv, thread_data, p, f, alstate, tx_dns, tx_id);
+    }
+    tx_dns = DetectGetInnerTx(txptr, ALPROTO_QUIC, ALPROTO_DNS, STREAM_TOCLIENT);
+    if (tx_dns) {
+        JsonDnsLogger(tv, thread_data, p, f, alstate, tx_dns, tx_id);
+    }
+    return TM_ECODE_OK;
+}
+
 static TmEcode LogDnsLogThreadInit(ThreadVars *t, const void *initdata, void **data)
 {
     LogDnsLogThread *aft = SCCalloc(1, sizeof(LogDnsLogThread));
@@ -643,6 +665,10 @@ static OutputInitResult JsonDnsLogInitCtxSub(SCConfNode *conf, OutputCtx *parent
 
     dnslog_ctx->eve_ctx = ojc;
     dnslog_ctx->version = JsonDnsCheckVersion(conf);
+    if (conf != NULL) {
+        const char *features = SCConfNodeLookupChildValue(conf, "features");
+        dnslog_ctx->features = features != NULL && SCConfValIsTrue(features);
+    }
 
     OutputCtx *output_ctx = SCCalloc(1, sizeof(OutputCtx));
     if (unlikely(output_ctx == NULL)) {
@@ -680,3 +706,10 @@ void JsonDoh2LogRegister(void)
             JsonDnsLogInitCtxSub, ALPROTO_DOH2, JsonDoh2Logger, LogDnsLogThreadInit,
             LogDnsLogThreadDeinit);
 }
+
+void JsonDoqLogRegister(void)
+{
+    OutputRegisterTxSubModule(LOGGER_JSON_TX, "eve-log", "JsonDoQLog", "eve-log.doq",
+            JsonDnsLogInitCtxSub, ALPROTO_QUIC, JsonDoqLogger, LogDnsLogThreadInit,
+            LogDnsLogThreadDeinit);
+}
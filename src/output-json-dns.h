@@ -26,6 +26,7 @@
 
 void JsonDnsLogRegister(void);
 void JsonDoh2LogRegister(void);
+void JsonDoqLogRegister(void);
 
 bool AlertJsonDns(void *vtx, SCJsonBuilder *js);
 bool AlertJsonDoh2(void *vtx, SCJsonBuilder *js);
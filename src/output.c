@@ -931,6 +931,9 @@ void OutputRegisterRootLoggers(void)
     RegisterSimpleJsonApplayerLogger(
             ALPROTO_TEMPLATE, (EveJsonSimpleTxLogFunc)SCTemplateLoggerLog, NULL);
     RegisterSimpleJsonApplayerLogger(ALPROTO_RDP, (EveJsonSimpleTxLogFunc)SCRdpToJson, NULL);
+    RegisterSimpleJsonApplayerLogger(
+            ALPROTO_TELNET, (EveJsonSimpleTxLogFunc)SCTelnetLoggerLog, NULL);
+    RegisterSimpleJsonApplayerLogger(ALPROTO_NTP, (EveJsonSimpleTxLogFunc)SCNtpLoggerLog, NULL);
     // special case : http2 is logged in http object
     RegisterSimpleJsonApplayerLogger(ALPROTO_HTTP2, (EveJsonSimpleTxLogFunc)SCHttp2LogJson, "http");
     // underscore instead of dash for bittorrent_dht
@@ -1150,6 +1153,8 @@ void OutputRegisterLoggers(void)
             JsonLogThreadDeinit);
     /* DoH2 JSON logger. */
     JsonDoh2LogRegister();
+    /* DoQ JSON logger. */
+    JsonDoqLogRegister();
     /* POP3 JSON logger */
     OutputRegisterTxSubModule(LOGGER_JSON_TX, "eve-log", "JsonPop3Log", "eve-log.pop3",
             OutputJsonLogInitSub, ALPROTO_POP3, JsonGenericDirFlowLogger, JsonLogThreadInit,
@@ -1167,6 +1172,14 @@ void OutputRegisterLoggers(void)
             OutputJsonLogInitSub, ALPROTO_RDP, JsonGenericDirPacketLogger, JsonLogThreadInit,
             JsonLogThreadDeinit);
     SCLogDebug("rdp json logger registered.");
+    /* Telnet JSON logger. */
+    OutputRegisterTxSubModule(LOGGER_JSON_TX, "eve-log", "JsonTelnetLog", "eve-log.telnet",
+            OutputJsonLogInitSub, ALPROTO_TELNET, JsonGenericDirFlowLogger, JsonLogThreadInit,
+            JsonLogThreadDeinit);
+    /* NTP JSON logger. */
+    OutputRegisterTxSubModule(LOGGER_JSON_TX, "eve-log", "JsonNtpLog", "eve-log.ntp",
+            OutputJsonLogInitSub, ALPROTO_NTP, JsonGenericDirPacketLogger, JsonLogThreadInit,
+            JsonLogThreadDeinit);
     /* DCERPC JSON logger. */
     JsonDCERPCLogRegister();
     /* app layer frames */